@@ -0,0 +1,124 @@
+// Processes oxproc runs itself (`type = "proxy"`, `"forward"`, `"kubectl-forward"`, `"compose"`).
+// The manager starts them as `oxproc __builtin NAME` (or, for a forward through SSH, as
+// `ssh -L`, and for a compose service, as `docker compose up`) like any other command, so they
+// are supervised, logged, restarted and shown in `status` the same way, and need no `cmd`.
+
+use std::path::Path;
+
+use crate::compose::ComposeSpec;
+use crate::config::ProcessConfig;
+use crate::forward::ForwardSpec;
+use crate::kubectl::KubectlForwardSpec;
+use crate::proxy::ProxySpec;
+
+/// A process kind built into oxproc, with its settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Builtin {
+    Proxy(ProxySpec),
+    Forward(ForwardSpec),
+    KubectlForward(KubectlForwardSpec),
+    Compose(ComposeSpec),
+}
+
+impl Builtin {
+    /// The built-in kind a process table asks for with `type`, if any.
+    pub fn from_table(tbl: &toml::value::Table, root: &Path) -> Result<Option<Builtin>, String> {
+        let kind = match tbl.get("type") {
+            None => return Ok(None),
+            Some(v) => v.as_str().ok_or("'type' must be a string")?,
+        };
+        if tbl.contains_key("cmd") {
+            return Err(format!("'cmd' can't be combined with type = \"{}\"", kind));
+        }
+        match kind {
+            "proxy" => Ok(Some(Builtin::Proxy(ProxySpec::from_table(tbl, root)?))),
+            "forward" => Ok(Some(Builtin::Forward(ForwardSpec::from_table(tbl)?))),
+            "kubectl-forward" => Ok(Some(Builtin::KubectlForward(
+                KubectlForwardSpec::from_table(tbl)?,
+            ))),
+            "compose" => Ok(Some(Builtin::Compose(ComposeSpec::from_table(tbl, root)?))),
+            other => Err(format!(
+                "unknown type '{}' (expected \"proxy\", \"forward\", \"kubectl-forward\" or \"compose\")",
+                other
+            )),
+        }
+    }
+
+    /// The port it listens on, if oxproc knows it.
+    pub fn listen(&self) -> Option<u16> {
+        match self {
+            Builtin::Proxy(spec) => Some(spec.listen),
+            Builtin::Forward(spec) => Some(spec.listen),
+            Builtin::KubectlForward(spec) => Some(spec.listen()),
+            Builtin::Compose(_) => None,
+        }
+    }
+
+    /// The command the manager runs for built-in process `name`.
+    pub fn command(&self, root: &Path, name: &str) -> String {
+        use crate::watch::shell_quote;
+        match self {
+            Builtin::Forward(spec) => {
+                if let Some(via) = &spec.via {
+                    return spec.ssh_command(via);
+                }
+            }
+            Builtin::Compose(spec) => return spec.command(),
+            Builtin::Proxy(_) | Builtin::KubectlForward(_) => {}
+        }
+        let exe = std::env::current_exe()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| "oxproc".into());
+        let variant: String = crate::dirs::variant_for(root)
+            .map(|v| v.args())
+            .unwrap_or_default()
+            .iter()
+            .map(|a| format!(" {}", shell_quote(a)))
+            .collect();
+        format!(
+            "exec {} --root {}{} __builtin {}",
+            shell_quote(&exe),
+            shell_quote(&root.to_string_lossy()),
+            variant,
+            shell_quote(name)
+        )
+    }
+}
+
+/// `listen = PORT` of a built-in process.
+pub fn listen_port(tbl: &toml::value::Table) -> Result<u16, String> {
+    tbl.get("listen")
+        .and_then(|v| v.as_integer())
+        .and_then(|n| u16::try_from(n).ok())
+        .filter(|n| *n > 0)
+        .ok_or_else(|| "'listen' must be a port number".to_string())
+}
+
+/// Check what built-in processes refer to, once every process is known.
+pub fn check(configs: &[ProcessConfig]) -> Result<(), (String, String)> {
+    for c in configs {
+        if let Some(Builtin::Proxy(spec)) = &c.builtin {
+            crate::proxy::check_routes(spec, configs).map_err(|e| (c.name.clone(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// `oxproc __builtin NAME`: run the built-in process `name` of the project at `root`.
+pub fn run(root: &Path, name: &str) -> anyhow::Result<()> {
+    let configs = crate::config::load_config_from(root)?;
+    let Some(builtin) = configs
+        .iter()
+        .find(|c| c.name == name)
+        .and_then(|c| c.builtin.clone())
+    else {
+        anyhow::bail!("'{}' is not a built-in process", name);
+    };
+    let rt = tokio::runtime::Runtime::new()?;
+    match builtin {
+        Builtin::Proxy(spec) => rt.block_on(crate::proxy::serve(root, spec, configs)),
+        Builtin::Forward(spec) => rt.block_on(crate::forward::serve(spec)),
+        Builtin::KubectlForward(spec) => rt.block_on(crate::kubectl::serve(spec)),
+        Builtin::Compose(_) => anyhow::bail!("'{}' runs through docker compose", name),
+    }
+}
@@ -0,0 +1,265 @@
+// `oxproc logs --output <path>`: export logs for sharing in bug reports.
+// `.tar.gz`/`.tgz`/`.tar` paths get an archive with logs, state and effective config;
+// any other path gets the selected logs concatenated into a plain text file.
+
+use crate::config::{self, ProcessConfig};
+use crate::state;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Top-level directory inside the archive.
+const BUNDLE_DIR: &str = "oxproc-logs";
+
+/// Effective settings for one process, as written to `config.json` in the bundle.
+#[derive(Debug, Serialize)]
+struct EffectiveProcess {
+    name: String,
+    cmd: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cwd: Option<String>,
+    stdout_log: String,
+    stderr_log: String,
+    autostart: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_delay_ms: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_jitter_ms: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_restarts: Option<u32>,
+    restart_window_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ready: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    clean_env: bool,
+    /// Names only: values may hold secrets
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    env_keys: Vec<String>,
+}
+
+impl EffectiveProcess {
+    fn from_config(c: &ProcessConfig, root: &Path) -> Self {
+        let (stdout_log, stderr_log) = c.log_paths(root);
+        EffectiveProcess {
+            name: c.name.clone(),
+            cmd: c.command_line(root),
+            cwd: c.cwd.clone(),
+            stdout_log: stdout_log.to_string_lossy().to_string(),
+            stderr_log: stderr_log.to_string_lossy().to_string(),
+            autostart: c.autostart,
+            start_delay_ms: c.start_delay.map(|d| d.as_millis()),
+            start_jitter_ms: c.start_jitter.map(|d| d.as_millis()),
+            max_restarts: c.max_restarts,
+            restart_window_ms: c.restart_window.as_millis(),
+            ready: c.ready.as_ref().map(|r| r.to_string()),
+            clean_env: !c.env.inherit,
+            env_keys: c.env.vars.keys().cloned().collect(),
+        }
+    }
+}
+
+fn is_archive(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    name.ends_with(".tar.gz") || name.ends_with(".tgz") || name.ends_with(".tar")
+}
+
+/// Whether a file was modified within `since` (always true without a cutoff).
+fn modified_since(path: &Path, cutoff: Option<SystemTime>) -> bool {
+    match cutoff {
+        None => true,
+        Some(cutoff) => fs::metadata(path)
+            .and_then(|m| m.modified())
+            .is_ok_and(|t| t >= cutoff),
+    }
+}
+
+/// Export logs for `names` (or every process) to `output`. With `since`, log files not written
+/// in that window are skipped and older events are dropped (log lines carry no timestamps).
+pub fn export_logs(
+    root: &Path,
+    names: &[String],
+    since: Option<Duration>,
+    output: &Path,
+) -> Result<()> {
+    let configs: Vec<ProcessConfig> = config::load_config_from(root)?
+        .into_iter()
+        .filter(|c| names.is_empty() || names.contains(&c.name))
+        .collect();
+    if configs.is_empty() {
+        anyhow::bail!("No matching processes.");
+    }
+    let cutoff = since.map(|d| SystemTime::now() - d);
+    if is_archive(output) {
+        write_archive(root, &configs, cutoff, output)?;
+    } else {
+        write_text(root, &configs, cutoff, output)?;
+    }
+    Ok(())
+}
+
+fn write_text(
+    root: &Path,
+    configs: &[ProcessConfig],
+    cutoff: Option<SystemTime>,
+    output: &Path,
+) -> Result<()> {
+    let mut out =
+        File::create(output).with_context(|| format!("Failed to create {}", output.display()))?;
+    for c in configs {
+        let (stdout_log, stderr_log) = c.log_paths(root);
+        for (label, path) in [("stdout", stdout_log), ("stderr", stderr_log)] {
+            if !path.exists() || !modified_since(&path, cutoff) {
+                continue;
+            }
+            writeln!(out, "== {} ({}) ==", c.name, label)?;
+            out.write_all(&fs::read(&path)?)?;
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_archive(
+    root: &Path,
+    configs: &[ProcessConfig],
+    cutoff: Option<SystemTime>,
+    output: &Path,
+) -> Result<()> {
+    let file =
+        File::create(output).with_context(|| format!("Failed to create {}", output.display()))?;
+    let name = output.to_string_lossy();
+    if name.ends_with(".tar") {
+        let mut tar = tar::Builder::new(file);
+        add_bundle_entries(&mut tar, root, configs, cutoff)?;
+        tar.into_inner()?.flush()?;
+    } else {
+        let gz = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        let mut tar = tar::Builder::new(gz);
+        add_bundle_entries(&mut tar, root, configs, cutoff)?;
+        tar.into_inner()?.finish()?.flush()?;
+    }
+    Ok(())
+}
+
+fn add_bundle_entries<W: Write>(
+    tar: &mut tar::Builder<W>,
+    root: &Path,
+    configs: &[ProcessConfig],
+    cutoff: Option<SystemTime>,
+) -> Result<()> {
+    for c in configs {
+        let (stdout_log, stderr_log) = c.log_paths(root);
+        for (suffix, path) in [("out", stdout_log), ("err", stderr_log)] {
+            if path.exists() && modified_since(&path, cutoff) {
+                let entry = format!("{}/logs/{}.{}.log", BUNDLE_DIR, c.name, suffix);
+                tar.append_path_with_name(&path, entry)?;
+            }
+        }
+    }
+
+    // Project config as written, plus what oxproc made of it
+    for path in [crate::config::proc_toml_path(root), root.join("Procfile")] {
+        let Some(file) = path.file_name().filter(|_| path.exists()) else {
+            continue;
+        };
+        let entry = format!("{}/{}", BUNDLE_DIR, file.to_string_lossy());
+        tar.append_path_with_name(&path, entry)?;
+    }
+    let effective: Vec<EffectiveProcess> = configs
+        .iter()
+        .map(|c| EffectiveProcess::from_config(c, root))
+        .collect();
+    append_bytes(tar, "config.json", &serde_json::to_vec_pretty(&effective)?)?;
+
+    let state_dir = state::state_dir_from_root(root);
+    for path in [
+        state::state_file_path(&state_dir),
+        state::manager_log_path(&state_dir),
+    ] {
+        if path.exists() {
+            let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+            tar.append_path_with_name(&path, format!("{}/{}", BUNDLE_DIR, file_name))?;
+        }
+    }
+    let events_path = state::events_log_path(&state_dir);
+    if events_path.exists() {
+        let data = fs::read_to_string(&events_path)?;
+        let kept = filter_events(&data, cutoff.map(DateTime::<Utc>::from));
+        append_bytes(tar, "events.jsonl", kept.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Keep event lines at or after `cutoff`; lines that don't parse are kept.
+fn filter_events(data: &str, cutoff: Option<DateTime<Utc>>) -> String {
+    let Some(cutoff) = cutoff else {
+        return data.to_string();
+    };
+    let mut out = String::new();
+    for line in data.lines() {
+        let keep = serde_json::from_str::<crate::events::Event>(line)
+            .map(|e| e.at >= cutoff)
+            .unwrap_or(true);
+        if keep {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn append_bytes<W: Write>(tar: &mut tar::Builder<W>, name: &str, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp() as u64);
+    header.set_cksum();
+    tar.append_data(&mut header, format!("{}/{}", BUNDLE_DIR, name), data)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundles_logs_and_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("proc.toml"), "[web]\ncmd = \"echo web\"\n").unwrap();
+        fs::write(root.join("web.out.log"), "hello\n").unwrap();
+
+        let out = root.join("bundle.tar.gz");
+        export_logs(root, &[], None, &out).unwrap();
+
+        let gz = flate2::read::GzDecoder::new(File::open(&out).unwrap());
+        let mut archive = tar::Archive::new(gz);
+        let names: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert!(names.contains(&"oxproc-logs/logs/web.out.log".to_string()));
+        assert!(names.contains(&"oxproc-logs/proc.toml".to_string()));
+        assert!(names.contains(&"oxproc-logs/config.json".to_string()));
+        assert!(!names.iter().any(|n| n.ends_with("web.err.log")));
+    }
+
+    #[test]
+    fn filters_events_by_time() {
+        let data = concat!(
+            r#"{"at":"2024-01-01T00:00:00Z","kind":"exited","process":"web","message":"old"}"#,
+            "\n",
+            r#"{"at":"2030-01-01T00:00:00Z","kind":"exited","process":"web","message":"new"}"#,
+            "\n"
+        );
+        let cutoff = "2025-01-01T00:00:00Z".parse().unwrap();
+        let kept = filter_events(data, Some(cutoff));
+        assert!(!kept.contains("old"));
+        assert!(kept.contains("new"));
+    }
+}
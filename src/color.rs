@@ -1,5 +1,7 @@
 use blake3::Hasher;
-use std::sync::OnceLock;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ColorMode {
@@ -43,9 +45,16 @@ fn current_mode() -> ColorMode {
     CONFIG.get().map(|c| c.mode).unwrap_or(ColorMode::Auto)
 }
 
+static ASSUME_TTY: AtomicBool = AtomicBool::new(false);
+
+/// Treat stdout as a terminal for `auto` color, e.g. when it was redirected into a pager.
+pub fn assume_tty() {
+    ASSUME_TTY.store(true, Ordering::Relaxed);
+}
+
 fn stdout_is_tty() -> bool {
     use std::io::IsTerminal;
-    std::io::stdout().is_terminal()
+    ASSUME_TTY.load(Ordering::Relaxed) || std::io::stdout().is_terminal()
 }
 
 fn color_enabled() -> bool {
@@ -56,6 +65,18 @@ fn color_enabled() -> bool {
     }
 }
 
+/// Whether programs oxproc runs should color their output, going by oxproc's own mode:
+/// `Some(true)` with `--color always`, `Some(false)` with `--color never`. In auto mode,
+/// output that oxproc relays through a pipe (`piped`) to a terminal should be colored; other
+/// output is left to the program's own detection (`None`).
+pub fn child_color(piped: bool) -> Option<bool> {
+    match current_mode() {
+        ColorMode::Always => Some(true),
+        ColorMode::Never => Some(false),
+        ColorMode::Auto => (piped && stdout_is_tty()).then_some(true),
+    }
+}
+
 const PALETTE: [&str; 12] = [
     "\u{1b}[31m", // red
     "\u{1b}[32m", // green
@@ -73,7 +94,98 @@ const PALETTE: [&str; 12] = [
 
 pub const RESET: &str = "\u{1b}[0m";
 
-fn color_index(label: &str) -> usize {
+/// How many colors the terminal can show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorDepth {
+    Basic,
+    Ansi256,
+    TrueColor,
+}
+
+/// Detect color depth from `COLORTERM` (truecolor/24bit) and `TERM` (*-256color).
+fn detect_depth() -> ColorDepth {
+    let colorterm = std::env::var("COLORTERM")
+        .unwrap_or_default()
+        .to_lowercase();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        return ColorDepth::TrueColor;
+    }
+    if std::env::var("TERM").is_ok_and(|t| t.contains("256color")) {
+        return ColorDepth::Ansi256;
+    }
+    ColorDepth::Basic
+}
+
+fn current_depth() -> ColorDepth {
+    static DEPTH: OnceLock<ColorDepth> = OnceLock::new();
+    *DEPTH.get_or_init(detect_depth)
+}
+
+/// A user-supplied color: a named ANSI color, a 256-color index or a `#rrggbb` value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ColorSpec {
+    Named(&'static str),
+    Ansi256(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl ColorSpec {
+    /// Parse `"red"`, `"208"` or `"#ff8800"`.
+    pub fn parse(s: &str) -> Option<ColorSpec> {
+        let s = s.trim();
+        if let Some(hex) = s.strip_prefix('#') {
+            if hex.len() != 6 || !hex.is_ascii() {
+                return None;
+            }
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+            return Some(ColorSpec::Rgb(channel(0)?, channel(2)?, channel(4)?));
+        }
+        if let Ok(n) = s.parse::<u8>() {
+            return Some(ColorSpec::Ansi256(n));
+        }
+        named_color(s).map(ColorSpec::Named)
+    }
+
+    /// Escape sequence at the given depth, or `None` if the terminal cannot show it.
+    /// Truecolor values are approximated on 256-color terminals.
+    pub fn escape(&self, depth: ColorDepth) -> Option<String> {
+        match *self {
+            ColorSpec::Named(esc) => Some(esc.to_string()),
+            ColorSpec::Ansi256(n) if depth >= ColorDepth::Ansi256 => {
+                Some(format!("\u{1b}[38;5;{}m", n))
+            }
+            ColorSpec::Rgb(r, g, b) if depth == ColorDepth::TrueColor => {
+                Some(format!("\u{1b}[38;2;{};{};{}m", r, g, b))
+            }
+            ColorSpec::Rgb(r, g, b) if depth == ColorDepth::Ansi256 => {
+                Some(format!("\u{1b}[38;5;{}m", rgb_to_ansi256(r, g, b)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Nearest entry in the 6x6x6 color cube of the 256-color palette.
+fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |c: u8| ((c as u16 * 5 + 127) / 255) as u8;
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+/// Prefix colors from the `[color]` table: a palette to hash names into and per-name pins.
+#[derive(Clone, Debug, Default)]
+pub struct Theme {
+    pub palette: Vec<ColorSpec>,
+    pub pinned: HashMap<String, ColorSpec>,
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Install the project's color theme. Only the first call has an effect.
+pub fn set_theme(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+fn hash_index(label: &str, len: usize) -> usize {
     let mut hasher = Hasher::new();
     hasher.update(label.as_bytes());
     let hash = hasher.finalize();
@@ -81,19 +193,305 @@ fn color_index(label: &str) -> usize {
     let mut bytes = [0u8; 8];
     bytes.copy_from_slice(&hash.as_bytes()[0..8]);
     let v = u64::from_le_bytes(bytes);
-    (v as usize) % PALETTE.len()
+    (v as usize) % len
+}
+
+#[cfg(test)]
+fn color_index(label: &str) -> usize {
+    hash_index(label, PALETTE.len())
+}
+
+fn theme_esc_for(theme: &Theme, label: &str, depth: ColorDepth) -> String {
+    if let Some(esc) = theme.pinned.get(label).and_then(|c| c.escape(depth)) {
+        return esc;
+    }
+    // Hash into the configured palette, skipping colors this terminal can't show;
+    // fall back to the built-in palette when none are usable.
+    let usable: Vec<String> = theme
+        .palette
+        .iter()
+        .filter_map(|c| c.escape(depth))
+        .collect();
+    if usable.is_empty() {
+        PALETTE[hash_index(label, PALETTE.len())].to_string()
+    } else {
+        usable[hash_index(label, usable.len())].clone()
+    }
+}
+
+/// Colors chosen for the labels of the current run by [`assign_colors`].
+static ASSIGNED: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+/// Choose colors for the labels shown together in this run so that no two share one while
+/// the palette has colors left. Pinned labels keep their pin and its color is not handed to
+/// anyone else. The rest are taken in sorted order, each getting its hashed color or, if
+/// that is taken, the next free one, so the same set of names always gets the same colors.
+pub fn assign_colors<'a>(labels: impl IntoIterator<Item = &'a str>) {
+    let default = Theme::default();
+    let theme = THEME.get().unwrap_or(&default);
+    let assigned = assign(theme, labels, current_depth());
+    *ASSIGNED.lock().unwrap() = Some(assigned);
+}
+
+fn assign<'a>(
+    theme: &Theme,
+    labels: impl IntoIterator<Item = &'a str>,
+    depth: ColorDepth,
+) -> HashMap<String, String> {
+    let mut labels: Vec<&str> = labels.into_iter().collect();
+    labels.sort();
+    labels.dedup();
+    let mut palette: Vec<String> = theme
+        .palette
+        .iter()
+        .filter_map(|c| c.escape(depth))
+        .collect();
+    if palette.is_empty() {
+        palette = PALETTE.iter().map(|c| c.to_string()).collect();
+    }
+    let mut assigned = HashMap::new();
+    let mut taken = vec![false; palette.len()];
+    for label in &labels {
+        if let Some(esc) = theme.pinned.get(*label).and_then(|c| c.escape(depth)) {
+            if let Some(i) = palette.iter().position(|c| *c == esc) {
+                taken[i] = true;
+            }
+            assigned.insert(label.to_string(), esc);
+        }
+    }
+    for label in labels {
+        if assigned.contains_key(label) {
+            continue;
+        }
+        let preferred = hash_index(label, palette.len());
+        let free = (0..palette.len())
+            .map(|step| (preferred + step) % palette.len())
+            .find(|&i| !taken[i]);
+        // More labels than colors: sharing is unavoidable, so keep the hashed one
+        let i = free.unwrap_or(preferred);
+        taken[i] = true;
+        assigned.insert(label.to_string(), palette[i].clone());
+    }
+    assigned
+}
+
+pub fn color_esc_for(label: &str) -> String {
+    if let Some(esc) = ASSIGNED.lock().unwrap().as_ref().and_then(|a| a.get(label)) {
+        return esc.clone();
+    }
+    match THEME.get() {
+        Some(theme) => theme_esc_for(theme, label, current_depth()),
+        None => PALETTE[hash_index(label, PALETTE.len())].to_string(),
+    }
+}
+
+/// Escape sequence for a named color (`red`, `bright_blue`, …; `bold` is also accepted).
+pub fn named_color(name: &str) -> Option<&'static str> {
+    let esc = match name.trim().to_lowercase().replace(['-', ' '], "_").as_str() {
+        "black" => "\u{1b}[30m",
+        "red" => "\u{1b}[31m",
+        "green" => "\u{1b}[32m",
+        "yellow" => "\u{1b}[33m",
+        "blue" => "\u{1b}[34m",
+        "magenta" => "\u{1b}[35m",
+        "cyan" => "\u{1b}[36m",
+        "white" => "\u{1b}[37m",
+        "bright_black" | "gray" | "grey" => "\u{1b}[90m",
+        "bright_red" => "\u{1b}[91m",
+        "bright_green" => "\u{1b}[92m",
+        "bright_yellow" => "\u{1b}[93m",
+        "bright_blue" => "\u{1b}[94m",
+        "bright_magenta" => "\u{1b}[95m",
+        "bright_cyan" => "\u{1b}[96m",
+        "bright_white" => "\u{1b}[97m",
+        "bold" => "\u{1b}[1m",
+        _ => return None,
+    };
+    Some(esc)
+}
+
+/// A `[log.highlight]` rule: text matching `pattern` is wrapped in `color`.
+#[derive(Debug, Clone)]
+pub struct HighlightRule {
+    pub pattern: regex::Regex,
+    pub color: String,
+}
+
+/// Colorize every match of the highlight rules in `line`. When rules overlap, the earliest
+/// match wins. No-op when colors are disabled.
+pub fn highlight(line: &str, rules: &[HighlightRule]) -> String {
+    if rules.is_empty() || !color_enabled() {
+        return line.to_string();
+    }
+    highlight_spans(line, rules)
+}
+
+fn highlight_spans(line: &str, rules: &[HighlightRule]) -> String {
+    let mut spans: Vec<(usize, usize, &str)> = Vec::new();
+    for rule in rules {
+        for m in rule.pattern.find_iter(line) {
+            if !m.is_empty() {
+                spans.push((m.start(), m.end(), &rule.color));
+            }
+        }
+    }
+    if spans.is_empty() {
+        return line.to_string();
+    }
+    spans.sort_by_key(|s| s.0);
+    let mut out = String::with_capacity(line.len() + spans.len() * 10);
+    let mut pos = 0;
+    for (start, end, esc) in spans {
+        if start < pos {
+            continue; // overlaps an earlier highlight
+        }
+        out.push_str(&line[pos..start]);
+        out.push_str(esc);
+        out.push_str(&line[start..end]);
+        out.push_str(RESET);
+        pos = end;
+    }
+    out.push_str(&line[pos..]);
+    out
+}
+
+static ALIGN: AtomicBool = AtomicBool::new(true);
+static PREFIX_WIDTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Enable or disable prefix padding (`--no-align`).
+pub fn set_align(enabled: bool) {
+    ALIGN.store(enabled, Ordering::Relaxed);
+}
+
+/// Pad prefixes to the longest of `labels` so merged output lines up in columns.
+pub fn align_to<'a>(labels: impl IntoIterator<Item = &'a str>) {
+    if ALIGN.load(Ordering::Relaxed) {
+        let width = labels.into_iter().map(|l| l.chars().count()).max();
+        PREFIX_WIDTH.store(width.unwrap_or(0), Ordering::Relaxed);
+    }
 }
 
-pub fn color_esc_for(label: &str) -> &'static str {
-    let idx = color_index(label);
-    PALETTE[idx]
+/// A parsed `prefix_format` such as `"{time} {name}|"`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrefixFormat(Vec<PrefixPart>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum PrefixPart {
+    Literal(String),
+    Name,
+    Stream,
+    Instance,
+    Time,
+}
+
+impl PrefixFormat {
+    /// Parse a format string. Tokens: `{name}`, `{stream}` (out/err), `{instance}`, `{time}`;
+    /// `{{` and `}}` are literal braces.
+    pub fn parse(s: &str) -> Result<PrefixFormat, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut token = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => token.push(c),
+                            None => return Err("unclosed '{' in prefix format".into()),
+                        }
+                    }
+                    let part = match token.as_str() {
+                        "name" => PrefixPart::Name,
+                        "stream" => PrefixPart::Stream,
+                        "instance" => PrefixPart::Instance,
+                        "time" => PrefixPart::Time,
+                        other => return Err(format!("unknown prefix token '{{{}}}'", other)),
+                    };
+                    if !literal.is_empty() {
+                        parts.push(PrefixPart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(part);
+                }
+                '}' => return Err("unmatched '}' in prefix format".into()),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(PrefixPart::Literal(literal));
+        }
+        Ok(PrefixFormat(parts))
+    }
+
+    fn render(&self, label: &str, stderr: bool, width: usize, esc: Option<&str>) -> String {
+        let mut out = String::new();
+        for part in &self.0 {
+            match part {
+                PrefixPart::Literal(s) => out.push_str(s),
+                PrefixPart::Name => {
+                    match esc {
+                        Some(esc) => out.push_str(&format!("{}{}{}", esc, label, RESET)),
+                        None => out.push_str(label),
+                    }
+                    out.push_str(&" ".repeat(width.saturating_sub(label.chars().count())));
+                }
+                PrefixPart::Stream => out.push_str(if stderr { "err" } else { "out" }),
+                // Every process currently runs as a single instance
+                PrefixPart::Instance => out.push('0'),
+                PrefixPart::Time => {
+                    out.push_str(&chrono::Local::now().format("%H:%M:%S").to_string())
+                }
+            }
+        }
+        out
+    }
+}
+
+static PREFIX_FORMAT: OnceLock<PrefixFormat> = OnceLock::new();
+
+/// Install the project's `prefix_format`. Only the first call has an effect.
+pub fn set_prefix_format(format: PrefixFormat) {
+    let _ = PREFIX_FORMAT.set(format);
 }
 
 pub fn prefix(label: &str) -> String {
+    let width = PREFIX_WIDTH.load(Ordering::Relaxed);
     if color_enabled() {
-        format!("[{}{}{}] ", color_esc_for(label), label, RESET)
+        format_prefix(label, width, Some(&color_esc_for(label)))
     } else {
-        format!("[{}] ", label)
+        format_prefix(label, width, None)
+    }
+}
+
+/// Prefix for a line of process output. Uses `prefix_format` when configured; otherwise
+/// `[name] `, followed by `[ERR] ` for stderr lines.
+pub fn stream_prefix(label: &str, stderr: bool) -> String {
+    let Some(format) = PREFIX_FORMAT.get() else {
+        let p = prefix(label);
+        return if stderr { format!("{}[ERR] ", p) } else { p };
+    };
+    let width = PREFIX_WIDTH.load(Ordering::Relaxed);
+    if color_enabled() {
+        format.render(label, stderr, width, Some(&color_esc_for(label)))
+    } else {
+        format.render(label, stderr, width, None)
+    }
+}
+
+fn format_prefix(label: &str, width: usize, esc: Option<&str>) -> String {
+    let pad = " ".repeat(width.saturating_sub(label.chars().count()));
+    match esc {
+        Some(esc) => format!("[{}{}{}{}] ", esc, label, RESET, pad),
+        None => format!("[{}{}] ", label, pad),
     }
 }
 
@@ -108,6 +506,121 @@ mod tests {
         assert_eq!(a, b);
     }
 
+    #[test]
+    fn highlights_matches_without_overlap() {
+        let rules = vec![
+            HighlightRule {
+                pattern: regex::Regex::new("WARN").unwrap(),
+                color: named_color("yellow").unwrap().to_string(),
+            },
+            HighlightRule {
+                pattern: regex::Regex::new("WARNING").unwrap(),
+                color: named_color("red").unwrap().to_string(),
+            },
+        ];
+        let out = highlight_spans("a WARNING here", &rules);
+        assert_eq!(out, format!("a {}WARN{}ING here", "\u{1b}[33m", RESET));
+        assert_eq!(highlight_spans("all quiet", &rules), "all quiet");
+    }
+
+    #[test]
+    fn resolves_named_colors() {
+        assert_eq!(named_color("Bright-Red"), Some("\u{1b}[91m"));
+        assert!(named_color("chartreuse").is_none());
+    }
+
+    #[test]
+    fn parses_color_specs() {
+        assert_eq!(
+            ColorSpec::parse("#ff8800"),
+            Some(ColorSpec::Rgb(255, 136, 0))
+        );
+        assert_eq!(ColorSpec::parse("208"), Some(ColorSpec::Ansi256(208)));
+        assert_eq!(
+            ColorSpec::parse("cyan"),
+            Some(ColorSpec::Named("\u{1b}[36m"))
+        );
+        assert!(ColorSpec::parse("#ff88").is_none());
+        assert!(ColorSpec::parse("300").is_none());
+    }
+
+    #[test]
+    fn theme_pins_and_degrades_by_depth() {
+        let mut theme = Theme {
+            palette: vec![ColorSpec::Rgb(255, 136, 0)],
+            pinned: HashMap::new(),
+        };
+        theme.pinned.insert("web".into(), ColorSpec::Ansi256(33));
+        assert_eq!(
+            theme_esc_for(&theme, "web", ColorDepth::TrueColor),
+            "\u{1b}[38;5;33m"
+        );
+        assert_eq!(
+            theme_esc_for(&theme, "api", ColorDepth::TrueColor),
+            "\u{1b}[38;2;255;136;0m"
+        );
+        assert_eq!(
+            theme_esc_for(&theme, "api", ColorDepth::Ansi256),
+            "\u{1b}[38;5;214m"
+        );
+        // Nothing usable on a basic terminal: deterministic built-in palette
+        assert_eq!(
+            theme_esc_for(&theme, "api", ColorDepth::Basic),
+            PALETTE[color_index("api")]
+        );
+    }
+
+    #[test]
+    fn assigns_distinct_colors_around_pins() {
+        let mut theme = Theme {
+            palette: ["red", "green", "blue"]
+                .iter()
+                .map(|n| ColorSpec::parse(n).unwrap())
+                .collect(),
+            pinned: HashMap::new(),
+        };
+        theme
+            .pinned
+            .insert("db".into(), ColorSpec::parse("green").unwrap());
+        let labels = ["web", "api", "db"];
+        let a = assign(&theme, labels, ColorDepth::Basic);
+        assert_eq!(a["db"], named_color("green").unwrap());
+        let mut colors: Vec<&String> = a.values().collect();
+        colors.sort();
+        colors.dedup();
+        assert_eq!(colors.len(), 3);
+        // Same names in any order: same colors
+        assert_eq!(assign(&theme, ["db", "api", "web"], ColorDepth::Basic), a);
+
+        // More names than colors still gives every name one
+        let crowd = assign(&theme, ["a", "b", "c", "d", "e"], ColorDepth::Basic);
+        assert_eq!(crowd.len(), 5);
+    }
+
+    #[test]
+    fn aligned_prefixes_pad_inside_brackets() {
+        assert_eq!(format_prefix("web", 6, None), "[web   ] ");
+        assert_eq!(format_prefix("worker", 6, None), "[worker] ");
+        assert_eq!(format_prefix("web", 0, None), "[web] ");
+        assert_eq!(
+            format_prefix("web", 4, Some("\u{1b}[31m")),
+            format!("[\u{1b}[31mweb{} ] ", RESET)
+        );
+    }
+
+    #[test]
+    fn renders_prefix_format_tokens() {
+        let f = PrefixFormat::parse("{name}:{stream}#{instance} {{x}}| ").unwrap();
+        assert_eq!(f.render("web", true, 5, None), "web  :err#0 {x}| ");
+        assert_eq!(f.render("web", false, 0, None), "web:out#0 {x}| ");
+        let t = PrefixFormat::parse("{time} {name}|").unwrap();
+        let out = t.render("api", false, 0, None);
+        assert_eq!(out.len(), "00:00:00 api|".len());
+        assert!(PrefixFormat::parse("{host}").is_err());
+        assert!(PrefixFormat::parse("oops}").is_err());
+        assert!(PrefixFormat::parse("{name").is_err());
+    }
+
     #[test]
     fn prefix_shapes_colored() {
         init(Some(ColorMode::Always));
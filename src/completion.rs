@@ -0,0 +1,59 @@
+// Shell completion (`source <(COMPLETE=bash oxproc)`). Process-name arguments complete from
+// the project in the current directory: names the manager is running for commands that act
+// on running processes, configured names for `start`.
+
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+
+/// Processes recorded in the current project's state.json.
+pub fn managed() -> ArgValueCandidates {
+    ArgValueCandidates::new(|| {
+        let Ok(root) = std::env::current_dir() else {
+            return Vec::new();
+        };
+        let Ok(st) = crate::state::load_state_from_root(&root) else {
+            return Vec::new();
+        };
+        st.processes
+            .iter()
+            .map(|p| {
+                let help = if crate::state::pid_alive(p.pid as i32) {
+                    format!("pid {}", p.pid)
+                } else {
+                    "exited".to_string()
+                };
+                CompletionCandidate::new(&p.name).help(Some(help.into()))
+            })
+            .collect()
+    })
+}
+
+/// Processes in the current project's config.
+pub fn configured() -> ArgValueCandidates {
+    ArgValueCandidates::new(|| {
+        let Ok(root) = std::env::current_dir() else {
+            return Vec::new();
+        };
+        crate::config::load_config_from(&root)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| CompletionCandidate::new(c.name))
+            .collect()
+    })
+}
+
+/// Groups named in the current project's config.
+pub fn groups() -> ArgValueCandidates {
+    ArgValueCandidates::new(|| {
+        let Ok(root) = std::env::current_dir() else {
+            return Vec::new();
+        };
+        let mut groups: Vec<String> = crate::config::load_config_from(&root)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|c| c.group)
+            .collect();
+        groups.sort();
+        groups.dedup();
+        groups.into_iter().map(CompletionCandidate::new).collect()
+    })
+}
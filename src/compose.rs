@@ -0,0 +1,91 @@
+// `type = "compose"`: a service from a docker compose file, run with `docker compose up` in
+// the foreground, so in a mixed stack the compose services start, stop, restart and log
+// through oxproc next to the processes it runs directly.
+
+use std::path::{Path, PathBuf};
+
+use crate::watch::shell_quote;
+
+/// A compose entry's `service` and `file`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComposeSpec {
+    pub service: String,
+    /// The compose file, relative to the project root (`docker-compose.yml` by default)
+    pub file: PathBuf,
+}
+
+impl ComposeSpec {
+    /// Read `service = "postgres"` and `file = "docker-compose.yml"` from a process table.
+    pub fn from_table(tbl: &toml::value::Table, root: &Path) -> Result<ComposeSpec, String> {
+        let service = tbl
+            .get("service")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.trim().is_empty())
+            .ok_or("a compose entry needs 'service = \"name\"'")?
+            .to_string();
+        let file = match tbl.get("file") {
+            None => "docker-compose.yml",
+            Some(v) => v.as_str().ok_or("'file' must be a path")?,
+        };
+        Ok(ComposeSpec {
+            service,
+            file: root.join(file),
+        })
+    }
+
+    /// `docker compose` with the file, as a shell word list.
+    fn compose(&self) -> String {
+        format!(
+            "docker compose -f {}",
+            shell_quote(&self.file.to_string_lossy())
+        )
+    }
+
+    /// Bring the service up in the foreground, with only its own output (unprefixed) in the
+    /// log. Compose stops it on SIGTERM, and `up` exits once it does.
+    pub fn command(&self) -> String {
+        let service = shell_quote(&self.service);
+        format!(
+            "exec {} up --no-log-prefix --attach {} {}",
+            self.compose(),
+            service,
+            service
+        )
+    }
+
+    /// Stop the service, after its `up` was killed before it could.
+    pub async fn stop(&self) {
+        let _ = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!(
+                "{} stop {}",
+                self.compose(),
+                shell_quote(&self.service)
+            ))
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn brings_the_service_up_in_the_foreground() {
+        let tbl: toml::value::Table =
+            toml::from_str("service = \"postgres\"\nfile = \"dev/compose.yml\"").unwrap();
+        let spec = ComposeSpec::from_table(&tbl, Path::new("/work/app")).unwrap();
+        assert_eq!(
+            spec.command(),
+            "exec docker compose -f '/work/app/dev/compose.yml' up --no-log-prefix \
+             --attach 'postgres' 'postgres'"
+        );
+        let tbl: toml::value::Table = toml::from_str("service = \"redis\"").unwrap();
+        let spec = ComposeSpec::from_table(&tbl, Path::new("/work/app")).unwrap();
+        assert_eq!(spec.file, Path::new("/work/app/docker-compose.yml"));
+        assert!(ComposeSpec::from_table(&toml::value::Table::new(), Path::new("/")).is_err());
+    }
+}
@@ -1,8 +1,19 @@
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use thiserror::Error;
 
+use crate::builtin::Builtin;
+use crate::color::{ColorSpec, HighlightRule, PrefixFormat, Theme};
+use crate::container::ContainerSpec;
+use crate::env::{EnvSpec, ProjectEnv};
+use crate::level::LevelPatterns;
+use crate::mirror::MirrorTarget;
+use crate::ready::ReadyCheck;
+use crate::watch::WatchTool;
+use nix::sys::signal::Signal;
+
 // Flexible TOML layout support:
 // - Processes can live under [processes.<name>] or as top-level tables (legacy)
 // - Tasks live under [tasks.<name>]
@@ -19,12 +30,405 @@ pub struct ProcessConfig {
     pub stdout_log: Option<String>,
     pub stderr_log: Option<String>,
     pub cwd: Option<String>,
+    /// Start with the formation (`autostart = false` processes start only on demand)
+    pub autostart: bool,
+    /// Wait this long before spawning (`start_delay = "5s"`)
+    pub start_delay: Option<Duration>,
+    /// Add a random extra delay in `[0, start_jitter)` on top of `start_delay`
+    pub start_jitter: Option<Duration>,
+    /// Which exits the manager restarts the process after (`restart`)
+    pub restart: RestartPolicy,
+    /// Restart the process at most this many times within `restart_window`; unset when
+    /// `restart = "never"`
+    pub max_restarts: Option<u32>,
+    /// Sliding window for `max_restarts` (default 60s)
+    pub restart_window: Duration,
+    /// An exit sooner than this after spawning is a failed start, not a normal exit
+    /// (`min_uptime = "5s"`)
+    pub min_uptime: Option<Duration>,
+    /// Mark the process failed if it is still starting after this long
+    /// (`start_timeout = "60s"`)
+    pub start_timeout: Option<Duration>,
+    /// Also kill it then, instead of leaving it running (`kill_on_start_timeout = true`)
+    pub kill_on_start_timeout: bool,
+    /// Exit codes that count as success (`allowed_exit_codes`, default `[0]`); a process
+    /// killed by a signal exits with 128 plus the signal number
+    pub allowed_exit_codes: Vec<i32>,
+    /// Line classification (`error_pattern`, `warn_pattern`, `info_pattern`)
+    pub levels: LevelPatterns,
+    /// Readiness probe (`ready = "tcp:3000"`); without one a running process counts as ready
+    pub ready: Option<ReadyCheck>,
+    /// Run the command under an external watcher (`watch_tool = "cargo-watch"`)
+    pub watch_tool: Option<WatchTool>,
+    /// Paths the watcher observes (`watch = ["src"]`); the tool's default when empty
+    pub watch: Vec<String>,
+    /// Processes that must be ready before this one starts (`depends_on = ["db"]`)
+    pub depends_on: Vec<String>,
+    /// `env` entries and whether the caller's environment is inherited (`inherit_env`)
+    pub env: EnvSpec,
+    /// Other oxproc projects started before this process (`requires_project`), absolute
+    pub requires_project: Vec<PathBuf>,
+    /// `port = 3000`, available to the command as `{port}`
+    pub port: Option<u16>,
+    /// Drop log lines beyond this rate (`log_rate_limit = "1000/s"`)
+    pub log_rate_limit: Option<RateLimit>,
+    /// Lines matching this continue the previous log record (`multiline = true` or a regex)
+    pub multiline: Option<regex::Regex>,
+    /// How the manager writes log files (`log_format = "json"` for JSONL records)
+    pub log_format: LogFormat,
+    /// Signal that makes the process reload its config in place (`reload_signal = "SIGHUP"`),
+    /// sent by `reload` and `restart --soft` instead of restarting it
+    pub reload_signal: Option<Signal>,
+    /// What the process reads on stdin in daemon mode (`stdin = "null" | "pipe" | "pty"`)
+    pub stdin: StdinMode,
+    /// Output line announcing where the process serves (`url_pattern = "on (http\\S+)"`); the
+    /// first capture group, or the whole match, is shown by `status` and `start`
+    pub url_pattern: Option<regex::Regex>,
+    /// Group the process belongs to (`group = "backend"`), for selecting it with `--group`
+    pub group: Option<String>,
+    /// How readily the Linux OOM killer picks this process (`oom_score_adj`, -1000 to 1000)
+    pub oom_score_adj: Option<i32>,
+    /// Let the process write core dumps and gather them under the state dir (`core_dumps`)
+    pub core_dumps: bool,
+    /// Live copy of the output for other tools (`mirror = "unix:/tmp/web.sock"` or `"fifo:PATH"`)
+    pub mirror: Option<MirrorTarget>,
+    /// Whether the environment the process starts with is kept in state.json (`record_env`)
+    pub record_env: RecordEnv,
+    /// A process oxproc runs itself (`type = "proxy"`, `"forward"` or `"compose"`), with its
+    /// `command` generated
+    pub builtin: Option<Builtin>,
+    /// The container it runs in (`runtime = "docker"` with `image`), its `command` generated
+    pub container: Option<ContainerSpec>,
+}
+
+/// Which exits the manager restarts a process after.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Leave it down (the default without `max_restarts`)
+    #[default]
+    Never,
+    /// After a crash or failed start (the default with `max_restarts`)
+    OnFailure,
+    /// After any exit oxproc didn't ask for, including a clean one
+    Always,
+}
+
+impl RestartPolicy {
+    /// Whether an exit oxproc didn't ask for is followed by a restart. `clean` is an allowed
+    /// exit code after the process stayed up for `min_uptime`.
+    pub fn restarts_after(self, clean: bool) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => !clean,
+            RestartPolicy::Always => true,
+        }
+    }
+}
+
+/// The restart budget for a process with a `restart` policy but no `max_restarts`.
+pub const DEFAULT_MAX_RESTARTS: u32 = 10;
+
+/// Where a daemon-mode process's stdin comes from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StdinMode {
+    /// `/dev/null`: reads see end of file right away
+    #[default]
+    Null,
+    /// A pipe the manager holds open, so the process can be sent input
+    Pipe,
+    /// A pseudo-terminal, for programs that only act interactive on a tty; also writable
+    Pty,
+}
+
+/// How much of a process's environment is recorded at spawn, for `debug process`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecordEnv {
+    /// Nothing (the default): the environment may hold secrets
+    #[default]
+    Off,
+    /// Every variable, with likely credentials replaced by `<redacted>` (`record_env = true`)
+    Redacted,
+    /// Every variable as is (`record_env = "full"`)
+    Full,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// At most `lines` log lines per `per`, e.g. `"1000/s"` or `"5000/10s"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub lines: u32,
+    pub per: Duration,
+}
+
+impl RateLimit {
+    pub fn parse(s: &str) -> Result<RateLimit, String> {
+        let (lines, per) = s
+            .split_once('/')
+            .ok_or_else(|| format!("expected a rate like \"1000/s\", got '{}'", s))?;
+        let lines: u32 = lines
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid line count in '{}'", s))?;
+        let per = per.trim();
+        // "1000/s" means per one second
+        let per = if per.starts_with(|c: char| c.is_ascii_digit()) {
+            parse_duration(per)?
+        } else {
+            parse_duration(&format!("1{}", per))?
+        };
+        if lines == 0 || per.is_zero() {
+            return Err(format!("'{}' would drop every line", s));
+        }
+        Ok(RateLimit { lines, per })
+    }
+}
+
+pub const DEFAULT_RESTART_WINDOW: Duration = Duration::from_secs(60);
+
+impl ProcessConfig {
+    /// Resolved stdout/stderr log paths (`<name>.out.log`/`<name>.err.log` by default).
+    pub fn log_paths(&self, root: &Path) -> (std::path::PathBuf, std::path::PathBuf) {
+        let resolve = |p: String| {
+            if Path::new(&p).is_absolute() {
+                Path::new(&p).to_path_buf()
+            } else {
+                root.join(p)
+            }
+        };
+        let (out, err) = self.log_files(root);
+        (resolve(out), resolve(err))
+    }
+
+    /// The stdout/stderr log files as configured, relative to `root` unless absolute.
+    pub fn log_files(&self, root: &Path) -> (String, String) {
+        // Each namespace logs to its own files (`web.feature-x.out.log`), explicit ones too
+        // (`logs/web.log` → `logs/web.feature-x.log`)
+        let namespace = crate::dirs::variant_for(root).and_then(|v| v.namespace.as_deref());
+        let name = match namespace {
+            Some(namespace) => format!("{}.{}", self.name, namespace),
+            None => self.name.clone(),
+        };
+        let file = |explicit: &Option<String>, stream: &str| match (explicit, namespace) {
+            (Some(path), Some(namespace)) => namespaced_path(path, namespace),
+            (Some(path), None) => path.clone(),
+            (None, _) => format!("{}.{}.log", name, stream),
+        };
+        (file(&self.stdout_log, "out"), file(&self.stderr_log, "err"))
+    }
+
+    /// Directory the process runs in: its `cwd` under `root`, or `root` itself.
+    pub fn working_dir(&self, root: &Path) -> PathBuf {
+        command_dir(root, self.cwd.as_deref())
+    }
+
+    /// The shell command to run, with template variables filled in and wrapped in the
+    /// configured watcher and `env_wrapper` if any.
+    pub fn command_line(&self, root: &Path) -> String {
+        let dir = command_dir(root, self.cwd.as_deref());
+        let command = self.render_command(root);
+        let cmd = match self.watch_tool {
+            Some(tool) => crate::watch::wrap(&command, tool, &self.watch, &dir),
+            None => command,
+        };
+        self.env.wrap_command(&cmd, &dir)
+    }
+
+    /// A command run on the process's behalf (a `ready = { cmd = ... }` check), with the
+    /// same template variables and `env_wrapper` as the process itself.
+    pub fn helper_command_line(&self, root: &Path, template: &str) -> String {
+        let dir = command_dir(root, self.cwd.as_deref());
+        self.env.wrap_command(&self.render(root, template), &dir)
+    }
+
+    /// `sh -c command_line` in the process's directory and environment. Used for the process
+    /// itself and for commands run on its behalf, so they see the same `PORT`, `PATH` and so
+    /// on.
+    pub fn shell_command(&self, root: &Path, command_line: &str) -> tokio::process::Command {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c");
+        cmd.arg(command_line);
+        self.env.apply(&mut cmd);
+        // Output goes to log files, so only an explicit --color at start reaches the processes
+        self.env
+            .apply_color(&mut cmd, crate::color::child_color(false));
+        cmd.current_dir(self.working_dir(root));
+        cmd
+    }
+
+    /// The command with `{project_root}`, `{state_dir}`, `{process}`, `{instance}` and
+    /// `{port}` substituted. Anything else in braces is left for the shell.
+    pub fn render_command(&self, root: &Path) -> String {
+        self.render(root, &self.command)
+    }
+
+    fn render(&self, root: &Path, template: &str) -> String {
+        let mut vars = vec![
+            ("project_root", root.to_string_lossy().to_string()),
+            (
+                "state_dir",
+                crate::dirs::state_dir_for_project(root)
+                    .to_string_lossy()
+                    .to_string(),
+            ),
+            ("process", self.name.clone()),
+            ("instance", "0".to_string()),
+        ];
+        if let Some(port) = self.effective_port() {
+            vars.push(("port", port.to_string()));
+        }
+        render_template(template, &vars)
+    }
+
+    /// Move every port the process listens on (`port`, a `tcp:` ready check, a built-in's
+    /// `listen`) up by `offset`.
+    fn shift_ports(&mut self, offset: u16) -> Result<(), String> {
+        let shift = |port: &mut u16| {
+            *port = port
+                .checked_add(offset)
+                .ok_or_else(|| format!("port {} + offset {} is out of range", port, offset))?;
+            Ok::<(), String>(())
+        };
+        if let Some(port) = self.port.as_mut() {
+            shift(port)?;
+        }
+        if let Some(ReadyCheck::Tcp { port, .. }) = self.ready.as_mut() {
+            shift(port)?;
+        }
+        match self.builtin.as_mut() {
+            Some(crate::builtin::Builtin::Proxy(spec)) => shift(&mut spec.listen),
+            Some(crate::builtin::Builtin::Forward(spec)) => shift(&mut spec.listen),
+            Some(crate::builtin::Builtin::KubectlForward(spec)) => spec
+                .ports
+                .iter_mut()
+                .try_for_each(|(local, _)| shift(local)),
+            Some(crate::builtin::Builtin::Compose(_)) | None => Ok(()),
+        }
+    }
+
+    /// `port`, or else the port the `ready` probe connects to.
+    pub fn effective_port(&self) -> Option<u16> {
+        self.port.or(match self.ready {
+            Some(ReadyCheck::Tcp { port, .. }) => Some(port),
+            _ => None,
+        })
+    }
+
+    /// Effective delay before spawning: `start_delay` plus a random share of `start_jitter`.
+    pub fn startup_delay(&self) -> Duration {
+        let base = self.start_delay.unwrap_or_default();
+        match self.start_jitter {
+            Some(j) if !j.is_zero() => base + j.mul_f64(random_fraction(&self.name)),
+            _ => base,
+        }
+    }
+}
+
+/// Replace `{name}` with its value for each known variable. `${name}` is shell syntax and is
+/// left alone, as are unknown names (brace expansion, awk programs and the like).
+fn render_template(template: &str, vars: &[(&str, String)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let shell_var = out.ends_with('$');
+        let known = after.find('}').and_then(|close| {
+            vars.iter()
+                .find(|(name, _)| *name == &after[..close])
+                .map(|(_, value)| (close, value))
+        });
+        match known {
+            Some((close, value)) if !shell_var => {
+                out.push_str(value);
+                rest = &after[close + 1..];
+            }
+            _ => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A pseudo-random value in [0, 1), seeded per call; good enough to spread start times.
+fn random_fraction(seed: &str) -> f64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut h = std::collections::hash_map::RandomState::new().build_hasher();
+    h.write(seed.as_bytes());
+    (h.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Parse a wall-clock time such as `18:00` or `18:00:30`.
+pub fn parse_time_of_day(s: &str) -> Result<chrono::NaiveTime, String> {
+    let s = s.trim();
+    chrono::NaiveTime::parse_from_str(s, "%H:%M:%S")
+        .or_else(|_| chrono::NaiveTime::parse_from_str(s, "%H:%M"))
+        .map_err(|_| format!("invalid time '{}' (expected HH:MM)", s))
+}
+
+/// Parse a human duration such as `500ms`, `5s`, `2m`, `1h` or `1m30s`.
+/// A bare number is taken as seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err("empty duration".into());
+    }
+    if let Ok(secs) = s.parse::<f64>() {
+        return Duration::try_from_secs_f64(secs).map_err(|e| e.to_string());
+    }
+    let mut total = Duration::ZERO;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let num_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or_else(|| format!("missing unit in '{}'", s))?;
+        if num_len == 0 {
+            return Err(format!("invalid duration '{}'", s));
+        }
+        let value: f64 = rest[..num_len]
+            .parse()
+            .map_err(|_| format!("invalid number in '{}'", s))?;
+        rest = &rest[num_len..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let unit_secs = match &rest[..unit_len] {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            "d" => 86400.0,
+            other => return Err(format!("unknown unit '{}' in '{}'", other, s)),
+        };
+        rest = &rest[unit_len..];
+        total += Duration::try_from_secs_f64(value * unit_secs).map_err(|e| e.to_string())?;
+    }
+    Ok(total)
 }
 
 #[derive(Debug, Clone)]
 pub enum TaskKind {
     /// A shell task executes a command (optionally in a cwd)
-    Shell { cmd: String, cwd: Option<String> },
+    Shell {
+        cmd: String,
+        cwd: Option<String>,
+        env: EnvSpec,
+        /// Exit codes that count as success, as for processes
+        allowed_exit_codes: Vec<i32>,
+        /// A pipeline fails if any command in it fails (`pipefail`; top-level `pipefail` sets
+        /// the default)
+        pipefail: bool,
+        /// Run in this process's container (`in = "api"`), with `cwd` as the directory there
+        inside: Option<String>,
+    },
     /// A composite task triggers other tasks (optionally in parallel)
     Composite {
         children: Vec<String>,
@@ -49,6 +453,27 @@ pub enum ConfigError {
     EmptyProcfile,
     #[error("Invalid task definition for '{0}': {1}")]
     InvalidTask(String, String),
+    #[error("Invalid process definition for '{0}': {1}")]
+    InvalidProcess(String, String),
+    #[error("Invalid [log] setting: {0}")]
+    InvalidLog(String),
+    #[error("Invalid [color] setting: {0}")]
+    InvalidColor(String),
+    #[error("Invalid project setting: {0}")]
+    InvalidSetting(String),
+    #[error("Process '{0}' is defined twice: {1} and {2}")]
+    DuplicateProcess(String, String, String),
+    #[error("No process is in group '{0}' (groups: {1})")]
+    UnknownGroup(String, String),
+}
+
+/// Project-wide log display settings from the `[log]` table.
+#[derive(Debug, Clone, Default)]
+pub struct LogSettings {
+    /// `[log.highlight]`: regex = color name
+    pub highlight: Vec<HighlightRule>,
+    /// `[log] prefix_format`, e.g. `"{time} {name}|"`
+    pub prefix_format: Option<PrefixFormat>,
 }
 
 use serde::Serialize;
@@ -59,8 +484,22 @@ pub enum ConfigSource {
     Procfile,
 }
 
+/// The proc.toml for a project root. The `oxproc service` root reads the global
+/// `services.toml` instead.
+pub fn proc_toml_path(root: &Path) -> PathBuf {
+    if root == crate::dirs::services_root() {
+        crate::dirs::services_config()
+    } else {
+        root.join(crate::dirs::config_file_for(root).unwrap_or("proc.toml"))
+    }
+}
+
 pub fn detect_source(root: &Path) -> Result<ConfigSource, ConfigError> {
-    let proc_toml = root.join("proc.toml");
+    let proc_toml = proc_toml_path(root);
+    // An explicitly chosen file (`-f`) doesn't fall back to the Procfile
+    if crate::dirs::config_file_for(root).is_some() && !proc_toml.exists() {
+        return Err(ConfigError::NoConfigFile);
+    }
     let procfile = root.join("Procfile");
     if proc_toml.exists() {
         Ok(ConfigSource::ProcToml)
@@ -72,10 +511,21 @@ pub fn detect_source(root: &Path) -> Result<ConfigSource, ConfigError> {
 }
 
 pub fn load_config_from(root: &Path) -> Result<Vec<ProcessConfig>, ConfigError> {
-    match detect_source(root)? {
-        ConfigSource::ProcToml => load_processes_from_toml(&root.join("proc.toml")),
-        ConfigSource::Procfile => load_processes_from_procfile(&root.join("Procfile")),
+    let mut processes = match detect_source(root)? {
+        ConfigSource::ProcToml => load_processes(&proc_toml_path(root), root)?,
+        ConfigSource::Procfile => load_processes_from_procfile(&root.join("Procfile"))?,
+    };
+    // `--namespace` with `--port-offset`: a second copy of the formation on other ports
+    if let Some(offset) = crate::dirs::variant_for(root)
+        .map(|v| v.port_offset)
+        .filter(|o| *o != 0)
+    {
+        for p in processes.iter_mut() {
+            p.shift_ports(offset)
+                .map_err(|e| ConfigError::InvalidProcess(p.name.clone(), e))?;
+        }
     }
+    Ok(processes)
 }
 
 fn load_processes_from_procfile(path: &Path) -> Result<Vec<ProcessConfig>, ConfigError> {
@@ -83,24 +533,71 @@ fn load_processes_from_procfile(path: &Path) -> Result<Vec<ProcessConfig>, Confi
     if content.trim().is_empty() {
         return Err(ConfigError::EmptyProcfile);
     }
-    let mut configs = Vec::new();
-    for line in content.lines() {
+    let mut configs: Vec<ProcessConfig> = Vec::new();
+    let mut first_line: HashMap<String, usize> = HashMap::new();
+    for (n, line) in content.lines().enumerate() {
         if let Some((name, command)) = line.split_once(':') {
+            if let Some(first) = first_line.insert(name.trim().to_string(), n + 1) {
+                return Err(ConfigError::DuplicateProcess(
+                    name.trim().to_string(),
+                    format!("Procfile:{}", first),
+                    format!("Procfile:{}", n + 1),
+                ));
+            }
             configs.push(ProcessConfig {
                 name: name.trim().to_string(),
                 command: command.trim().to_string(),
                 stdout_log: None,
                 stderr_log: None,
                 cwd: None,
+                autostart: true,
+                start_delay: None,
+                start_jitter: None,
+                restart: RestartPolicy::Never,
+                max_restarts: None,
+                restart_window: DEFAULT_RESTART_WINDOW,
+                min_uptime: None,
+                start_timeout: None,
+                kill_on_start_timeout: false,
+                allowed_exit_codes: vec![0],
+                levels: LevelPatterns::default(),
+                ready: None,
+                watch_tool: None,
+                watch: Vec::new(),
+                depends_on: Vec::new(),
+                env: EnvSpec::default(),
+                requires_project: Vec::new(),
+                port: None,
+                log_rate_limit: None,
+                multiline: None,
+                log_format: LogFormat::Text,
+                reload_signal: None,
+                stdin: StdinMode::Null,
+                url_pattern: None,
+                group: None,
+                oom_score_adj: None,
+                core_dumps: false,
+                mirror: None,
+                record_env: RecordEnv::Off,
+                builtin: None,
+                container: None,
             });
         }
     }
     Ok(configs)
 }
 
-fn load_processes_from_toml(path: &Path) -> Result<Vec<ProcessConfig>, ConfigError> {
+/// Processes from a proc.toml, with relative paths resolved against `root`.
+fn load_processes(path: &Path, root: &Path) -> Result<Vec<ProcessConfig>, ConfigError> {
     let content = fs::read_to_string(path)?;
     let value: toml::Value = toml::from_str(&content)?;
+    let file = path.file_name().unwrap_or_default().to_string_lossy();
+    let project = project_env(&value, root)?;
+    let project_requires = match value.get("requires_project") {
+        None => Vec::new(),
+        Some(v) => crate::env::parse_path_list("requires_project", v, root)
+            .map_err(ConfigError::InvalidSetting)?,
+    };
 
     let mut processes: HashMap<String, ProcessConfig> = HashMap::new();
 
@@ -108,29 +605,8 @@ fn load_processes_from_toml(path: &Path) -> Result<Vec<ProcessConfig>, ConfigErr
     if let Some(proc_tbl) = value.get("processes").and_then(|v| v.as_table()) {
         for (name, item) in proc_tbl.iter() {
             if let Some(tbl) = item.as_table() {
-                if let Some(cmd) = tbl.get("cmd").and_then(|v| v.as_str()) {
-                    let stdout = tbl
-                        .get("stdout")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string());
-                    let stderr = tbl
-                        .get("stderr")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string());
-                    let cwd = tbl
-                        .get("cwd")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string());
-                    processes.insert(
-                        name.clone(),
-                        ProcessConfig {
-                            name: name.clone(),
-                            command: cmd.to_string(),
-                            stdout_log: stdout,
-                            stderr_log: stderr,
-                            cwd,
-                        },
-                    );
+                if let Some(config) = process_from_table(name, tbl, root, &project)? {
+                    processes.insert(name.clone(), config);
                 }
             }
         }
@@ -139,55 +615,959 @@ fn load_processes_from_toml(path: &Path) -> Result<Vec<ProcessConfig>, ConfigErr
     // 2) Top-level tables (back-compat). Skip reserved key "tasks".
     if let Some(root_tbl) = value.as_table() {
         for (name, item) in root_tbl.iter() {
-            if name == "tasks" || name == "processes" {
+            if name == "tasks" || name == "processes" || name == "log" || name == "color" {
                 continue;
             }
-            if processes.contains_key(name) {
-                continue; // Prefer explicit [processes]
-            }
             if let Some(tbl) = item.as_table() {
-                if let Some(cmd) = tbl.get("cmd").and_then(|v| v.as_str()) {
-                    let stdout = tbl
-                        .get("stdout")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string());
-                    let stderr = tbl
-                        .get("stderr")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string());
-                    let cwd = tbl
-                        .get("cwd")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string());
-                    processes.insert(
+                if processes.contains_key(name)
+                    && ["cmd", "type", "runtime"]
+                        .iter()
+                        .any(|k| tbl.contains_key(*k))
+                {
+                    return Err(ConfigError::DuplicateProcess(
                         name.clone(),
-                        ProcessConfig {
-                            name: name.clone(),
-                            command: cmd.to_string(),
-                            stdout_log: stdout,
-                            stderr_log: stderr,
-                            cwd,
-                        },
-                    );
+                        table_location(&file, &content, &format!("processes.{}", name)),
+                        table_location(&file, &content, name),
+                    ));
+                }
+                if let Some(config) = process_from_table(name, tbl, root, &project)? {
+                    processes.insert(name.clone(), config);
+                }
+            }
+        }
+    }
+
+    let mut processes: Vec<ProcessConfig> = processes.into_values().collect();
+    for p in processes.iter_mut() {
+        for required in &project_requires {
+            if !p.requires_project.contains(required) {
+                p.requires_project.push(required.clone());
+            }
+        }
+    }
+    check_dependencies(&processes)?;
+    crate::builtin::check(&processes).map_err(|(name, e)| ConfigError::InvalidProcess(name, e))?;
+    let allow_external_logs = match value.get("allow_external_logs") {
+        None => false,
+        Some(v) => v.as_bool().ok_or_else(|| {
+            ConfigError::InvalidSetting("'allow_external_logs' must be true or false".into())
+        })?,
+    };
+    if !allow_external_logs {
+        check_log_paths(&processes, root)?;
+    }
+    Ok(processes)
+}
+
+/// Reject `stdout`/`stderr` paths outside the project and its state dir, so a shared
+/// proc.toml can't have oxproc write (or append to) arbitrary files.
+fn check_log_paths(configs: &[ProcessConfig], root: &Path) -> Result<(), ConfigError> {
+    let allowed = [
+        lexically_normal(root),
+        lexically_normal(&crate::dirs::state_dir_for_project(root)),
+    ];
+    for c in configs {
+        let (out, err) = c.log_paths(root);
+        for (key, path) in [("stdout", out), ("stderr", err)] {
+            let path = lexically_normal(&path);
+            if !allowed.iter().any(|dir| path.starts_with(dir)) {
+                return Err(ConfigError::InvalidProcess(
+                    c.name.clone(),
+                    format!(
+                        "'{}' log {} is outside the project and its state dir; set \
+                         allow_external_logs = true at the top of proc.toml to allow it",
+                        key,
+                        path.display()
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `path` with `.` and `..` components resolved without touching the file system.
+fn lexically_normal(path: &Path) -> PathBuf {
+    use std::path::Component;
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Where the table `[header]` is defined in `file` (proc.toml, or the one chosen with `-f`),
+/// for error messages: `FILE:LINE [header]`, or just the header if it isn't written as a
+/// table header.
+fn table_location(file: &str, content: &str, header: &str) -> String {
+    let line = content.lines().position(|l| {
+        l.trim()
+            .strip_prefix('[')
+            .and_then(|rest| rest.split(']').next())
+            .is_some_and(|h| {
+                h.split('.').map(str::trim).collect::<Vec<_>>()
+                    == header.split('.').collect::<Vec<_>>()
+            })
+    });
+    match line {
+        Some(n) => format!("{}:{} [{}]", file, n + 1, header),
+        None => format!("[{}]", header),
+    }
+}
+
+/// Top-level `path_prepend` and `env_wrapper`, applied to every process and task.
+fn project_env(value: &toml::Value, root: &Path) -> Result<ProjectEnv, ConfigError> {
+    ProjectEnv::from_value(value, root).map_err(ConfigError::InvalidSetting)
+}
+
+/// The OS names `cmd` variants are keyed by, as in `std::env::consts::OS`.
+const OS_VARIANTS: &[&str] = &["linux", "macos", "windows"];
+
+/// A `cmd`: a string, or per-OS variants (`cmd.linux = "..."`, `cmd.macos = "..."`) of which
+/// this OS's is used. `None` when there is no variant for this OS.
+fn os_command(value: &toml::Value) -> Result<Option<String>, String> {
+    match value {
+        toml::Value::String(s) => Ok(Some(s.clone())),
+        toml::Value::Table(variants) => {
+            for (os, v) in variants {
+                if !OS_VARIANTS.contains(&os.as_str()) {
+                    return Err(format!(
+                        "unknown OS in 'cmd.{}' (expected {})",
+                        os,
+                        OS_VARIANTS.join(", ")
+                    ));
+                }
+                if !v.is_str() {
+                    return Err(format!("'cmd.{}' must be a string", os));
                 }
             }
+            Ok(variants
+                .get(std::env::consts::OS)
+                .and_then(|v| v.as_str())
+                .map(str::to_string))
+        }
+        _ => Err("'cmd' must be a string, or per-OS strings like cmd.linux = \"...\"".into()),
+    }
+}
+
+/// The warning for a table whose per-OS `cmd` has no variant for this OS.
+fn missing_os_variant(tbl: &toml::value::Table) -> Option<String> {
+    let variants = tbl.get("cmd")?.as_table()?;
+    let os = std::env::consts::OS;
+    (!variants.contains_key(os)).then(|| format!("has no 'cmd.{}' and is skipped on this OS", os))
+}
+
+/// The warning for a `max_restarts` that `restart = "never"` makes pointless.
+fn ignored_max_restarts(tbl: &toml::value::Table) -> Option<String> {
+    let never = tbl.get("restart").and_then(|v| v.as_str()) == Some("never");
+    (never && tbl.contains_key("max_restarts"))
+        .then(|| "max_restarts is ignored with restart = \"never\"".to_string())
+}
+
+/// `allowed_exit_codes`: a list of codes from 0 to 255, `[0]` when unset.
+fn parse_exit_codes(value: Option<&toml::Value>) -> Result<Vec<i32>, String> {
+    let Some(value) = value else {
+        return Ok(vec![0]);
+    };
+    let err = || "'allowed_exit_codes' must be an array of integers from 0 to 255".to_string();
+    let items = value.as_array().ok_or_else(err)?;
+    items
+        .iter()
+        .map(|v| {
+            v.as_integer()
+                .filter(|n| (0..=255).contains(n))
+                .map(|n| n as i32)
+                .ok_or_else(err)
+        })
+        .collect()
+}
+
+/// `status` as a shell reports it: the exit code, or 128 plus the signal number for a
+/// process killed by a signal (143 for SIGTERM).
+pub fn shell_exit_code(status: &std::process::ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.code().or_else(|| status.signal().map(|s| 128 + s))
+    }
+    #[cfg(not(unix))]
+    {
+        status.code()
+    }
+}
+
+/// `path` with `.namespace` after the file's stem: `logs/api.out.log` → `logs/api.ns.out.log`.
+fn namespaced_path(path: &str, namespace: &str) -> String {
+    let file_start = path.rfind('/').map_or(0, |i| i + 1);
+    // A leading dot (`.api.log`) is part of the stem
+    let stem_end = path[file_start..]
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| *c == '.')
+        .map_or(path.len(), |(i, _)| file_start + i);
+    format!("{}.{}{}", &path[..stem_end], namespace, &path[stem_end..])
+}
+
+/// `Ok` when `status` is one of `allowed_exit_codes`, else the code to report: the shell
+/// exit code, or 1 when there is none.
+pub fn check_exit(
+    status: &std::process::ExitStatus,
+    allowed_exit_codes: &[i32],
+) -> Result<(), i32> {
+    match shell_exit_code(status) {
+        Some(code) if allowed_exit_codes.contains(&code) => Ok(()),
+        Some(code) => Err(code),
+        None => Err(1),
+    }
+}
+
+/// The directory a command runs in: its `cwd` under the project root, or the root itself.
+fn command_dir(root: &Path, cwd: Option<&str>) -> PathBuf {
+    match cwd {
+        Some(cwd) => root.join(cwd),
+        None => root.to_path_buf(),
+    }
+}
+
+/// Build a process from its table; tables without a `cmd` (for this OS), built-in `type` or
+/// container `runtime` are not processes.
+fn process_from_table(
+    name: &str,
+    tbl: &toml::value::Table,
+    root: &Path,
+    project: &ProjectEnv,
+) -> Result<Option<ProcessConfig>, ConfigError> {
+    let builtin = Builtin::from_table(tbl, root)
+        .map_err(|e| ConfigError::InvalidProcess(name.to_string(), e))?;
+    let mut container = ContainerSpec::from_table(tbl, root)
+        .map_err(|e| ConfigError::InvalidProcess(name.to_string(), e))?;
+    // Published ports are written into the generated `docker run`, so `--port-offset` moves
+    // them here rather than in `shift_ports`
+    if let (Some(container), Some(variant)) = (container.as_mut(), crate::dirs::variant_for(root)) {
+        container
+            .shift_host_ports(variant.port_offset)
+            .map_err(|e| ConfigError::InvalidProcess(name.to_string(), e))?;
+    }
+    let cmd = tbl
+        .get("cmd")
+        .map(os_command)
+        .transpose()
+        .map_err(|e| ConfigError::InvalidProcess(name.to_string(), e))?
+        .flatten();
+    let cmd = match (&builtin, cmd) {
+        (Some(builtin), _) => builtin.command(root, name),
+        (None, Some(cmd)) => cmd,
+        // A container runs its image's own command without one
+        (None, None) if container.is_some() => String::new(),
+        (None, None) => return Ok(None),
+    };
+    let get_str = |key: &str| tbl.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+    let autostart = match tbl.get("autostart") {
+        None => true,
+        Some(v) => v.as_bool().ok_or_else(|| {
+            ConfigError::InvalidProcess(name.to_string(), "'autostart' must be a boolean".into())
+        })?,
+    };
+    let kill_on_start_timeout = match tbl.get("kill_on_start_timeout") {
+        None => false,
+        Some(v) => v.as_bool().ok_or_else(|| {
+            ConfigError::InvalidProcess(
+                name.to_string(),
+                "'kill_on_start_timeout' must be a boolean".into(),
+            )
+        })?,
+    };
+    let allowed_exit_codes = parse_exit_codes(tbl.get("allowed_exit_codes"))
+        .map_err(|e| ConfigError::InvalidProcess(name.to_string(), e))?;
+    let max_restarts = match tbl.get("max_restarts") {
+        // Supervising a kubectl forward means bringing it back when it drops
+        None => match &builtin {
+            Some(Builtin::KubectlForward(_)) => Some(crate::kubectl::DEFAULT_MAX_RESTARTS),
+            _ => None,
+        },
+        Some(v) => Some(
+            v.as_integer()
+                .and_then(|n| u32::try_from(n).ok())
+                .ok_or_else(|| {
+                    ConfigError::InvalidProcess(
+                        name.to_string(),
+                        "'max_restarts' must be a non-negative integer".into(),
+                    )
+                })?,
+        ),
+    };
+    let restart = match tbl.get("restart").map(|v| v.as_str()) {
+        None if max_restarts.is_some() => RestartPolicy::OnFailure,
+        None | Some(Some("never")) => RestartPolicy::Never,
+        Some(Some("on-failure")) => RestartPolicy::OnFailure,
+        Some(Some("always")) => RestartPolicy::Always,
+        Some(_) => {
+            return Err(ConfigError::InvalidProcess(
+                name.to_string(),
+                "'restart' must be \"always\", \"on-failure\" or \"never\"".into(),
+            ))
+        }
+    };
+    let max_restarts = match restart {
+        RestartPolicy::Never => None,
+        _ => Some(max_restarts.unwrap_or(DEFAULT_MAX_RESTARTS)),
+    };
+    let ready = match tbl.get("ready") {
+        // A forward is ready once its local port accepts connections
+        None => match &builtin {
+            Some(Builtin::Forward(spec)) => Some(spec.listen),
+            Some(Builtin::KubectlForward(spec)) => Some(spec.listen()),
+            _ => None,
+        }
+        .map(|port| ReadyCheck::Tcp {
+            host: "127.0.0.1".into(),
+            port,
+        }),
+        Some(toml::Value::String(s)) => Some(
+            ReadyCheck::parse(s).map_err(|e| ConfigError::InvalidProcess(name.to_string(), e))?,
+        ),
+        Some(toml::Value::Table(t)) => match t.get("cmd").and_then(|v| v.as_str()) {
+            Some(cmd) if t.len() == 1 => Some(ReadyCheck::Command(cmd.to_string())),
+            _ => {
+                return Err(ConfigError::InvalidProcess(
+                    name.to_string(),
+                    "'ready' as a table takes only 'cmd', e.g. { cmd = \"curl -f localhost:{port}/health\" }".into(),
+                ))
+            }
+        },
+        Some(_) => {
+            return Err(ConfigError::InvalidProcess(
+                name.to_string(),
+                "'ready' must be a string like \"tcp:3000\" or a table like { cmd = \"...\" }"
+                    .into(),
+            ))
+        }
+    };
+    let watch_tool = match tbl.get("watch_tool") {
+        None => None,
+        Some(toml::Value::String(s)) => Some(
+            WatchTool::parse(s).map_err(|e| ConfigError::InvalidProcess(name.to_string(), e))?,
+        ),
+        Some(_) => {
+            return Err(ConfigError::InvalidProcess(
+                name.to_string(),
+                "'watch_tool' must be a string".into(),
+            ))
+        }
+    };
+    let watch = get_str_list(name, tbl, "watch")?;
+    let depends_on = get_str_list(name, tbl, "depends_on")?;
+    // Paths may use `~`, `$VAR` and `${VAR}`
+    let get_path = |key: &str| {
+        get_str(key)
+            .map(|p| {
+                crate::env::expand_vars(&p).map_err(|e| {
+                    ConfigError::InvalidProcess(name.to_string(), format!("'{}': {}", key, e))
+                })
+            })
+            .transpose()
+    };
+    let cwd = get_path("cwd")?;
+    let dir = command_dir(root, cwd.as_deref());
+    let env = EnvSpec::from_table(tbl, &dir)
+        .map_err(|e| ConfigError::InvalidProcess(name.to_string(), e))?
+        .with_project(project);
+    let port = match tbl.get("port") {
+        None => None,
+        Some(v) => Some(
+            v.as_integer()
+                .and_then(|n| u16::try_from(n).ok())
+                .filter(|p| *p > 0)
+                .ok_or_else(|| {
+                    ConfigError::InvalidProcess(
+                        name.to_string(),
+                        "'port' must be a port number".into(),
+                    )
+                })?,
+        ),
+    };
+    // A built-in listens on its `listen` port unless told otherwise
+    let port = port.or(builtin.as_ref().and_then(Builtin::listen));
+    if cmd.contains("{port}") && port.is_none() && ready.is_none() {
+        return Err(ConfigError::InvalidProcess(
+            name.to_string(),
+            "command uses {port} but neither 'port' nor a tcp 'ready' check is set".into(),
+        ));
+    }
+    let log_rate_limit = match tbl.get("log_rate_limit") {
+        None => None,
+        Some(v) => Some(
+            v.as_str()
+                .ok_or_else(|| "must be a string like \"1000/s\"".to_string())
+                .and_then(RateLimit::parse)
+                .map_err(|e| {
+                    ConfigError::InvalidProcess(name.to_string(), format!("'log_rate_limit' {}", e))
+                })?,
+        ),
+    };
+    let multiline = match tbl.get("multiline") {
+        None | Some(toml::Value::Boolean(false)) => None,
+        Some(toml::Value::Boolean(true)) => {
+            Some(regex::Regex::new(crate::records::DEFAULT_CONTINUATION).expect("valid default"))
+        }
+        Some(toml::Value::String(_)) => get_regex(name, tbl, "multiline")?,
+        Some(_) => {
+            return Err(ConfigError::InvalidProcess(
+                name.to_string(),
+                "'multiline' must be true, false or a regex".into(),
+            ))
+        }
+    };
+    let log_format = match tbl.get("log_format").map(|v| v.as_str()) {
+        None | Some(Some("text")) => LogFormat::Text,
+        Some(Some("json")) => LogFormat::Json,
+        Some(_) => {
+            return Err(ConfigError::InvalidProcess(
+                name.to_string(),
+                "'log_format' must be \"text\" or \"json\"".into(),
+            ))
+        }
+    };
+    let oom_score_adj = match tbl.get("oom_score_adj") {
+        None => None,
+        Some(v) => Some(
+            v.as_integer()
+                .and_then(|n| i32::try_from(n).ok())
+                .filter(|n| crate::oom::RANGE.contains(n))
+                .ok_or_else(|| {
+                    ConfigError::InvalidProcess(
+                        name.to_string(),
+                        "'oom_score_adj' must be an integer from -1000 to 1000".into(),
+                    )
+                })?,
+        ),
+    };
+    let core_dumps = match tbl.get("core_dumps") {
+        None => false,
+        Some(v) => v.as_bool().ok_or_else(|| {
+            ConfigError::InvalidProcess(name.to_string(), "'core_dumps' must be a boolean".into())
+        })?,
+    };
+    let mirror = get_path("mirror")?
+        .map(|m| MirrorTarget::parse(&m, root))
+        .transpose()
+        .map_err(|e| ConfigError::InvalidProcess(name.to_string(), e))?;
+    let record_env = match tbl.get("record_env") {
+        None | Some(toml::Value::Boolean(false)) => RecordEnv::Off,
+        Some(toml::Value::Boolean(true)) => RecordEnv::Redacted,
+        Some(toml::Value::String(s)) if s == "full" => RecordEnv::Full,
+        Some(_) => {
+            return Err(ConfigError::InvalidProcess(
+                name.to_string(),
+                "'record_env' must be true, false or \"full\"".into(),
+            ))
+        }
+    };
+    let group = match tbl.get("group") {
+        None => None,
+        Some(toml::Value::String(s)) if !s.is_empty() => Some(s.clone()),
+        Some(_) => {
+            return Err(ConfigError::InvalidProcess(
+                name.to_string(),
+                "'group' must be a non-empty string".into(),
+            ))
+        }
+    };
+    let reload_signal = match tbl.get("reload_signal") {
+        None => None,
+        Some(v) => Some(v.as_str().and_then(parse_signal).ok_or_else(|| {
+            ConfigError::InvalidProcess(
+                name.to_string(),
+                "'reload_signal' must be a signal name like \"SIGHUP\"".into(),
+            )
+        })?),
+    };
+    let stdin = match tbl.get("stdin").map(|v| v.as_str()) {
+        None | Some(Some("null")) => StdinMode::Null,
+        Some(Some("pipe")) => StdinMode::Pipe,
+        Some(Some("pty")) => StdinMode::Pty,
+        Some(_) => {
+            return Err(ConfigError::InvalidProcess(
+                name.to_string(),
+                "'stdin' must be \"null\", \"pipe\" or \"pty\"".into(),
+            ))
+        }
+    };
+    let requires_project = match tbl.get("requires_project") {
+        None => Vec::new(),
+        Some(v) => crate::env::parse_path_list("requires_project", v, root)
+            .map_err(|e| ConfigError::InvalidProcess(name.to_string(), e))?,
+    };
+    let cmd = match &container {
+        Some(container) => {
+            let args = Some(cmd.as_str()).filter(|c| !c.is_empty());
+            container.command(root, name, args, env.vars.keys())
+        }
+        None => cmd,
+    };
+    Ok(Some(ProcessConfig {
+        name: name.to_string(),
+        command: cmd,
+        stdout_log: get_path("stdout")?,
+        stderr_log: get_path("stderr")?,
+        cwd,
+        autostart,
+        start_delay: get_duration(name, tbl, "start_delay")?,
+        start_jitter: get_duration(name, tbl, "start_jitter")?,
+        restart,
+        max_restarts,
+        restart_window: get_duration(name, tbl, "restart_window")?
+            .unwrap_or(DEFAULT_RESTART_WINDOW),
+        min_uptime: get_duration(name, tbl, "min_uptime")?,
+        start_timeout: get_duration(name, tbl, "start_timeout")?,
+        kill_on_start_timeout,
+        allowed_exit_codes,
+        levels: LevelPatterns {
+            error: get_regex(name, tbl, "error_pattern")?,
+            warn: get_regex(name, tbl, "warn_pattern")?,
+            info: get_regex(name, tbl, "info_pattern")?,
+        },
+        ready,
+        watch_tool,
+        watch,
+        depends_on,
+        env,
+        requires_project,
+        port,
+        log_rate_limit,
+        multiline,
+        log_format,
+        reload_signal,
+        stdin,
+        url_pattern: get_regex(name, tbl, "url_pattern")?,
+        group,
+        oom_score_adj,
+        core_dumps,
+        mirror,
+        record_env,
+        builtin,
+        container,
+    }))
+}
+
+/// The URL `line` announces under a `url_pattern`: its first capture group, or the whole match.
+pub fn url_in(pattern: &regex::Regex, line: &str) -> Option<String> {
+    let caps = pattern.captures(line)?;
+    let url = caps.get(1).or_else(|| caps.get(0))?;
+    Some(url.as_str().to_string())
+}
+
+/// A signal by name, with or without the `SIG` prefix (`"SIGHUP"`, `"usr2"`).
+pub fn parse_signal(s: &str) -> Option<Signal> {
+    let upper = s.trim().to_ascii_uppercase();
+    let name = if upper.starts_with("SIG") {
+        upper
+    } else {
+        format!("SIG{}", upper)
+    };
+    name.parse().ok()
+}
+
+/// Read an optional list of strings; a single string is accepted as a one-item list.
+fn get_str_list(
+    name: &str,
+    tbl: &toml::value::Table,
+    key: &str,
+) -> Result<Vec<String>, ConfigError> {
+    let invalid = || {
+        ConfigError::InvalidProcess(
+            name.to_string(),
+            format!("'{}' must be a list of strings", key),
+        )
+    };
+    match tbl.get(key) {
+        None => Ok(Vec::new()),
+        Some(toml::Value::String(s)) => Ok(vec![s.clone()]),
+        Some(toml::Value::Array(items)) => items
+            .iter()
+            .map(|v| v.as_str().map(|s| s.to_string()))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid),
+        Some(_) => Err(invalid()),
+    }
+}
+
+/// Reject `depends_on` entries naming unknown processes and dependency cycles.
+fn check_dependencies(configs: &[ProcessConfig]) -> Result<(), ConfigError> {
+    let by_name: HashMap<&str, &ProcessConfig> =
+        configs.iter().map(|c| (c.name.as_str(), c)).collect();
+    for c in configs {
+        for dep in &c.depends_on {
+            if !by_name.contains_key(dep.as_str()) {
+                return Err(ConfigError::InvalidProcess(
+                    c.name.clone(),
+                    format!("depends on unknown process '{}'", dep),
+                ));
+            }
+        }
+    }
+    // Depth-first walk from each process; reaching a process already on the path is a cycle
+    fn visit<'a>(
+        name: &'a str,
+        by_name: &HashMap<&str, &'a ProcessConfig>,
+        path: &mut Vec<&'a str>,
+        done: &mut std::collections::HashSet<&'a str>,
+    ) -> Result<(), ConfigError> {
+        if done.contains(name) {
+            return Ok(());
+        }
+        if let Some(pos) = path.iter().position(|n| *n == name) {
+            let mut cycle: Vec<&str> = path[pos..].to_vec();
+            cycle.push(name);
+            return Err(ConfigError::InvalidProcess(
+                name.to_string(),
+                format!("dependency cycle: {}", cycle.join(" -> ")),
+            ));
+        }
+        path.push(name);
+        for dep in &by_name[name].depends_on {
+            visit(dep, by_name, path, done)?;
+        }
+        path.pop();
+        done.insert(name);
+        Ok(())
+    }
+    let mut done = std::collections::HashSet::new();
+    for c in configs {
+        visit(&c.name, &by_name, &mut Vec::new(), &mut done)?;
+    }
+    Ok(())
+}
+
+/// The processes chosen by `names` and `--group`s: the named ones, then each group's members,
+/// without repeats. A group no process is in is an error rather than an
+/// empty selection, which commands would take to mean every process.
+pub fn select(
+    configs: &[ProcessConfig],
+    names: &[String],
+    groups: &[String],
+) -> Result<Vec<String>, ConfigError> {
+    let mut selected = names.to_vec();
+    for group in groups {
+        let members: Vec<&ProcessConfig> = configs
+            .iter()
+            .filter(|c| c.group.as_ref() == Some(group))
+            .collect();
+        if members.is_empty() {
+            let mut known: Vec<&str> = configs.iter().filter_map(|c| c.group.as_deref()).collect();
+            known.sort();
+            known.dedup();
+            let known = if known.is_empty() {
+                "none defined".to_string()
+            } else {
+                known.join(", ")
+            };
+            return Err(ConfigError::UnknownGroup(group.clone(), known));
+        }
+        for c in members {
+            if !selected.contains(&c.name) {
+                selected.push(c.name.clone());
+            }
+        }
+    }
+    Ok(selected)
+}
+
+/// `names` plus everything they depend on, transitively.
+pub fn with_dependencies(configs: &[ProcessConfig], names: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    let mut queue: Vec<String> = names.to_vec();
+    while let Some(name) = queue.pop() {
+        if out.contains(&name) {
+            continue;
+        }
+        if let Some(c) = configs.iter().find(|c| c.name == name) {
+            queue.extend(c.depends_on.iter().cloned());
+        }
+        out.push(name);
+    }
+    out
+}
+
+/// `names` in the order they start: each after what it depends on, otherwise in config order.
+/// Dependencies outside `names` are skipped.
+pub fn start_order(configs: &[ProcessConfig], names: &[String]) -> Vec<String> {
+    let mut order: Vec<String> = Vec::new();
+    while order.len() < names.len() {
+        let before = order.len();
+        for c in configs {
+            let ready = c
+                .depends_on
+                .iter()
+                .all(|d| order.contains(d) || !names.contains(d));
+            if names.contains(&c.name) && !order.contains(&c.name) && ready {
+                order.push(c.name.clone());
+            }
+        }
+        if order.len() == before {
+            // Cycles are rejected at load time; don't spin on a hand-built config
+            break;
+        }
+    }
+    order
+}
+
+fn get_regex(
+    name: &str,
+    tbl: &toml::value::Table,
+    key: &str,
+) -> Result<Option<regex::Regex>, ConfigError> {
+    let invalid = |msg: String| ConfigError::InvalidProcess(name.to_string(), msg);
+    match tbl.get(key) {
+        None => Ok(None),
+        Some(toml::Value::String(s)) => regex::Regex::new(s)
+            .map(Some)
+            .map_err(|e| invalid(format!("'{}' is not a valid regex: {}", key, e))),
+        Some(_) => Err(invalid(format!("'{}' must be a string", key))),
+    }
+}
+
+/// Read an optional duration key given as a string (`"5s"`) or a number of seconds.
+fn get_duration(
+    name: &str,
+    tbl: &toml::value::Table,
+    key: &str,
+) -> Result<Option<Duration>, ConfigError> {
+    let invalid = |msg: String| ConfigError::InvalidProcess(name.to_string(), msg);
+    match tbl.get(key) {
+        None => Ok(None),
+        Some(toml::Value::String(s)) => parse_duration(s)
+            .map(Some)
+            .map_err(|e| invalid(format!("'{}': {}", key, e))),
+        Some(toml::Value::Integer(n)) if *n >= 0 => Ok(Some(Duration::from_secs(*n as u64))),
+        Some(toml::Value::Float(f)) if *f >= 0.0 => Duration::try_from_secs_f64(*f)
+            .map(Some)
+            .map_err(|e| invalid(format!("'{}': {}", key, e))),
+        Some(_) => Err(invalid(format!("'{}' must be a duration like \"5s\"", key))),
+    }
+}
+
+pub fn load_log_settings(root: &Path) -> Result<LogSettings, ConfigError> {
+    if detect_source(root)? != ConfigSource::ProcToml {
+        return Ok(LogSettings::default());
+    }
+    let content = fs::read_to_string(proc_toml_path(root))?;
+    let value: toml::Value = toml::from_str(&content)?;
+    let mut settings = LogSettings::default();
+    let Some(log) = value.get("log").and_then(|v| v.as_table()) else {
+        return Ok(settings);
+    };
+    if let Some(fmt) = log.get("prefix_format") {
+        let fmt = fmt
+            .as_str()
+            .ok_or_else(|| ConfigError::InvalidLog("'prefix_format' must be a string".into()))?;
+        settings.prefix_format = Some(
+            PrefixFormat::parse(fmt)
+                .map_err(|e| ConfigError::InvalidLog(format!("prefix_format: {}", e)))?,
+        );
+    }
+    if let Some(hl) = log.get("highlight") {
+        let tbl = hl
+            .as_table()
+            .ok_or_else(|| ConfigError::InvalidLog("'highlight' must be a table".into()))?;
+        for (pattern, color) in tbl.iter() {
+            let color_name = color.as_str().ok_or_else(|| {
+                ConfigError::InvalidLog(format!(
+                    "highlight color for '{}' must be a string",
+                    pattern
+                ))
+            })?;
+            let esc = crate::color::named_color(color_name).ok_or_else(|| {
+                ConfigError::InvalidLog(format!("unknown color '{}' for '{}'", color_name, pattern))
+            })?;
+            let re = regex::Regex::new(pattern).map_err(|e| {
+                ConfigError::InvalidLog(format!("'{}' is not a valid regex: {}", pattern, e))
+            })?;
+            settings.highlight.push(HighlightRule {
+                pattern: re,
+                color: esc.to_string(),
+            });
         }
     }
+    Ok(settings)
+}
+
+/// Top-level `name = "acme-api"`: a readable label for the project in status output and
+/// listings. The hashed root path still identifies the project.
+pub fn load_project_name(root: &Path) -> Result<Option<String>, ConfigError> {
+    if detect_source(root)? != ConfigSource::ProcToml {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(proc_toml_path(root))?;
+    let value: toml::Value = toml::from_str(&content)?;
+    match value.get("name") {
+        None => Ok(None),
+        Some(toml::Value::String(name)) if !name.trim().is_empty() => {
+            Ok(Some(name.trim().to_string()))
+        }
+        Some(_) => Err(ConfigError::InvalidSetting(
+            "'name' must be a non-empty string".into(),
+        )),
+    }
+}
+
+/// Top-level `forward_user_signals = true`: foreground mode also passes SIGUSR1/SIGUSR2 on
+/// to the children.
+pub fn load_forward_user_signals(root: &Path) -> Result<bool, ConfigError> {
+    if detect_source(root)? != ConfigSource::ProcToml {
+        return Ok(false);
+    }
+    let content = fs::read_to_string(proc_toml_path(root))?;
+    let value: toml::Value = toml::from_str(&content)?;
+    match value.get("forward_user_signals") {
+        None => Ok(false),
+        Some(v) => v.as_bool().ok_or_else(|| {
+            ConfigError::InvalidSetting("'forward_user_signals' must be true or false".into())
+        }),
+    }
+}
+
+/// What the manager does once the formation has been idle for `idle_timeout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleAction {
+    /// Stop every process and exit, as `oxproc stop` would
+    Stop,
+    /// Pause every process (SIGSTOP) until the next `oxproc start`
+    Suspend,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleShutdown {
+    pub timeout: Duration,
+    pub action: IdleAction,
+}
 
-    Ok(processes.into_values().collect())
+/// Top-level `idle_timeout = "30m"` and `idle_action = "stop" | "suspend"` (default stop).
+pub fn load_idle_shutdown(root: &Path) -> Result<Option<IdleShutdown>, ConfigError> {
+    if detect_source(root)? != ConfigSource::ProcToml {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(proc_toml_path(root))?;
+    let value: toml::Value = toml::from_str(&content)?;
+    let action = match value.get("idle_action").map(|v| v.as_str()) {
+        None | Some(Some("stop")) => IdleAction::Stop,
+        Some(Some("suspend")) => IdleAction::Suspend,
+        Some(_) => {
+            return Err(ConfigError::InvalidSetting(
+                "'idle_action' must be \"stop\" or \"suspend\"".into(),
+            ))
+        }
+    };
+    let Some(timeout) = value.get("idle_timeout") else {
+        if value.get("idle_action").is_some() {
+            return Err(ConfigError::InvalidSetting(
+                "'idle_action' needs an 'idle_timeout'".into(),
+            ));
+        }
+        return Ok(None);
+    };
+    let timeout = timeout
+        .as_str()
+        .ok_or_else(|| "must be a duration like \"30m\"".to_string())
+        .and_then(parse_duration)
+        .and_then(|d| {
+            if d.is_zero() {
+                Err("must be more than zero".to_string())
+            } else {
+                Ok(d)
+            }
+        })
+        .map_err(|e| ConfigError::InvalidSetting(format!("'idle_timeout' {}", e)))?;
+    Ok(Some(IdleShutdown { timeout, action }))
+}
+
+/// Top-level `low_power = true`: poll less often while on battery.
+pub fn load_low_power(root: &Path) -> Result<bool, ConfigError> {
+    if detect_source(root)? != ConfigSource::ProcToml {
+        return Ok(false);
+    }
+    let content = fs::read_to_string(proc_toml_path(root))?;
+    let value: toml::Value = toml::from_str(&content)?;
+    match value.get("low_power") {
+        None => Ok(false),
+        Some(v) => v
+            .as_bool()
+            .ok_or_else(|| ConfigError::InvalidSetting("'low_power' must be true or false".into())),
+    }
+}
+
+/// Load the `[color]` table: `palette = [...]` and `[color.pin] name = "color"`, along with
+/// processes' own `color` keys. Colors may be names, 256-color indexes or `#rrggbb`.
+pub fn load_color_theme(root: &Path) -> Result<Theme, ConfigError> {
+    if detect_source(root)? != ConfigSource::ProcToml {
+        return Ok(Theme::default());
+    }
+    let content = fs::read_to_string(proc_toml_path(root))?;
+    let value: toml::Value = toml::from_str(&content)?;
+    let mut theme = Theme::default();
+    fn spec(v: &toml::Value, what: &str) -> Result<ColorSpec, ConfigError> {
+        let parsed = match v {
+            toml::Value::String(s) => ColorSpec::parse(s),
+            toml::Value::Integer(n) => u8::try_from(*n).ok().map(ColorSpec::Ansi256),
+            _ => None,
+        };
+        parsed.ok_or_else(|| ConfigError::InvalidColor(format!("invalid color {} for {}", v, what)))
+    }
+    // A process's own `color` pins it like a `[color.pin]` entry
+    if let Some(procs) = value.get("processes").and_then(|v| v.as_table()) {
+        for (name, tbl) in procs {
+            if let Some(v) = tbl.get("color") {
+                theme
+                    .pinned
+                    .insert(name.clone(), spec(v, &format!("'{}'", name))?);
+            }
+        }
+    }
+    let Some(color) = value.get("color").and_then(|v| v.as_table()) else {
+        return Ok(theme);
+    };
+    if let Some(palette) = color.get("palette") {
+        let items = palette
+            .as_array()
+            .ok_or_else(|| ConfigError::InvalidColor("'palette' must be an array".into()))?;
+        for item in items {
+            theme.palette.push(spec(item, "palette")?);
+        }
+    }
+    if let Some(pin) = color.get("pin") {
+        let tbl = pin
+            .as_table()
+            .ok_or_else(|| ConfigError::InvalidColor("'pin' must be a table".into()))?;
+        for (name, v) in tbl.iter() {
+            theme
+                .pinned
+                .insert(name.clone(), spec(v, &format!("'{}'", name))?);
+        }
+    }
+    Ok(theme)
 }
 
 pub fn load_tasks_from(root: &Path) -> Result<Option<HashMap<String, TaskConfig>>, ConfigError> {
     match detect_source(root)? {
         ConfigSource::Procfile => Ok(None),
         ConfigSource::ProcToml => {
-            let content = fs::read_to_string(root.join("proc.toml"))?;
+            let content = fs::read_to_string(proc_toml_path(root))?;
             let value: toml::Value = toml::from_str(&content)?;
+            let project = project_env(&value, root)?;
             let mut tasks: HashMap<String, TaskConfig> = HashMap::new();
             if let Some(tbl) = value.get("tasks").and_then(|v| v.as_table()) {
                 fn collect_tasks(
                     prefix: &str,
                     table: &toml::value::Table,
+                    root: &Path,
+                    project: &ProjectEnv,
+                    pipefail_default: bool,
                     tasks: &mut HashMap<String, TaskConfig>,
                 ) -> Result<(), ConfigError> {
                     for (key, val) in table.iter() {
@@ -211,35 +1591,81 @@ pub fn load_tasks_from(root: &Path) -> Result<Option<HashMap<String, TaskConfig>
                                 }
 
                                 if has_cmd {
-                                    let cmd = child
-                                        .get("cmd")
+                                    let cmd = os_command(&child["cmd"])
+                                        .map_err(|e| ConfigError::InvalidTask(full.clone(), e))?;
+                                    // Not available on this OS; `check` says so
+                                    let Some(cmd) = cmd else {
+                                        collect_tasks(
+                                            &full,
+                                            child,
+                                            root,
+                                            project,
+                                            pipefail_default,
+                                            tasks,
+                                        )?;
+                                        continue;
+                                    };
+                                    let cwd = child
+                                        .get("cwd")
                                         .and_then(|v| v.as_str())
-                                        .ok_or_else(|| {
+                                        .map(crate::env::expand_vars)
+                                        .transpose()
+                                        .map_err(|e| {
                                             ConfigError::InvalidTask(
                                                 full.clone(),
-                                                "'cmd' must be a string".into(),
+                                                format!("'cwd': {}", e),
                                             )
                                         })?;
-                                    let cwd = child
-                                        .get("cwd")
-                                        .and_then(|v| v.as_str())
-                                        .map(|s| s.to_string());
+                                    let inside = child
+                                        .get("in")
+                                        .map(|v| {
+                                            v.as_str().map(str::to_string).ok_or_else(|| {
+                                                ConfigError::InvalidTask(
+                                                    full.clone(),
+                                                    "'in' must be a process name".into(),
+                                                )
+                                            })
+                                        })
+                                        .transpose()?;
+                                    let dir = command_dir(root, cwd.as_deref());
+                                    let env = EnvSpec::from_table(child, &dir)
+                                        .map_err(|e| ConfigError::InvalidTask(full.clone(), e))?
+                                        .with_project(project);
+                                    let allowed_exit_codes = parse_exit_codes(
+                                        child.get("allowed_exit_codes"),
+                                    )
+                                    .map_err(|e| ConfigError::InvalidTask(full.clone(), e))?;
+                                    let pipefail = match child.get("pipefail") {
+                                        None => pipefail_default,
+                                        Some(v) => v.as_bool().ok_or_else(|| {
+                                            ConfigError::InvalidTask(
+                                                full.clone(),
+                                                "'pipefail' must be true or false".into(),
+                                            )
+                                        })?,
+                                    };
                                     tasks.insert(
                                         full.clone(),
                                         TaskConfig {
                                             kind: TaskKind::Shell {
-                                                cmd: cmd.to_string(),
+                                                cmd,
                                                 cwd,
+                                                env,
+                                                allowed_exit_codes,
+                                                pipefail,
+                                                inside,
                                             },
                                         },
                                     );
                                 } else {
                                     // Composite
-                                    if child.get("cwd").is_some() {
-                                        return Err(ConfigError::InvalidTask(
-                                            full.clone(),
-                                            "composite tasks cannot set 'cwd'".into(),
-                                        ));
+                                    for key in ["cwd", "in"] {
+                                        if child.contains_key(key) {
+                                            return Err(ConfigError::InvalidTask(
+                                                full.clone(),
+                                                format!("composite tasks cannot set '{}'", key),
+                                            ));
+                                        }
                                     }
                                     let run = child
                                         .get("run")
@@ -274,33 +1700,332 @@ pub fn load_tasks_from(root: &Path) -> Result<Option<HashMap<String, TaskConfig>
                             }
 
                             // Recurse to allow dotted namespaces: [tasks.frontend.build]
-                            collect_tasks(&full, child, tasks)?;
+                            collect_tasks(&full, child, root, project, pipefail_default, tasks)?;
                         }
                     }
                     Ok(())
                 }
 
-                collect_tasks("", tbl, &mut tasks)?;
+                let pipefail = match value.get("pipefail") {
+                    None => false,
+                    Some(v) => v.as_bool().ok_or_else(|| {
+                        ConfigError::InvalidSetting("'pipefail' must be true or false".into())
+                    })?,
+                };
+                collect_tasks("", tbl, root, &project, pipefail, &mut tasks)?;
             }
             Ok(Some(tasks))
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Write;
+/// A non-fatal problem found in the config: something oxproc ignores or that is likely a
+/// mistake. Loading still succeeds; `check`, `start` and `list` report these.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// Where: a process, task or table name
+    pub scope: String,
+    pub message: String,
+}
 
-    #[test]
-    fn loads_processes_from_top_level_and_tasks() {
-        let dir = tempfile::tempdir().unwrap();
-        let path = dir.path().join("proc.toml");
-        let mut file = std::fs::File::create(&path).unwrap();
-        writeln!(
-            file,
-            r#"
-[web]
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.scope, self.message)
+    }
+}
+
+const PROCESS_KEYS: &[&str] = &[
+    "cmd",
+    "stdout",
+    "stderr",
+    "cwd",
+    "autostart",
+    "start_delay",
+    "start_jitter",
+    "restart",
+    "max_restarts",
+    "restart_window",
+    "min_uptime",
+    "start_timeout",
+    "kill_on_start_timeout",
+    "allowed_exit_codes",
+    "error_pattern",
+    "warn_pattern",
+    "info_pattern",
+    "ready",
+    "watch_tool",
+    "watch",
+    "depends_on",
+    "env",
+    "inherit_env",
+    "env_allowlist",
+    "force_color",
+    "term",
+    "path_prepend",
+    "env_wrapper",
+    "requires_project",
+    "port",
+    "log_rate_limit",
+    "multiline",
+    "log_format",
+    "reload_signal",
+    "stdin",
+    "url_pattern",
+    "group",
+    "color",
+    "oom_score_adj",
+    "core_dumps",
+    "mirror",
+    "record_env",
+    "type",
+    "listen",
+    "routes",
+    "target",
+    "via",
+    "service",
+    "file",
+    "context",
+    "namespace",
+    "svc",
+    "runtime",
+    "image",
+    "volumes",
+    "ports",
+];
+const TASK_KEYS: &[&str] = &[
+    "cmd",
+    "run",
+    "cwd",
+    "parallel",
+    "allowed_exit_codes",
+    "pipefail",
+    "env",
+    "inherit_env",
+    "env_allowlist",
+    "force_color",
+    "term",
+    "path_prepend",
+    "env_wrapper",
+    "in",
+];
+const PROJECT_KEYS: &[&str] = &[
+    "name",
+    "path_prepend",
+    "env_wrapper",
+    "requires_project",
+    "forward_user_signals",
+    "pipefail",
+    "allow_external_logs",
+    "idle_timeout",
+    "idle_action",
+    "low_power",
+];
+const LOG_KEYS: &[&str] = &["prefix_format", "highlight"];
+const COLOR_KEYS: &[&str] = &["palette", "pin"];
+
+/// Collect warnings about the project's config. Errors that prevent loading are returned as
+/// errors, exactly as the loaders report them.
+pub fn diagnostics(root: &Path) -> Result<Vec<Diagnostic>, ConfigError> {
+    let mut out = Vec::new();
+    let mut warn = |scope: &str, message: String| {
+        out.push(Diagnostic {
+            scope: scope.to_string(),
+            message,
+        })
+    };
+    match detect_source(root)? {
+        ConfigSource::Procfile => {
+            let content = fs::read_to_string(root.join("Procfile"))?;
+            for (n, line) in content.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                match line.split_once(':') {
+                    None => warn(
+                        "Procfile",
+                        format!("line {} has no 'name:' and is ignored", n + 1),
+                    ),
+                    Some((name, cmd)) if cmd.trim().is_empty() => {
+                        warn(name.trim(), "empty command".into())
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        ConfigSource::ProcToml => {
+            let content = fs::read_to_string(proc_toml_path(root))?;
+            let value: toml::Value = toml::from_str(&content)?;
+            let Some(top) = value.as_table() else {
+                return Ok(out);
+            };
+            let check_keys = |warn: &mut dyn FnMut(&str, String),
+                              scope: &str,
+                              tbl: &toml::value::Table,
+                              known: &[&str]| {
+                for key in tbl.keys() {
+                    if !known.contains(&key.as_str()) && !tbl[key].is_table() {
+                        warn(scope, format!("unknown key '{}' is ignored", key));
+                    }
+                }
+            };
+            for (key, item) in top {
+                match (key.as_str(), item) {
+                    ("processes", toml::Value::Table(procs)) => {
+                        for (name, p) in procs {
+                            match p.as_table() {
+                                Some(tbl)
+                                    if ["cmd", "type", "runtime"]
+                                        .iter()
+                                        .any(|k| tbl.contains_key(*k)) =>
+                                {
+                                    check_keys(&mut warn, name, tbl, PROCESS_KEYS);
+                                    if let Some(message) = missing_os_variant(tbl) {
+                                        warn(name, message);
+                                    }
+                                    if let Some(message) = ignored_max_restarts(tbl) {
+                                        warn(name, message);
+                                    }
+                                }
+                                _ => warn(
+                                    &format!("processes.{}", name),
+                                    "has no 'cmd' and is ignored".into(),
+                                ),
+                            }
+                        }
+                    }
+                    ("tasks", toml::Value::Table(tasks)) => {
+                        fn walk(
+                            prefix: &str,
+                            tbl: &toml::value::Table,
+                            warn: &mut dyn FnMut(&str, String),
+                        ) {
+                            for (key, v) in tbl {
+                                let Some(child) = v.as_table() else {
+                                    continue;
+                                };
+                                let full = format!("{}.{}", prefix, key);
+                                if child.contains_key("cmd") || child.contains_key("run") {
+                                    for k in child.keys() {
+                                        if !TASK_KEYS.contains(&k.as_str()) && !child[k].is_table()
+                                        {
+                                            warn(&full, format!("unknown key '{}' is ignored", k));
+                                        }
+                                    }
+                                    if let Some(message) = missing_os_variant(child) {
+                                        warn(&full, message);
+                                    }
+                                }
+                                walk(&full, child, warn);
+                            }
+                        }
+                        walk("tasks", tasks, &mut warn);
+                    }
+                    ("log", toml::Value::Table(tbl)) => check_keys(&mut warn, "log", tbl, LOG_KEYS),
+                    ("color", toml::Value::Table(tbl)) => {
+                        check_keys(&mut warn, "color", tbl, COLOR_KEYS)
+                    }
+                    (name, toml::Value::Table(tbl)) => {
+                        if tbl.contains_key("cmd") {
+                            warn(
+                                name,
+                                format!(
+                                    "top-level process tables are deprecated; move it to [processes.{}]",
+                                    name
+                                ),
+                            );
+                            check_keys(&mut warn, name, tbl, PROCESS_KEYS);
+                            if let Some(message) = missing_os_variant(tbl) {
+                                warn(name, message);
+                            }
+                            if let Some(message) = ignored_max_restarts(tbl) {
+                                warn(name, message);
+                            }
+                        } else {
+                            warn(name, "table has no 'cmd' and is ignored".into());
+                        }
+                    }
+                    (name, _) if !PROJECT_KEYS.contains(&name) => warn(
+                        "proc.toml",
+                        format!("unknown top-level key '{}' is ignored", name),
+                    ),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // Suspicious values in the loaded processes
+    let configs = load_config_from(root)?;
+    let mut log_owners: HashMap<PathBuf, &str> = HashMap::new();
+    for c in &configs {
+        if c.command.trim().is_empty() {
+            warn(&c.name, "empty command".into());
+        }
+        if let Some(cwd) = &c.cwd {
+            if !root.join(cwd).is_dir() {
+                warn(&c.name, format!("cwd '{}' does not exist", cwd));
+            }
+        }
+        if c.max_restarts == Some(0) {
+            warn(
+                &c.name,
+                "max_restarts = 0 disables automatic restarts".into(),
+            );
+        }
+        if c.start_timeout.is_some() && c.ready.is_none() && c.min_uptime.is_none() {
+            warn(
+                &c.name,
+                "start_timeout has no effect without ready or min_uptime".into(),
+            );
+        }
+        if c.start_jitter.is_some() && c.start_delay.is_none() {
+            warn(
+                &c.name,
+                "start_jitter without start_delay only adds a random delay".into(),
+            );
+        }
+        let (out_log, err_log) = c.log_paths(root);
+        if out_log == err_log {
+            warn(
+                &c.name,
+                "stdout and stderr share one log file; lines may interleave mid-line".into(),
+            );
+        }
+        for path in [out_log, err_log] {
+            match log_owners.get(&path) {
+                Some(other) if *other != c.name => warn(
+                    &c.name,
+                    format!("log file {} is also used by '{}'", path.display(), other),
+                ),
+                _ => {
+                    log_owners.insert(path, &c.name);
+                }
+            }
+        }
+    }
+    out.sort_by(|a, b| a.scope.cmp(&b.scope));
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_processes_from_toml(path: &Path) -> Result<Vec<ProcessConfig>, ConfigError> {
+        load_processes(path, path.parent().unwrap())
+    }
+    use std::io::Write;
+
+    #[test]
+    fn loads_processes_from_top_level_and_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proc.toml");
+        let mut file = std::fs::File::create(&path).unwrap();
+        writeln!(
+            file,
+            r#"
+[web]
 cmd = "echo web"
 
 [tasks.build]
@@ -343,6 +2068,478 @@ cmd = "echo worker"
         assert_eq!(procs[1].name, "worker");
     }
 
+    #[test]
+    fn parses_autostart_flag() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proc.toml");
+        std::fs::write(
+            &path,
+            r#"
+[web]
+cmd = "echo web"
+
+[processes.ngrok]
+cmd = "ngrok http 3000"
+autostart = false
+"#,
+        )
+        .unwrap();
+
+        let procs = load_processes_from_toml(&path).unwrap();
+        let web = procs.iter().find(|p| p.name == "web").unwrap();
+        let ngrok = procs.iter().find(|p| p.name == "ngrok").unwrap();
+        assert!(web.autostart);
+        assert!(!ngrok.autostart);
+
+        std::fs::write(&path, "[web]\ncmd = \"echo\"\nautostart = \"no\"\n").unwrap();
+        assert!(matches!(
+            load_processes_from_toml(&path),
+            Err(ConfigError::InvalidProcess(..))
+        ));
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("5s").unwrap(), Duration::from_secs(5));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("1m30s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("3").unwrap(), Duration::from_secs(3));
+        assert!(parse_duration("5 parsecs").is_err());
+        assert!(parse_duration("").is_err());
+    }
+
+    #[test]
+    fn parses_start_delay_and_jitter() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proc.toml");
+        std::fs::write(
+            &path,
+            r#"
+[web]
+cmd = "echo web"
+start_delay = "2s"
+start_jitter = 1
+"#,
+        )
+        .unwrap();
+
+        let procs = load_processes_from_toml(&path).unwrap();
+        let web = &procs[0];
+        assert_eq!(web.start_delay, Some(Duration::from_secs(2)));
+        assert_eq!(web.start_jitter, Some(Duration::from_secs(1)));
+        let d = web.startup_delay();
+        assert!(d >= Duration::from_secs(2) && d < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn parses_restart_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proc.toml");
+        std::fs::write(
+            &path,
+            r#"
+[web]
+cmd = "echo web"
+max_restarts = 5
+restart_window = "2m"
+min_uptime = "5s"
+start_timeout = "1m"
+kill_on_start_timeout = true
+allowed_exit_codes = [0, 143]
+oom_score_adj = 500
+
+[worker]
+cmd = "echo worker"
+
+[api]
+cmd = "echo api"
+restart = "always"
+"#,
+        )
+        .unwrap();
+
+        let procs = load_processes_from_toml(&path).unwrap();
+        let web = procs.iter().find(|p| p.name == "web").unwrap();
+        let worker = procs.iter().find(|p| p.name == "worker").unwrap();
+        assert_eq!(web.max_restarts, Some(5));
+        assert_eq!(web.restart_window, Duration::from_secs(120));
+        assert_eq!(worker.max_restarts, None);
+        assert_eq!(worker.restart_window, DEFAULT_RESTART_WINDOW);
+        assert_eq!(web.restart, RestartPolicy::OnFailure);
+        assert_eq!(worker.restart, RestartPolicy::Never);
+        let api = procs.iter().find(|p| p.name == "api").unwrap();
+        assert_eq!(api.restart, RestartPolicy::Always);
+        assert_eq!(api.max_restarts, Some(DEFAULT_MAX_RESTARTS));
+        assert_eq!(web.min_uptime, Some(Duration::from_secs(5)));
+        assert_eq!(worker.min_uptime, None);
+        assert_eq!(web.start_timeout, Some(Duration::from_secs(60)));
+        assert!(web.kill_on_start_timeout);
+        assert!(!worker.kill_on_start_timeout);
+        assert_eq!(web.allowed_exit_codes, [0, 143]);
+        assert_eq!(worker.allowed_exit_codes, [0]);
+        assert_eq!(web.oom_score_adj, Some(500));
+        assert_eq!(worker.oom_score_adj, None);
+
+        std::fs::write(&path, "[web]\ncmd = \"echo\"\nmax_restarts = -1\n").unwrap();
+        assert!(load_processes_from_toml(&path).is_err());
+        std::fs::write(&path, "[web]\ncmd = \"echo\"\nrestart = \"sometimes\"\n").unwrap();
+        assert!(load_processes_from_toml(&path).is_err());
+        std::fs::write(&path, "[web]\ncmd = \"echo\"\nallowed_exit_codes = [256]\n").unwrap();
+        assert!(load_processes_from_toml(&path).is_err());
+        std::fs::write(&path, "[web]\ncmd = \"echo\"\noom_score_adj = 1001\n").unwrap();
+        assert!(load_processes_from_toml(&path).is_err());
+    }
+
+    #[test]
+    fn namespaces_explicit_log_paths() {
+        assert_eq!(namespaced_path("api.log", "b"), "api.b.log");
+        assert_eq!(
+            namespaced_path("logs/api.out.log", "b"),
+            "logs/api.b.out.log"
+        );
+        assert_eq!(
+            namespaced_path("/var/log/v1.2/api", "b"),
+            "/var/log/v1.2/api.b"
+        );
+        assert_eq!(namespaced_path("logs/.api.log", "b"), "logs/.api.b.log");
+    }
+
+    #[test]
+    fn parses_allowed_exit_codes() {
+        let parse = |s: &str| {
+            let value: toml::Value = toml::from_str(&format!("codes = {}", s)).unwrap();
+            parse_exit_codes(value.get("codes"))
+        };
+        assert_eq!(parse_exit_codes(None), Ok(vec![0]));
+        assert_eq!(parse("[0, 1, 143]"), Ok(vec![0, 1, 143]));
+        assert_eq!(parse("[]"), Ok(vec![]));
+        assert!(parse("[-1]").is_err());
+        assert!(parse("[256]").is_err());
+        assert!(parse("['0']").is_err());
+        assert!(parse("0").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn allowed_exit_codes_decide_success_and_restarts() {
+        use std::os::unix::process::ExitStatusExt;
+        let exited = |code: i32| std::process::ExitStatus::from_raw(code << 8);
+        let signalled = |sig: i32| std::process::ExitStatus::from_raw(sig);
+
+        assert_eq!(check_exit(&exited(0), &[0]), Ok(()));
+        assert_eq!(check_exit(&exited(3), &[0]), Err(3));
+        assert_eq!(check_exit(&exited(3), &[0, 3]), Ok(()));
+        assert_eq!(check_exit(&exited(0), &[3]), Err(0));
+        // Killed by SIGTERM reads as 143, like in a shell
+        assert_eq!(check_exit(&signalled(15), &[0]), Err(143));
+        assert_eq!(check_exit(&signalled(15), &[0, 143]), Ok(()));
+
+        let clean = |status, allowed: &[i32]| check_exit(&status, allowed).is_ok();
+        assert!(!RestartPolicy::OnFailure.restarts_after(clean(exited(3), &[0, 3])));
+        assert!(RestartPolicy::OnFailure.restarts_after(clean(exited(3), &[0])));
+        assert!(RestartPolicy::OnFailure.restarts_after(clean(signalled(15), &[0])));
+        assert!(!RestartPolicy::OnFailure.restarts_after(clean(signalled(15), &[0, 143])));
+        assert!(RestartPolicy::Always.restarts_after(clean(exited(0), &[0])));
+        assert!(!RestartPolicy::Never.restarts_after(clean(exited(1), &[0])));
+    }
+
+    #[test]
+    fn rejects_log_paths_outside_the_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("app");
+        std::fs::create_dir(&root).unwrap();
+        let path = root.join("proc.toml");
+        std::fs::write(
+            &path,
+            "[processes.web]\ncmd = \"web\"\nstdout = \"logs/../web.log\"\n",
+        )
+        .unwrap();
+        assert!(load_processes_from_toml(&path).is_ok());
+
+        std::fs::write(
+            &path,
+            "[processes.web]\ncmd = \"web\"\nstderr = \"../../etc/web.log\"\n",
+        )
+        .unwrap();
+        let err = load_processes_from_toml(&path).unwrap_err().to_string();
+        assert!(err.contains("'stderr' log"), "{}", err);
+        assert!(err.contains("allow_external_logs"), "{}", err);
+
+        std::fs::write(
+            &path,
+            "allow_external_logs = true\n[processes.web]\ncmd = \"web\"\nstderr = \"/tmp/web.log\"\n",
+        )
+        .unwrap();
+        assert!(load_processes_from_toml(&path).is_ok());
+    }
+
+    #[test]
+    fn parses_ready_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proc.toml");
+        std::fs::write(&path, "[web]\ncmd = \"echo\"\nready = \"tcp:3000\"\n").unwrap();
+        let procs = load_processes_from_toml(&path).unwrap();
+        assert_eq!(
+            procs[0].ready.as_ref().map(|r| r.to_string()),
+            Some("tcp:3000".to_string())
+        );
+
+        std::fs::write(&path, "[web]\ncmd = \"echo\"\nready = \"udp:53\"\n").unwrap();
+        assert!(matches!(
+            load_processes_from_toml(&path),
+            Err(ConfigError::InvalidProcess(..))
+        ));
+    }
+
+    #[test]
+    fn parses_watch_tool() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proc.toml");
+        std::fs::write(
+            &path,
+            "[api]\ncmd = \"cargo run\"\nwatch_tool = \"cargo-watch\"\nwatch = [\"src\"]\n",
+        )
+        .unwrap();
+        let procs = load_processes_from_toml(&path).unwrap();
+        assert_eq!(procs[0].watch_tool, Some(WatchTool::CargoWatch));
+        assert_eq!(
+            procs[0].command_line(dir.path()),
+            "cargo watch -w 'src' -s 'cargo run'"
+        );
+
+        std::fs::write(&path, "[api]\ncmd = \"x\"\nwatch_tool = \"nodemon\"\n").unwrap();
+        assert!(load_processes_from_toml(&path).is_err());
+    }
+
+    #[test]
+    fn validates_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proc.toml");
+        std::fs::write(
+            &path,
+            r#"
+[db]
+cmd = "postgres"
+
+[api]
+cmd = "api"
+depends_on = ["db"]
+
+[web]
+cmd = "web"
+depends_on = "api"
+"#,
+        )
+        .unwrap();
+        let procs = load_processes_from_toml(&path).unwrap();
+        let mut closure = with_dependencies(&procs, &["web".to_string()]);
+        closure.sort();
+        assert_eq!(closure, vec!["api", "db", "web"]);
+        assert_eq!(start_order(&procs, &closure), vec!["db", "api", "web"]);
+
+        std::fs::write(&path, "[a]\ncmd = \"a\"\ndepends_on = [\"nope\"]\n").unwrap();
+        assert!(load_processes_from_toml(&path).is_err());
+
+        std::fs::write(
+            &path,
+            "[a]\ncmd = \"a\"\ndepends_on = [\"b\"]\n[b]\ncmd = \"b\"\ndepends_on = [\"a\"]\n",
+        )
+        .unwrap();
+        match load_processes_from_toml(&path) {
+            Err(ConfigError::InvalidProcess(_, msg)) => assert!(msg.contains("cycle")),
+            other => panic!("expected cycle error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn parses_level_patterns() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proc.toml");
+        std::fs::write(
+            &path,
+            r#"
+[web]
+cmd = "echo web"
+error_pattern = "ERROR|panic"
+warn_pattern = "WARN"
+"#,
+        )
+        .unwrap();
+
+        let procs = load_processes_from_toml(&path).unwrap();
+        let levels = &procs[0].levels;
+        assert!(levels.error.as_ref().unwrap().is_match("panic at main"));
+        assert!(levels.warn.as_ref().unwrap().is_match("WARN x"));
+        assert!(levels.info.is_none());
+
+        std::fs::write(&path, "[web]\ncmd = \"echo\"\nerror_pattern = \"(\"\n").unwrap();
+        assert!(matches!(
+            load_processes_from_toml(&path),
+            Err(ConfigError::InvalidProcess(..))
+        ));
+    }
+
+    #[test]
+    fn captures_announced_urls() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proc.toml");
+        std::fs::write(
+            &path,
+            r#"
+[web]
+cmd = "vite"
+url_pattern = 'Local:\s+(http\S+)'
+
+[api]
+cmd = "api"
+url_pattern = 'http://\S+'
+"#,
+        )
+        .unwrap();
+
+        let procs = load_processes_from_toml(&path).unwrap();
+        let pattern = |name: &str| {
+            let p = procs.iter().find(|p| p.name == name).unwrap();
+            p.url_pattern.clone().unwrap()
+        };
+        let (web, api) = (pattern("web"), pattern("api"));
+        assert_eq!(
+            url_in(&web, "  ➜  Local:   http://localhost:5173/").as_deref(),
+            Some("http://localhost:5173/")
+        );
+        assert_eq!(url_in(&web, "ready in 300ms"), None);
+        assert_eq!(
+            url_in(&api, "serving on http://0.0.0.0:8080").as_deref(),
+            Some("http://0.0.0.0:8080")
+        );
+    }
+
+    #[test]
+    fn selects_processes_by_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proc.toml");
+        std::fs::write(
+            &path,
+            r#"
+[processes.api]
+cmd = "api"
+group = "backend"
+
+[processes.web]
+cmd = "web"
+group = "frontend"
+
+[processes.worker]
+cmd = "worker"
+group = "backend"
+"#,
+        )
+        .unwrap();
+
+        let procs = load_processes_from_toml(&path).unwrap();
+        let names = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let mut web_and_backend = select(&procs, &names(&["web"]), &names(&["backend"])).unwrap();
+        web_and_backend.sort();
+        assert_eq!(web_and_backend, names(&["api", "web", "worker"]));
+        assert_eq!(
+            select(&procs, &names(&["worker"]), &names(&["backend"])).unwrap(),
+            names(&["worker", "api"])
+        );
+        let err = select(&procs, &[], &names(&["db"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No process is in group 'db' (groups: backend, frontend)"
+        );
+    }
+
+    #[test]
+    fn loads_log_highlight_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proc.toml");
+        std::fs::write(
+            &path,
+            r#"
+[web]
+cmd = "echo web"
+
+[log.highlight]
+"WARN" = "yellow"
+"ERROR|panic" = "bright_red"
+"#,
+        )
+        .unwrap();
+
+        let settings = load_log_settings(dir.path()).unwrap();
+        assert_eq!(settings.highlight.len(), 2);
+        assert!(settings
+            .highlight
+            .iter()
+            .any(|r| r.pattern.is_match("panic!") && r.color == "\u{1b}[91m"));
+        // [log] is not mistaken for a legacy process table
+        assert_eq!(load_processes_from_toml(&path).unwrap().len(), 1);
+
+        assert!(settings.prefix_format.is_none());
+
+        std::fs::write(&path, "[log]\nprefix_format = \"{time} {name}|\"\n").unwrap();
+        assert!(load_log_settings(dir.path())
+            .unwrap()
+            .prefix_format
+            .is_some());
+        std::fs::write(&path, "[log]\nprefix_format = \"{host}\"\n").unwrap();
+        assert!(matches!(
+            load_log_settings(dir.path()),
+            Err(ConfigError::InvalidLog(_))
+        ));
+
+        std::fs::write(&path, "[log.highlight]\nWARN = \"mauve\"\n").unwrap();
+        assert!(matches!(
+            load_log_settings(dir.path()),
+            Err(ConfigError::InvalidLog(_))
+        ));
+    }
+
+    #[test]
+    fn loads_color_palette_and_pins() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("proc.toml");
+        std::fs::write(
+            &path,
+            r##"
+[web]
+cmd = "echo web"
+
+[color]
+palette = ["#ff8800", "blue", 208]
+
+[color.pin]
+web = "#00aaff"
+"##,
+        )
+        .unwrap();
+
+        let theme = load_color_theme(dir.path()).unwrap();
+        assert_eq!(theme.palette.len(), 3);
+        assert_eq!(theme.palette[2], ColorSpec::Ansi256(208));
+        assert_eq!(theme.pinned["web"], ColorSpec::Rgb(0, 0xaa, 0xff));
+        assert_eq!(load_processes_from_toml(&path).unwrap().len(), 1);
+
+        std::fs::write(
+            &path,
+            "[processes.api]\ncmd = \"api\"\ncolor = \"cyan\"\n[color.pin]\nweb = \"red\"\n",
+        )
+        .unwrap();
+        let theme = load_color_theme(dir.path()).unwrap();
+        assert_eq!(theme.pinned["api"], ColorSpec::parse("cyan").unwrap());
+        assert_eq!(theme.pinned["web"], ColorSpec::parse("red").unwrap());
+
+        std::fs::write(&path, "[color]\npalette = [\"#12\"]\n").unwrap();
+        assert!(matches!(
+            load_color_theme(dir.path()),
+            Err(ConfigError::InvalidColor(_))
+        ));
+    }
+
     #[test]
     fn tasks_absent_returns_empty_map() {
         let dir = tempfile::tempdir().unwrap();
@@ -418,4 +2615,323 @@ cmd = "echo API"
             _ => panic!("expected composite task"),
         }
     }
+
+    #[test]
+    fn combines_process_and_project_path_prepend() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("proc.toml"),
+            r#"
+path_prepend = [".venv/bin"]
+
+[web]
+cmd = "vite"
+cwd = "frontend"
+path_prepend = "node_modules/.bin"
+
+[tasks.lint]
+cmd = "ruff check"
+"#,
+        )
+        .unwrap();
+
+        let configs = load_config_from(dir.path()).unwrap();
+        assert_eq!(
+            configs[0].env.path_prepend,
+            vec![
+                dir.path().join("frontend/node_modules/.bin"),
+                dir.path().join(".venv/bin")
+            ]
+        );
+        let tasks = load_tasks_from(dir.path()).unwrap().unwrap();
+        match &tasks["lint"].kind {
+            TaskKind::Shell { env, .. } => {
+                assert_eq!(env.path_prepend, vec![dir.path().join(".venv/bin")])
+            }
+            _ => panic!("expected shell task"),
+        }
+    }
+
+    #[test]
+    fn reads_the_idle_shutdown_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let idle = |toml: &str| {
+            std::fs::write(dir.path().join("proc.toml"), toml).unwrap();
+            load_idle_shutdown(dir.path())
+        };
+        assert_eq!(idle("[processes.web]\ncmd = \"x\"").unwrap(), None);
+        assert_eq!(
+            idle("idle_timeout = \"30m\"\nidle_action = \"suspend\"").unwrap(),
+            Some(IdleShutdown {
+                timeout: Duration::from_secs(1800),
+                action: IdleAction::Suspend,
+            })
+        );
+        assert_eq!(
+            idle("idle_timeout = \"1h\"").unwrap().unwrap().action,
+            IdleAction::Stop
+        );
+        assert!(idle("idle_timeout = 30").is_err());
+        assert!(idle("idle_timeout = \"0s\"").is_err());
+        assert!(idle("idle_timeout = \"5m\"\nidle_action = \"sleep\"").is_err());
+        assert!(idle("idle_action = \"stop\"").is_err());
+    }
+
+    #[test]
+    fn top_level_pipefail_is_the_task_default() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("proc.toml"),
+            r#"
+pipefail = true
+
+[tasks.gen]
+cmd = "gen | tee out"
+
+[tasks.grep]
+cmd = "grep -r TODO . | wc -l"
+pipefail = false
+"#,
+        )
+        .unwrap();
+        let tasks = load_tasks_from(dir.path()).unwrap().unwrap();
+        let pipefail = |name: &str| match &tasks[name].kind {
+            TaskKind::Shell { pipefail, .. } => *pipefail,
+            _ => panic!("expected shell task"),
+        };
+        assert!(pipefail("gen"));
+        assert!(!pipefail("grep"));
+    }
+
+    #[test]
+    fn diagnoses_ignored_and_suspicious_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("proc.toml"),
+            r#"
+colour = "always"
+
+[notes]
+text = "not a process"
+
+[processes.web]
+cmd = "vite"
+restart_policy = "always"
+stdout = "app.log"
+
+[processes.api]
+cmd = "cargo run"
+stdout = "app.log"
+
+[processes.once]
+cmd = "migrate"
+restart = "never"
+max_restarts = 3
+
+[tasks.build]
+cmd = "make"
+cwdd = "x"
+"#,
+        )
+        .unwrap();
+        let messages: Vec<String> = diagnostics(dir.path())
+            .unwrap()
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert!(messages.contains(&"web: unknown key 'restart_policy' is ignored".to_string()));
+        assert!(messages.contains(&"tasks.build: unknown key 'cwdd' is ignored".to_string()));
+        assert!(messages.contains(&"notes: table has no 'cmd' and is ignored".to_string()));
+        assert!(messages
+            .contains(&"once: max_restarts is ignored with restart = \"never\"".to_string()));
+        assert!(messages
+            .iter()
+            .any(|m| m.contains("unknown top-level key 'colour'")));
+        assert!(messages
+            .iter()
+            .any(|m| m.contains("app.log is also used by")));
+
+        std::fs::remove_file(dir.path().join("proc.toml")).unwrap();
+        std::fs::write(
+            dir.path().join("Procfile"),
+            "web: vite
+oops
+",
+        )
+        .unwrap();
+        let diags = diagnostics(dir.path()).unwrap();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].message, "line 2 has no 'name:' and is ignored");
+    }
+
+    #[test]
+    fn picks_the_command_for_this_os() {
+        let dir = tempfile::tempdir().unwrap();
+        let other = if std::env::consts::OS == "windows" {
+            "linux"
+        } else {
+            "windows"
+        };
+        std::fs::write(
+            dir.path().join("proc.toml"),
+            format!(
+                r#"
+[processes.web]
+cmd.{os} = "serve"
+cmd.{other} = "serve.exe"
+
+[processes.caffeinate]
+cmd.{other} = "keep-awake"
+
+[tasks.build]
+cmd.{os} = "make"
+
+[tasks.sign]
+cmd.{other} = "signtool sign"
+"#,
+                os = std::env::consts::OS,
+                other = other
+            ),
+        )
+        .unwrap();
+        let configs = load_config_from(dir.path()).unwrap();
+        assert_eq!(configs.len(), 1);
+        assert_eq!(configs[0].command, "serve");
+        let tasks = load_tasks_from(dir.path()).unwrap().unwrap();
+        assert!(matches!(&tasks["build"].kind, TaskKind::Shell { cmd, .. } if cmd == "make"));
+        assert!(!tasks.contains_key("sign"));
+        let messages: Vec<String> = diagnostics(dir.path())
+            .unwrap()
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        let missing = format!(
+            "has no 'cmd.{}' and is skipped on this OS",
+            std::env::consts::OS
+        );
+        assert_eq!(
+            messages,
+            vec![
+                format!("caffeinate: {}", missing),
+                format!("tasks.sign: {}", missing)
+            ]
+        );
+
+        std::fs::write(
+            dir.path().join("proc.toml"),
+            "[processes.web]\ncmd.beos = \"serve\"\n",
+        )
+        .unwrap();
+        assert!(load_config_from(dir.path()).is_err());
+    }
+
+    #[test]
+    fn commands_run_in_their_cwd_under_the_root() {
+        let root = Path::new("/srv/app");
+        assert_eq!(command_dir(root, None), root);
+        assert_eq!(command_dir(root, Some("web")), Path::new("/srv/app/web"));
+        assert_eq!(command_dir(root, Some("/opt/tool")), Path::new("/opt/tool"));
+    }
+
+    #[test]
+    fn rejects_duplicate_process_definitions() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("proc.toml"),
+            "[processes.web]\ncmd = \"a\"\n\n[web]\ncmd = \"b\"\n",
+        )
+        .unwrap();
+        let err = load_config_from(dir.path()).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "Process 'web' is defined twice: proc.toml:1 [processes.web] and proc.toml:4 [web]"
+        );
+
+        std::fs::remove_file(dir.path().join("proc.toml")).unwrap();
+        std::fs::write(dir.path().join("Procfile"), "web: a\nworker: b\nweb: c\n").unwrap();
+        let err = load_config_from(dir.path()).unwrap_err().to_string();
+        assert!(err.ends_with("Procfile:1 and Procfile:3"), "{}", err);
+
+        // Locations name the file that was loaded, e.g. with `-f proc.ci.toml`
+        let ci = dir.path().join("proc.ci.toml");
+        std::fs::write(&ci, "[web]\ncmd = \"b\"\n\n[processes.web]\ncmd = \"a\"\n").unwrap();
+        let err = load_processes(&ci, dir.path()).unwrap_err().to_string();
+        assert!(
+            err.ends_with("proc.ci.toml:4 [processes.web] and proc.ci.toml:1 [web]"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn renders_command_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("proc.toml"),
+            r#"
+[processes.mlflow]
+cmd = "mlflow server --backend-store-uri {project_root}/.mlflow --port {port} # {process}.{instance} ${process} {a,b}"
+ready = "tcp:5000"
+"#,
+        )
+        .unwrap();
+        let configs = load_config_from(dir.path()).unwrap();
+        assert_eq!(
+            configs[0].render_command(dir.path()),
+            format!(
+                "mlflow server --backend-store-uri {}/.mlflow --port 5000 # mlflow.0 ${{process}} {{a,b}}",
+                dir.path().display()
+            )
+        );
+
+        std::fs::write(
+            dir.path().join("proc.toml"),
+            "[processes.web]\ncmd = \"serve --port {port}\"\n",
+        )
+        .unwrap();
+        assert!(load_config_from(dir.path()).is_err());
+    }
+
+    #[test]
+    fn reads_project_name() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("proc.toml"),
+            "name = \"acme-api\"\n\n[processes.web]\ncmd = \"serve\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            load_project_name(dir.path()).unwrap().as_deref(),
+            Some("acme-api")
+        );
+        assert!(diagnostics(dir.path()).unwrap().is_empty());
+
+        std::fs::write(dir.path().join("proc.toml"), "name = 3\n").unwrap();
+        assert!(load_project_name(dir.path()).is_err());
+    }
+
+    #[test]
+    fn parses_log_rate_limits() {
+        let per_sec = RateLimit::parse("1000/s").unwrap();
+        assert_eq!(per_sec.lines, 1000);
+        assert_eq!(per_sec.per, Duration::from_secs(1));
+        assert_eq!(
+            RateLimit::parse("5000 / 10s").unwrap().per,
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            RateLimit::parse("60/m").unwrap().per,
+            Duration::from_secs(60)
+        );
+        assert!(RateLimit::parse("1000").is_err());
+        assert!(RateLimit::parse("0/s").is_err());
+        assert!(RateLimit::parse("10/fortnight").is_err());
+    }
+
+    #[test]
+    fn parses_signal_names() {
+        assert_eq!(parse_signal("SIGHUP"), Some(Signal::SIGHUP));
+        assert_eq!(parse_signal("usr2"), Some(Signal::SIGUSR2));
+        assert_eq!(parse_signal("SIGNOPE"), None);
+    }
 }
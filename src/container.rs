@@ -0,0 +1,340 @@
+// `runtime = "docker"` or `"podman"`: a process that runs in a container. The manager runs
+// `docker run` in the foreground like any other command, so the container's output (what
+// `docker logs --follow` shows) goes to the process's logs, and SIGTERM from `stop` reaches the
+// container through the CLI. The container is named after the project and process so a stale
+// one is replaced on start and an orphaned one removed after a forced stop, and its ID (from
+// `--cidfile`) is what `status` shows in place of a process group.
+
+use std::path::{Path, PathBuf};
+
+use crate::watch::shell_quote;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Runtime {
+    Docker,
+    Podman,
+}
+
+impl Runtime {
+    pub fn program(&self) -> &'static str {
+        match self {
+            Runtime::Docker => "docker",
+            Runtime::Podman => "podman",
+        }
+    }
+}
+
+/// A process's `runtime`, `image`, `volumes` and `ports`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerSpec {
+    pub runtime: Runtime,
+    pub image: String,
+    /// `-v` arguments, with relative host paths made absolute
+    pub volumes: Vec<String>,
+    /// `-p` arguments (`"8080:80"`; a bare port publishes it on the same port)
+    pub ports: Vec<String>,
+}
+
+impl ContainerSpec {
+    /// Read the container settings from a process table, if it has a `runtime`.
+    pub fn from_table(
+        tbl: &toml::value::Table,
+        root: &Path,
+    ) -> Result<Option<ContainerSpec>, String> {
+        let runtime = match tbl.get("runtime").map(|v| v.as_str()) {
+            None => return Ok(None),
+            Some(Some("docker")) => Runtime::Docker,
+            Some(Some("podman")) => Runtime::Podman,
+            Some(_) => return Err("'runtime' must be \"docker\" or \"podman\"".into()),
+        };
+        if tbl.contains_key("type") {
+            return Err("'runtime' can't be combined with 'type'".into());
+        }
+        let image = tbl
+            .get("image")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.trim().is_empty())
+            .ok_or("a container needs 'image = \"name:tag\"'")?
+            .to_string();
+        let volumes = strings(
+            tbl,
+            "volumes",
+            "'volumes' must be a list like [\"./data:/data\"]",
+        )?
+        .into_iter()
+        .map(|v| absolute_volume(&v, root))
+        .collect();
+        let ports = match tbl.get("ports") {
+            None => Vec::new(),
+            Some(toml::Value::Array(items)) => items
+                .iter()
+                .map(|item| match item {
+                    toml::Value::Integer(n) => u16::try_from(*n)
+                        .map(|p| format!("{}:{}", p, p))
+                        .map_err(|_| format!("{} is not a port", n)),
+                    toml::Value::String(s) => Ok(s.clone()),
+                    _ => Err("'ports' must be a list like [\"8080:80\", 5432]".to_string()),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err("'ports' must be a list like [\"8080:80\", 5432]".into()),
+        };
+        Ok(Some(ContainerSpec {
+            runtime,
+            image,
+            volumes,
+            ports,
+        }))
+    }
+
+    /// Move the host side of each `ports` mapping up by `offset` (`--port-offset`). Ports
+    /// left to the runtime (`"80"`, `"127.0.0.1::80"`) and `{port}` templates, which follow
+    /// `port`, stay as they are.
+    pub fn shift_host_ports(&mut self, offset: u16) -> Result<(), String> {
+        if offset == 0 {
+            return Ok(());
+        }
+        let shift = |port: &str| -> Result<String, String> {
+            let Ok(n) = port.parse::<u16>() else {
+                return Ok(port.to_string());
+            };
+            n.checked_add(offset)
+                .map(|n| n.to_string())
+                .ok_or_else(|| format!("port {} + offset {} is out of range", n, offset))
+        };
+        for mapping in &mut self.ports {
+            // [ip:]host:container[/proto]; the host side may be a range (`8000-8010`)
+            let mut parts: Vec<&str> = mapping.split(':').collect();
+            if parts.len() < 2 {
+                continue;
+            }
+            let host = parts.len() - 2;
+            let shifted = parts[host]
+                .split('-')
+                .map(shift)
+                .collect::<Result<Vec<_>, _>>()?
+                .join("-");
+            parts[host] = &shifted;
+            *mapping = parts.join(":");
+        }
+        Ok(())
+    }
+
+    /// The shell command the manager runs: remove a leftover container of the same name, then
+    /// run the image in the foreground with `args` (the process's `cmd`, if any) as its
+    /// command and the `env` variables passed through.
+    pub fn command<'a>(
+        &self,
+        root: &Path,
+        name: &str,
+        args: Option<&str>,
+        env: impl Iterator<Item = &'a String>,
+    ) -> String {
+        let program = self.runtime.program();
+        let container = shell_quote(&container_name(root, name));
+        let cidfile = cid_path(root, name);
+        let cidfile = shell_quote(&cidfile.to_string_lossy());
+        let mut run = format!(
+            "{} run --rm --name {} --cidfile {}",
+            program, container, cidfile
+        );
+        for key in env {
+            run.push_str(&format!(" -e {}", shell_quote(key)));
+        }
+        for volume in &self.volumes {
+            run.push_str(&format!(" -v {}", shell_quote(volume)));
+        }
+        for port in &self.ports {
+            run.push_str(&format!(" -p {}", port));
+        }
+        run.push_str(&format!(" {}", shell_quote(&self.image)));
+        if let Some(args) = args {
+            run.push_str(&format!(" {}", args));
+        }
+        let dir = shell_quote(&containers_dir(root).to_string_lossy());
+        format!(
+            "{program} rm -f {container} >/dev/null 2>&1; mkdir -p {dir}; rm -f {cidfile}; exec {run}"
+        )
+    }
+
+    /// `docker exec` into the process's container, with `-t` when `tty`, `-w` for `cwd` and
+    /// the `env` variables passed through. The command to run there goes after these.
+    pub fn exec_args<'a>(
+        &self,
+        root: &Path,
+        name: &str,
+        tty: bool,
+        cwd: Option<&str>,
+        env: impl Iterator<Item = &'a String>,
+    ) -> Vec<String> {
+        let mut args = vec![
+            self.runtime.program().to_string(),
+            "exec".into(),
+            "-i".into(),
+        ];
+        if tty {
+            args.push("-t".into());
+        }
+        if let Some(cwd) = cwd {
+            args.extend(["-w".into(), cwd.to_string()]);
+        }
+        for key in env {
+            args.extend(["-e".into(), key.clone()]);
+        }
+        args.push(container_name(root, name));
+        args
+    }
+
+    /// Remove the process's container, after its `run` was killed before it could stop it.
+    pub async fn remove(&self, root: &Path, name: &str) {
+        let _ = tokio::process::Command::new(self.runtime.program())
+            .args(["rm", "-f", &container_name(root, name)])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .await;
+    }
+}
+
+/// `oxproc-<state dir>-<process>`, kept to the characters container names allow.
+pub fn container_name(root: &Path, name: &str) -> String {
+    let dir = crate::dirs::state_dir_for_project(root);
+    let project = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!("oxproc-{}-{}", project, name)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+fn containers_dir(root: &Path) -> PathBuf {
+    crate::dirs::state_dir_for_project(root).join("containers")
+}
+
+/// Where `run` writes the container ID.
+pub fn cid_path(root: &Path, name: &str) -> PathBuf {
+    containers_dir(root).join(format!("{}.cid", name))
+}
+
+/// The ID of the process's container, once `run` has created it.
+pub fn container_id(root: &Path, name: &str) -> Option<String> {
+    let id = std::fs::read_to_string(cid_path(root, name)).ok()?;
+    let id = id.trim();
+    (!id.is_empty()).then(|| id.to_string())
+}
+
+fn strings(tbl: &toml::value::Table, key: &str, error: &str) -> Result<Vec<String>, String> {
+    match tbl.get(key) {
+        None => Ok(Vec::new()),
+        Some(toml::Value::Array(items)) => items
+            .iter()
+            .map(|v| {
+                v.as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| error.to_string())
+            })
+            .collect(),
+        Some(_) => Err(error.to_string()),
+    }
+}
+
+/// `./data:/data` → `/project/data:/data`. Named volumes (`pgdata:/var/lib/postgresql`) and
+/// absolute paths are left alone.
+fn absolute_volume(volume: &str, root: &Path) -> String {
+    match volume.split_once(':') {
+        Some((host, rest)) if host.starts_with('.') => {
+            let host = root.join(host.strip_prefix("./").unwrap_or(host));
+            format!("{}:{}", host.to_string_lossy(), rest)
+        }
+        _ => volume.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_the_image_with_volumes_ports_and_env() {
+        let root = Path::new("/work/app");
+        let tbl: toml::value::Table = toml::from_str(
+            r#"
+runtime = "podman"
+image = "postgres:16"
+volumes = ["./data:/var/lib/postgresql/data", "cache:/cache"]
+ports = ["5433:5432", 6379]
+"#,
+        )
+        .unwrap();
+        let spec = ContainerSpec::from_table(&tbl, root).unwrap().unwrap();
+        assert_eq!(
+            spec.volumes,
+            vec!["/work/app/data:/var/lib/postgresql/data", "cache:/cache"]
+        );
+        assert_eq!(spec.ports, vec!["5433:5432", "6379:6379"]);
+
+        let env = ["POSTGRES_PASSWORD".to_string()];
+        let cmd = spec.command(root, "db", Some("postgres -c fsync=off"), env.iter());
+        let name = container_name(root, "db");
+        assert!(name.starts_with("oxproc-") && name.ends_with("-db"));
+        assert!(cmd.starts_with(&format!(
+            "podman rm -f '{}' >/dev/null 2>&1; mkdir -p ",
+            name
+        )));
+        assert!(cmd.contains(&format!(
+            "; exec podman run --rm --name '{}' --cidfile ",
+            name
+        )));
+        assert!(cmd.ends_with(
+            " -e 'POSTGRES_PASSWORD' -v '/work/app/data:/var/lib/postgresql/data' \
+             -v 'cache:/cache' -p 5433:5432 -p 6379:6379 'postgres:16' postgres -c fsync=off"
+        ));
+
+        let mut shifted = ContainerSpec {
+            ports: vec![
+                "5433:5432".into(),
+                "127.0.0.1:8080:80/tcp".into(),
+                "9000-9002:9000-9002".into(),
+                "{port}:80".into(),
+                "53".into(),
+                "127.0.0.1::5353".into(),
+            ],
+            ..spec.clone()
+        };
+        shifted.shift_host_ports(100).unwrap();
+        assert_eq!(
+            shifted.ports,
+            vec![
+                "5533:5432",
+                "127.0.0.1:8180:80/tcp",
+                "9100-9102:9000-9002",
+                "{port}:80",
+                "53",
+                "127.0.0.1::5353",
+            ]
+        );
+        assert!(shifted
+            .command(root, "db", None, env.iter())
+            .contains(" -p 5533:5432 "));
+        shifted.ports = vec!["65500:80".into()];
+        assert!(shifted.shift_host_ports(100).is_err());
+
+        let exec = spec.exec_args(root, "db", false, Some("/app"), env.iter());
+        assert_eq!(
+            exec.join(" "),
+            format!("podman exec -i -w /app -e POSTGRES_PASSWORD {}", name)
+        );
+
+        let bad: toml::value::Table = toml::from_str("runtime = \"lxc\"\nimage = \"x\"").unwrap();
+        assert!(ContainerSpec::from_table(&bad, root).is_err());
+        let no_image: toml::value::Table = toml::from_str("runtime = \"docker\"").unwrap();
+        assert!(ContainerSpec::from_table(&no_image, root).is_err());
+    }
+}
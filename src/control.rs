@@ -0,0 +1,338 @@
+// Control plane between the CLI and a running manager.
+// The manager listens on `control.sock` in the project's state dir; each request and response
+// is a single JSON object on its own line. The wire format is versioned and documented in
+// `schema/control-v1.json` so other tools (editors, GUIs) can talk to the manager directly.
+
+use crate::state;
+pub use crate::state::ProcessState;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+
+/// Version of the control protocol. Bump on incompatible changes; additions are advertised
+/// through `CAPABILITIES` instead.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Request `cmd`s the manager accepts, reported by `hello`.
+pub const CAPABILITIES: &[&str] = &[
+    "hello", "status", "metrics", "start", "restart", "reload", "send", "debug", "stop",
+];
+
+/// JSON Schema of the protocol, printed by `oxproc protocol`.
+pub const SCHEMA: &str = include_str!("../schema/control-v1.json");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    /// Negotiate the protocol version and list capabilities
+    Hello {
+        version: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client: Option<String>,
+    },
+    /// State of every configured process
+    Status,
+    /// Output volume and rate of every started process
+    Metrics,
+    /// Start configured processes that are not currently running
+    Start { names: Vec<String> },
+    /// Stop and relaunch running processes (starting any that are stopped)
+    Restart {
+        names: Vec<String>,
+        /// Seconds to wait after SIGTERM before SIGKILL (manager default if unset)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        grace: Option<u64>,
+        /// Send `reload_signal` instead to processes that have one
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        soft: bool,
+    },
+    /// Send each process its `reload_signal` (every running process that has one if empty)
+    Reload { names: Vec<String> },
+    /// Write `text` and a newline to a process's stdin (`stdin = "pipe"` or `"pty"`)
+    Send { name: String, text: String },
+    /// The manager's memory use and buffer levels
+    Debug,
+    /// Stop every process and shut the manager down, or stop only the named processes
+    Stop {
+        /// Seconds to wait after SIGTERM before SIGKILL (manager default if unset)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        grace: Option<u64>,
+        /// Stop these and leave them stopped; the manager keeps running
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        names: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default)]
+    pub message: String,
+    /// Structured result for requests that return one (`hello`, `status`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+/// `hello` result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hello {
+    pub protocol: u32,
+    pub server: String,
+    pub capabilities: Vec<String>,
+}
+
+/// `debug` result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagerDebug {
+    /// Resident memory of the manager process, where the platform reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rss_bytes: Option<u64>,
+    /// Longer output lines are split into pieces of this size
+    pub max_line_bytes: u64,
+    pub processes: Vec<ProcessBuffers>,
+}
+
+/// Output buffered in the manager for one process.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessBuffers {
+    pub name: String,
+    /// Bytes of unfinished lines held right now
+    pub buffered_bytes: u64,
+    /// Lines longer than `max_line_bytes` so far
+    pub split_lines: u64,
+    /// Whether the manager holds the process's stdin open (`stdin = "pipe"` or `"pty"`)
+    pub stdin_open: bool,
+}
+
+/// `stop` result; the response message has one line per action taken.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StopSummary {
+    /// Processes that were running when the stop began
+    pub processes: usize,
+    /// Processes still there after the grace period, killed with SIGKILL
+    pub sigkilled: usize,
+    /// Descendants outside their process's group that had to be killed separately
+    pub escaped: usize,
+    #[serde(skip)]
+    pub lines: Vec<String>,
+}
+
+/// Agree on the lower of the client's and our protocol version.
+pub fn negotiate(client_version: u32) -> Result<Hello, String> {
+    if client_version == 0 {
+        return Err("protocol versions start at 1".into());
+    }
+    Ok(Hello {
+        protocol: client_version.min(PROTOCOL_VERSION),
+        server: format!("oxproc {}", env!("CARGO_PKG_VERSION")),
+        capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+    })
+}
+
+/// One entry of the `metrics` result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessMetrics {
+    pub name: String,
+    #[serde(flatten)]
+    pub output: state::OutputStats,
+}
+
+/// One entry of the `status` result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessStatus {
+    pub name: String,
+    pub state: ProcessState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    pub restarts: u32,
+}
+
+impl Response {
+    pub fn ok(message: impl Into<String>) -> Self {
+        Response {
+            ok: true,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn with_data(mut self, data: impl Serialize) -> Self {
+        self.data = serde_json::to_value(data).ok();
+        self
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Response {
+            ok: false,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+/// Send a request to the manager for the project whose state lives in `state_dir`.
+/// Fails if no manager is listening.
+pub fn send_request(state_dir: &Path, req: &Request) -> Result<Response> {
+    send_request_timeout(state_dir, req, Duration::from_secs(30))
+}
+
+/// [`send_request`], giving up when no response arrives within `timeout`.
+pub fn send_request_timeout(
+    state_dir: &Path,
+    req: &Request,
+    timeout: Duration,
+) -> Result<Response> {
+    let path = state::control_socket_path(state_dir);
+    let mut stream = UnixStream::connect(&path)?;
+    stream.set_read_timeout(Some(timeout))?;
+    let mut line = serde_json::to_string(req)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    let mut reader = BufReader::new(stream);
+    let mut buf = String::new();
+    reader.read_line(&mut buf)?;
+    if buf.trim().is_empty() {
+        anyhow::bail!("Manager closed the control connection without a response");
+    }
+    Ok(serde_json::from_str(buf.trim())?)
+}
+
+/// Whether a manager is accepting control connections for this state dir.
+pub fn manager_listening(state_dir: &Path) -> bool {
+    UnixStream::connect(state::control_socket_path(state_dir)).is_ok()
+}
+
+/// Whether the manager answers a `hello` within a couple of seconds. Unlike
+/// [`manager_listening`], this tells a hung manager (whose socket still accepts connections)
+/// from a working one.
+pub fn manager_answers(state_dir: &Path) -> bool {
+    let hello = Request::Hello {
+        version: PROTOCOL_VERSION,
+        client: None,
+    };
+    send_request_timeout(state_dir, &hello, Duration::from_secs(2)).is_ok_and(|r| r.ok)
+}
+
+/// Bind the control socket, replacing a leftover socket file from a previous manager.
+pub fn bind(state_dir: &Path) -> Result<tokio::net::UnixListener> {
+    let path = state::control_socket_path(state_dir);
+    if path.exists() {
+        std::fs::remove_file(&path)?;
+    }
+    Ok(tokio::net::UnixListener::bind(&path)?)
+}
+
+/// Longest request line the manager reads; a client sending more is disconnected.
+pub const MAX_REQUEST_BYTES: usize = 1024 * 1024;
+
+/// Accept connections forever, answering each request line with `handler`.
+pub async fn serve<F, Fut>(listener: tokio::net::UnixListener, handler: F)
+where
+    F: Fn(Request) -> Fut + Clone + Send + Sync + 'static,
+    Fut: std::future::Future<Output = Response> + Send,
+{
+    use std::sync::atomic::AtomicU64;
+    use std::sync::Arc;
+    use tokio::io::{AsyncWriteExt, BufReader};
+
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            let (read, mut write) = stream.into_split();
+            let mut lines = crate::lines::CappedLines::new(
+                BufReader::new(read),
+                MAX_REQUEST_BYTES,
+                Arc::new(AtomicU64::new(0)),
+            );
+            while let Ok(Some(line)) = lines.next_line().await {
+                let too_long = line.split;
+                let resp = if too_long {
+                    Response::error(format!("request longer than {} bytes", MAX_REQUEST_BYTES))
+                } else {
+                    match serde_json::from_str::<Request>(&line.text) {
+                        Ok(req) => handler(req).await,
+                        Err(e) => Response::error(format!("invalid request: {}", e)),
+                    }
+                };
+                let Ok(mut out) = serde_json::to_string(&resp) else {
+                    break;
+                };
+                out.push('\n');
+                if write.write_all(out.as_bytes()).await.is_err() || too_long {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_wire_format_is_tagged() {
+        let req = Request::Start {
+            names: vec!["web".into()],
+        };
+        let json = serde_json::to_string(&req).unwrap();
+        assert_eq!(json, r#"{"cmd":"start","names":["web"]}"#);
+        let req: Request = serde_json::from_str(r#"{"cmd":"restart","names":["web"]}"#).unwrap();
+        assert!(matches!(req, Request::Restart { grace: None, .. }));
+    }
+
+    #[tokio::test]
+    async fn round_trips_request_over_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let listener = bind(dir.path()).unwrap();
+        tokio::spawn(serve(listener, |req: Request| async move {
+            match req {
+                Request::Start { names } | Request::Restart { names, .. } => {
+                    Response::ok(names.join(","))
+                }
+                _ => Response::error("unsupported"),
+            }
+        }));
+        let state_dir = dir.path().to_path_buf();
+        let resp = tokio::task::spawn_blocking(move || {
+            send_request(
+                &state_dir,
+                &Request::Start {
+                    names: vec!["a".into(), "b".into()],
+                },
+            )
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(resp.ok);
+        assert_eq!(resp.message, "a,b");
+    }
+
+    #[test]
+    fn negotiates_version_and_documents_every_request() {
+        let hello = negotiate(7).unwrap();
+        assert_eq!(hello.protocol, PROTOCOL_VERSION);
+        assert!(negotiate(0).is_err());
+
+        // Every capability must appear in the published schema as a request `cmd`
+        let schema: serde_json::Value = serde_json::from_str(SCHEMA).unwrap();
+        let cmds: Vec<&str> = schema["$defs"]["Request"]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v["properties"]["cmd"]["const"].as_str())
+            .collect();
+        for cap in CAPABILITIES {
+            assert!(cmds.contains(cap), "{} missing from schema", cap);
+        }
+        let status: Request = serde_json::from_str(r#"{"cmd":"status"}"#).unwrap();
+        assert!(matches!(status, Request::Status));
+    }
+}
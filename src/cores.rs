@@ -0,0 +1,255 @@
+// Core dumps for processes with `core_dumps = true`. The core size limit is raised before the
+// command runs, and when the process dies with a core the manager gathers the file into
+// `cores/` in the state dir, named after the process, for `oxproc debug cores`. Where the
+// kernel writes cores (`core_pattern`) is a system-wide setting oxproc leaves alone: cores
+// written to the process's directory are moved, ones written elsewhere are linked, and ones
+// piped to a program such as systemd-coredump stay with it.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use nix::sys::signal::Signal;
+use tokio::process::Command;
+
+/// Cores kept per process; they can be large, so older ones are removed.
+const KEPT: usize = 3;
+
+const STAMP: &str = "%Y%m%dT%H%M%S";
+
+/// Let the child write cores: raise its soft core size limit to the hard limit just before it
+/// execs, so everything it forks inherits it.
+#[cfg(unix)]
+pub fn apply(cmd: &mut Command) {
+    // SAFETY: the closure only makes the getrlimit and setrlimit system calls
+    unsafe {
+        cmd.pre_exec(|| {
+            use nix::sys::resource::{getrlimit, setrlimit, Resource};
+            if let Ok((_, hard)) = getrlimit(Resource::RLIMIT_CORE) {
+                let _ = setrlimit(Resource::RLIMIT_CORE, hard, hard);
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub fn apply(_cmd: &mut Command) {}
+
+/// Signals whose default action writes a core.
+const CORE_SIGNALS: &[Signal] = &[
+    Signal::SIGQUIT,
+    Signal::SIGILL,
+    Signal::SIGTRAP,
+    Signal::SIGABRT,
+    Signal::SIGBUS,
+    Signal::SIGFPE,
+    Signal::SIGSEGV,
+    Signal::SIGSYS,
+];
+
+/// Whether an exit may have left a core: the kernel says so, or the process (or, through the
+/// shell, the command it ran) was killed by a signal that dumps one.
+pub fn may_have_dumped(status: &std::process::ExitStatus) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.core_dumped()
+            || crate::config::shell_exit_code(status)
+                .and_then(|code| Signal::try_from(code - 128).ok())
+                .is_some_and(|signal| CORE_SIGNALS.contains(&signal))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        false
+    }
+}
+
+pub fn cores_dir(dir: &Path) -> PathBuf {
+    dir.join("cores")
+}
+
+/// A core written since `since` by one of `pids` (a process and its descendants): `core` or
+/// `core.*` in its directory `cwd`, or a file naming one of the pids where `core_pattern`
+/// puts cores. The newest, if several.
+pub fn find(cwd: &Path, pids: &[u32], since: DateTime<Utc>) -> Option<PathBuf> {
+    let names_pid = |file_name: &str| {
+        file_name
+            .split(|c: char| !c.is_ascii_digit())
+            .filter_map(|n| n.parse::<u32>().ok())
+            .any(|n| pids.contains(&n))
+    };
+    let mut candidates: Vec<PathBuf> = read_dir(cwd)
+        .filter(|p| file_name(p) == "core" || file_name(p).starts_with("core."))
+        .collect();
+    if let Some(dir) = pattern_dir() {
+        candidates.extend(read_dir(&dir).filter(|p| names_pid(&file_name(p))));
+    }
+    candidates
+        .into_iter()
+        .filter_map(|p| {
+            let modified = std::fs::metadata(&p).and_then(|m| m.modified()).ok()?;
+            let modified = DateTime::<Utc>::from(modified);
+            (modified >= since).then_some((modified, p))
+        })
+        .max()
+        .map(|(_, p)| p)
+}
+
+/// Gather the core `pid` of process `name` left, if any, into the state dir `dir`: moved there
+/// from the process's directory, linked from anywhere else. Returns where it is now.
+pub fn collect(
+    dir: &Path,
+    name: &str,
+    cwd: &Path,
+    pids: &[u32],
+    since: DateTime<Utc>,
+) -> Option<PathBuf> {
+    let core = find(cwd, pids, since)?;
+    let cores = cores_dir(dir);
+    std::fs::create_dir_all(&cores).ok()?;
+    let pid = pids.first().copied().unwrap_or_default();
+    let target = cores.join(format!(
+        "{}-{}-{}.core",
+        name,
+        pid,
+        Utc::now().format(STAMP)
+    ));
+    let moved = core.parent() == Some(cwd) && std::fs::rename(&core, &target).is_ok();
+    if !moved {
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&core, &target).ok()?;
+    }
+    prune(dir, name);
+    Some(target)
+}
+
+/// A core gathered into the state dir.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Core {
+    pub process: String,
+    pub pid: u32,
+    pub at: NaiveDateTime,
+    pub path: PathBuf,
+    /// Where the core really is, for one left where the kernel wrote it
+    pub target: Option<PathBuf>,
+    pub size: Option<u64>,
+}
+
+/// The cores gathered in the state dir `dir`, oldest first.
+pub fn list(dir: &Path) -> Vec<Core> {
+    let mut cores: Vec<Core> = read_dir(&cores_dir(dir))
+        .filter_map(|path| {
+            let file_name = file_name(&path);
+            let stem = file_name.strip_suffix(".core")?;
+            let (rest, stamp) = stem.rsplit_once('-')?;
+            let (process, pid) = rest.rsplit_once('-')?;
+            Some(Core {
+                process: process.to_string(),
+                pid: pid.parse().ok()?,
+                at: NaiveDateTime::parse_from_str(stamp, STAMP).ok()?,
+                target: std::fs::read_link(&path).ok(),
+                // Through the link; a core removed since has no size
+                size: std::fs::metadata(&path).ok().map(|m| m.len()),
+                path,
+            })
+        })
+        .collect();
+    cores.sort_by(|a, b| (a.at, &a.process).cmp(&(b.at, &b.process)));
+    cores
+}
+
+/// The core gathered for `pid` of process `name`, if any.
+pub fn collected(dir: &Path, name: &str, pid: u32) -> Option<PathBuf> {
+    list(dir)
+        .into_iter()
+        .find(|c| c.process == name && c.pid == pid)
+        .map(|c| c.path)
+}
+
+/// The kernel's `core_pattern`, on Linux.
+pub fn kernel_pattern() -> Option<String> {
+    let pattern = std::fs::read_to_string("/proc/sys/kernel/core_pattern").ok()?;
+    Some(pattern.trim().to_string())
+}
+
+/// The directory an absolute `core_pattern` writes to.
+fn pattern_dir() -> Option<PathBuf> {
+    let pattern = kernel_pattern()?;
+    let pattern = Path::new(&pattern);
+    pattern
+        .is_absolute()
+        .then(|| pattern.parent().map(Path::to_path_buf))
+        .flatten()
+}
+
+/// Keep the newest `KEPT` cores of `name`.
+fn prune(dir: &Path, name: &str) {
+    let mut cores: Vec<Core> = list(dir)
+        .into_iter()
+        .filter(|c| c.process == name)
+        .collect();
+    cores.sort_by_key(|c| c.at);
+    for core in cores.iter().rev().skip(KEPT) {
+        let _ = std::fs::remove_file(&core.path);
+    }
+}
+
+fn read_dir(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gathers_a_core_from_the_working_directory() {
+        let state = tempfile::tempdir().unwrap();
+        let cwd = tempfile::tempdir().unwrap();
+        let since = Utc::now() - chrono::Duration::seconds(5);
+        assert_eq!(
+            collect(state.path(), "web", cwd.path(), &[4242], since),
+            None
+        );
+
+        std::fs::write(cwd.path().join("core"), b"\x7fELF").unwrap();
+        let core = collect(state.path(), "web", cwd.path(), &[4242], since).unwrap();
+        assert!(!cwd.path().join("core").exists());
+        let cores = list(state.path());
+        assert_eq!(cores.len(), 1);
+        assert_eq!((cores[0].process.as_str(), cores[0].pid), ("web", 4242));
+        assert_eq!(cores[0].size, Some(4));
+        assert_eq!(cores[0].target, None);
+        assert_eq!(collected(state.path(), "web", 4242), Some(core));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn children_may_write_cores() {
+        use nix::sys::resource::{getrlimit, Resource};
+        let (_, hard) = getrlimit(Resource::RLIMIT_CORE).unwrap();
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "ulimit -c"]);
+        apply(&mut cmd);
+        let out = cmd.output().await.unwrap();
+        let limit = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        assert_eq!(
+            limit == "unlimited",
+            hard == nix::sys::resource::RLIM_INFINITY
+        );
+        if hard > 0 {
+            assert_ne!(limit, "0");
+        }
+    }
+}
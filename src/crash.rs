@@ -0,0 +1,179 @@
+// Crash-loop diagnostics: when a process runs out of restarts, the manager saves what it takes
+// to tell why under `crashes/` in the state dir, before later runs bury it: the end of both
+// logs, the environment, the recent exits from the event log and a core dump, if one was left.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Serialize;
+
+use crate::events::{Event, EventKind};
+
+/// Lines kept from the end of each log.
+pub const LOG_LINES: usize = 200;
+
+/// Dumps kept per process; older ones are removed.
+const KEPT: usize = 5;
+
+/// Exits and restarts kept from the event log.
+const EXITS: usize = 20;
+
+const STAMP: &str = "%Y%m%dT%H%M%S";
+
+/// What the manager knows about a process that gave up.
+#[derive(Debug, Clone)]
+pub struct Crash {
+    pub name: String,
+    pub pid: u32,
+    pub started_at: DateTime<Utc>,
+    pub reason: String,
+    pub command: String,
+    pub cwd: PathBuf,
+    pub stdout_log: PathBuf,
+    pub stderr_log: PathBuf,
+    /// Likely credentials already redacted, unless the process has `record_env = "full"`
+    pub env: BTreeMap<String, String>,
+}
+
+/// `crash.json` in a dump.
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    process: &'a str,
+    pid: u32,
+    reason: &'a str,
+    command: &'a str,
+    cwd: String,
+    exits: Vec<Event>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    core_dump: Option<String>,
+}
+
+pub fn crashes_dir(dir: &Path) -> PathBuf {
+    dir.join("crashes")
+}
+
+/// Write a dump for `crash` into `crashes/<name>-<time>/` under the state dir `dir`, and
+/// return its path.
+pub fn save(dir: &Path, crash: &Crash) -> anyhow::Result<PathBuf> {
+    let dump = crashes_dir(dir).join(format!("{}-{}", crash.name, Utc::now().format(STAMP)));
+    std::fs::create_dir_all(&dump)?;
+    for (log, file) in [
+        (&crash.stdout_log, "stdout.log"),
+        (&crash.stderr_log, "stderr.log"),
+    ] {
+        let lines =
+            crate::manager::tail_last_lines(&log.to_string_lossy(), LOG_LINES).unwrap_or_default();
+        let mut text = lines.join("\n");
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        std::fs::write(dump.join(file), text)?;
+    }
+    let env: String = crash
+        .env
+        .iter()
+        .map(|(k, v)| format!("{}={}\n", k, v))
+        .collect();
+    std::fs::write(dump.join("env"), env)?;
+    let report = Report {
+        process: &crash.name,
+        pid: crash.pid,
+        reason: &crash.reason,
+        command: &crash.command,
+        cwd: crash.cwd.to_string_lossy().to_string(),
+        exits: recent_exits(dir, &crash.name),
+        core_dump: crate::cores::collected(dir, &crash.name, crash.pid)
+            .or_else(|| crate::cores::find(&crash.cwd, &[crash.pid], crash.started_at))
+            .map(|p| p.to_string_lossy().to_string()),
+    };
+    std::fs::write(
+        dump.join("crash.json"),
+        serde_json::to_string_pretty(&report)?,
+    )?;
+    prune(dir, &crash.name);
+    Ok(dump)
+}
+
+/// The process's latest exits, restarts and failures from `events.jsonl`.
+fn recent_exits(dir: &Path, name: &str) -> Vec<Event> {
+    let data = std::fs::read_to_string(crate::state::events_log_path(dir)).unwrap_or_default();
+    let mut exits: Vec<Event> = data
+        .lines()
+        .filter_map(|l| serde_json::from_str::<Event>(l).ok())
+        .filter(|e| e.process.as_deref() == Some(name))
+        .filter(|e| {
+            matches!(
+                e.kind,
+                EventKind::Exited | EventKind::Restarted | EventKind::Failed
+            )
+        })
+        .collect();
+    exits.drain(..exits.len().saturating_sub(EXITS));
+    exits
+}
+
+/// Keep the newest `KEPT` dumps of `name`.
+fn prune(dir: &Path, name: &str) {
+    let Ok(entries) = crashes_dir(dir).read_dir() else {
+        return;
+    };
+    let mut dumps: Vec<(NaiveDateTime, PathBuf)> = entries
+        .flatten()
+        .filter_map(|e| {
+            let file_name = e.file_name().to_string_lossy().to_string();
+            let stamp = file_name.strip_prefix(name)?.strip_prefix('-')?;
+            let at = NaiveDateTime::parse_from_str(stamp, STAMP).ok()?;
+            Some((at, e.path()))
+        })
+        .collect();
+    dumps.sort();
+    for (_, path) in dumps.iter().rev().skip(KEPT) {
+        let _ = std::fs::remove_dir_all(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_logs_env_and_exits() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("web.err.log");
+        let lines: Vec<String> = (0..250).map(|i| format!("line {}", i)).collect();
+        std::fs::write(&log, lines.join("\n") + "\n").unwrap();
+        crate::events::record(dir.path(), EventKind::Exited, Some("web"), "exit code 1");
+        crate::events::record(dir.path(), EventKind::Exited, Some("api"), "exit code 2");
+        let crash = Crash {
+            name: "web".into(),
+            pid: 4242,
+            started_at: Utc::now(),
+            reason: "exceeded 3 restart(s)".into(),
+            command: "npm start".into(),
+            cwd: dir.path().to_path_buf(),
+            stdout_log: dir.path().join("missing.log"),
+            stderr_log: log,
+            env: BTreeMap::from([("PORT".to_string(), "3000".to_string())]),
+        };
+
+        let dump = save(dir.path(), &crash).unwrap();
+        let stderr = std::fs::read_to_string(dump.join("stderr.log")).unwrap();
+        assert_eq!(stderr.lines().count(), LOG_LINES);
+        assert_eq!(stderr.lines().next(), Some("line 50"));
+        assert_eq!(
+            std::fs::read_to_string(dump.join("stdout.log")).unwrap(),
+            ""
+        );
+        assert_eq!(
+            std::fs::read_to_string(dump.join("env")).unwrap(),
+            "PORT=3000\n"
+        );
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dump.join("crash.json")).unwrap())
+                .unwrap();
+        assert_eq!(report["exits"].as_array().unwrap().len(), 1);
+        assert_eq!(report["exits"][0]["message"], "exit code 1");
+        assert!(report.get("core_dump").is_none());
+    }
+}
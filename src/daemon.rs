@@ -1,44 +1,89 @@
 #[cfg(unix)]
-use crate::{config::load_config_from, dirs, manager, state};
+use crate::output::outln;
+#[cfg(unix)]
+use crate::{config::load_config_from, control, dirs, manager, state};
 #[cfg(unix)]
 use anyhow::Result;
 #[cfg(unix)]
-use daemonize::Daemonize;
+use daemonize::{Daemonize, Outcome};
 #[cfg(unix)]
 use fs2::FileExt;
 #[cfg(unix)]
-use std::fs::{self, OpenOptions};
+use std::fs::{self, File, OpenOptions};
+#[cfg(unix)]
+use std::io::{Seek, SeekFrom, Write};
 #[cfg(unix)]
 use std::os::unix::fs::OpenOptionsExt;
 // no path buffer needed here
 #[cfg(unix)]
+use chrono::{DateTime, NaiveTime, TimeZone, Utc};
+#[cfg(unix)]
 use tokio::runtime::Builder;
 
 #[cfg(unix)]
-pub fn start_daemon(root: &std::path::Path) -> Result<()> {
+pub fn start_daemon(
+    root: &std::path::Path,
+    names: &[String],
+    force: bool,
+    ignore_parent: bool,
+    stop_at: Option<DateTime<Utc>>,
+) -> Result<()> {
     // Resolve state dir and create it
     let project_root = dirs::normalize_root(root)?;
     let state_dir = dirs::state_dir_for_project(&project_root);
     fs::create_dir_all(&state_dir)?;
 
+    let configs = load_config_from(&project_root)?;
+    let idle = crate::config::load_idle_shutdown(&project_root)?;
+    for name in names {
+        if !configs.iter().any(|c| &c.name == name) {
+            anyhow::bail!("Unknown process '{}'", name);
+        }
+    }
+
+    // With a manager already running, start only what is missing: the named processes, or
+    // every autostart one (including any added to proc.toml since)
+    if control::manager_listening(&state_dir) {
+        if stop_at.is_some() {
+            anyhow::bail!(
+                "oxproc is already running for {}; --for and --until only apply to a new \
+                 manager (stop it first)",
+                project_root.display()
+            );
+        }
+        let names: Vec<String> = if names.is_empty() {
+            outln!(
+                "oxproc is already running for {}; starting what is missing",
+                project_root.display()
+            );
+            configs
+                .iter()
+                .filter(|c| c.autostart)
+                .map(|c| c.name.clone())
+                .collect()
+        } else {
+            names.to_vec()
+        };
+        let resp = control::send_request(&state_dir, &control::Request::Start { names })?;
+        if !resp.message.is_empty() {
+            outln!("{}", resp.message);
+        }
+        if !resp.ok {
+            anyhow::bail!("Manager could not start all requested processes");
+        }
+        return Ok(());
+    }
+
+    if !ignore_parent {
+        check_enclosing_project(&project_root, &configs)?;
+    }
+
+    // Take the lock first so stale-state cleanup cannot race another `start`
+    let mut lock_file = acquire_manager_lock(&state_dir, force)?;
+
     // Clean up stale pid file if present
     let _ = state::cleanup_stale_state_if_any(&project_root);
 
-    // Acquire a simple lock to avoid concurrent daemons
-    let lock_path = state::manager_lock_path(&state_dir);
-    let lock_file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .mode(0o600)
-        .open(&lock_path)?;
-    lock_file.try_lock_exclusive().map_err(|_| {
-        anyhow::anyhow!(
-            "Another oxproc daemon seems to be running (lock held at {}).",
-            lock_path.display()
-        )
-    })?;
-
     let manager_log = state::manager_log_path(&state_dir);
     let log_file = OpenOptions::new()
         .create(true)
@@ -49,10 +94,20 @@ pub fn start_daemon(root: &std::path::Path) -> Result<()> {
     let pid_path = state::manager_pid_path(&state_dir);
 
     // User-facing feedback prior to daemonizing
-    println!("Starting oxproc daemon for {}", project_root.display());
-    println!("State: {}", state_dir.display());
-    println!("PID file: {}", pid_path.display());
-    println!("Manager log: {}", manager_log.display());
+    match crate::config::load_project_name(&project_root)
+        .ok()
+        .flatten()
+    {
+        Some(name) => outln!(
+            "Starting oxproc daemon for {} ({})",
+            name,
+            project_root.display()
+        ),
+        None => outln!("Starting oxproc daemon for {}", project_root.display()),
+    }
+    outln!("State: {}", state_dir.display());
+    outln!("PID file: {}", pid_path.display());
+    outln!("Manager log: {}", manager_log.display());
 
     let daemonize = Daemonize::new()
         .pid_file(&pid_path)
@@ -61,22 +116,256 @@ pub fn start_daemon(root: &std::path::Path) -> Result<()> {
         .stdout(log_file.try_clone()?)
         .stderr(log_file.try_clone()?);
 
-    match daemonize.start() {
-        Ok(()) => {
-            // We are in the daemon process now
-            let rt = Builder::new_multi_thread().enable_all().build()?;
-            rt.block_on(async move {
-                let configs = load_config_from(&project_root)?;
-                manager::run_manager_daemon(configs, state_dir, &project_root).await
-            })?
+    match daemonize.execute() {
+        Outcome::Parent(Ok(parent)) => {
+            // The daemon owns the lock now; our copy of the fd is closed on return.
+            if parent.first_child_exit_code != 0 {
+                anyhow::bail!(
+                    "Failed to daemonize (exit code {}); see {}",
+                    parent.first_child_exit_code,
+                    manager_log.display()
+                );
+            }
+            Ok(())
+        }
+        Outcome::Parent(Err(e)) => Err(anyhow::anyhow!(
+            "Failed to daemonize: {}. Already running?",
+            e
+        )),
+        Outcome::Child(Ok(_)) => {
+            // We are in the daemon process now; the inherited lock fd keeps the lock held.
+            let result = run_daemon(
+                &mut lock_file,
+                configs,
+                &state_dir,
+                &project_root,
+                names,
+                stop_at,
+                idle,
+            );
+
+            // Graceful shutdown: drop the pid file and release the lock explicitly
+            let _ = fs::remove_file(&pid_path);
+            let _ = lock_file.set_len(0);
+            let _ = FileExt::unlock(&lock_file);
+            // Never return into the CLI code path (e.g. `start -f` following logs)
+            match result {
+                Ok(()) => std::process::exit(0),
+                Err(e) => {
+                    eprintln!("manager error: {:#}", e);
+                    std::process::exit(1)
+                }
+            }
+        }
+        Outcome::Child(Err(e)) => {
+            eprintln!("failed to daemonize: {}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn run_daemon(
+    lock_file: &mut File,
+    configs: Vec<crate::config::ProcessConfig>,
+    state_dir: &std::path::Path,
+    project_root: &std::path::Path,
+    names: &[String],
+    stop_at: Option<DateTime<Utc>>,
+    idle: Option<crate::config::IdleShutdown>,
+) -> Result<()> {
+    write_lock_holder(lock_file, std::process::id())?;
+    let rt = Builder::new_multi_thread().enable_all().build()?;
+    rt.block_on(manager::run_manager_daemon(
+        configs,
+        state_dir.to_path_buf(),
+        project_root,
+        names,
+        stop_at,
+        idle,
+    ))
+}
+
+/// The next time the clock in `now`'s time zone reads `time`: later today, or tomorrow if
+/// that has passed (or doesn't exist today, across a DST change).
+pub fn next_occurrence<Tz: TimeZone>(time: NaiveTime, now: DateTime<Tz>) -> DateTime<Utc> {
+    let mut date = now.date_naive();
+    loop {
+        if let Some(at) = date
+            .and_time(time)
+            .and_local_timezone(now.timezone())
+            .earliest()
+            .filter(|at| *at > now)
+        {
+            return at.with_timezone(&Utc);
         }
-        Err(e) => {
-            return Err(anyhow::anyhow!(
-                "Failed to daemonize: {}. Already running?",
-                e
-            ));
+        date = date.succ_opt().expect("date in range");
+    }
+}
+
+/// Refuse to start inside another project whose manager is running, listing the ports and
+/// log files both would use.
+#[cfg(unix)]
+fn check_enclosing_project(
+    project_root: &std::path::Path,
+    configs: &[crate::config::ProcessConfig],
+) -> Result<()> {
+    let Some(parent) = crate::overview::enclosing_managers(project_root)
+        .into_iter()
+        .next()
+    else {
+        return Ok(());
+    };
+    let parent_root = std::path::Path::new(&parent.manager.project_root);
+    eprintln!(
+        "warning: {} is inside {}, whose oxproc manager (pid {}) is running.",
+        project_root.display(),
+        parent_root.display(),
+        parent.manager.pid
+    );
+    let theirs = load_config_from(parent_root).unwrap_or_default();
+    for ours in configs {
+        for other in &theirs {
+            if let Some(port) = ours
+                .effective_port()
+                .filter(|p| other.effective_port() == Some(*p))
+            {
+                eprintln!(
+                    "  both use port {} ({} here, {} there)",
+                    port, ours.name, other.name
+                );
+            }
+            let (out, err) = ours.log_paths(project_root);
+            let (other_out, other_err) = other.log_paths(parent_root);
+            for log in [out, err] {
+                if log == other_out || log == other_err {
+                    eprintln!(
+                        "  both write {} ({} here, {} there)",
+                        log.display(),
+                        ours.name,
+                        other.name
+                    );
+                }
+            }
+        }
+    }
+    anyhow::bail!(
+        "Not starting a second project inside a running one, as their ports and logs may \
+         clash. Use --ignore-parent to start anyway."
+    )
+}
+
+/// Acquire the per-project manager lock, recording the holder PID in the lock file.
+///
+/// When the lock is held, the recorded holder PID is checked; with `force`, a lock whose
+/// holder is verifiably gone is broken by replacing the lock file.
+#[cfg(unix)]
+fn acquire_manager_lock(state_dir: &std::path::Path, force: bool) -> Result<File> {
+    let lock_path = state::manager_lock_path(state_dir);
+    let mut lock_file = open_lock_file(&lock_path)?;
+    if lock_file.try_lock_exclusive().is_err() {
+        let holder = state::read_lock_holder(state_dir);
+        let holder_alive = holder.map(state::pid_alive).unwrap_or(false);
+        match (holder, holder_alive) {
+            (Some(pid), true) => anyhow::bail!(
+                "Another oxproc daemon is running (pid {}, lock held at {}).",
+                pid,
+                lock_path.display()
+            ),
+            (Some(pid), false) if force => {
+                // The holder is gone but its lock survived (e.g. an fd leaked into an orphan);
+                // unlink the file so a fresh inode can be locked.
+                fs::remove_file(&lock_path)?;
+                lock_file = open_lock_file(&lock_path)?;
+                lock_file.try_lock_exclusive().map_err(|_| {
+                    anyhow::anyhow!("Failed to acquire lock at {}", lock_path.display())
+                })?;
+                outln!("Broke stale manager lock (holder pid {} is gone).", pid);
+            }
+            (Some(pid), false) => anyhow::bail!(
+                "Manager lock at {} is held but its holder (pid {}) is not running. \
+                 Retry with `oxproc start --force` to break it.",
+                lock_path.display(),
+                pid
+            ),
+            (None, _) => anyhow::bail!(
+                "Another oxproc daemon seems to be running (lock held at {}).",
+                lock_path.display()
+            ),
         }
     }
+    write_lock_holder(&mut lock_file, std::process::id())?;
+    Ok(lock_file)
+}
+
+#[cfg(unix)]
+fn open_lock_file(path: &std::path::Path) -> std::io::Result<File> {
+    // Never truncate on open: the holder PID must survive for diagnostics.
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(unix)]
+fn write_lock_holder(lock_file: &mut File, pid: u32) -> std::io::Result<()> {
+    lock_file.set_len(0)?;
+    lock_file.seek(SeekFrom::Start(0))?;
+    writeln!(lock_file, "{}", pid)?;
+    lock_file.flush()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_occurrence_rolls_over_to_tomorrow() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 14, 17, 30, 0).unwrap();
+        let at = |t: &str| next_occurrence(crate::config::parse_time_of_day(t).unwrap(), now);
+        assert_eq!(
+            at("18:00"),
+            Utc.with_ymd_and_hms(2026, 3, 14, 18, 0, 0).unwrap()
+        );
+        assert_eq!(
+            at("9:15"),
+            Utc.with_ymd_and_hms(2026, 3, 15, 9, 15, 0).unwrap()
+        );
+        assert_eq!(
+            at("17:30"),
+            Utc.with_ymd_and_hms(2026, 3, 15, 17, 30, 0).unwrap()
+        );
+        assert!(crate::config::parse_time_of_day("6pm").is_err());
+    }
+
+    #[test]
+    fn acquires_lock_and_records_holder_pid() {
+        let dir = tempfile::tempdir().unwrap();
+        let _lock = acquire_manager_lock(dir.path(), false).unwrap();
+        assert_eq!(
+            state::read_lock_holder(dir.path()),
+            Some(std::process::id() as i32)
+        );
+    }
 
-    Ok(())
+    #[test]
+    fn refuses_lock_held_by_live_process_even_with_force() {
+        let dir = tempfile::tempdir().unwrap();
+        let _lock = acquire_manager_lock(dir.path(), false).unwrap();
+        assert!(acquire_manager_lock(dir.path(), false).is_err());
+        assert!(acquire_manager_lock(dir.path(), true).is_err());
+    }
+
+    #[test]
+    fn force_breaks_lock_of_dead_holder() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut held = acquire_manager_lock(dir.path(), false).unwrap();
+        // Pretend the holder is a PID that cannot exist
+        write_lock_holder(&mut held, 0x7fff_fff0).unwrap();
+        assert!(acquire_manager_lock(dir.path(), false).is_err());
+        assert!(acquire_manager_lock(dir.path(), true).is_ok());
+    }
 }
@@ -0,0 +1,265 @@
+// `oxproc debug state`: the manager's bookkeeping for this project in one place (state
+// file, lock holder, heartbeat, control socket, recent events), for bug reports and for
+// working out why a manager looks stuck. `oxproc debug process NAME`: exactly what one
+// process was launched with.
+
+use crate::control::{self, Request};
+use crate::output::outln;
+use crate::state::{self, format_ago, format_bytes, ManagerState};
+use anyhow::Result;
+use chrono::Utc;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// How long to wait for the manager to answer on its control socket.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub fn print_state(root: &Path, events: usize) -> Result<()> {
+    let root = crate::dirs::normalize_root(root)?;
+    let dir = state::state_dir_from_root(&root);
+    outln!("Project:        {}", root.display());
+    outln!("State dir:      {}", describe_path(&dir));
+
+    let state_file = state::state_file_path(&dir);
+    outln!("state.json:     {}", describe_path(&state_file));
+    let st: Option<ManagerState> = std::fs::read_to_string(&state_file).ok().and_then(|data| {
+        match serde_json::from_str(&data) {
+            Ok(st) => Some(st),
+            Err(e) => {
+                outln!("                does not parse: {}", e);
+                None
+            }
+        }
+    });
+    if let Some(st) = &st {
+        outln!(
+            "Manager:        pid {} ({:?}), state format v{}, started {}",
+            st.manager.pid,
+            st.manager.health(),
+            st.manager.version,
+            st.manager.started_at
+        );
+        if let Some(exe) = &st.manager.exe {
+            outln!("Executable:     {}", exe);
+        }
+        match st.manager.heartbeat {
+            Some(beat) => outln!(
+                "Heartbeat:      {} ago (every {}s)",
+                (Utc::now() - beat)
+                    .to_std()
+                    .map(format_ago)
+                    .unwrap_or_else(|_| "0s".into()),
+                state::HEARTBEAT_INTERVAL.as_secs()
+            ),
+            None => outln!("Heartbeat:      none recorded"),
+        }
+    }
+
+    let lock = state::manager_lock_path(&dir);
+    match state::read_lock_holder(&dir) {
+        Some(pid) => outln!(
+            "Lock holder:    pid {} ({}) in {}",
+            pid,
+            if state::pid_alive(pid) {
+                "alive"
+            } else {
+                "dead"
+            },
+            lock.display()
+        ),
+        None => outln!("Lock holder:    none ({})", describe_path(&lock)),
+    }
+    let pid_file = state::manager_pid_path(&dir);
+    match std::fs::read_to_string(&pid_file) {
+        Ok(pid) => outln!(
+            "PID file:       pid {} in {}",
+            pid.trim(),
+            pid_file.display()
+        ),
+        Err(_) => outln!("PID file:       {}", describe_path(&pid_file)),
+    }
+
+    let socket = state::control_socket_path(&dir);
+    outln!("Control socket: {}", describe_path(&socket));
+    let mut answers = false;
+    if socket.exists() {
+        let hello = Request::Hello {
+            version: control::PROTOCOL_VERSION,
+            client: None,
+        };
+        match control::send_request_timeout(&dir, &hello, PROBE_TIMEOUT) {
+            Ok(resp) if resp.ok => {
+                answers = true;
+                outln!("                answers hello");
+            }
+            Ok(resp) => outln!("                refused hello: {}", resp.message),
+            Err(e) => outln!("                no answer: {}", e),
+        }
+    }
+    outln!(
+        "Manager log:    {}",
+        describe_path(&state::manager_log_path(&dir))
+    );
+
+    if let Some(st) = &st {
+        outln!();
+        outln!("Processes:");
+        for p in &st.processes {
+            outln!(
+                "  {:<12} pid={} pgid={} state={} alive={}",
+                p.name,
+                p.pid,
+                p.pgid,
+                p.state,
+                state::pid_alive(p.pid as i32)
+            );
+        }
+        if !st.pending.is_empty() {
+            outln!("  pending: {}", st.pending.join(", "));
+        }
+        if !st.stopped.is_empty() {
+            outln!("  stopped: {}", st.stopped.join(", "));
+        }
+    }
+
+    if answers {
+        print_memory(&dir);
+    }
+
+    let events_path = state::events_log_path(&dir);
+    let data = std::fs::read_to_string(&events_path).unwrap_or_default();
+    let lines: Vec<&str> = data.lines().collect();
+    outln!();
+    outln!(
+        "Events ({} of {} in {}):",
+        lines.len().min(events),
+        lines.len(),
+        events_path.display()
+    );
+    for line in &lines[lines.len().saturating_sub(events)..] {
+        outln!("  {}", line);
+    }
+    Ok(())
+}
+
+pub fn print_process(root: &Path, name: &str) -> Result<()> {
+    let st = state::load_state_from_root(root)?;
+    let Some(p) = st.processes.iter().find(|p| p.name == name) else {
+        anyhow::bail!("Process '{}' has not been started by the manager", name);
+    };
+    outln!(
+        "Process:        {} (pid {}, {}, started {})",
+        p.name,
+        p.pid,
+        p.state,
+        p.started_at
+    );
+    let Some(launch) = &p.launch else {
+        outln!("Launch:         not recorded (started by an older oxproc)");
+        return Ok(());
+    };
+    outln!(
+        "Command:        sh -c {}",
+        crate::watch::shell_quote(&launch.command)
+    );
+    outln!("Directory:      {}", launch.cwd);
+    match &launch.env {
+        Some(env) => {
+            outln!("Environment:    {} variables", env.len());
+            for (key, value) in env {
+                outln!("  {}={}", key, value);
+            }
+        }
+        None => outln!("Environment:    not recorded (set record_env = true for this process)"),
+    }
+    Ok(())
+}
+
+/// `debug cores`: the core dumps gathered in the state dir, and where the kernel puts cores.
+pub fn print_cores(root: &Path) -> Result<()> {
+    let dir = state::state_dir_from_root(root);
+    let cores = crate::cores::list(&dir);
+    if cores.is_empty() {
+        outln!(
+            "No core dumps gathered in {}.",
+            crate::cores::cores_dir(&dir).display()
+        );
+    }
+    for core in &cores {
+        let size = core
+            .size
+            .map(format_bytes)
+            .unwrap_or_else(|| "removed".into());
+        let target = core
+            .target
+            .as_ref()
+            .map(|t| format!(" -> {}", t.display()))
+            .unwrap_or_default();
+        outln!(
+            "{:<12} pid={:<7} {} {:>7}  {}{}",
+            core.process,
+            core.pid,
+            core.at.format("%Y-%m-%d %H:%M:%S"),
+            size,
+            core.path.display(),
+            target
+        );
+    }
+    if let Some(pattern) = crate::cores::kernel_pattern() {
+        outln!();
+        outln!("Kernel core_pattern: {}", pattern);
+        if pattern.starts_with('|') {
+            outln!("  Cores go to that program, not to files oxproc can gather (try `coredumpctl list`).");
+        }
+    }
+    Ok(())
+}
+
+/// The running manager's memory use and output buffers.
+fn print_memory(dir: &Path) {
+    let info: Option<control::ManagerDebug> =
+        control::send_request_timeout(dir, &Request::Debug, PROBE_TIMEOUT)
+            .ok()
+            .filter(|resp| resp.ok)
+            .and_then(|resp| resp.data)
+            .and_then(|data| serde_json::from_value(data).ok());
+    let Some(info) = info else {
+        // An older manager without `debug`
+        return;
+    };
+    outln!();
+    match info.rss_bytes {
+        Some(rss) => outln!("Memory:         {} resident", format_bytes(rss)),
+        None => outln!("Memory:"),
+    }
+    outln!(
+        "  lines over {} are logged in pieces",
+        format_bytes(info.max_line_bytes)
+    );
+    for p in &info.processes {
+        outln!(
+            "  {:<12} buffered={} split_lines={}{}",
+            p.name,
+            format_bytes(p.buffered_bytes),
+            p.split_lines,
+            if p.stdin_open { " stdin=open" } else { "" }
+        );
+    }
+}
+
+/// A path with whether it exists, and its size and age for files.
+fn describe_path(path: &Path) -> String {
+    let Ok(meta) = std::fs::metadata(path) else {
+        return format!("{} (missing)", path.display());
+    };
+    if !meta.is_file() {
+        return path.display().to_string();
+    }
+    let age = meta
+        .modified()
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+        .map(|d| format!(", written {} ago", format_ago(d)))
+        .unwrap_or_default();
+    format!("{} ({}{})", path.display(), format_bytes(meta.len()), age)
+}
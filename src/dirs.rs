@@ -1,23 +1,60 @@
 use std::env;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
+/// The project root `p` names, absolute and with symlinks and `..` resolved, so `./api`,
+/// `api/` and `/abs/api` all address the same state dir. A path to a `proc.toml` or
+/// `Procfile` names the directory holding it.
 pub fn normalize_root<P: AsRef<Path>>(p: P) -> std::io::Result<PathBuf> {
-    let pb = p.as_ref();
-    if pb.is_absolute() {
-        Ok(pb.to_path_buf())
-    } else {
-        std::fs::canonicalize(pb)
+    let path = p.as_ref();
+    if path.is_file() {
+        let name = path.file_name().and_then(|n| n.to_str());
+        if !matches!(name, Some("proc.toml" | "Procfile")) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "not a project directory, proc.toml or Procfile",
+            ));
+        }
+        let dir = path.parent().filter(|d| !d.as_os_str().is_empty());
+        return std::fs::canonicalize(dir.unwrap_or(Path::new(".")));
     }
+    std::fs::canonicalize(path)
 }
 
-fn xdg_state_home() -> PathBuf {
-    if let Ok(dir) = env::var("XDG_STATE_HOME") {
-        return PathBuf::from(dir);
-    }
-    if let Ok(home) = env::var("HOME") {
-        return Path::new(&home).join(".local/state");
+/// `$var`, or `fallback` under the home directory.
+fn xdg_home(var: &str, fallback: &str) -> Option<PathBuf> {
+    if let Ok(dir) = env::var(var) {
+        return Some(PathBuf::from(dir));
     }
-    PathBuf::from(".oxproc-state")
+    env::var("HOME")
+        .ok()
+        .map(|home| Path::new(&home).join(fallback))
+}
+
+fn xdg_state_home() -> PathBuf {
+    xdg_home("XDG_STATE_HOME", ".local/state").unwrap_or_else(|| PathBuf::from(".oxproc-state"))
+}
+
+/// Root of `oxproc service`, the project of personal services shared by every directory.
+/// Relative paths in services.toml, default log files included, resolve here.
+pub fn services_root() -> PathBuf {
+    let root = xdg_home("XDG_DATA_HOME", ".local/share")
+        .unwrap_or_else(|| PathBuf::from(".oxproc-data"))
+        .join("oxproc/services");
+    // Resolved like any other root once it exists, so comparisons against it hold
+    std::fs::canonicalize(&root).unwrap_or(root)
+}
+
+/// oxproc's user config directory, `$XDG_CONFIG_HOME/oxproc`.
+pub fn config_dir() -> PathBuf {
+    xdg_home("XDG_CONFIG_HOME", ".config")
+        .unwrap_or_else(|| PathBuf::from(".config"))
+        .join("oxproc")
+}
+
+/// The services config, `$XDG_CONFIG_HOME/oxproc/services.toml`.
+pub fn services_config() -> PathBuf {
+    config_dir().join("services.toml")
 }
 
 pub fn project_id<P: AsRef<Path>>(root: P) -> String {
@@ -29,7 +66,137 @@ pub fn project_id<P: AsRef<Path>>(root: P) -> String {
     hash.to_hex()[..12].to_string()
 }
 
+/// Overrides the directory holding every project's state dir. `--state-dir` sets it, so
+/// oxproc processes spawned along the way (required projects) use the same state.
+pub const STATE_DIR_ENV: &str = "OXPROC_STATE_DIR";
+
+/// Directory holding every project's state dir.
+pub fn state_home() -> PathBuf {
+    if let Some(dir) = env::var_os(STATE_DIR_ENV).filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    xdg_state_home().join("oxproc")
+}
+
 pub fn state_dir_for_project<P: AsRef<Path>>(root: P) -> PathBuf {
-    let id = project_id(root.as_ref());
-    xdg_state_home().join("oxproc").join(id)
+    // Services get a fixed name rather than a hash
+    if root.as_ref() == services_root() {
+        return state_home().join("services");
+    }
+    let mut name = project_id(root.as_ref());
+    // Formations of their own, next to the one of the regular proc.toml
+    if let Some(variant) = variant_for(root.as_ref()) {
+        if let Some(file) = &variant.file {
+            name.push('-');
+            name.push_str(&state_suffix(file));
+        }
+        if let Some(namespace) = &variant.namespace {
+            name.push('@');
+            name.push_str(namespace);
+        }
+    }
+    state_home().join(name)
+}
+
+/// How this run addresses its project: another config file (`-f`) and/or a namespace
+/// (`--namespace`), each giving the formation a state dir of its own.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Variant {
+    /// Name of the config file used instead of proc.toml
+    pub file: Option<String>,
+    pub namespace: Option<String>,
+    /// Added to every process's port in the namespace
+    pub port_offset: u16,
+}
+
+impl Variant {
+    /// The global options that select this variant again, for oxproc processes started on
+    /// the project's behalf.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(file) = &self.file {
+            args.extend(["-f".to_string(), file.clone()]);
+        }
+        if let Some(namespace) = &self.namespace {
+            args.extend(["--namespace".to_string(), namespace.clone()]);
+        }
+        if self.port_offset != 0 {
+            args.extend(["--port-offset".to_string(), self.port_offset.to_string()]);
+        }
+        args
+    }
+}
+
+/// The project root the variant applies to (canonical), and the variant.
+static VARIANT: OnceLock<(PathBuf, Variant)> = OnceLock::new();
+
+/// Use `variant` for `root` for the rest of this run. Only `root` is affected; other
+/// projects (`requires_project`) keep their proc.toml and state.
+pub fn set_variant(root: &Path, variant: Variant) {
+    let root = std::fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let _ = VARIANT.set((root, variant));
+}
+
+/// The variant selected for `root`, if any.
+pub fn variant_for(root: &Path) -> Option<&'static Variant> {
+    let (selected, variant) = VARIANT.get()?;
+    let root = std::fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    (root == *selected).then_some(variant)
+}
+
+/// The alternate config file selected with `-f` for `root`, if any.
+pub fn config_file_for(root: &Path) -> Option<&'static str> {
+    variant_for(root)?.file.as_deref()
+}
+
+/// `proc.ci.toml` → `proc.ci`, kept to characters safe in a directory name.
+fn state_suffix(file: &str) -> String {
+    let stem = file.strip_suffix(".toml").unwrap_or(file);
+    stem.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alternate_configs_get_their_own_state_dir_name() {
+        assert_eq!(state_suffix("proc.ci.toml"), "proc.ci");
+        assert_eq!(state_suffix("review app.toml"), "review-app");
+    }
+
+    #[test]
+    fn normalizes_spellings_of_a_root_and_its_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let api = dir.path().join("api");
+        std::fs::create_dir(&api).unwrap();
+        std::fs::write(api.join("proc.toml"), "").unwrap();
+        std::fs::write(api.join("notes.txt"), "").unwrap();
+        let expected = std::fs::canonicalize(&api).unwrap();
+
+        for spelling in [
+            api.clone(),
+            dir.path().join("api/"),
+            dir.path().join("api/../api"),
+            api.join("proc.toml"),
+        ] {
+            assert_eq!(
+                normalize_root(&spelling).unwrap(),
+                expected,
+                "{:?}",
+                spelling
+            );
+        }
+        let err = normalize_root(api.join("notes.txt")).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(normalize_root(dir.path().join("missing")).is_err());
+    }
 }
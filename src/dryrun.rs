@@ -0,0 +1,96 @@
+// `oxproc start --dry-run`: what a start would spawn, resolved from the config, without
+// touching the state dir or forking a manager.
+
+use crate::config::{self, ProcessConfig};
+use crate::output::outln;
+use anyhow::Result;
+use std::path::Path;
+
+pub fn print_dry_run(root: &Path, names: &[String]) -> Result<()> {
+    let root = crate::dirs::normalize_root(root)?;
+    let configs = config::load_config_from(&root)?;
+    for name in names {
+        if !configs.iter().any(|c| &c.name == name) {
+            anyhow::bail!("Unknown process '{}'", name);
+        }
+    }
+    // Same selection as the manager: the named processes, or every autostart one, plus
+    // their dependencies
+    let wanted: Vec<String> = configs
+        .iter()
+        .filter(|c| {
+            if names.is_empty() {
+                c.autostart
+            } else {
+                names.contains(&c.name)
+            }
+        })
+        .map(|c| c.name.clone())
+        .collect();
+    let wanted = config::with_dependencies(&configs, &wanted);
+    let order = config::start_order(&configs, &wanted);
+
+    outln!("Dry run for {} (nothing is started)", root.display());
+    outln!(
+        "State: {}",
+        crate::dirs::state_dir_for_project(&root).display()
+    );
+    outln!("Start order: {}", order.join(" -> "));
+    for name in &order {
+        if let Some(c) = configs.iter().find(|c| &c.name == name) {
+            outln!();
+            print_process(c, &root);
+        }
+    }
+    let skipped: Vec<&str> = configs
+        .iter()
+        .filter(|c| !wanted.contains(&c.name))
+        .map(|c| c.name.as_str())
+        .collect();
+    if !skipped.is_empty() {
+        outln!();
+        outln!("Not started: {}", skipped.join(", "));
+    }
+    Ok(())
+}
+
+fn print_process(c: &ProcessConfig, root: &Path) {
+    outln!("{}", c.name);
+    outln!("  command: {}", c.command_line(root));
+    let cwd = match &c.cwd {
+        Some(cwd) => root.join(cwd),
+        None => root.to_path_buf(),
+    };
+    if cwd.is_dir() {
+        outln!("  cwd:     {}", cwd.display());
+    } else {
+        outln!("  cwd:     {} (does not exist)", cwd.display());
+    }
+    let env = c.env.describe();
+    if env.is_empty() {
+        outln!("  env:     (inherited)");
+    }
+    for (i, line) in env.iter().enumerate() {
+        let label = if i == 0 { "env:" } else { "" };
+        outln!("  {:<8} {}", label, line);
+    }
+    let (out, err) = c.log_paths(root);
+    outln!("  stdout:  {}", out.display());
+    outln!("  stderr:  {}", err.display());
+    if let Some(port) = c.effective_port() {
+        outln!("  port:    {}", port);
+    }
+    if !c.depends_on.is_empty() {
+        outln!("  after:   {}", c.depends_on.join(", "));
+    }
+    for project in &c.requires_project {
+        outln!("  project: {}", project.display());
+    }
+    let delay = c.start_delay.unwrap_or_default();
+    if !delay.is_zero() || c.start_jitter.is_some() {
+        match c.start_jitter {
+            Some(j) => outln!("  delay:   {:?} (+ up to {:?})", delay, j),
+            None => outln!("  delay:   {:?}", delay),
+        }
+    }
+}
@@ -0,0 +1,670 @@
+// Environment handed to spawned processes and tasks: explicit `env` entries on top of either
+// the inherited environment or, with `inherit_env = false`, a minimal one, and optionally a
+// shim (`env_wrapper`) such as mise or direnv that the command runs under.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::watch::{find_in_path, shell_quote};
+
+/// Variables kept from the parent environment when `inherit_env = false`.
+const MINIMAL_VARS: &[&str] = &["PATH", "HOME"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvSpec {
+    /// Start from the caller's environment (`inherit_env`, default true)
+    pub inherit: bool,
+    /// `env_allowlist`: inherit only variables matching these names (`*` matches any run of
+    /// characters), plus `PATH` and `HOME`
+    pub allowlist: Vec<String>,
+    /// `env = { KEY = "value" }`, applied last
+    pub vars: BTreeMap<String, String>,
+    /// Directories put in front of `PATH` (`path_prepend`), already made absolute
+    pub path_prepend: Vec<PathBuf>,
+    /// `env_wrapper`; unset falls back to the project-wide setting
+    pub wrapper: Option<EnvWrapper>,
+    /// `force_color`: make color-aware programs color their output (or not) even through
+    /// oxproc's pipes; unset follows oxproc's own `--color` mode
+    pub force_color: Option<bool>,
+    /// `term`: the `TERM` to run with, or `"inherit"` to pass the caller's through even when
+    /// the environment is otherwise cleared
+    pub term: Option<String>,
+}
+
+/// A command prefix that sets up the toolchain environment before running the real command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvWrapper {
+    /// `env_wrapper = false`: run the command directly
+    Off,
+    /// An explicit prefix such as `mise exec --`
+    Command(String),
+    /// `env_wrapper = "auto"`: direnv for a `.envrc`, mise for `.tool-versions`/`mise.toml`
+    Auto,
+}
+
+impl EnvWrapper {
+    pub fn parse(value: &toml::Value) -> Result<EnvWrapper, String> {
+        match value {
+            toml::Value::Boolean(false) => Ok(EnvWrapper::Off),
+            toml::Value::String(s) if s == "auto" => Ok(EnvWrapper::Auto),
+            toml::Value::String(s) if !s.trim().is_empty() => {
+                Ok(EnvWrapper::Command(s.trim().to_string()))
+            }
+            _ => Err("'env_wrapper' must be a command prefix, \"auto\" or false".into()),
+        }
+    }
+
+    /// The prefix to use for a command running in `dir`, if any. `auto` looks for the
+    /// marker files in `dir` and its parents and only picks a tool that is installed.
+    fn prefix(&self, dir: &Path) -> Option<String> {
+        match self {
+            EnvWrapper::Off => None,
+            EnvWrapper::Command(prefix) => Some(prefix.clone()),
+            EnvWrapper::Auto => {
+                let has = |file: &str| dir.ancestors().any(|d| d.join(file).exists());
+                if has(".envrc") && find_in_path("direnv").is_some() {
+                    Some(format!(
+                        "direnv exec {}",
+                        shell_quote(&dir.to_string_lossy())
+                    ))
+                } else if (has(".tool-versions") || has("mise.toml") || has(".mise.toml"))
+                    && find_in_path("mise").is_some()
+                {
+                    Some("mise exec --".to_string())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Project-wide defaults from the top level of proc.toml.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectEnv {
+    /// Top-level `path_prepend`, resolved against the project root
+    pub path_prepend: Vec<PathBuf>,
+    /// Top-level `env_wrapper`
+    pub wrapper: Option<EnvWrapper>,
+}
+
+impl ProjectEnv {
+    pub fn from_value(value: &toml::Value, root: &Path) -> Result<ProjectEnv, String> {
+        let path_prepend = match value.get("path_prepend") {
+            None => Vec::new(),
+            Some(v) => parse_path_list("path_prepend", v, root)?,
+        };
+        let wrapper = value
+            .get("env_wrapper")
+            .map(EnvWrapper::parse)
+            .transpose()?;
+        Ok(ProjectEnv {
+            path_prepend,
+            wrapper,
+        })
+    }
+}
+
+impl Default for EnvSpec {
+    fn default() -> Self {
+        EnvSpec {
+            inherit: true,
+            allowlist: Vec::new(),
+            vars: BTreeMap::new(),
+            path_prepend: Vec::new(),
+            wrapper: None,
+            force_color: None,
+            term: None,
+        }
+    }
+}
+
+impl EnvSpec {
+    /// Read `env`, `inherit_env`, `env_allowlist`, `path_prepend` and `env_wrapper` from a
+    /// process or task table. Relative `path_prepend` entries are resolved against `dir`, where
+    /// the command runs.
+    pub fn from_table(tbl: &toml::value::Table, dir: &Path) -> Result<EnvSpec, String> {
+        let inherit = match tbl.get("inherit_env") {
+            None => true,
+            Some(v) => v
+                .as_bool()
+                .ok_or_else(|| "'inherit_env' must be a boolean".to_string())?,
+        };
+        let allowlist = match tbl.get("env_allowlist") {
+            None => Vec::new(),
+            Some(v) => v
+                .as_array()
+                .and_then(|a| {
+                    a.iter()
+                        .map(|p| p.as_str().map(str::to_string))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| "'env_allowlist' must be an array of strings".to_string())?,
+        };
+        let mut vars = BTreeMap::new();
+        if let Some(env) = tbl.get("env") {
+            let env = env
+                .as_table()
+                .ok_or_else(|| "'env' must be a table of strings".to_string())?;
+            for (key, value) in env {
+                let value = match value {
+                    toml::Value::String(s) => s.clone(),
+                    toml::Value::Integer(n) => n.to_string(),
+                    toml::Value::Float(f) => f.to_string(),
+                    toml::Value::Boolean(b) => b.to_string(),
+                    _ => return Err(format!("env value for '{}' must be a string", key)),
+                };
+                vars.insert(key.clone(), value);
+            }
+        }
+        let path_prepend = match tbl.get("path_prepend") {
+            None => Vec::new(),
+            Some(v) => parse_path_list("path_prepend", v, dir)?,
+        };
+        let wrapper = tbl.get("env_wrapper").map(EnvWrapper::parse).transpose()?;
+        let force_color = match tbl.get("force_color") {
+            None => None,
+            Some(toml::Value::String(s)) if s == "auto" => None,
+            Some(v) => Some(
+                v.as_bool()
+                    .ok_or_else(|| "'force_color' must be true, false or \"auto\"".to_string())?,
+            ),
+        };
+        let term = match tbl.get("term") {
+            None => None,
+            Some(v) => Some(
+                v.as_str()
+                    .ok_or_else(|| "'term' must be a string".to_string())?
+                    .to_string(),
+            ),
+        };
+        Ok(EnvSpec {
+            inherit,
+            allowlist,
+            vars,
+            path_prepend,
+            wrapper,
+            force_color,
+            term,
+        })
+    }
+
+    /// Fill in project-wide defaults: the project's `path_prepend` goes after the command's
+    /// own entries, and its `env_wrapper` applies unless the command sets one.
+    pub fn with_project(mut self, project: &ProjectEnv) -> EnvSpec {
+        self.path_prepend
+            .extend(project.path_prepend.iter().cloned());
+        if self.wrapper.is_none() {
+            self.wrapper = project.wrapper.clone();
+        }
+        self
+    }
+
+    /// `other` layered over this environment: its variables win and its `path_prepend`
+    /// entries go first. Inheritance is kept only if both inherit, and its `env_allowlist`
+    /// replaces ours; the wrapper is ours unless we have none.
+    pub fn overlay(mut self, other: &EnvSpec) -> EnvSpec {
+        self.inherit &= other.inherit;
+        if !other.allowlist.is_empty() {
+            self.allowlist = other.allowlist.clone();
+        }
+        self.vars
+            .extend(other.vars.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self.path_prepend = other
+            .path_prepend
+            .iter()
+            .chain(&self.path_prepend)
+            .cloned()
+            .collect();
+        if self.wrapper.is_none() {
+            self.wrapper = other.wrapper.clone();
+        }
+        self.force_color = other.force_color.or(self.force_color);
+        if other.term.is_some() {
+            self.term = other.term.clone();
+        }
+        self
+    }
+
+    /// Whether commands run in `dir` go through an `env_wrapper`.
+    pub fn is_wrapped(&self, dir: &Path) -> bool {
+        self.wrapper.as_ref().and_then(|w| w.prefix(dir)).is_some()
+    }
+
+    /// `cmd` as run under the configured `env_wrapper` (unchanged without one).
+    pub fn wrap_command(&self, cmd: &str, dir: &Path) -> String {
+        match self.wrapper.as_ref().and_then(|w| w.prefix(dir)) {
+            Some(prefix) => format!("{} sh -c {}", prefix, shell_quote(cmd)),
+            None => cmd.to_string(),
+        }
+    }
+
+    /// How the environment differs from the caller's, one entry per line (for `start --dry-run`).
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if !self.inherit || !self.allowlist.is_empty() {
+            let kept: Vec<&str> = MINIMAL_VARS
+                .iter()
+                .copied()
+                .chain(self.allowlist.iter().map(String::as_str))
+                .collect();
+            lines.push(format!("(cleared; keeps only {})", kept.join(", ")));
+        }
+        if !self.path_prepend.is_empty() {
+            let dirs: Vec<String> = self
+                .path_prepend
+                .iter()
+                .map(|d| d.display().to_string())
+                .collect();
+            lines.push(format!("PATH={}:$PATH", dirs.join(":")));
+        }
+        match self.force_color {
+            Some(true) => lines.push("FORCE_COLOR=1 CLICOLOR_FORCE=1".into()),
+            Some(false) => lines.push("NO_COLOR=1".into()),
+            None => {}
+        }
+        match self.term.as_deref() {
+            Some("inherit") => lines.push("TERM=$TERM".into()),
+            Some(term) => lines.push(format!("TERM={}", term)),
+            None => {}
+        }
+        for (key, value) in &self.vars {
+            lines.push(format!("{}={}", key, value));
+        }
+        lines
+    }
+
+    /// Tell color-aware programs whether to color their output: `force_color` if set, else
+    /// `oxproc_color` (see [`crate::color::child_color`]). Then set `TERM` as configured.
+    /// Variables set in `env` are left alone.
+    pub fn apply_color(&self, cmd: &mut tokio::process::Command, oxproc_color: Option<bool>) {
+        let mut set = |key: &str, value: Option<&str>| {
+            if self.vars.contains_key(key) {
+                return;
+            }
+            match value {
+                Some(value) => cmd.env(key, value),
+                None => cmd.env_remove(key),
+            };
+        };
+        match self.force_color.or(oxproc_color) {
+            Some(true) => {
+                set("FORCE_COLOR", Some("1"));
+                set("CLICOLOR_FORCE", Some("1"));
+                set("NO_COLOR", None);
+            }
+            Some(false) => {
+                set("NO_COLOR", Some("1"));
+                set("FORCE_COLOR", None);
+                set("CLICOLOR_FORCE", None);
+            }
+            None => {}
+        }
+        match self.term.as_deref() {
+            Some("inherit") => {
+                if let Ok(term) = std::env::var("TERM") {
+                    set("TERM", Some(&term));
+                }
+            }
+            Some(term) => set("TERM", Some(term)),
+            None => {}
+        }
+    }
+
+    /// Set up `cmd`'s environment.
+    pub fn apply(&self, cmd: &mut tokio::process::Command) {
+        if !self.inherit || !self.allowlist.is_empty() {
+            cmd.env_clear();
+            for key in MINIMAL_VARS {
+                if let Some(value) = std::env::var_os(key) {
+                    cmd.env(key, value);
+                }
+            }
+            for (key, value) in std::env::vars_os() {
+                let allowed = key
+                    .to_str()
+                    .is_some_and(|k| self.allowlist.iter().any(|p| glob_match(p, k)));
+                if allowed {
+                    cmd.env(key, value);
+                }
+            }
+        }
+        cmd.envs(&self.vars);
+        if !self.path_prepend.is_empty() {
+            let current = self
+                .vars
+                .get("PATH")
+                .map(std::ffi::OsString::from)
+                .or_else(|| std::env::var_os("PATH"))
+                .unwrap_or_default();
+            let dirs = self
+                .path_prepend
+                .iter()
+                .cloned()
+                .chain(std::env::split_paths(&current));
+            if let Ok(path) = std::env::join_paths(dirs) {
+                cmd.env("PATH", path);
+            }
+        }
+    }
+
+    /// The environment `cmd` starts with once [`EnvSpec::apply`] and [`EnvSpec::apply_color`]
+    /// have set it up.
+    pub fn resolved(&self, cmd: &tokio::process::Command) -> BTreeMap<String, String> {
+        let mut env: BTreeMap<String, String> = if self.inherit && self.allowlist.is_empty() {
+            std::env::vars_os()
+                .map(|(k, v)| {
+                    (
+                        k.to_string_lossy().into_owned(),
+                        v.to_string_lossy().into_owned(),
+                    )
+                })
+                .collect()
+        } else {
+            BTreeMap::new()
+        };
+        for (key, value) in cmd.as_std().get_envs() {
+            let key = key.to_string_lossy().into_owned();
+            match value {
+                Some(value) => env.insert(key, value.to_string_lossy().into_owned()),
+                None => env.remove(&key),
+            };
+        }
+        env
+    }
+}
+
+/// Parts of variable names that suggest the value is a credential.
+const SECRET_HINTS: &[&str] = &[
+    "SECRET",
+    "TOKEN",
+    "PASSWORD",
+    "PASSWD",
+    "KEY",
+    "CREDENTIAL",
+    "AUTH",
+    "PRIVATE",
+    "SESSION",
+    "COOKIE",
+];
+
+/// Replace the values of variables whose names look like they hold credentials.
+pub fn redact(env: &mut BTreeMap<String, String>) {
+    for (key, value) in env.iter_mut() {
+        let upper = key.to_ascii_uppercase();
+        if SECRET_HINTS.iter().any(|hint| upper.contains(hint)) {
+            *value = "<redacted>".into();
+        }
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` stands for any run of characters.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return pattern == name;
+    };
+    let Some(mut remaining) = name.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = rest.split('*').collect();
+    let last = parts.pop().unwrap_or("");
+    for part in parts {
+        match remaining.find(part) {
+            Some(i) => remaining = &remaining[i + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.len() >= last.len() && remaining.ends_with(last)
+}
+
+/// Parse a list of paths (or a single one) for `key`, resolving relative entries against
+/// `base`.
+pub fn parse_path_list(
+    key: &str,
+    value: &toml::Value,
+    base: &Path,
+) -> Result<Vec<PathBuf>, String> {
+    let invalid = || format!("'{}' must be a list of strings", key);
+    let items: Vec<&str> = match value {
+        toml::Value::String(s) => vec![s.as_str()],
+        toml::Value::Array(items) => items
+            .iter()
+            .map(|v| v.as_str())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(invalid)?,
+        _ => return Err(invalid()),
+    };
+    Ok(items.into_iter().map(|p| base.join(p)).collect())
+}
+
+/// Expand a leading `~`, `$NAME` and `${NAME}` in a configured path. An unset variable is an
+/// error rather than an empty string, so a typo doesn't silently point somewhere else. `$$` is
+/// a literal `$`.
+pub fn expand_vars(input: &str) -> Result<String, String> {
+    let lookup = |name: &str| {
+        std::env::var(name).map_err(|_| format!("environment variable '{}' is not set", name))
+    };
+    let mut out = String::new();
+    let mut rest = input;
+    if rest == "~" || rest.starts_with("~/") {
+        out.push_str(&lookup("HOME")?);
+        rest = &rest[1..];
+    }
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        if let Some(stripped) = after.strip_prefix('$') {
+            out.push('$');
+            rest = stripped;
+        } else if let Some(braced) = after.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .ok_or_else(|| format!("unclosed '${{' in '{}'", input))?;
+            out.push_str(&lookup(&braced[..end])?);
+            rest = &braced[end + 1..];
+        } else {
+            let len = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            if len == 0 {
+                out.push('$');
+            } else {
+                out.push_str(&lookup(&after[..len])?);
+            }
+            rest = &after[len..];
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowlist_keeps_only_matching_variables() {
+        let tbl: toml::value::Table =
+            toml::from_str("env_allowlist = [\"OXPROC_TEST_CI\", \"OXPROC_TEST_GH_*\"]").unwrap();
+        let spec = EnvSpec::from_table(&tbl, Path::new("/p")).unwrap();
+        std::env::set_var("OXPROC_TEST_CI", "true");
+        std::env::set_var("OXPROC_TEST_GH_SHA", "abc");
+        std::env::set_var("OXPROC_TEST_SECRET", "x");
+        let mut cmd = tokio::process::Command::new("true");
+        spec.apply(&mut cmd);
+        let envs: BTreeMap<String, Option<String>> = cmd
+            .as_std()
+            .get_envs()
+            .map(|(k, v)| {
+                let text = |s: &std::ffi::OsStr| s.to_string_lossy().into_owned();
+                (text(k), v.map(text))
+            })
+            .collect();
+        assert_eq!(envs["OXPROC_TEST_CI"].as_deref(), Some("true"));
+        assert_eq!(envs["OXPROC_TEST_GH_SHA"].as_deref(), Some("abc"));
+        assert!(!envs.contains_key("OXPROC_TEST_SECRET"));
+        assert!(envs.contains_key("PATH"));
+    }
+
+    #[test]
+    fn resolves_and_redacts_the_launch_environment() {
+        let tbl: toml::value::Table = toml::from_str(
+            "inherit_env = false\nenv = { DATABASE_URL = \"pg://db\", API_TOKEN = \"t0k\" }",
+        )
+        .unwrap();
+        let spec = EnvSpec::from_table(&tbl, Path::new("/p")).unwrap();
+        let mut cmd = tokio::process::Command::new("true");
+        spec.apply(&mut cmd);
+        spec.apply_color(&mut cmd, Some(false));
+        let mut env = spec.resolved(&cmd);
+        assert_eq!(env["NO_COLOR"], "1");
+        assert!(!env.contains_key("CARGO_PKG_NAME"));
+        redact(&mut env);
+        assert_eq!(env["DATABASE_URL"], "pg://db");
+        assert_eq!(env["API_TOKEN"], "<redacted>");
+    }
+
+    #[test]
+    fn color_follows_oxproc_unless_forced() {
+        let envs = |spec: &EnvSpec, oxproc: Option<bool>| {
+            let mut cmd = tokio::process::Command::new("true");
+            spec.apply_color(&mut cmd, oxproc);
+            cmd.as_std()
+                .get_envs()
+                .map(|(k, v)| {
+                    let text = |s: &std::ffi::OsStr| s.to_string_lossy().into_owned();
+                    (text(k), v.map(text))
+                })
+                .collect::<BTreeMap<String, Option<String>>>()
+        };
+        let follow = EnvSpec::default();
+        assert_eq!(
+            envs(&follow, Some(true))["FORCE_COLOR"].as_deref(),
+            Some("1")
+        );
+        assert_eq!(envs(&follow, Some(false))["NO_COLOR"].as_deref(), Some("1"));
+        assert!(envs(&follow, None).is_empty());
+
+        let tbl: toml::value::Table =
+            toml::from_str("force_color = false\nterm = \"xterm-256color\"").unwrap();
+        let never = EnvSpec::from_table(&tbl, Path::new("/p")).unwrap();
+        let got = envs(&never, Some(true));
+        assert_eq!(got["NO_COLOR"].as_deref(), Some("1"));
+        assert_eq!(got["FORCE_COLOR"], None);
+        assert_eq!(got["TERM"].as_deref(), Some("xterm-256color"));
+
+        // An explicit `env` entry wins
+        let tbl: toml::value::Table = toml::from_str("env = { FORCE_COLOR = \"3\" }").unwrap();
+        let own = EnvSpec::from_table(&tbl, Path::new("/p")).unwrap();
+        assert!(!envs(&own, Some(true)).contains_key("FORCE_COLOR"));
+    }
+
+    #[test]
+    fn glob_matches_stars_anywhere() {
+        assert!(glob_match("GITHUB_*", "GITHUB_SHA"));
+        assert!(glob_match("GITHUB_*", "GITHUB_"));
+        assert!(!glob_match("GITHUB_*", "GITLAB_SHA"));
+        assert!(glob_match("*_TOKEN", "NPM_TOKEN"));
+        assert!(glob_match("A*B*C", "AxxBC"));
+        assert!(!glob_match("A*BB", "AB"));
+        assert!(glob_match("CI", "CI"));
+        assert!(!glob_match("CI", "CIRCLE"));
+    }
+
+    #[test]
+    fn overlay_lets_the_task_win() {
+        let process: toml::value::Table =
+            toml::from_str("env = { PORT = 3000, MODE = \"dev\" }\npath_prepend = \"bin\"")
+                .unwrap();
+        let task: toml::value::Table = toml::from_str(
+            "inherit_env = false\nenv = { MODE = \"ci\" }\npath_prepend = \"tools\"",
+        )
+        .unwrap();
+        let process = EnvSpec::from_table(&process, Path::new("/p")).unwrap();
+        let task = EnvSpec::from_table(&task, Path::new("/p")).unwrap();
+        let merged = process.overlay(&task);
+        assert!(!merged.inherit);
+        assert_eq!(merged.vars["PORT"], "3000");
+        assert_eq!(merged.vars["MODE"], "ci");
+        assert_eq!(
+            merged.path_prepend,
+            [PathBuf::from("/p/tools"), PathBuf::from("/p/bin")]
+        );
+    }
+
+    #[test]
+    fn parses_env_and_inherit_flag() {
+        let tbl: toml::value::Table =
+            toml::from_str("inherit_env = false\nenv = { PORT = 3000, MODE = \"dev\" }").unwrap();
+        let spec = EnvSpec::from_table(&tbl, Path::new("/p")).unwrap();
+        assert!(!spec.inherit);
+        assert_eq!(spec.vars["PORT"], "3000");
+        assert_eq!(spec.vars["MODE"], "dev");
+        let mut cmd = tokio::process::Command::new("true");
+        spec.apply(&mut cmd);
+        let envs: Vec<_> = cmd.as_std().get_envs().collect();
+        assert!(envs.contains(&("PORT".as_ref(), Some("3000".as_ref()))));
+
+        let tbl: toml::value::Table = toml::from_str("inherit_env = \"no\"").unwrap();
+        assert!(EnvSpec::from_table(&tbl, Path::new("/p")).is_err());
+        assert_eq!(
+            EnvSpec::from_table(&toml::value::Table::new(), Path::new("/p")).unwrap(),
+            EnvSpec::default()
+        );
+    }
+
+    #[test]
+    fn wraps_commands_in_env_wrapper() {
+        let tbl: toml::value::Table = toml::from_str("env_wrapper = \"mise exec --\"").unwrap();
+        let spec = EnvSpec::from_table(&tbl, Path::new("/p")).unwrap();
+        assert_eq!(
+            spec.wrap_command("echo 'hi'", Path::new("/p")),
+            "mise exec -- sh -c 'echo '\\''hi'\\'''"
+        );
+
+        // A project-wide wrapper applies unless the command opts out
+        let project = ProjectEnv {
+            path_prepend: Vec::new(),
+            wrapper: Some(EnvWrapper::Command("direnv exec .".into())),
+        };
+        let inherited = EnvSpec::default().with_project(&project);
+        assert!(inherited
+            .wrap_command("ls", Path::new("/p"))
+            .starts_with("direnv exec ."));
+        let tbl: toml::value::Table = toml::from_str("env_wrapper = false").unwrap();
+        let opted_out = EnvSpec::from_table(&tbl, Path::new("/p"))
+            .unwrap()
+            .with_project(&project);
+        assert_eq!(opted_out.wrap_command("ls", Path::new("/p")), "ls");
+    }
+
+    #[test]
+    fn expands_home_and_variables() {
+        std::env::set_var("OXPROC_TEST_DIR", "data");
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(expand_vars("~/x").unwrap(), format!("{}/x", home));
+        assert_eq!(
+            expand_vars("$HOME/${OXPROC_TEST_DIR}/$OXPROC_TEST_DIR.log").unwrap(),
+            format!("{}/data/data.log", home)
+        );
+        assert_eq!(expand_vars("a$$b ~ $").unwrap(), "a$b ~ $");
+        assert_eq!(
+            expand_vars("${OXPROC_TEST_UNSET}/x").unwrap_err(),
+            "environment variable 'OXPROC_TEST_UNSET' is not set"
+        );
+    }
+
+    #[test]
+    fn resolves_path_prepend_against_dir() {
+        let tbl: toml::value::Table =
+            toml::from_str("path_prepend = [\"node_modules/.bin\", \"/opt/bin\"]").unwrap();
+        let spec = EnvSpec::from_table(&tbl, Path::new("/p/web")).unwrap();
+        assert_eq!(
+            spec.path_prepend,
+            vec![
+                PathBuf::from("/p/web/node_modules/.bin"),
+                PathBuf::from("/opt/bin")
+            ]
+        );
+    }
+}
@@ -0,0 +1,95 @@
+// Manager event log: one JSON object per line in `events.jsonl` under the state dir.
+// Events are also echoed to stdout, which the daemon redirects into manager.log.
+
+use crate::state::events_log_path;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Started,
+    Exited,
+    Restarted,
+    Reloaded,
+    Failed,
+    /// The formation sat idle for `idle_timeout`
+    Idle,
+    /// Suspended processes were continued
+    Resumed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub at: DateTime<Utc>,
+    pub kind: EventKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process: Option<String>,
+    pub message: String,
+}
+
+/// Append an event to the project's event log. Failures to write are reported but not fatal.
+pub fn record(dir: &Path, kind: EventKind, process: Option<&str>, message: impl Into<String>) {
+    let event = Event {
+        at: Utc::now(),
+        kind,
+        process: process.map(|p| p.to_string()),
+        message: message.into(),
+    };
+    println!(
+        "[{}] {:?}{}: {}",
+        event.at.format("%Y-%m-%dT%H:%M:%S"),
+        event.kind,
+        event
+            .process
+            .as_deref()
+            .map(|p| format!(" {}", p))
+            .unwrap_or_default(),
+        event.message
+    );
+    if let Err(e) = append(dir, &event) {
+        eprintln!("failed to record event: {}", e);
+    }
+}
+
+fn append(dir: &Path, event: &Event) -> anyhow::Result<()> {
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(events_log_path(dir))?;
+    let mut line = serde_json::to_string(event)?;
+    line.push('\n');
+    f.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn records_events_as_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        record(dir.path(), EventKind::Exited, Some("web"), "exit code 1");
+        record(dir.path(), EventKind::Restarted, Some("web"), "attempt 1");
+        record(
+            dir.path(),
+            EventKind::Failed,
+            Some("web"),
+            "budget exhausted",
+        );
+        let data = fs::read_to_string(events_log_path(dir.path())).unwrap();
+        let events: Vec<Event> = data
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1].kind, EventKind::Restarted);
+        assert_eq!(events[2].kind, EventKind::Failed);
+        assert_eq!(events[2].process.as_deref(), Some("web"));
+    }
+}
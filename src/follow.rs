@@ -0,0 +1,422 @@
+// Following log files. One task owns every followed file and reads whichever have grown,
+// rather than each file polling on its own timer: following twenty replicas used to mean
+// forty tasks waking every 300ms. On Linux the task sleeps until inotify reports a change in
+// one of the files' directories; elsewhere it checks all files on one shared tick.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// How often files are checked where change notifications aren't available, unless
+/// `--poll-interval` says otherwise.
+const POLL: Duration = Duration::from_millis(300);
+
+/// Longest wait between checks when notifications are on, in case one is missed.
+#[cfg(target_os = "linux")]
+const SAFETY_TICK: Duration = Duration::from_secs(2);
+
+/// Pause after a notification so a burst of writes across files is read in one pass.
+#[cfg(target_os = "linux")]
+const COALESCE: Duration = Duration::from_millis(20);
+
+/// How long to wait for a log file that doesn't exist yet before giving up on it.
+const APPEAR_TIMEOUT: Duration = Duration::from_secs(10);
+
+type OnLine<'a> = Box<dyn FnMut(&str) -> bool + Send + 'a>;
+type OnNotice<'a> = Box<dyn FnMut(Notice) + Send + 'a>;
+
+/// Something that happened to a followed file itself, for a marker line in the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notice {
+    /// Renamed or deleted and recreated, e.g. by logrotate; the new file is read from the top
+    Replaced,
+    /// Cut short in place; read again from the top
+    Truncated,
+    /// Removed with nothing in its place yet
+    Deleted,
+}
+
+impl std::fmt::Display for Notice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Notice::Replaced => "log file was replaced (rotated?); following the new file",
+            Notice::Truncated => "log file was truncated; reading from the start",
+            Notice::Deleted => "log file was deleted; waiting for it to come back",
+        })
+    }
+}
+
+/// A set of files followed together. Build it with [`Followers::add`], then [`Followers::run`].
+#[derive(Default)]
+pub struct Followers<'a> {
+    files: Vec<Followed<'a>>,
+}
+
+struct Followed<'a> {
+    path: PathBuf,
+    file: Option<File>,
+    /// Not opened yet: an existing file is followed from its end, one created later from the top
+    fresh: bool,
+    pos: u64,
+    partial: Vec<u8>,
+    /// The path was gone at the last check (reported once)
+    deleted: bool,
+    on_line: OnLine<'a>,
+    on_notice: Option<OnNotice<'a>>,
+}
+
+impl<'a> Followers<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Follow `path` from its current end, calling `on_line` for each new line (without the
+    /// newline) until it returns false.
+    pub fn add(&mut self, path: impl Into<PathBuf>, on_line: impl FnMut(&str) -> bool + Send + 'a) {
+        self.push(path.into(), Box::new(on_line), None);
+    }
+
+    /// Like [`Followers::add`], also calling `on_notice` when the file is replaced, truncated
+    /// or deleted.
+    pub fn add_with_notices(
+        &mut self,
+        path: impl Into<PathBuf>,
+        on_line: impl FnMut(&str) -> bool + Send + 'a,
+        on_notice: impl FnMut(Notice) + Send + 'a,
+    ) {
+        self.push(path.into(), Box::new(on_line), Some(Box::new(on_notice)));
+    }
+
+    fn push(&mut self, path: PathBuf, on_line: OnLine<'a>, on_notice: Option<OnNotice<'a>>) {
+        self.files.push(Followed {
+            path,
+            file: None,
+            fresh: true,
+            pos: 0,
+            partial: Vec::new(),
+            deleted: false,
+            on_line,
+            on_notice,
+        });
+    }
+
+    /// Deliver lines until every callback has returned false, or a file failed to appear or
+    /// could no longer be read. Rotated or recreated files are reopened and read from the
+    /// top; truncated ones are reread.
+    pub async fn run(mut self) {
+        let mut wakeups = Wakeups::new(&self.files);
+        let began = Instant::now();
+        loop {
+            let waited = began.elapsed();
+            self.files.retain_mut(|f| f.poll(waited));
+            if self.files.is_empty() {
+                return;
+            }
+            wakeups.wait().await;
+        }
+    }
+}
+
+/// Follow a single log file; see [`Followers::add`].
+pub async fn tail_file(path: impl Into<PathBuf>, on_line: impl FnMut(&str) -> bool + Send) {
+    let mut followers = Followers::new();
+    followers.add(path, on_line);
+    followers.run().await
+}
+
+impl Followed<'_> {
+    /// Read what was appended since the last poll. False once this file is done with.
+    fn poll(&mut self, waited: Duration) -> bool {
+        if self.file.is_none() {
+            let Ok(mut f) = File::open(&self.path) else {
+                self.fresh = false;
+                return waited < APPEAR_TIMEOUT;
+            };
+            self.pos = if self.fresh {
+                match f.seek(SeekFrom::End(0)) {
+                    Ok(pos) => pos,
+                    Err(_) => return false,
+                }
+            } else {
+                0
+            };
+            self.fresh = false;
+            self.file = Some(f);
+        }
+        self.read_available().unwrap_or(false)
+    }
+
+    fn read_available(&mut self) -> std::io::Result<bool> {
+        let mut buf = [0u8; 8192];
+        loop {
+            let Some(f) = self.file.as_mut() else {
+                return Ok(true);
+            };
+            let n = f.read(&mut buf)?;
+            if n > 0 {
+                self.pos += n as u64;
+                self.partial.extend_from_slice(&buf[..n]);
+                if !self.deliver() {
+                    return Ok(false);
+                }
+                continue;
+            }
+            // Keep reading the old file while nothing has taken its place: whoever renamed
+            // it may still be writing to it
+            if !self.path.exists() {
+                if !self.deleted {
+                    self.deleted = true;
+                    self.notice(Notice::Deleted);
+                }
+                return Ok(true);
+            }
+            // Log replaced (rotated or recreated): reopen and read the new file from the top
+            if self.deleted || replaced(&self.path, f) {
+                if let Ok(new) = File::open(&self.path) {
+                    self.file = Some(new);
+                    self.pos = 0;
+                    self.partial.clear();
+                    self.deleted = false;
+                    self.notice(Notice::Replaced);
+                    continue;
+                }
+                return Ok(true);
+            }
+            // If file truncated, read again from the start
+            if f.metadata()?.len() < self.pos {
+                self.pos = f.seek(SeekFrom::Start(0))?;
+                self.partial.clear();
+                self.notice(Notice::Truncated);
+                continue;
+            }
+            return Ok(true);
+        }
+    }
+
+    fn notice(&mut self, notice: Notice) {
+        if let Some(on_notice) = self.on_notice.as_mut() {
+            on_notice(notice);
+        }
+    }
+
+    /// Hand every complete line to the callback; false once it asked to stop.
+    fn deliver(&mut self) -> bool {
+        let mut start = 0;
+        while let Some(idx) = self.partial[start..].iter().position(|&b| b == b'\n') {
+            let line = String::from_utf8_lossy(&self.partial[start..start + idx]);
+            if !(self.on_line)(&line) {
+                return false;
+            }
+            start += idx + 1;
+        }
+        self.partial.drain(..start);
+        true
+    }
+}
+
+/// Whether `path` now names a different file than the open handle.
+#[cfg(unix)]
+fn replaced(path: &std::path::Path, f: &File) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (std::fs::metadata(path), f.metadata()) {
+        (Ok(on_disk), Ok(open)) => on_disk.ino() != open.ino() || on_disk.dev() != open.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn replaced(_path: &std::path::Path, _f: &File) -> bool {
+    false
+}
+
+/// What the follower task sleeps on between checks.
+#[cfg(target_os = "linux")]
+struct Wakeups {
+    inotify: Option<tokio::io::unix::AsyncFd<InotifyFd>>,
+    /// Some directory could not be watched (it doesn't exist yet), so poll as well
+    partial: bool,
+}
+
+#[cfg(target_os = "linux")]
+struct InotifyFd(nix::sys::inotify::Inotify);
+
+#[cfg(target_os = "linux")]
+impl std::os::fd::AsRawFd for InotifyFd {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        use std::os::fd::AsFd;
+        self.0.as_fd().as_raw_fd()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Wakeups {
+    fn new(files: &[Followed<'_>]) -> Self {
+        use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+        let Ok(inotify) = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC) else {
+            return Wakeups {
+                inotify: None,
+                partial: true,
+            };
+        };
+        // Watch directories rather than the files, so creation and rotation are seen too
+        let mut dirs: Vec<PathBuf> = files
+            .iter()
+            .map(|f| match f.path.parent() {
+                Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+                _ => PathBuf::from("."),
+            })
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        let flags = AddWatchFlags::IN_MODIFY
+            | AddWatchFlags::IN_CREATE
+            | AddWatchFlags::IN_MOVED_TO
+            | AddWatchFlags::IN_DELETE;
+        let failed = dirs
+            .iter()
+            .filter(|dir| inotify.add_watch(dir.as_path(), flags).is_err())
+            .count();
+        Wakeups {
+            inotify: tokio::io::unix::AsyncFd::new(InotifyFd(inotify)).ok(),
+            partial: failed > 0,
+        }
+    }
+
+    async fn wait(&mut self) {
+        let Some(fd) = &self.inotify else {
+            return tokio::time::sleep(crate::power::follow_poll(POLL)).await;
+        };
+        let timeout = if self.partial {
+            crate::power::follow_poll(POLL)
+        } else {
+            crate::power::scale(SAFETY_TICK)
+        };
+        tokio::select! {
+            guard = fd.readable() => {
+                tokio::time::sleep(COALESCE).await;
+                if let Ok(mut guard) = guard {
+                    while matches!(guard.get_inner().0.read_events(), Ok(events) if !events.is_empty()) {}
+                    guard.clear_ready();
+                }
+            }
+            _ = tokio::time::sleep(timeout) => {}
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+struct Wakeups;
+
+#[cfg(not(target_os = "linux"))]
+impl Wakeups {
+    fn new(_files: &[Followed<'_>]) -> Self {
+        Wakeups
+    }
+
+    async fn wait(&mut self) {
+        tokio::time::sleep(crate::power::follow_poll(POLL)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    fn collect(lines: &Arc<Mutex<Vec<String>>>, want: usize) -> impl FnMut(&str) -> bool + Send {
+        let lines = lines.clone();
+        move |line| {
+            let mut lines = lines.lock().unwrap();
+            lines.push(line.to_string());
+            lines.len() < want
+        }
+    }
+
+    #[tokio::test]
+    async fn follows_appended_lines_across_files_from_their_end() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.log"), dir.path().join("b.log"));
+        std::fs::write(&a, "old\n").unwrap();
+        let seen_a = Arc::new(Mutex::new(Vec::new()));
+        let seen_b = Arc::new(Mutex::new(Vec::new()));
+        let mut followers = Followers::new();
+        followers.add(&a, collect(&seen_a, 2));
+        followers.add(&b, collect(&seen_b, 1));
+        let run = tokio::spawn(followers.run());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let mut f = std::fs::OpenOptions::new().append(true).open(&a).unwrap();
+        write!(f, "one\ntw").unwrap();
+        f.flush().unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        writeln!(f, "o").unwrap();
+        // Created after following began: read from the top
+        std::fs::write(&b, "first\n").unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(*seen_a.lock().unwrap(), ["one", "two"]);
+        assert_eq!(*seen_b.lock().unwrap(), ["first"]);
+    }
+
+    #[tokio::test]
+    async fn reports_rotation_and_follows_the_new_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("web.log");
+        std::fs::write(&path, "").unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let notices = Arc::new(Mutex::new(Vec::new()));
+        let mut followers = Followers::new();
+        let noted = notices.clone();
+        followers.add_with_notices(&path, collect(&seen, 2), move |n| {
+            noted.lock().unwrap().push(n)
+        });
+        let run = tokio::spawn(followers.run());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let mut old = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        // Rotated away: lines still written to the old file are read until a new one appears
+        std::fs::rename(&path, dir.path().join("web.log.1")).unwrap();
+        writeln!(old, "late").unwrap();
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        std::fs::write(&path, "fresh\n").unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(*seen.lock().unwrap(), ["late", "fresh"]);
+        assert_eq!(
+            *notices.lock().unwrap(),
+            [Notice::Deleted, Notice::Replaced]
+        );
+    }
+
+    #[tokio::test]
+    async fn rereads_truncated_and_recreated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("web.log");
+        std::fs::write(&path, "a long line before truncation\n").unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let run = tokio::spawn(tail_file(path.clone(), collect(&seen, 2)));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        std::fs::write(&path, "short\n").unwrap();
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        std::fs::remove_file(&path).unwrap();
+        std::fs::write(&path, "recreated\n").unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(*seen.lock().unwrap(), ["short", "recreated"]);
+    }
+}
@@ -0,0 +1,134 @@
+// `type = "forward"`: a local port forwarded to another address, directly or through an SSH
+// tunnel (`via = "ssh bastion"`), so tunnels get restarts and status like any other process
+// instead of living in ad-hoc `ssh -L` commands.
+
+use tokio::net::{TcpListener, TcpStream};
+
+/// A forward's `listen` port, `target` address and optional `via`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardSpec {
+    pub listen: u16,
+    /// `host:port` the connections go to (as seen from the SSH host, with `via`)
+    pub target: String,
+    /// Arguments to `ssh` naming the host to tunnel through (`via = "ssh -p 2222 bastion"`)
+    pub via: Option<Vec<String>>,
+}
+
+impl ForwardSpec {
+    /// Read `listen`, `target = "host:port"` and `via = "ssh HOST"` from a process table.
+    pub fn from_table(tbl: &toml::value::Table) -> Result<ForwardSpec, String> {
+        let listen = crate::builtin::listen_port(tbl)?;
+        let target = tbl
+            .get("target")
+            .and_then(|v| v.as_str())
+            .filter(|t| {
+                t.rsplit_once(':')
+                    .is_some_and(|(_, p)| p.parse::<u16>().is_ok())
+            })
+            .ok_or("a forward needs 'target = \"host:port\"'")?
+            .to_string();
+        let via = match tbl.get("via") {
+            None => None,
+            Some(v) => {
+                let words: Vec<String> = v
+                    .as_str()
+                    .ok_or("'via' must be a string like \"ssh bastion\"")?
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect();
+                match words.split_first() {
+                    Some((ssh, args)) if ssh == "ssh" && !args.is_empty() => Some(args.to_vec()),
+                    _ => {
+                        return Err(
+                            "'via' must be \"ssh\" followed by the host to go through".into()
+                        )
+                    }
+                }
+            }
+        };
+        Ok(ForwardSpec {
+            listen,
+            target,
+            via,
+        })
+    }
+
+    /// The `ssh -L` command for a forward `via` SSH: no remote command, and exit (to be
+    /// restarted) when the forward can't be set up or the connection dies.
+    pub fn ssh_command(&self, via: &[String]) -> String {
+        use crate::watch::shell_quote;
+        let args: Vec<String> = via.iter().map(|a| shell_quote(a)).collect();
+        format!(
+            "exec ssh -N -o ExitOnForwardFailure=yes -o ServerAliveInterval=15 -o ServerAliveCountMax=3 -L {} {}",
+            shell_quote(&format!("127.0.0.1:{}:{}", self.listen, self.target)),
+            args.join(" ")
+        )
+    }
+}
+
+/// Accept on `127.0.0.1:listen` and copy each connection to and from `target` until killed.
+pub async fn serve(spec: ForwardSpec) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", spec.listen)).await?;
+    println!("forwarding 127.0.0.1:{} -> {}", spec.listen, spec.target);
+    run(listener, spec.target).await
+}
+
+async fn run(listener: TcpListener, target: String) -> anyhow::Result<()> {
+    loop {
+        let (mut client, peer) = listener.accept().await?;
+        let target = target.clone();
+        tokio::spawn(async move {
+            match TcpStream::connect(&target).await {
+                Ok(mut upstream) => {
+                    let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+                }
+                Err(e) => eprintln!("{}: can't reach {}: {}", peer, target, e),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn spec(toml: &str) -> Result<ForwardSpec, String> {
+        ForwardSpec::from_table(&toml::from_str(toml).unwrap())
+    }
+
+    #[test]
+    fn tunnels_through_ssh() {
+        let forward =
+            spec("listen = 5433\ntarget = \"db.internal:5432\"\nvia = \"ssh -p 2222 bastion\"")
+                .unwrap();
+        assert_eq!(
+            forward.ssh_command(forward.via.as_deref().unwrap()),
+            "exec ssh -N -o ExitOnForwardFailure=yes -o ServerAliveInterval=15 \
+             -o ServerAliveCountMax=3 -L '127.0.0.1:5433:db.internal:5432' '-p' '2222' 'bastion'"
+        );
+        assert!(spec("listen = 5433\ntarget = \"db.internal\"").is_err());
+        assert!(spec("listen = 5433\ntarget = \"db:5432\"\nvia = \"bastion\"").is_err());
+    }
+
+    #[tokio::test]
+    async fn copies_connections_to_the_target() {
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = upstream.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut conn, _) = upstream.accept().await.unwrap();
+            let mut buf = [0u8; 4];
+            conn.read_exact(&mut buf).await.unwrap();
+            conn.write_all(&buf).await.unwrap();
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(run(listener, target));
+
+        let mut conn = TcpStream::connect(addr).await.unwrap();
+        conn.write_all(b"ping").await.unwrap();
+        let mut echoed = [0u8; 4];
+        conn.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(&echoed, b"ping");
+    }
+}
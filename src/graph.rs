@@ -0,0 +1,245 @@
+// Task and process structure as a Graphviz or Mermaid graph (`run TASK --graph`,
+// `list --graph`), for documenting big configs and seeing why things run in the order they do.
+// Composite tasks point at the tasks they run (numbered when they run one after another);
+// processes point at what they `depends_on`, with dashed edges.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+use crate::config::{ProcessConfig, TaskConfig, TaskKind};
+use crate::task;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeKind {
+    Process,
+    Shell,
+    Composite {
+        parallel: bool,
+    },
+    /// Referred to, but not defined
+    Missing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EdgeKind {
+    /// A composite task runs the child; `Some(n)` for the n-th step of a sequential one
+    Runs(Option<usize>),
+    DependsOn,
+}
+
+#[derive(Debug, Default)]
+pub struct Graph {
+    /// Display name and kind; an edge refers to a node by its index
+    nodes: Vec<(String, NodeKind)>,
+    edges: Vec<(usize, usize, EdgeKind)>,
+}
+
+impl Graph {
+    fn node(&mut self, name: &str, kind: NodeKind) -> usize {
+        let is_process = kind == NodeKind::Process;
+        if let Some(i) = self
+            .nodes
+            .iter()
+            .position(|(n, k)| n == name && (*k == NodeKind::Process) == is_process)
+        {
+            return i;
+        }
+        self.nodes.push((name.to_string(), kind));
+        self.nodes.len() - 1
+    }
+
+    /// Add the tasks, or with `from` (a normalized task key) only that task and what it runs.
+    pub fn add_tasks(&mut self, tasks: &HashMap<String, TaskConfig>, from: Option<&str>) {
+        let mut keys: Vec<String> = match from {
+            Some(key) => vec![key.to_string()],
+            None => {
+                let mut all: Vec<String> = tasks.keys().cloned().collect();
+                all.sort_by_key(|k| k.to_lowercase());
+                all
+            }
+        };
+        let mut next = 0;
+        while next < keys.len() {
+            let key = keys[next].clone();
+            next += 1;
+            let Some(config) = tasks.get(&key) else {
+                continue;
+            };
+            let parent = self.node(&task::display_task_name(&key), task_kind(config));
+            let TaskKind::Composite { children, parallel } = &config.kind else {
+                continue;
+            };
+            for (i, child) in children.iter().enumerate() {
+                let child_key = task::resolve_child_name(&key, child);
+                let kind = tasks.get(&child_key).map_or(NodeKind::Missing, task_kind);
+                let to = self.node(&task::display_task_name(&child_key), kind);
+                if !keys.contains(&child_key) {
+                    keys.push(child_key);
+                }
+                let step = (!parallel).then_some(i + 1);
+                self.edges.push((parent, to, EdgeKind::Runs(step)));
+            }
+        }
+    }
+
+    /// Add every process and its `depends_on` edges, in name order so the output is stable.
+    pub fn add_processes(&mut self, configs: &[ProcessConfig]) {
+        let mut sorted: Vec<&ProcessConfig> = configs.iter().collect();
+        sorted.sort_by_key(|c| c.name.to_lowercase());
+        for config in sorted {
+            let from = self.node(&config.name, NodeKind::Process);
+            for dep in &config.depends_on {
+                let to = self.node(dep, NodeKind::Process);
+                self.edges.push((from, to, EdgeKind::DependsOn));
+            }
+        }
+    }
+
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.dot(),
+            GraphFormat::Mermaid => self.mermaid(),
+        }
+    }
+
+    fn dot(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut out = String::from("digraph oxproc {\n  rankdir=LR;\n");
+        for (i, (name, kind)) in self.nodes.iter().enumerate() {
+            let attrs = match kind {
+                NodeKind::Process => format!("label={}, shape=ellipse", quote(name)),
+                NodeKind::Shell => format!("label={}, shape=box", quote(name)),
+                NodeKind::Composite { parallel } => format!(
+                    "label={}, shape=box, style=rounded",
+                    quote(&composite_label(name, *parallel))
+                ),
+                NodeKind::Missing => format!(
+                    "label={}, shape=box, style=dashed",
+                    quote(&format!("{} (missing)", name))
+                ),
+            };
+            let _ = writeln!(out, "  n{} [{}];", i, attrs);
+        }
+        for (from, to, kind) in &self.edges {
+            let attrs = match kind {
+                EdgeKind::Runs(Some(step)) => format!(" [label=\"{}\"]", step),
+                EdgeKind::Runs(None) => String::new(),
+                EdgeKind::DependsOn => " [style=dashed]".to_string(),
+            };
+            let _ = writeln!(out, "  n{} -> n{}{};", from, to, attrs);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn mermaid(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('"', "#quot;"));
+        let mut out = String::from("flowchart LR\n");
+        for (i, (name, kind)) in self.nodes.iter().enumerate() {
+            let shape = match kind {
+                NodeKind::Process => format!("([{}])", quote(name)),
+                NodeKind::Shell => format!("[{}]", quote(name)),
+                NodeKind::Composite { parallel } => {
+                    format!("({})", quote(&composite_label(name, *parallel)))
+                }
+                NodeKind::Missing => format!("[{}]", quote(&format!("{} (missing)", name))),
+            };
+            let _ = writeln!(out, "  n{}{}", i, shape);
+        }
+        for (from, to, kind) in &self.edges {
+            let arrow = match kind {
+                EdgeKind::Runs(Some(step)) => format!("-->|{}|", step),
+                EdgeKind::Runs(None) => "-->".to_string(),
+                EdgeKind::DependsOn => "-.->".to_string(),
+            };
+            let _ = writeln!(out, "  n{} {} n{}", from, arrow, to);
+        }
+        out
+    }
+}
+
+fn task_kind(config: &TaskConfig) -> NodeKind {
+    match config.kind {
+        TaskKind::Shell { .. } => NodeKind::Shell,
+        TaskKind::Composite { parallel, .. } => NodeKind::Composite { parallel },
+    }
+}
+
+fn composite_label(name: &str, parallel: bool) -> String {
+    if parallel {
+        format!("{} (parallel)", name)
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(toml: &str) -> (Vec<ProcessConfig>, HashMap<String, TaskConfig>) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("proc.toml"), toml).unwrap();
+        (
+            crate::config::load_config_from(dir.path()).unwrap(),
+            crate::config::load_tasks_from(dir.path()).unwrap().unwrap(),
+        )
+    }
+
+    const CONFIG: &str = r#"
+[processes.db]
+cmd = "postgres"
+
+[processes.web]
+cmd = "npm start"
+depends_on = ["db"]
+
+[tasks.build]
+run = ["assets", "compile"]
+
+[tasks.build.assets]
+cmd = "npm run build"
+
+[tasks.build.compile]
+cmd = "cargo build"
+
+[tasks.lint]
+cmd = "cargo clippy"
+"#;
+
+    #[test]
+    fn renders_a_task_and_what_it_runs() {
+        let (_, tasks) = project(CONFIG);
+        let mut graph = Graph::default();
+        graph.add_tasks(&tasks, Some("build"));
+        assert_eq!(
+            graph.render(GraphFormat::Dot),
+            "digraph oxproc {\n  rankdir=LR;\n\
+             \x20 n0 [label=\"build\", shape=box, style=rounded];\n\
+             \x20 n1 [label=\"build:assets\", shape=box];\n\
+             \x20 n2 [label=\"build:compile\", shape=box];\n\
+             \x20 n0 -> n1 [label=\"1\"];\n\
+             \x20 n0 -> n2 [label=\"2\"];\n}\n"
+        );
+    }
+
+    #[test]
+    fn renders_processes_and_tasks_as_mermaid() {
+        let (configs, tasks) = project(CONFIG);
+        let mut graph = Graph::default();
+        graph.add_processes(&configs);
+        graph.add_tasks(&tasks, None);
+        let out = graph.render(GraphFormat::Mermaid);
+        assert!(out.starts_with("flowchart LR\n"), "{}", out);
+        assert!(out.contains("  n0([\"db\"])\n  n1([\"web\"])\n"), "{}", out);
+        assert!(out.contains("  n1 -.-> n0\n"));
+        assert!(out.contains("[\"lint\"]"));
+        assert!(out.contains(" -->|2| "));
+    }
+}
@@ -0,0 +1,112 @@
+// `oxproc init --template <name>`: write a starter proc.toml for a common stack. Templates
+// ship in the binary; a `<name>.toml` in `$XDG_CONFIG_HOME/oxproc/templates/` replaces the
+// built-in one of that name or adds a new template.
+
+use crate::output::outln;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+const BUILTIN: &[(&str, &str)] = &[
+    ("django", include_str!("../templates/django.toml")),
+    ("nextjs", include_str!("../templates/nextjs.toml")),
+    ("rails", include_str!("../templates/rails.toml")),
+    ("rust-axum", include_str!("../templates/rust-axum.toml")),
+];
+
+fn override_dir() -> PathBuf {
+    crate::dirs::config_dir().join("templates")
+}
+
+/// Template names with where each comes from, built-in ones first.
+fn available() -> Vec<(String, &'static str)> {
+    let mut names: Vec<(String, &str)> = BUILTIN
+        .iter()
+        .map(|(name, _)| (name.to_string(), "built-in"))
+        .collect();
+    if let Ok(entries) = std::fs::read_dir(override_dir()) {
+        let mut custom: Vec<String> = entries
+            .flatten()
+            .filter_map(|e| {
+                let path = e.path();
+                (path.extension()? == "toml")
+                    .then(|| path.file_stem()?.to_str().map(String::from))?
+            })
+            .collect();
+        custom.sort();
+        for name in custom {
+            match names.iter_mut().find(|(n, _)| *n == name) {
+                Some(entry) => entry.1 = "overridden",
+                None => names.push((name, "custom")),
+            }
+        }
+    }
+    names
+}
+
+/// A template's text: the override file if there is one, else the built-in.
+fn template(name: &str) -> Result<String> {
+    let custom = override_dir().join(format!("{}.toml", name));
+    if custom.exists() {
+        return Ok(std::fs::read_to_string(custom)?);
+    }
+    match BUILTIN.iter().find(|(n, _)| *n == name) {
+        Some((_, text)) => Ok(text.to_string()),
+        None => {
+            let names: Vec<String> = available().into_iter().map(|(n, _)| n).collect();
+            anyhow::bail!(
+                "Unknown template '{}'; available: {}",
+                name,
+                names.join(", ")
+            )
+        }
+    }
+}
+
+pub fn print_templates() {
+    for (name, source) in available() {
+        outln!("{:<12} {}", name, source);
+    }
+    outln!("Custom templates: {}/<name>.toml", override_dir().display());
+}
+
+/// Write `root/proc.toml` from a template. An existing config is only replaced with `force`.
+pub fn init(root: &Path, name: &str, force: bool) -> Result<()> {
+    let path = root.join("proc.toml");
+    if !force {
+        if path.exists() {
+            anyhow::bail!(
+                "{} already exists; pass --force to replace it",
+                path.display()
+            );
+        }
+        if root.join("Procfile").exists() {
+            anyhow::bail!(
+                "{} has a Procfile; pass --force to create a proc.toml, which takes precedence",
+                root.display()
+            );
+        }
+    }
+    let text = template(name)?;
+    std::fs::write(&path, text)?;
+    outln!("Wrote {} from the '{}' template.", path.display(), name);
+    outln!("Review it, then run `oxproc check` and `oxproc start --dry-run`.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_templates_are_valid_configs() {
+        for (name, text) in BUILTIN {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::write(dir.path().join("proc.toml"), text).unwrap();
+            let configs = crate::config::load_config_from(dir.path())
+                .unwrap_or_else(|e| panic!("template {}: {}", name, e));
+            assert!(!configs.is_empty(), "template {} has no processes", name);
+            let warnings = crate::config::diagnostics(dir.path()).unwrap();
+            assert!(warnings.is_empty(), "template {}: {:?}", name, warnings);
+        }
+    }
+}
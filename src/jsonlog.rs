@@ -0,0 +1,80 @@
+// JSONL log records. With `log_format = "json"` the manager writes one record per line
+// instead of the bare text, carrying what a plain log loses: the stream, the PID that printed
+// it and a per-process sequence number. Readers accept both kinds of line.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogRecord {
+    pub ts: DateTime<Utc>,
+    /// Per-process counter shared by stdout and stderr, so records of both streams can be put
+    /// back in order. It starts at 1 with each manager; gaps are lines dropped by
+    /// `log_rate_limit`.
+    pub seq: u64,
+    pub process: String,
+    /// `stdout` or `stderr`
+    pub stream: String,
+    pub pid: u32,
+    pub instance: u32,
+    pub line: String,
+}
+
+/// Parse a log line written as a JSONL record.
+pub fn parse(raw: &str) -> Option<LogRecord> {
+    if !raw.starts_with('{') {
+        return None;
+    }
+    serde_json::from_str(raw).ok()
+}
+
+/// The text of a log line: the record's `line` for JSONL records, the line itself otherwise.
+pub fn text_of(raw: &str) -> Cow<'_, str> {
+    match parse(raw) {
+        Some(record) => Cow::Owned(record.line),
+        None => Cow::Borrowed(raw),
+    }
+}
+
+/// A line as a JSON object for `logs --format json`. Records pass through unchanged; plain
+/// lines get the process and stream they were read from, without the fields only the manager
+/// knows.
+pub fn to_json(raw: &str, process: &str, stderr: bool) -> String {
+    if parse(raw).is_some() {
+        return raw.to_string();
+    }
+    serde_json::json!({
+        "process": process,
+        "stream": if stderr { "stderr" } else { "stdout" },
+        "line": raw,
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_records_and_plain_lines() {
+        let record = LogRecord {
+            ts: Utc::now(),
+            seq: 7,
+            process: "web".into(),
+            stream: "stderr".into(),
+            pid: 42,
+            instance: 0,
+            line: "boom".into(),
+        };
+        let raw = serde_json::to_string(&record).unwrap();
+        assert_eq!(parse(&raw), Some(record));
+        assert_eq!(text_of(&raw), "boom");
+        assert_eq!(to_json(&raw, "web", false), raw);
+
+        assert_eq!(text_of("{not json"), "{not json");
+        let plain: serde_json::Value = serde_json::from_str(&to_json("hi", "web", true)).unwrap();
+        assert_eq!(plain["stream"], "stderr");
+        assert_eq!(plain["line"], "hi");
+    }
+}
@@ -0,0 +1,170 @@
+// `type = "kubectl-forward"`: `kubectl port-forward` under supervision. kubectl often keeps
+// running after the connection to the pod drops, printing errors while every new connection
+// fails, so oxproc runs it itself (as `oxproc __builtin NAME`), passes its output through, and
+// exits as soon as it reports a lost connection, for the manager to start it again.
+
+use std::process::Stdio;
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::sync::Notify;
+
+/// Restarts allowed within `restart_window` when `max_restarts` isn't set: a forward is
+/// expected to drop now and then.
+pub const DEFAULT_MAX_RESTARTS: u32 = 10;
+
+/// What kubectl prints once the forward is broken.
+const DROPPED: &[&str] = &["lost connection to pod", "an error occurred forwarding"];
+
+/// A kubectl forward's `context`, `namespace`, target (`svc`) and `ports`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KubectlForwardSpec {
+    pub context: Option<String>,
+    pub namespace: Option<String>,
+    /// `svc/api`, `deploy/web`, `pod/worker-0`, …
+    pub target: String,
+    /// Local and remote port pairs; the first local port is the process's port
+    pub ports: Vec<(u16, u16)>,
+}
+
+impl KubectlForwardSpec {
+    /// Read `context`, `namespace`, `svc = "svc/api"` and `ports = "8080:80"` (or a list of
+    /// them) from a process table.
+    pub fn from_table(tbl: &toml::value::Table) -> Result<KubectlForwardSpec, String> {
+        let string = |key: &str| -> Result<Option<String>, String> {
+            match tbl.get(key) {
+                None => Ok(None),
+                Some(v) => v
+                    .as_str()
+                    .map(|s| Some(s.to_string()))
+                    .ok_or_else(|| format!("'{}' must be a string", key)),
+            }
+        };
+        let target = string("svc")?
+            .filter(|s| !s.trim().is_empty())
+            .ok_or("a kubectl forward needs 'svc', e.g. svc = \"svc/api\"")?;
+        let ports_error = "'ports' must be \"LOCAL:REMOTE\" or a list of them, e.g. \"8080:80\"";
+        let specs: Vec<&str> = match tbl.get("ports") {
+            Some(toml::Value::String(s)) => vec![s.as_str()],
+            Some(toml::Value::Array(items)) => items
+                .iter()
+                .map(|v| v.as_str().ok_or(ports_error))
+                .collect::<Result<_, _>>()?,
+            _ => return Err(ports_error.into()),
+        };
+        let ports = specs
+            .iter()
+            .map(|s| parse_ports(s).ok_or(ports_error))
+            .collect::<Result<Vec<_>, _>>()?;
+        if ports.is_empty() {
+            return Err(ports_error.into());
+        }
+        Ok(KubectlForwardSpec {
+            context: string("context")?,
+            namespace: string("namespace")?,
+            target,
+            ports,
+        })
+    }
+
+    /// The local port of the first pair.
+    pub fn listen(&self) -> u16 {
+        self.ports[0].0
+    }
+
+    /// Arguments to `kubectl`.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(context) = &self.context {
+            args.extend(["--context".to_string(), context.clone()]);
+        }
+        if let Some(namespace) = &self.namespace {
+            args.extend(["--namespace".to_string(), namespace.clone()]);
+        }
+        args.extend([
+            "port-forward".to_string(),
+            "--address".to_string(),
+            "127.0.0.1".to_string(),
+            self.target.clone(),
+        ]);
+        args.extend(self.ports.iter().map(|(l, r)| format!("{}:{}", l, r)));
+        args
+    }
+}
+
+/// `8080:80`, or `8080` for the same port on both ends.
+fn parse_ports(s: &str) -> Option<(u16, u16)> {
+    let (local, remote) = s.split_once(':').unwrap_or((s, s));
+    let local = local.trim().parse().ok().filter(|p| *p > 0)?;
+    let remote = remote.trim().parse().ok().filter(|p| *p > 0)?;
+    Some((local, remote))
+}
+
+/// Run `kubectl port-forward` until it exits or reports a dropped connection; either way the
+/// forward is down, so this returns an error for the manager to restart it.
+pub async fn serve(spec: KubectlForwardSpec) -> anyhow::Result<()> {
+    let mut child = Command::new("kubectl")
+        .args(spec.args())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("can't run kubectl: {}", e))?;
+    let dropped = Arc::new(Notify::new());
+    tokio::spawn(pass_through(
+        child.stdout.take().unwrap(),
+        false,
+        dropped.clone(),
+    ));
+    tokio::spawn(pass_through(
+        child.stderr.take().unwrap(),
+        true,
+        dropped.clone(),
+    ));
+    tokio::select! {
+        status = child.wait() => anyhow::bail!("kubectl port-forward exited ({})", status?),
+        _ = dropped.notified() => {
+            let _ = child.kill().await;
+            anyhow::bail!("connection to {} dropped", spec.target)
+        }
+    }
+}
+
+/// Copy kubectl's output to ours, notifying `dropped` on a line that says the forward broke.
+async fn pass_through(stream: impl AsyncRead + Unpin, stderr: bool, dropped: Arc<Notify>) {
+    let mut lines = BufReader::new(stream).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+        if DROPPED.iter().any(|p| line.contains(p)) {
+            dropped.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(toml: &str) -> Result<KubectlForwardSpec, String> {
+        KubectlForwardSpec::from_table(&toml::from_str(toml).unwrap())
+    }
+
+    #[test]
+    fn forwards_ports_of_a_service() {
+        let forward =
+            spec("context = \"dev\"\nsvc = \"svc/api\"\nports = [\"8080:80\", \"9090\"]").unwrap();
+        assert_eq!(forward.listen(), 8080);
+        assert_eq!(
+            forward.args().join(" "),
+            "--context dev port-forward --address 127.0.0.1 svc/api 8080:80 9090:9090"
+        );
+        assert!(spec("svc = \"svc/api\"").is_err());
+        assert!(spec("svc = \"svc/api\"\nports = \"http:80\"").is_err());
+        assert!(spec("ports = \"8080:80\"").is_err());
+    }
+}
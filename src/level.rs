@@ -0,0 +1,88 @@
+// Log line classification (error/warn/info) driven by per-process regex patterns.
+
+use regex::Regex;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Info,
+    Warn,
+    Error,
+}
+
+/// Per-process patterns from `error_pattern`, `warn_pattern` and `info_pattern`.
+#[derive(Debug, Clone, Default)]
+pub struct LevelPatterns {
+    pub error: Option<Regex>,
+    pub warn: Option<Regex>,
+    pub info: Option<Regex>,
+}
+
+impl LevelPatterns {
+    /// Classify a line. Patterns are tried from most to least severe; unmatched lines fall back
+    /// to their stream (stderr = error, stdout = info).
+    pub fn classify(&self, line: &str, stderr: bool) -> LogLevel {
+        let matches = |re: &Option<Regex>| re.as_ref().is_some_and(|r| r.is_match(line));
+        if matches(&self.error) {
+            LogLevel::Error
+        } else if matches(&self.warn) {
+            LogLevel::Warn
+        } else if matches(&self.info) {
+            LogLevel::Info
+        } else if stderr {
+            LogLevel::Error
+        } else {
+            LogLevel::Info
+        }
+    }
+}
+
+/// Keeps lines of one stream at or above a minimum level.
+#[derive(Debug, Clone)]
+pub struct LevelFilter {
+    pub patterns: LevelPatterns,
+    pub min: LogLevel,
+    pub stderr: bool,
+}
+
+impl LevelFilter {
+    pub fn keeps(&self, line: &str) -> bool {
+        self.patterns.classify(line, self.stderr) >= self.min
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns() -> LevelPatterns {
+        LevelPatterns {
+            error: Some(Regex::new("ERROR|panic").unwrap()),
+            warn: Some(Regex::new("WARN").unwrap()),
+            info: None,
+        }
+    }
+
+    #[test]
+    fn classifies_by_pattern_then_stream() {
+        let p = patterns();
+        assert_eq!(
+            p.classify("thread panicked: panic!", false),
+            LogLevel::Error
+        );
+        assert_eq!(p.classify("WARN disk almost full", false), LogLevel::Warn);
+        assert_eq!(p.classify("listening on :3000", false), LogLevel::Info);
+        assert_eq!(p.classify("listening on :3000", true), LogLevel::Error);
+    }
+
+    #[test]
+    fn filter_keeps_at_or_above_min() {
+        let f = LevelFilter {
+            patterns: patterns(),
+            min: LogLevel::Warn,
+            stderr: false,
+        };
+        assert!(f.keeps("ERROR boom"));
+        assert!(f.keeps("WARN hmm"));
+        assert!(!f.keeps("all good"));
+    }
+}
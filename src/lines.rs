@@ -0,0 +1,136 @@
+// Line reading with a bound on memory. A process that prints megabytes without a newline (a
+// minified bundle, binary garbage) would otherwise grow the manager's line buffer without
+// limit; here a line longer than the cap is handed on in cap-sized pieces instead. Bytes
+// that are not UTF-8 are replaced rather than ending the stream.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+/// Longest line the manager holds in memory for a process's output; longer lines are split.
+pub const MAX_LINE_BYTES: usize = 64 * 1024;
+
+/// One line read by [`CappedLines`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct Line {
+    pub text: String,
+    /// The line did not end here: it hit the cap and continues in the next one
+    pub split: bool,
+}
+
+/// Reads lines of at most `max` bytes. Bytes held for an unfinished line are added to
+/// `buffered` while held, so several readers can report into one gauge.
+pub struct CappedLines<R> {
+    reader: R,
+    max: usize,
+    buf: Vec<u8>,
+    buffered: Arc<AtomicU64>,
+}
+
+impl<R: AsyncBufRead + Unpin> CappedLines<R> {
+    pub fn new(reader: R, max: usize, buffered: Arc<AtomicU64>) -> Self {
+        CappedLines {
+            reader,
+            max,
+            buf: Vec::new(),
+            buffered,
+        }
+    }
+
+    /// The next line without its line ending, or `None` at end of input.
+    pub async fn next_line(&mut self) -> std::io::Result<Option<Line>> {
+        let line = self.read().await;
+        self.buffered
+            .fetch_sub(self.buf.len() as u64, Ordering::Relaxed);
+        self.buf.clear();
+        line
+    }
+
+    async fn read(&mut self) -> std::io::Result<Option<Line>> {
+        loop {
+            let available = self.reader.fill_buf().await?;
+            if available.is_empty() {
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+                return Ok(Some(self.take(false)));
+            }
+            let room = self.max - self.buf.len();
+            if room == 0 {
+                // Exactly at the cap: a newline right here still ends the line
+                if available[0] == b'\n' {
+                    self.reader.consume(1);
+                    return Ok(Some(self.take(false)));
+                }
+                return Ok(Some(self.take(true)));
+            }
+            let scan = &available[..available.len().min(room)];
+            let newline = scan.iter().position(|b| *b == b'\n');
+            let n = newline.unwrap_or(scan.len());
+            self.buf.extend_from_slice(&scan[..n]);
+            self.buffered.fetch_add(n as u64, Ordering::Relaxed);
+            if newline.is_some() {
+                self.reader.consume(n + 1);
+                return Ok(Some(self.take(false)));
+            }
+            self.reader.consume(n);
+        }
+    }
+
+    fn take(&self, split: bool) -> Line {
+        let mut bytes = &self.buf[..];
+        if !split {
+            bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+        }
+        Line {
+            text: String::from_utf8_lossy(bytes).into_owned(),
+            split,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read_all(input: &[u8], max: usize) -> Vec<Line> {
+        let gauge = Arc::new(AtomicU64::new(0));
+        let mut lines = CappedLines::new(input, max, gauge.clone());
+        let mut out = Vec::new();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            out.push(line);
+        }
+        assert_eq!(gauge.load(Ordering::Relaxed), 0);
+        out
+    }
+
+    fn line(text: &str, split: bool) -> Line {
+        Line {
+            text: text.into(),
+            split,
+        }
+    }
+
+    #[tokio::test]
+    async fn splits_lines_longer_than_the_cap() {
+        assert_eq!(
+            read_all(b"ab\r\n0123456789abc\nfive!\nend", 5).await,
+            vec![
+                line("ab", false),
+                line("01234", true),
+                line("56789", true),
+                line("abc", false),
+                line("five!", false),
+                line("end", false),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn replaces_invalid_utf8() {
+        assert_eq!(
+            read_all(b"ok \xff\xfe\n", 64).await,
+            vec![line("ok \u{fffd}\u{fffd}", false)]
+        );
+    }
+}
@@ -17,9 +17,15 @@ pub struct TaskInfo {
 
 #[derive(Debug, Serialize)]
 pub struct ListInfo {
+    /// `name` from proc.toml
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     pub source: ConfigSource,
     pub processes: Vec<String>,
     pub tasks: Vec<TaskInfo>,
+    /// Non-fatal config problems (see `config::diagnostics`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<config::Diagnostic>,
 }
 
 pub fn gather_list_info(root: &Path) -> Result<ListInfo> {
@@ -28,7 +34,7 @@ pub fn gather_list_info(root: &Path) -> Result<ListInfo> {
         .into_iter()
         .map(|p| p.name)
         .collect::<Vec<_>>();
-    processes.sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()));
+    processes.sort_by_key(|a| a.to_lowercase());
 
     let mut tasks: Vec<TaskInfo> = Vec::new();
     if let Some(map) = config::load_tasks_from(root)? {
@@ -48,7 +54,7 @@ pub fn gather_list_info(root: &Path) -> Result<ListInfo> {
                         .iter()
                         .map(|c| task::display_task_name(&task::resolve_child_name(k, c)))
                         .collect();
-                    resolved.sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()));
+                    resolved.sort_by_key(|a| a.to_lowercase());
                     TaskInfo {
                         name: name_display,
                         kind: "composite".to_string(),
@@ -59,20 +65,25 @@ pub fn gather_list_info(root: &Path) -> Result<ListInfo> {
             };
             items.push((k.clone(), info));
         }
-        items.sort_by(|a, b| a.0.to_lowercase().cmp(&b.0.to_lowercase()));
+        items.sort_by_key(|a| a.0.to_lowercase());
         tasks = items.into_iter().map(|(_, i)| i).collect();
     }
 
     Ok(ListInfo {
+        name: config::load_project_name(root)?,
         source,
         processes,
         tasks,
+        warnings: config::diagnostics(root)?,
     })
 }
 
 pub fn format_list_human(info: &ListInfo, processes_only: bool, tasks_only: bool) -> String {
     let mut out = String::new();
     use std::fmt::Write as _;
+    if let Some(name) = &info.name {
+        let _ = writeln!(out, "Project: {}", name);
+    }
     let _ = writeln!(out, "Source: {:?}", info.source);
 
     let show_processes = !tasks_only;
@@ -123,6 +134,13 @@ pub fn format_list_human(info: &ListInfo, processes_only: bool, tasks_only: bool
         }
     }
 
+    if !info.warnings.is_empty() {
+        let _ = writeln!(out, "Warnings ({}):", info.warnings.len());
+        for w in &info.warnings {
+            let _ = writeln!(out, "  {}", w);
+        }
+    }
+
     out
 }
 
@@ -166,6 +184,8 @@ cmd = "echo build""#
         let human = format_list_human(&info, false, false);
         assert!(human.contains("Processes (1):"));
         assert!(human.contains("Tasks (1):"));
+        assert!(human.contains("Warnings (1):"));
+        assert!(human.contains("move it to [processes.web]"));
     }
 
     #[test]
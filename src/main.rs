@@ -1,19 +1,89 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
 use std::path::PathBuf;
 
+use output::{out, outln};
+
+mod builtin;
+mod bundle;
 mod color;
+mod completion;
+mod compose;
 mod config;
+mod container;
+mod control;
+mod cores;
+mod crash;
 #[cfg(unix)]
 mod daemon;
+mod debug;
 mod dirs;
+mod dryrun;
+mod env;
+mod events;
+mod follow;
+mod forward;
+mod graph;
+mod init;
+mod jsonlog;
+mod kubectl;
+mod level;
+mod lines;
 mod list;
 mod manager;
+mod mirror;
+mod oom;
+mod output;
+mod overview;
+#[cfg(unix)]
+mod pager;
+mod power;
+mod proctree;
+mod programs;
+mod proxy;
+mod ready;
+mod records;
+mod remote;
+mod requires;
+#[cfg(unix)]
+mod serve;
+mod session;
+mod snapshot;
 mod state;
+mod stats;
 mod task;
+#[cfg(unix)]
+mod term;
+mod watch;
 
 // config loader is used via config::load_config_from
 
+/// Print config warnings to stderr; returns how many there were. Load errors are left for
+/// the command itself to report.
+fn report_diagnostics(root: &std::path::Path) -> usize {
+    let warnings = config::diagnostics(root).unwrap_or_default();
+    for w in &warnings {
+        eprintln!("warning: {}", w);
+    }
+    warnings.len()
+}
+
+/// Print warnings for programs the processes run that are not installed; returns how many.
+fn report_missing_programs(root: &std::path::Path) -> usize {
+    let configs = config::load_config_from(root).unwrap_or_default();
+    let warnings = programs::check(root, &configs);
+    for w in &warnings {
+        eprintln!("warning: {}", w);
+    }
+    warnings.len()
+}
+
+/// How long `start --follow` waits for readiness before attaching to logs.
+const DEFAULT_READY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// How long foreground children get to exit after Ctrl+C before they are killed.
+const FOREGROUND_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
@@ -25,47 +95,259 @@ struct Cli {
     #[arg(global = true, long = "color", value_enum)]
     color: Option<ColorChoice>,
 
+    /// Don't pad process-name prefixes to a common width
+    #[arg(global = true, long = "no-align")]
+    no_align: bool,
+
+    /// How often followed log files are checked where the OS doesn't report changes
+    /// (default 300ms)
+    #[arg(global = true, long, value_name = "DURATION", value_parser = config::parse_duration)]
+    poll_interval: Option<std::time::Duration>,
+
+    /// Run the command on another machine over SSH (`host` or `host:project/path`)
+    #[arg(global = true, long = "host", value_name = "HOST")]
+    host: Option<String>,
+
+    /// Keep state (pid files, sockets, logs) under this directory instead of
+    /// $XDG_STATE_HOME/oxproc
+    #[arg(global = true, long = "state-dir", value_name = "PATH")]
+    state_dir: Option<PathBuf>,
+
+    /// Use this config file in the project root instead of proc.toml, with its own state
+    /// (e.g. `-f proc.ci.toml`)
+    #[arg(short = 'f', long = "file", value_name = "FILE")]
+    file: Option<PathBuf>,
+
+    /// Run a separate copy of the formation with its own state, lock and log files
+    #[arg(global = true, long, value_name = "NAME", value_parser = parse_namespace)]
+    namespace: Option<String>,
+
+    /// With --namespace: add this to every port (`port`, `{port}`, tcp checks, container `ports`)
+    #[arg(
+        global = true,
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        requires = "namespace"
+    )]
+    port_offset: u16,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    /// Start all processes as a background daemon
+    /// Start all processes as a background daemon (or only the named ones)
     Start {
+        /// Processes to start; starts them in the running manager if there is one
+        #[arg(add = completion::configured())]
+        names: Vec<String>,
+        /// A process to start, like the names above (as with `logs --name`); repeatable
+        #[arg(long = "name", value_name = "NAME", add = completion::configured())]
+        name: Vec<String>,
+        /// Also every process in this group (`group` in proc.toml); repeatable
+        #[arg(long = "group", value_name = "GROUP", add = completion::groups())]
+        groups: Vec<String>,
         /// Follow logs after starting (equivalent to: start && logs -f)
         #[arg(short, long)]
         follow: bool,
+        /// Break a stale manager lock whose holder process is no longer running
+        #[arg(long)]
+        force: bool,
+        /// Start even inside another project whose manager is running
+        #[arg(long)]
+        ignore_parent: bool,
+        /// With --follow: seconds to wait for processes to become ready before attaching
+        #[arg(long, default_value_t = DEFAULT_READY_TIMEOUT.as_secs())]
+        ready_timeout: u64,
+        /// With --follow: stop all processes on Ctrl+C instead of leaving them running
+        #[arg(long, requires = "follow")]
+        stop_on_detach: bool,
+        /// Print what would be spawned (commands, cwd, env, logs, ports, order) and exit
+        #[arg(long, conflicts_with_all = ["follow", "force"])]
+        dry_run: bool,
+        /// Stop everything again after this long (e.g. 2h, 45m)
+        #[arg(long = "for", value_name = "DURATION", value_parser = config::parse_duration)]
+        run_for: Option<std::time::Duration>,
+        /// Stop everything at this local time (HH:MM, the next time the clock reads it)
+        #[arg(long, value_name = "TIME", value_parser = config::parse_time_of_day, conflicts_with = "run_for")]
+        until: Option<chrono::NaiveTime>,
+    },
+    /// Start again what was running when the manager last went down (e.g. before a reboot)
+    Resume {
+        /// Print a login item (systemd user unit, or LaunchAgent on macOS) that runs `resume`
+        #[arg(long)]
+        login_agent: bool,
+    },
+    /// Start processes and their dependencies, then attach to logs unless -d (like compose up)
+    Up {
+        /// Processes to bring up (default: all autostart processes)
+        #[arg(add = completion::configured())]
+        names: Vec<String>,
+        /// Also every process in this group (`group` in proc.toml); repeatable
+        #[arg(long = "group", value_name = "GROUP", add = completion::groups())]
+        groups: Vec<String>,
+        /// Detached: start in the background without attaching to logs
+        #[arg(short, long)]
+        detach: bool,
+        /// Break a stale manager lock whose holder process is no longer running
+        #[arg(long)]
+        force: bool,
+        /// Start even inside another project whose manager is running
+        #[arg(long)]
+        ignore_parent: bool,
+        /// Seconds to wait for processes to become ready before attaching
+        #[arg(long, default_value_t = DEFAULT_READY_TIMEOUT.as_secs())]
+        ready_timeout: u64,
+    },
+    /// Stop all processes (like compose down); --clean also removes state and log files
+    Down {
+        /// Grace period in seconds before SIGKILL
+        #[arg(long, default_value_t = 5)]
+        grace: u64,
+        /// Remove the state dir and process log files after stopping
+        #[arg(long)]
+        clean: bool,
     },
     /// Show status for the current project's processes
     #[command(alias = "ps")]
-    Status {},
-    /// Stop all processes for the current project
+    Status {
+        /// Show the processes each managed process has spawned
+        #[arg(long)]
+        tree: bool,
+        /// Every project on this machine, with uptime, CPU and memory per process
+        #[arg(long, conflicts_with = "tree")]
+        all: bool,
+        /// Also show how much each process logs and its recent CPU and memory use
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Stop all processes for the current project, or only the named ones in the running
+    /// manager
     Stop {
+        /// Processes to stop; leaves the manager and other processes running
+        #[arg(add = completion::managed())]
+        names: Vec<String>,
+        /// A process to stop, like the names above (as with `logs --name`); repeatable
+        #[arg(long = "name", value_name = "NAME", add = completion::managed())]
+        name: Vec<String>,
+        /// Also every process in this group (`group` in proc.toml); repeatable
+        #[arg(long = "group", value_name = "GROUP", add = completion::groups())]
+        groups: Vec<String>,
+        /// Stop everything except these processes (comma-separated) and what they depend on;
+        /// the manager keeps running
+        #[arg(
+            long,
+            value_name = "NAMES",
+            value_delimiter = ',',
+            conflicts_with_all = ["names", "name", "groups"],
+            add = completion::managed()
+        )]
+        keep: Vec<String>,
         /// Grace period in seconds before SIGKILL
         #[arg(long, default_value_t = 5)]
         grace: u64,
     },
-    /// Restart all processes (stop then start). Add -f to follow logs.
+    /// Restart all processes (stop then start), or only the named ones in the running manager.
+    /// Add -f to follow logs.
     Restart {
+        /// Processes to restart; leaves the manager and other processes running
+        #[arg(add = completion::managed())]
+        names: Vec<String>,
+        /// A process to restart, like the names above (as with `logs --name`); repeatable
+        #[arg(long = "name", value_name = "NAME", add = completion::managed())]
+        name: Vec<String>,
+        /// Also every process in this group (`group` in proc.toml); repeatable
+        #[arg(long = "group", value_name = "GROUP", add = completion::groups())]
+        groups: Vec<String>,
         /// Grace period in seconds before SIGKILL
         #[arg(long, default_value_t = 5)]
         grace: u64,
         /// Follow logs after restarting
         #[arg(short, long)]
         follow: bool,
+        /// Send `reload_signal` instead of restarting to processes that have one
+        #[arg(long)]
+        soft: bool,
+        /// Replace the manager with one from this oxproc binary, restarting the processes it
+        /// ran (after upgrading oxproc)
+        #[arg(long, conflicts_with_all = ["names", "name", "groups", "soft"])]
+        upgrade_manager: bool,
+    },
+    /// Send processes their `reload_signal` (default: every running process that has one)
+    Reload {
+        /// Processes to reload
+        #[arg(add = completion::managed())]
+        names: Vec<String>,
+        /// Also every process in this group (`group` in proc.toml); repeatable
+        #[arg(long = "group", value_name = "GROUP", add = completion::groups())]
+        groups: Vec<String>,
+    },
+    /// Write a line to a process's stdin (needs `stdin = "pipe"` or `"pty"`)
+    Send {
+        /// Process to write to
+        #[arg(add = completion::managed())]
+        name: String,
+        /// Text to send; a newline is added
+        text: String,
+    },
+    /// Exit 0 if a process is ready, 1 if it is up but not ready yet, 2 if it is down
+    Ready {
+        /// Process to check
+        #[arg(add = completion::configured())]
+        name: String,
+        /// Print nothing; only set the exit status
+        #[arg(short, long)]
+        quiet: bool,
+    },
+    /// Print the CPU and memory samples the manager recorded (last 15 minutes) as JSON
+    Stats {
+        /// Only this process
+        #[arg(add = completion::managed())]
+        name: Option<String>,
+    },
+    /// Live merged view of a running manager's processes; Ctrl+D detaches, Ctrl+C offers to stop
+    Attach {
+        /// Number of lines from the end of each log to show first
+        #[arg(short = 'n', long, default_value_t = 100)]
+        lines: usize,
     },
     /// View logs. By default shows combined logs. Use --name to filter.
     Logs {
         /// Process name to filter
-        #[arg(long)]
+        #[arg(long, add = completion::managed())]
         name: Option<String>,
+        /// Also every process in this group (`group` in proc.toml); repeatable
+        #[arg(long = "group", value_name = "GROUP", add = completion::groups())]
+        groups: Vec<String>,
         /// Follow the logs
         #[arg(short, long)]
         follow: bool,
         /// Number of lines from the end
         #[arg(short = 'n', long, default_value_t = 100)]
         lines: usize,
+        /// Only show lines at or above this level (see error_pattern/warn_pattern)
+        #[arg(long, value_enum)]
+        level: Option<LevelChoice>,
+        /// Only show log records matching this regex (whole records with `multiline`)
+        #[arg(long, value_name = "REGEX")]
+        grep: Option<regex::Regex>,
+        /// Output format; json prints one object per line (JSONL), raw the bare lines
+        #[arg(long, value_enum, default_value_t = LogFormatChoice::Text)]
+        format: LogFormatChoice,
+        /// Print lines exactly as written: no prefixes, colors or markers (same as --format raw)
+        #[arg(long, conflicts_with = "format")]
+        raw: bool,
+        /// Print directly instead of paging through $PAGER
+        #[arg(long = "no-pager")]
+        no_pager: bool,
+        /// Export logs to a file; .tar.gz/.tgz/.tar also bundle state, events and config
+        #[arg(short, long, value_name = "PATH", conflicts_with = "follow")]
+        output: Option<PathBuf>,
+        /// With --output: only include logs written (and events recorded) within this window
+        #[arg(long, value_name = "DURATION", requires = "output", value_parser = config::parse_duration)]
+        since: Option<std::time::Duration>,
     },
     /// List configured processes and tasks (proc.toml only for tasks)
     #[command(alias = "ls")]
@@ -82,9 +364,91 @@ enum Commands {
         /// Show only tasks
         #[arg(long = "tasks-only")]
         tasks_only: bool,
+        /// Print task composition and process `depends_on` edges as a graph (default: dot)
+        #[arg(
+            long,
+            value_enum,
+            value_name = "FORMAT",
+            num_args = 0..=1,
+            default_missing_value = "dot",
+            conflicts_with_all = ["json", "names_only"]
+        )]
+        graph: Option<GraphChoice>,
+    },
+    /// Validate proc.toml/Procfile and report warnings
+    Check {
+        /// Treat warnings as errors (exit status 1)
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Write a starter proc.toml for a common stack (rails, django, nextjs, rust-axum)
+    Init {
+        /// Template to use; see --list
+        #[arg(long, short, required_unless_present = "list")]
+        template: Option<String>,
+        /// List the available templates
+        #[arg(long, conflicts_with = "template")]
+        list: bool,
+        /// Replace an existing proc.toml
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the JSON Schema of the manager's control protocol, for integrations
+    Protocol {},
+    /// Export or import the project's config and runtime state, to move it between machines
+    State {
+        #[command(subcommand)]
+        what: StateCommand,
+    },
+    /// Inspect oxproc's own bookkeeping, for bug reports
+    Debug {
+        #[command(subcommand)]
+        what: DebugCommand,
+    },
+    /// Serve editor integrations: newline-delimited JSON requests and events
+    Serve {
+        /// Talk over stdin/stdout
+        #[arg(long, required = true)]
+        stdio: bool,
+    },
+    /// Manage personal services from ~/.config/oxproc/services.toml, from any directory:
+    /// `oxproc service start postgres`, `oxproc service status`, …
+    Service {
+        /// Any oxproc command and its arguments
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            required = true,
+            value_name = "COMMAND"
+        )]
+        args: Vec<std::ffi::OsString>,
+    },
+    /// Run a built-in process (`type = "proxy"` or `"forward"`); the manager starts these
+    #[command(name = "__builtin", hide = true)]
+    Builtin { name: String },
+    /// Run a command inside a process's container (`docker exec`)
+    Exec {
+        /// A process with a container `runtime`
+        name: String,
+        /// The command and its arguments
+        #[arg(trailing_var_arg = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
     },
     /// Run a one-off task from proc.toml
     Run {
+        /// Run the task once in each process's cwd and environment, in parallel
+        #[arg(long)]
+        per_process: bool,
+        /// Print the task and the tasks it runs as a graph (default: dot) instead of running it
+        #[arg(
+            long,
+            value_enum,
+            value_name = "FORMAT",
+            num_args = 0..=1,
+            default_missing_value = "dot",
+            conflicts_with = "per_process"
+        )]
+        graph: Option<GraphChoice>,
         /// Task name under [tasks.<name>]
         task: String,
         /// Arguments passed to the task command after '--'
@@ -96,6 +460,38 @@ enum Commands {
     External(Vec<String>),
 }
 
+#[derive(Subcommand, Debug)]
+enum DebugCommand {
+    /// Show the state file, lock holder, heartbeat, control socket and recent events
+    State {
+        /// Number of events to show from the end of the event log
+        #[arg(short = 'n', long, default_value_t = 10)]
+        events: usize,
+    },
+    /// Show the command, directory and environment a process was launched with
+    Process {
+        #[arg(add = completion::managed())]
+        name: String,
+    },
+    /// List the core dumps gathered from processes with `core_dumps = true`
+    Cores {},
+}
+
+#[derive(Subcommand, Debug)]
+enum StateCommand {
+    /// Print the config and runtime state (running and stopped processes) as JSON
+    Export {},
+    /// Restore an exported snapshot: write its config and make its running processes the
+    /// session `oxproc resume` starts
+    Import {
+        /// The snapshot (default: stdin)
+        file: Option<PathBuf>,
+        /// Replace a config file that differs from the snapshot's
+        #[arg(long)]
+        force: bool,
+    },
+}
+
 #[derive(Clone, Debug, clap::ValueEnum)]
 enum ColorChoice {
     Auto,
@@ -103,6 +499,45 @@ enum ColorChoice {
     Never,
 }
 
+#[derive(Clone, Debug, clap::ValueEnum)]
+enum LevelChoice {
+    Info,
+    Warn,
+    Error,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum LogFormatChoice {
+    Text,
+    Json,
+    Raw,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum GraphChoice {
+    Dot,
+    Mermaid,
+}
+
+impl From<GraphChoice> for graph::GraphFormat {
+    fn from(g: GraphChoice) -> Self {
+        match g {
+            GraphChoice::Dot => graph::GraphFormat::Dot,
+            GraphChoice::Mermaid => graph::GraphFormat::Mermaid,
+        }
+    }
+}
+
+impl From<LevelChoice> for level::LogLevel {
+    fn from(l: LevelChoice) -> Self {
+        match l {
+            LevelChoice::Info => level::LogLevel::Info,
+            LevelChoice::Warn => level::LogLevel::Warn,
+            LevelChoice::Error => level::LogLevel::Error,
+        }
+    }
+}
+
 impl From<ColorChoice> for color::ColorMode {
     fn from(c: ColorChoice) -> Self {
         match c {
@@ -113,18 +548,154 @@ impl From<ColorChoice> for color::ColorMode {
     }
 }
 
+/// `-f FILE`: check that it names a file in the project root; returns its name.
+fn config_file_name(root: &std::path::Path, file: &std::path::Path) -> Result<String> {
+    let path = root.join(file);
+    if !path.is_file() {
+        anyhow::bail!("Config file not found: {}", path.display());
+    }
+    let dir = path.parent().map(std::fs::canonicalize).transpose()?;
+    if dir != Some(std::fs::canonicalize(root)?) {
+        anyhow::bail!(
+            "-f {}: the config file must be in the project root ({}); use --root for another directory",
+            file.display(),
+            root.display()
+        );
+    }
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string());
+    Ok(name.unwrap_or_default())
+}
+
+/// `--namespace NAME`: letters, digits, `.`, `_` and `-`, since it names a state dir.
+fn parse_namespace(s: &str) -> Result<String, String> {
+    let valid = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if valid && !s.starts_with('.') {
+        Ok(s.to_string())
+    } else {
+        Err("use letters, digits, '.', '_' and '-'".into())
+    }
+}
+
+/// `oxproc service <command>`: the command, run against the services root.
+fn service_cli(outer: Cli, args: Vec<std::ffi::OsString>) -> Result<Cli> {
+    let mut cli = Cli::parse_from(std::iter::once("oxproc service".into()).chain(args));
+    if outer.root.is_some() || cli.root.is_some() {
+        anyhow::bail!("--root cannot be used with `oxproc service`");
+    }
+    if matches!(cli.command, Some(Commands::Service { .. }) | None) {
+        anyhow::bail!("Usage: oxproc service <start|stop|status|logs|…> [ARGS]");
+    }
+    let config = dirs::services_config();
+    if !config.exists() {
+        anyhow::bail!(
+            "No services defined; create {} (same format as proc.toml)",
+            config.display()
+        );
+    }
+    std::fs::create_dir_all(dirs::services_root())?;
+    // Looked up again now that it exists, to get its resolved form
+    cli.root = Some(dirs::services_root());
+    cli.color = cli.color.or(outer.color);
+    cli.no_align |= outer.no_align;
+    cli.state_dir = cli.state_dir.or(outer.state_dir);
+    cli.namespace = cli.namespace.or(outer.namespace);
+    cli.port_offset = cli.port_offset.max(outer.port_offset);
+    Ok(cli)
+}
+
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    // Answers the shell when invoked with COMPLETE=<shell>; returns otherwise
+    clap_complete::CompleteEnv::with_factory(Cli::command).complete();
+    let mut cli = Cli::parse();
+    if let Some(host) = &cli.host {
+        let code = remote::run(
+            host,
+            cli.root.as_deref(),
+            std::env::args_os().skip(1).collect(),
+        )?;
+        std::process::exit(code);
+    }
+    // Every command addresses the project the same way, whichever way --root spells it
+    if let Some(root) = &cli.root {
+        let normalized = dirs::normalize_root(root)
+            .map_err(|e| anyhow::anyhow!("--root {}: {}", root.display(), e))?;
+        cli.root = Some(normalized);
+    }
+    if let Some(Commands::Service { args }) = &mut cli.command {
+        let args = std::mem::take(args);
+        cli = service_cli(cli, args)?;
+    }
+    if let Some(dir) = &cli.state_dir {
+        // Absolute, since the daemon changes directory
+        std::fs::create_dir_all(dir)?;
+        std::env::set_var(dirs::STATE_DIR_ENV, dirs::normalize_root(dir)?);
+    }
     color::init(cli.color.map(|c| c.into()));
+    color::set_align(!cli.no_align);
     let root = cli.root.unwrap_or_else(|| std::env::current_dir().unwrap());
+    let variant = dirs::Variant {
+        file: cli
+            .file
+            .as_deref()
+            .map(|f| config_file_name(&root, f))
+            .transpose()?,
+        namespace: cli.namespace.clone(),
+        port_offset: cli.port_offset,
+    };
+    if variant != dirs::Variant::default() {
+        dirs::set_variant(&root, variant);
+    }
+    if let Some(interval) = cli.poll_interval {
+        power::set_follow_poll(interval);
+    }
+    power::set_low_power(config::load_low_power(&root).unwrap_or(false));
+    match config::load_color_theme(&root) {
+        Ok(theme) => color::set_theme(theme),
+        Err(e @ config::ConfigError::InvalidColor(_)) => eprintln!("warning: {}", e),
+        Err(_) => {} // missing or broken config is reported by the command itself
+    }
     match cli.command {
-        Some(Commands::Start { follow }) => {
+        Some(Commands::Start {
+            names,
+            name,
+            groups,
+            follow,
+            force,
+            ignore_parent,
+            ready_timeout,
+            stop_on_detach,
+            dry_run,
+            run_for,
+            until,
+        }) => {
+            report_diagnostics(&root);
+            warn_other_release(&root);
+            let names = selected(&root, [names, name].concat(), &groups)?;
+            if dry_run {
+                report_missing_programs(&root);
+                return dryrun::print_dry_run(&root, &names);
+            }
             #[cfg(unix)]
             {
+                let stop_at = match (run_for, until) {
+                    (Some(d), _) => Some(chrono::Utc::now() + chrono::Duration::from_std(d)?),
+                    (_, Some(t)) => Some(daemon::next_occurrence(t, chrono::Local::now())),
+                    _ => None,
+                };
                 if follow {
-                    start_and_follow(&root)
+                    start_and_follow(
+                        &root,
+                        &names,
+                        force,
+                        ignore_parent,
+                        std::time::Duration::from_secs(ready_timeout),
+                        stop_on_detach,
+                        stop_at,
+                    )
                 } else {
-                    daemon::start_daemon(&root)
+                    daemon::start_daemon(&root, &names, force, ignore_parent, stop_at)
                 }
             }
             #[cfg(not(unix))]
@@ -132,13 +703,105 @@ fn main() -> Result<()> {
                 anyhow::bail!("Daemon mode is only supported on Unix (Linux/macOS)");
             }
         }
-        Some(Commands::Status {}) => {
-            state::print_status(&root)?;
+        Some(Commands::Resume { login_agent }) => {
+            if login_agent {
+                let exe = std::env::current_exe()?;
+                let (path, agent) = session::login_agent(&dirs::normalize_root(&root)?, &exe);
+                eprintln!("# Save as {} and enable it", path);
+                out!("{}", agent);
+                return Ok(());
+            }
+            #[cfg(unix)]
+            {
+                resume(&root)
+            }
+            #[cfg(not(unix))]
+            {
+                anyhow::bail!("Daemon mode is only supported on Unix (Linux/macOS)");
+            }
+        }
+        Some(Commands::Up {
+            names,
+            groups,
+            detach,
+            force,
+            ignore_parent,
+            ready_timeout,
+        }) => {
+            report_diagnostics(&root);
+            warn_other_release(&root);
+            let names = selected(&root, names, &groups)?;
+            #[cfg(unix)]
+            {
+                if detach {
+                    daemon::start_daemon(&root, &names, force, ignore_parent, None)
+                } else {
+                    start_and_follow(
+                        &root,
+                        &names,
+                        force,
+                        ignore_parent,
+                        std::time::Duration::from_secs(ready_timeout),
+                        false,
+                        None,
+                    )
+                }
+            }
+            #[cfg(not(unix))]
+            {
+                anyhow::bail!("Daemon mode is only supported on Unix (Linux/macOS)");
+            }
+        }
+        Some(Commands::Down { grace, clean }) => {
+            #[cfg(unix)]
+            {
+                manager::stop_all(&root, Some(std::time::Duration::from_secs(grace)))?;
+                if clean {
+                    manager::remove_state_and_logs(&root)?;
+                }
+                Ok(())
+            }
+            #[cfg(not(unix))]
+            {
+                anyhow::bail!("Stop is only supported on Unix in daemon mode");
+            }
+        }
+        Some(Commands::Status { tree, all, verbose }) => {
+            if all {
+                return overview::print_all();
+            }
+            warn_other_release(&root);
+            state::print_status(&root, tree, verbose)?;
             Ok(())
         }
-        Some(Commands::Stop { grace }) => {
+        Some(Commands::Stop {
+            names,
+            name,
+            groups,
+            keep,
+            grace,
+        }) => {
             #[cfg(unix)]
             {
+                let names = if keep.is_empty() {
+                    selected(&root, [names, name].concat(), &groups)?
+                } else {
+                    let names = all_except(&root, &keep)?;
+                    if names.is_empty() {
+                        outln!("Nothing to stop; only kept processes are running.");
+                        return Ok(());
+                    }
+                    names
+                };
+                if !names.is_empty() {
+                    return send_to_manager(
+                        &root,
+                        &control::Request::Stop {
+                            grace: Some(grace),
+                            names,
+                        },
+                    );
+                }
                 manager::stop_all(&root, Some(std::time::Duration::from_secs(grace)))?;
                 Ok(())
             }
@@ -147,22 +810,107 @@ fn main() -> Result<()> {
                 anyhow::bail!("Stop is only supported on Unix in daemon mode");
             }
         }
+        Some(Commands::Attach { lines }) => {
+            #[cfg(unix)]
+            {
+                manager::attach(&root, lines)
+            }
+            #[cfg(not(unix))]
+            {
+                let _ = lines;
+                anyhow::bail!("Attach is only supported on Unix in daemon mode");
+            }
+        }
         Some(Commands::Logs {
             name,
+            groups,
             follow,
             lines,
+            level,
+            grep,
+            format,
+            raw,
+            no_pager,
+            output,
+            since,
         }) => {
-            manager::print_logs(&root, name, follow, lines)?;
+            let names = selected(&root, name.into_iter().collect(), &groups)?;
+            if let Some(output) = output {
+                bundle::export_logs(&root, &names, since, &output)?;
+                outln!("Wrote {}", output.display());
+                return Ok(());
+            }
+            #[cfg(unix)]
+            let _pager = if follow || no_pager {
+                None
+            } else {
+                pager::Pager::start()
+            };
+            manager::print_logs(
+                &root,
+                &names,
+                follow,
+                lines,
+                level.map(Into::into),
+                grep,
+                match format {
+                    _ if raw => manager::LogOutput::Raw,
+                    LogFormatChoice::Text => manager::LogOutput::Text,
+                    LogFormatChoice::Json => manager::LogOutput::Json,
+                    LogFormatChoice::Raw => manager::LogOutput::Raw,
+                },
+            )?;
             Ok(())
         }
-        Some(Commands::Restart { grace, follow }) => {
+        Some(Commands::Restart {
+            names,
+            name,
+            groups,
+            grace,
+            follow,
+            soft,
+            upgrade_manager,
+        }) => {
             #[cfg(unix)]
             {
+                if upgrade_manager {
+                    return replace_manager(&root, grace, follow);
+                }
+                let mut names = selected(&root, [names, name].concat(), &groups)?;
+                if soft && names.is_empty() {
+                    // Process by process, so the ones with a reload_signal keep running. Only
+                    // what runs now: stopped and autostart = false processes stay down.
+                    let st = state::load_state_from_root(&root)?;
+                    for p in st.processes.iter().filter(|p| p.state.is_live()) {
+                        if !names.contains(&p.name) {
+                            names.push(p.name.clone());
+                        }
+                    }
+                    if names.is_empty() {
+                        anyhow::bail!("No running processes to restart");
+                    }
+                }
+                if !names.is_empty() {
+                    restart_named(&root, &names, grace, soft)?;
+                    if follow {
+                        manager::print_logs(
+                            &root,
+                            &[],
+                            true,
+                            100,
+                            None,
+                            None,
+                            manager::LogOutput::Text,
+                        )?;
+                    }
+                    return Ok(());
+                }
                 manager::stop_all(&root, Some(std::time::Duration::from_secs(grace)))?;
+                // It was running here already, enclosing project or not
                 if follow {
-                    start_and_follow(&root)
+                    start_and_follow(&root, &[], false, true, DEFAULT_READY_TIMEOUT, false, None)
                 } else {
-                    daemon::start_daemon(&root)
+                    daemon::start_daemon(&root, &[], false, true, None)
                 }
             }
             #[cfg(not(unix))]
@@ -170,29 +918,190 @@ fn main() -> Result<()> {
                 anyhow::bail!("Restart is only supported on Unix in daemon mode");
             }
         }
+        Some(Commands::Reload { names, groups }) => {
+            #[cfg(unix)]
+            {
+                let names = selected(&root, names, &groups)?;
+                send_to_manager(&root, &control::Request::Reload { names })
+            }
+            #[cfg(not(unix))]
+            {
+                anyhow::bail!("Reload is only supported on Unix in daemon mode");
+            }
+        }
+        Some(Commands::Send { name, text }) => {
+            #[cfg(unix)]
+            {
+                send_to_manager(&root, &control::Request::Send { name, text })
+            }
+            #[cfg(not(unix))]
+            {
+                anyhow::bail!("send is only supported on Unix in daemon mode");
+            }
+        }
         Some(Commands::List {
             json,
             names_only,
             processes_only,
             tasks_only,
+            graph,
         }) => {
+            if let Some(format) = graph {
+                let mut g = graph::Graph::default();
+                if !tasks_only {
+                    g.add_processes(&config::load_config_from(&root)?);
+                }
+                if !processes_only {
+                    if let Some(tasks) = config::load_tasks_from(&root)? {
+                        g.add_tasks(&tasks, None);
+                    }
+                }
+                out!("{}", g.render(format.into()));
+                return Ok(());
+            }
             let info = list::gather_list_info(&root)?;
             if json {
-                println!("{}", serde_json::to_string_pretty(&info)?);
+                outln!("{}", serde_json::to_string_pretty(&info)?);
                 return Ok(());
             }
             if names_only {
                 let s = list::format_list_names_only(&info, processes_only, tasks_only);
                 if !s.is_empty() {
-                    println!("{}", s);
+                    outln!("{}", s);
                 }
                 return Ok(());
             }
             let s = list::format_list_human(&info, processes_only, tasks_only);
-            print!("{}", s);
+            out!("{}", s);
             Ok(())
         }
-        Some(Commands::Run { task, args }) => run_task(&root, &task, &args),
+        Some(Commands::Serve { stdio: _ }) => {
+            #[cfg(unix)]
+            {
+                serve::run(&root)
+            }
+            #[cfg(not(unix))]
+            {
+                anyhow::bail!("serve is only supported on Unix");
+            }
+        }
+        Some(Commands::Stats { name }) => stats::print(&root, name.as_deref()),
+        Some(Commands::Ready { name, quiet }) => {
+            let recorded = state::load_state_from_root(&root)
+                .ok()
+                .and_then(|st| state::process_state(&st, &name));
+            let state = match recorded {
+                Some(state) => state,
+                None => {
+                    // No manager, or one that doesn't know the name: down, if it is a process
+                    let configs = config::load_config_from(&root).unwrap_or_default();
+                    if !configs.iter().any(|c| c.name == name) {
+                        eprintln!("Unknown process '{}'", name);
+                        std::process::exit(3);
+                    }
+                    state::ProcessState::Stopped
+                }
+            };
+            if !quiet {
+                outln!("{} {}", name, state);
+            }
+            std::process::exit(state.readiness() as i32);
+        }
+        Some(Commands::Check { strict }) => {
+            let configs = config::load_config_from(&root)?;
+            let tasks = config::load_tasks_from(&root)?.map_or(0, |t| t.len());
+            let warnings = report_diagnostics(&root) + report_missing_programs(&root);
+            outln!(
+                "{} process(es), {} task(s); {} warning(s).",
+                configs.len(),
+                tasks,
+                warnings
+            );
+            if strict && warnings > 0 {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Some(Commands::Init {
+            template,
+            list,
+            force,
+        }) => match template {
+            Some(template) if !list => init::init(&root, &template, force),
+            _ => {
+                init::print_templates();
+                Ok(())
+            }
+        },
+        Some(Commands::Builtin { name }) => builtin::run(&root, &name),
+        Some(Commands::Protocol {}) => {
+            outln!("{}", control::SCHEMA.trim_end());
+            Ok(())
+        }
+        Some(Commands::Debug {
+            what: DebugCommand::State { events },
+        }) => debug::print_state(&root, events),
+        Some(Commands::Debug {
+            what: DebugCommand::Process { name },
+        }) => debug::print_process(&root, &name),
+        Some(Commands::Debug {
+            what: DebugCommand::Cores {},
+        }) => debug::print_cores(&root),
+        Some(Commands::State {
+            what: StateCommand::Export {},
+        }) => {
+            let snapshot = snapshot::export(&root)?;
+            outln!("{}", serde_json::to_string_pretty(&snapshot)?);
+            Ok(())
+        }
+        Some(Commands::State {
+            what: StateCommand::Import { file, force },
+        }) => {
+            let data = match file {
+                Some(path) => std::fs::read_to_string(&path)?,
+                None => std::io::read_to_string(std::io::stdin())?,
+            };
+            let snapshot: snapshot::Snapshot = serde_json::from_str(&data)
+                .map_err(|e| anyhow::anyhow!("not an oxproc state snapshot: {}", e))?;
+            snapshot::import(&root, &snapshot, force)?;
+            outln!(
+                "Imported {} and {} running process(es); `oxproc resume` starts them.",
+                snapshot.config.name,
+                snapshot.running.len()
+            );
+            Ok(())
+        }
+        Some(Commands::Service { .. }) => unreachable!("resolved by service_cli"),
+        Some(Commands::Exec { name, command }) => {
+            use std::io::IsTerminal;
+            let container = process_container(&root, &name)?;
+            let tty = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+            let exec = container.exec_args(&root, &name, tty, None, std::iter::empty());
+            let status = std::process::Command::new(&exec[0])
+                .args(&exec[1..])
+                .args(&command)
+                .status()
+                .map_err(|e| anyhow::anyhow!("can't run {}: {}", exec[0], e))?;
+            std::process::exit(config::shell_exit_code(&status).unwrap_or(1));
+        }
+        Some(Commands::Run {
+            task,
+            args,
+            per_process,
+            graph,
+        }) => {
+            if let Some(format) = graph {
+                let (tasks, key) = find_task(&root, &task)?;
+                let mut g = graph::Graph::default();
+                g.add_tasks(&tasks, Some(&key));
+                out!("{}", g.render(format.into()));
+                Ok(())
+            } else if per_process {
+                run_task_per_process(&root, &task, &args)
+            } else {
+                run_task(&root, &task, &args)
+            }
+        }
         Some(Commands::External(v)) => {
             if v.is_empty() {
                 anyhow::bail!("No task name provided")
@@ -204,62 +1113,219 @@ fn main() -> Result<()> {
         }
         None => {
             // Default: foreground follow of all processes (dev UX)
+            report_diagnostics(&root);
             tokio_foreground_follow(&root)
         }
     }
 }
 
+/// Restart individual processes through the running manager's control socket.
 #[cfg(unix)]
-fn start_and_follow(root: &std::path::Path) -> Result<()> {
-    use std::process::Command;
-    use std::time::Duration;
+fn restart_named(root: &std::path::Path, names: &[String], grace: u64, soft: bool) -> Result<()> {
+    send_to_manager(
+        root,
+        &control::Request::Restart {
+            names: names.to_vec(),
+            grace: Some(grace),
+            soft,
+        },
+    )
+}
 
-    // Spawn a fresh `oxproc start` without --follow to perform the daemonization
-    let exe = std::env::current_exe()?;
-    let mut args: Vec<String> = Vec::new();
-    // forward --root if provided
-    args.push("start".to_string());
-    // If the user passed --root in the original invocation, `root` will reflect it; we must forward
-    // by comparing with current_dir and adding explicit flag only if different.
-    if let Ok(cwd) = std::env::current_dir() {
-        if cwd != root {
-            args.splice(
-                0..0,
-                vec!["--root".to_string(), root.to_string_lossy().to_string()],
-            );
-        }
+/// Warn when the project's manager runs another oxproc release than this CLI, whose requests
+/// and state it may not understand.
+fn warn_other_release(root: &std::path::Path) {
+    let Ok(st) = state::load_state_from_root(root) else {
+        return;
+    };
+    if st.manager.health() == state::ManagerHealth::Gone {
+        return;
+    }
+    if let Some(release) = st.manager.other_release() {
+        eprintln!(
+            "warning: the manager runs {} and this is oxproc {}; run `oxproc restart --upgrade-manager` to replace it",
+            release,
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+}
+
+/// `restart --upgrade-manager`: stop the manager and its processes, then start the ones it was
+/// running or about to start under a manager from this binary.
+#[cfg(unix)]
+fn replace_manager(root: &std::path::Path, grace: u64, follow: bool) -> Result<()> {
+    let st = state::load_state_from_root(root)
+        .ok()
+        .filter(|st| st.manager.health() != state::ManagerHealth::Gone);
+    let Some(st) = st else {
+        anyhow::bail!("No manager running for this project; use `oxproc start` first");
+    };
+    let mut names: Vec<String> = st
+        .processes
+        .iter()
+        .filter(|p| p.state.is_live())
+        .map(|p| p.name.clone())
+        .chain(st.pending.iter().cloned())
+        .collect();
+    names.sort();
+    names.dedup();
+    manager::stop_all(root, Some(std::time::Duration::from_secs(grace)))?;
+    if names.is_empty() {
+        outln!("Stopped the manager; it had no processes running.");
+        return Ok(());
+    }
+    // It was running here already, enclosing project or not
+    if follow {
+        start_and_follow(
+            root,
+            &names,
+            false,
+            true,
+            DEFAULT_READY_TIMEOUT,
+            false,
+            st.manager.stop_at,
+        )
+    } else {
+        daemon::start_daemon(root, &names, false, true, st.manager.stop_at)
     }
+}
 
-    let status = Command::new(exe)
-        .args(&args)
-        .stdin(std::process::Stdio::null())
-        .stdout(std::process::Stdio::inherit())
-        .stderr(std::process::Stdio::inherit())
-        .spawn();
+/// Process names given on the command line plus the members of each `--group`.
+fn selected(root: &std::path::Path, names: Vec<String>, groups: &[String]) -> Result<Vec<String>> {
+    if groups.is_empty() {
+        return Ok(names);
+    }
+    let configs = config::load_config_from(root)?;
+    Ok(config::select(&configs, &names, groups)?)
+}
 
-    match status {
-        Ok(_child) => {
-            // Wait for readiness then attach logs
-            println!("Waiting for manager to become ready…");
-            state::wait_for_manager_ready(root, Duration::from_secs(10))?;
-            println!("Attaching to logs (Ctrl+C to detach)…");
-            manager::print_logs(root, None, true, 100)?;
-            Ok(())
-        }
-        Err(e) => {
-            anyhow::bail!("Failed to spawn start: {}", e);
+/// For `stop --keep`: the running and pending processes, except `keep` and whatever those
+/// depend on.
+#[cfg(unix)]
+fn all_except(root: &std::path::Path, keep: &[String]) -> Result<Vec<String>> {
+    let configs = config::load_config_from(root)?;
+    for name in keep {
+        if !configs.iter().any(|c| &c.name == name) {
+            anyhow::bail!("Unknown process '{}'", name);
         }
     }
+    let keep = config::with_dependencies(&configs, keep);
+    let st = state::load_state_from_root(root)?;
+    Ok(st
+        .processes
+        .iter()
+        .filter(|p| p.state.is_live())
+        .map(|p| p.name.clone())
+        .chain(st.pending.iter().cloned())
+        .filter(|name| !keep.contains(name))
+        .collect())
+}
+
+/// Send a request to the running manager and print its reply; fails if it reports an error.
+#[cfg(unix)]
+fn send_to_manager(root: &std::path::Path, req: &control::Request) -> Result<()> {
+    let state_dir = state::state_dir_from_root(root);
+    if !control::manager_listening(&state_dir) {
+        anyhow::bail!("No manager running for this project; use `oxproc start` first");
+    }
+    warn_other_release(root);
+    let resp = control::send_request(&state_dir, req)?;
+    if !resp.ok {
+        anyhow::bail!("{}", resp.message);
+    }
+    if !resp.message.is_empty() {
+        outln!("{}", resp.message);
+    }
+    Ok(())
+}
+
+/// `oxproc resume`: start the processes of the last session that are still configured.
+#[cfg(unix)]
+fn resume(root: &std::path::Path) -> Result<()> {
+    let Some(session) = session::load(root) else {
+        outln!("Nothing to resume: no earlier session recorded for this project.");
+        return Ok(());
+    };
+    let configs = config::load_config_from(root)?;
+    let (names, gone): (Vec<String>, Vec<String>) = session
+        .processes
+        .into_iter()
+        .partition(|name| configs.iter().any(|c| &c.name == name));
+    if !gone.is_empty() {
+        eprintln!("warning: no longer in the config: {}", gone.join(", "));
+    }
+    if names.is_empty() {
+        outln!("Nothing to resume: nothing was running when the manager last stopped.");
+        return Ok(());
+    }
+    outln!(
+        "Resuming {} (running as of {})",
+        names.join(", "),
+        session
+            .saved_at
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M")
+    );
+    daemon::start_daemon(root, &names, false, false, None)
+}
+
+#[cfg(unix)]
+fn start_and_follow(
+    root: &std::path::Path,
+    names: &[String],
+    force: bool,
+    ignore_parent: bool,
+    ready_timeout: std::time::Duration,
+    stop_on_detach: bool,
+    stop_at: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<()> {
+    use std::time::Duration;
+
+    // Daemonize in-process: only this (parent) process returns here, so every CLI setting
+    // (--color, --root, --no-align, …) is still in effect for the follow below.
+    daemon::start_daemon(root, names, force, ignore_parent, stop_at)?;
+    state::wait_for_manager_ready(root, Duration::from_secs(10))?;
+    let all = config::load_config_from(root)?;
+    let wanted: Vec<String> = if names.is_empty() {
+        all.iter()
+            .filter(|c| c.autostart)
+            .map(|c| c.name.clone())
+            .collect()
+    } else {
+        names.to_vec()
+    };
+    let wanted = config::with_dependencies(&all, &wanted);
+    let configs: Vec<_> = all
+        .into_iter()
+        .filter(|c| wanted.contains(&c.name))
+        .collect();
+    outln!("Waiting for processes to become ready…");
+    if !ready::wait_with_checklist(root, &configs, ready_timeout) {
+        outln!("Not all processes became ready; attaching anyway.");
+    }
+    if stop_on_detach {
+        outln!("Attaching to logs (Ctrl+C stops all processes)…");
+    } else {
+        outln!("Attaching to logs (Ctrl+C detaches; processes keep running)…");
+    }
+    manager::print_logs(root, &[], true, 100, None, None, manager::LogOutput::Text)?;
+    if stop_on_detach {
+        outln!();
+        return manager::stop_all(root, None);
+    }
+    outln!();
+    outln!("Detached; processes are still running (`oxproc stop` stops them).");
+    Ok(())
 }
 
 fn tokio_foreground_follow(root: &std::path::Path) -> Result<()> {
     use futures::future::join_all;
     use std::process::Stdio;
     use std::sync::Arc;
+    use std::sync::Mutex;
     use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
     use tokio::process::Command;
     use tokio::runtime::Runtime;
-    use tokio::sync::Mutex;
 
     let rt = Runtime::new()?;
     rt.block_on(async move {
@@ -270,24 +1336,134 @@ fn tokio_foreground_follow(root: &std::path::Path) -> Result<()> {
             stream: T,
             _log_path: Option<String>,
             follow: bool,
-            prefix: &'static str,
+            stderr: bool,
+            highlight: Arc<Vec<color::HighlightRule>>,
         ) {
             let mut reader = BufReader::new(stream).lines();
             while let Some(line) = reader.next_line().await.unwrap() {
                 if follow {
-                    let p = color::prefix(&child_name);
-                    println!("{}{}{}", p, prefix, line);
+                    let p = color::stream_prefix(&child_name, stderr);
+                    outln!("{}{}", p, color::highlight(&line, &highlight));
                 }
             }
         }
 
-        let mut children = Vec::new();
+        type Children = Arc<Mutex<Vec<tokio::process::Child>>>;
+
+        /// Send `sig` to every child's process group.
+        #[cfg(unix)]
+        fn signal_groups(children: &Children, sig: nix::sys::signal::Signal) {
+            for child in children.lock().unwrap().iter() {
+                if let Some(pid) = child.id() {
+                    let _ = nix::sys::signal::killpg(nix::unistd::Pid::from_raw(pid as i32), sig);
+                }
+            }
+        }
+
+        /// Children run in their own process groups, so the terminal's job-control and resize
+        /// signals reach only us; pass them on. On Ctrl+Z the children are stopped first, then
+        /// oxproc stops itself so the shell gets the terminal back, and `fg` resumes everyone.
+        #[cfg(unix)]
+        async fn forward_signals(children: Children, user_signals: bool) -> Result<()> {
+            use nix::sys::signal::Signal;
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut forwarded = vec![
+                (
+                    Signal::SIGTSTP,
+                    signal(SignalKind::from_raw(Signal::SIGTSTP as i32))?,
+                ),
+                (
+                    Signal::SIGCONT,
+                    signal(SignalKind::from_raw(Signal::SIGCONT as i32))?,
+                ),
+                (Signal::SIGWINCH, signal(SignalKind::window_change())?),
+            ];
+            if user_signals {
+                forwarded.push((Signal::SIGUSR1, signal(SignalKind::user_defined1())?));
+                forwarded.push((Signal::SIGUSR2, signal(SignalKind::user_defined2())?));
+            }
+            loop {
+                let recv = forwarded.iter_mut().map(|(sig, stream)| {
+                    let sig = *sig;
+                    Box::pin(async move {
+                        stream.recv().await;
+                        sig
+                    })
+                });
+                let (sig, _, _) = futures::future::select_all(recv).await;
+                signal_groups(&children, sig);
+                if sig == Signal::SIGTSTP {
+                    let _ = nix::sys::signal::raise(Signal::SIGSTOP);
+                }
+            }
+        }
+
+        fn launch(
+            name: &str,
+            mut cmd: Command,
+            children: &Children,
+            highlight: &Arc<Vec<color::HighlightRule>>,
+        ) -> Result<Vec<tokio::task::JoinHandle<()>>> {
+            let mut child = cmd.spawn()?;
+            let pid = child.id().unwrap();
+            outln!("Started {} with PID: {}", name, pid);
+
+            let stdout = child.stdout.take().unwrap();
+            let stderr = child.stderr.take().unwrap();
+
+            let stdout_handle = tokio::spawn(handle_output(
+                name.to_string(),
+                stdout,
+                None,
+                true,
+                false,
+                highlight.clone(),
+            ));
+            let stderr_handle = tokio::spawn(handle_output(
+                name.to_string(),
+                stderr,
+                None,
+                true,
+                true,
+                highlight.clone(),
+            ));
+
+            children.lock().unwrap().push(child);
+            Ok(vec![stdout_handle, stderr_handle])
+        }
+
+        let children: Children = Arc::new(Mutex::new(Vec::new()));
+        let settings = config::load_log_settings(root)?;
+        if let Some(format) = settings.prefix_format {
+            color::set_prefix_format(format);
+        }
+        let highlight = Arc::new(settings.highlight);
+        let forward_user_signals = config::load_forward_user_signals(root)?;
         let mut handles = Vec::new();
 
+        // On-demand processes (`autostart = false`) are left out of the foreground formation
+        let configs: Vec<_> = configs.into_iter().filter(|c| c.autostart).collect();
+        color::align_to(configs.iter().map(|c| c.name.as_str()));
+        color::assign_colors(configs.iter().map(|c| c.name.as_str()));
+        let mut required: Vec<&std::path::PathBuf> = Vec::new();
+        for project in configs.iter().flat_map(|c| &c.requires_project) {
+            if !required.contains(&project) {
+                required.push(project);
+            }
+        }
+        for project in required {
+            #[cfg(unix)]
+            if requires::ensure_started(project)? {
+                outln!("Started required project {}", project.display());
+            }
+        }
         for config in configs {
             let mut cmd = Command::new("sh");
             cmd.arg("-c");
-            cmd.arg(&config.command);
+            cmd.arg(config.command_line(root));
+            config.env.apply(&mut cmd);
+            config.env.apply_color(&mut cmd, color::child_color(true));
             if let Some(cwd) = &config.cwd {
                 let abs = if std::path::Path::new(cwd).is_absolute() {
                     std::path::PathBuf::from(cwd)
@@ -303,42 +1479,67 @@ fn tokio_foreground_follow(root: &std::path::Path) -> Result<()> {
                 }
                 cmd.current_dir(abs);
             }
+            // Outside the terminal's foreground group, a child reading the terminal would be
+            // stopped by SIGTTIN
+            cmd.stdin(Stdio::null());
             cmd.stdout(Stdio::piped());
             cmd.stderr(Stdio::piped());
+            #[cfg(unix)]
+            cmd.process_group(0);
+            if let Some(adj) = config.oom_score_adj {
+                oom::apply(&mut cmd, adj);
+            }
 
-            let mut child = cmd.spawn()?;
-            let pid = child.id().unwrap();
-            println!("Started {} with PID: {}", config.name, pid);
-
-            let stdout = child.stdout.take().unwrap();
-            let stderr = child.stderr.take().unwrap();
-
-            let stdout_handle =
-                tokio::spawn(handle_output(config.name.clone(), stdout, None, true, ""));
-
-            let stderr_handle = tokio::spawn(handle_output(
-                config.name.clone(),
-                stderr,
-                None,
-                true,
-                "[ERR] ",
-            ));
+            let delay = config.startup_delay();
+            if delay.is_zero() {
+                handles.extend(launch(&config.name, cmd, &children, &highlight)?);
+                continue;
+            }
 
-            children.push(Arc::new(Mutex::new(child)));
-            handles.push(stdout_handle);
-            handles.push(stderr_handle);
+            outln!("Delaying {} by {:.1}s", config.name, delay.as_secs_f64());
+            let children = children.clone();
+            let highlight = highlight.clone();
+            handles.push(tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                match launch(&config.name, cmd, &children, &highlight) {
+                    Ok(outputs) => {
+                        join_all(outputs).await;
+                    }
+                    Err(e) => eprintln!("Failed to start {}: {}", config.name, e),
+                }
+            }));
         }
 
+        #[cfg(unix)]
+        let forwarder = tokio::spawn(forward_signals(children.clone(), forward_user_signals));
+
         tokio::select! {
             _ = join_all(handles) => {},
             _ = tokio::signal::ctrl_c() => {
-                println!("\nShutting down...");
-                for child in children.iter_mut() {
-                    let mut child_guard = child.lock().await;
-                    child_guard.kill().await?;
+                outln!("\nShutting down...");
+                #[cfg(unix)]
+                signal_groups(&children, nix::sys::signal::Signal::SIGINT);
+                let mut running = std::mem::take(&mut *children.lock().unwrap());
+                // Give them the grace period to clean up; a second Ctrl+C cuts it short
+                let deadline = tokio::time::Instant::now() + FOREGROUND_GRACE;
+                let exited = async {
+                    for child in running.iter_mut() {
+                        let _ = tokio::time::timeout_at(deadline, child.wait()).await;
+                    }
+                };
+                tokio::select! {
+                    _ = exited => {}
+                    _ = tokio::signal::ctrl_c() => {}
+                }
+                for child in running.iter_mut() {
+                    if child.try_wait()?.is_none() {
+                        child.kill().await?;
+                    }
                 }
             }
         }
+        #[cfg(unix)]
+        forwarder.abort();
 
         Ok::<(), anyhow::Error>(())
     })?;
@@ -346,9 +1547,14 @@ fn tokio_foreground_follow(root: &std::path::Path) -> Result<()> {
     Ok(())
 }
 
-fn run_task(root: &std::path::Path, task: &str, args: &[String]) -> Result<()> {
-    use tokio::runtime::Runtime;
-
+/// Load the project's tasks and find `task` in them, returning the normalized key.
+fn find_task(
+    root: &std::path::Path,
+    task: &str,
+) -> Result<(
+    std::collections::HashMap<String, config::TaskConfig>,
+    String,
+)> {
     // Gate: only available for proc.toml projects
     match config::detect_source(root)? {
         config::ConfigSource::Procfile => {
@@ -376,6 +1582,13 @@ fn run_task(root: &std::path::Path, task: &str, args: &[String]) -> Result<()> {
             );
         }
     };
+    Ok((tasks, key))
+}
+
+fn run_task(root: &std::path::Path, task: &str, args: &[String]) -> Result<()> {
+    use tokio::runtime::Runtime;
+
+    let (tasks, key) = find_task(root, task)?;
 
     // Execute task graph
     let rt = Runtime::new()?;
@@ -399,6 +1612,88 @@ fn run_task(root: &std::path::Path, task: &str, args: &[String]) -> Result<()> {
     }
 }
 
+/// `run --per-process`: a shell task once per distinct process cwd, with that process's
+/// environment under the task's own, all in parallel with output prefixed by process name.
+fn run_task_per_process(root: &std::path::Path, task: &str, args: &[String]) -> Result<()> {
+    let (tasks, key) = find_task(root, task)?;
+    let config::TaskKind::Shell {
+        cmd,
+        env,
+        allowed_exit_codes,
+        pipefail,
+        inside,
+        ..
+    } = &tasks[&key].kind
+    else {
+        anyhow::bail!(
+            "--per-process needs a task with a cmd; '{}' runs other tasks",
+            task
+        );
+    };
+    if let Some(process) = inside {
+        anyhow::bail!(
+            "--per-process can't run '{}', which runs in {}",
+            task,
+            process
+        );
+    }
+
+    // Processes sharing a directory share one run, so `pnpm install` doesn't race itself
+    let mut runs: Vec<(String, std::path::PathBuf, env::EnvSpec)> = Vec::new();
+    for p in config::load_config_from(root)? {
+        let dir = p.working_dir(root);
+        match runs.iter_mut().find(|(_, d, _)| *d == dir) {
+            Some((label, _, _)) => {
+                label.push(',');
+                label.push_str(&p.name);
+            }
+            None => runs.push((p.name.clone(), dir, p.env.overlay(env))),
+        }
+    }
+    if runs.is_empty() {
+        anyhow::bail!("No processes defined to run '{}' for", task);
+    }
+    let cmd = task_command(cmd, args, *pipefail)?;
+    color::align_to(runs.iter().map(|(label, _, _)| label.as_str()));
+    color::assign_colors(runs.iter().map(|(label, _, _)| label.as_str()));
+
+    let rt = tokio::runtime::Runtime::new()?;
+    let results = rt.block_on(futures::future::join_all(runs.iter().map(
+        |(label, dir, env)| {
+            run_shell_task(
+                root,
+                &key,
+                &cmd,
+                dir.to_str(),
+                env,
+                StdioMode::Prefixed(label),
+            )
+        },
+    )));
+    let mut failed = Vec::new();
+    let mut first_code = None;
+    for ((label, _, _), result) in runs.iter().zip(results) {
+        match result.map(|status| task_outcome(&status, allowed_exit_codes)) {
+            Ok(ExecOutcome::Success) => {}
+            Ok(ExecOutcome::Failed(code)) => {
+                first_code.get_or_insert(code);
+                failed.push(format!("{} (exit {})", label, code));
+            }
+            Err(e) => {
+                first_code.get_or_insert(1);
+                failed.push(format!("{} ({})", label, e));
+            }
+        }
+    }
+    match first_code {
+        None => Ok(()),
+        Some(code) => {
+            eprintln!("Failed for {}", failed.join(", "));
+            std::process::exit(code);
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 enum StdioMode<'a> {
     Inherit,
@@ -449,8 +1744,33 @@ fn exec_task<'a>(
         stack.push(name.to_string());
 
         let result = match &task_cfg.kind {
-            TaskKind::Shell { cmd, cwd } => {
-                run_shell_task(root, name, cmd, cwd.as_deref(), args, stdio).await?
+            TaskKind::Shell {
+                cmd,
+                cwd,
+                env,
+                allowed_exit_codes,
+                pipefail,
+                inside,
+            } => {
+                let status = match inside {
+                    None => {
+                        let cmd = task_command(cmd, args, *pipefail)?;
+                        run_shell_task(root, name, &cmd, cwd.as_deref(), env, stdio).await?
+                    }
+                    Some(process) => {
+                        let cmd = container_task_command(
+                            root,
+                            process,
+                            &task_command(cmd, args, false)?,
+                            cwd.as_deref(),
+                            env,
+                            *pipefail,
+                            matches!(stdio, StdioMode::Inherit),
+                        )?;
+                        run_shell_task(root, name, &cmd, None, env, stdio).await?
+                    }
+                };
+                task_outcome(&status, allowed_exit_codes)
             }
             TaskKind::Composite { children, parallel } => {
                 if *parallel {
@@ -495,7 +1815,7 @@ fn exec_task<'a>(
                     // Sequential: run in order, stop on first failure
                     for c in children {
                         let child_abs = task::resolve_child_name(name, c);
-                        println!("▶ running {}…", task::display_task_name(&child_abs));
+                        outln!("▶ running {}…", task::display_task_name(&child_abs));
                         match exec_task(root, tasks, &child_abs, args, stack, stdio).await? {
                             ExecOutcome::Success => {}
                             ExecOutcome::Failed(code) => return Ok(ExecOutcome::Failed(code)),
@@ -511,29 +1831,105 @@ fn exec_task<'a>(
     })
 }
 
+/// Success if the task's exit code (128 plus the signal for a killed task) is allowed.
+fn task_outcome(status: &std::process::ExitStatus, allowed_exit_codes: &[i32]) -> ExecOutcome {
+    match config::check_exit(status, allowed_exit_codes) {
+        Ok(()) => ExecOutcome::Success,
+        Err(code) => ExecOutcome::Failed(code),
+    }
+}
+
+/// The shell script for a task: its `cmd` with extra arguments appended, under
+/// `set -o pipefail` if asked. `/bin/sh` runs that when it knows the option (bash, ksh, zsh,
+/// recent dash); otherwise the script is handed to bash.
+fn task_command(cmd: &str, args: &[String], pipefail: bool) -> Result<String> {
+    let mut script = cmd.to_string();
+    if !args.is_empty() {
+        script.push(' ');
+        script.push_str(&args.join(" "));
+    }
+    if !pipefail {
+        return Ok(script);
+    }
+    let script = format!("set -o pipefail\n{}", script);
+    let supports = |shell: &str| {
+        std::process::Command::new(shell)
+            .args(["-c", "set -o pipefail"])
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    };
+    if supports("sh") {
+        Ok(script)
+    } else if supports("bash") {
+        Ok(format!("exec bash -c {}", watch::shell_quote(&script)))
+    } else {
+        anyhow::bail!(
+            "pipefail = true needs a shell with `set -o pipefail`; /bin/sh lacks it and bash was not found"
+        )
+    }
+}
+
+/// The container process `name` runs in, for `exec` and tasks with `in = "name"`.
+fn process_container(root: &std::path::Path, name: &str) -> Result<container::ContainerSpec> {
+    let configs = config::load_config_from(root)?;
+    let Some(process) = configs.iter().find(|p| p.name == name) else {
+        anyhow::bail!("Unknown process '{}'", name);
+    };
+    process.container.clone().ok_or_else(|| {
+        anyhow::anyhow!(
+            "'{}' doesn't run in a container; give it a runtime and image",
+            name
+        )
+    })
+}
+
+/// A task script run in `process`'s container: `docker exec` with the task's `cwd` as the
+/// working directory there and its `env` variables passed through. `pipefail` is left to the
+/// container's shell, since the host's says nothing about it.
+fn container_task_command(
+    root: &std::path::Path,
+    process: &str,
+    script: &str,
+    cwd: Option<&str>,
+    env: &env::EnvSpec,
+    pipefail: bool,
+    interactive: bool,
+) -> Result<String> {
+    use std::io::IsTerminal;
+    let container = process_container(root, process)?;
+    let tty = interactive && std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+    let exec: Vec<String> = container
+        .exec_args(root, process, tty, cwd, env.vars.keys())
+        .iter()
+        .map(|arg| watch::shell_quote(arg))
+        .collect();
+    let script = if pipefail {
+        format!("set -o pipefail\n{}", script)
+    } else {
+        script.to_string()
+    };
+    Ok(format!(
+        "{} sh -c {}",
+        exec.join(" "),
+        watch::shell_quote(&script)
+    ))
+}
+
 async fn run_shell_task(
     root: &std::path::Path,
     name: &str,
-    cmd_str: &str,
+    final_cmd: &str,
     cwd: Option<&str>,
-    args: &[String],
+    env: &env::EnvSpec,
     stdio: StdioMode<'_>,
-) -> Result<ExecOutcome> {
+) -> Result<std::process::ExitStatus> {
     use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 
-    // Build final command string
-    let mut final_cmd = cmd_str.to_string();
-    if !args.is_empty() {
-        let extra = args.join(" ");
-        final_cmd.push(' ');
-        final_cmd.push_str(&extra);
-    }
-
-    let mut cmd = tokio::process::Command::new("sh");
-    cmd.arg("-c").arg(&final_cmd);
-
     // cwd handling
-    if let Some(cwd) = cwd {
+    let dir = if let Some(cwd) = cwd {
         let abs = if std::path::Path::new(cwd).is_absolute() {
             std::path::PathBuf::from(cwd)
         } else {
@@ -546,10 +1942,18 @@ async fn run_shell_task(
                 abs.display()
             );
         }
-        cmd.current_dir(abs);
+        abs
     } else {
-        cmd.current_dir(root);
-    }
+        root.to_path_buf()
+    };
+
+    let mut cmd = tokio::process::Command::new("sh");
+    cmd.arg("-c").arg(env.wrap_command(final_cmd, &dir));
+    env.apply(&mut cmd);
+    // Prefixed output is relayed through a pipe; inherited output reaches the terminal as is
+    let piped = matches!(stdio, StdioMode::Prefixed(_));
+    env.apply_color(&mut cmd, color::child_color(piped));
+    cmd.current_dir(dir);
 
     match stdio {
         StdioMode::Inherit => {
@@ -557,15 +1961,7 @@ async fn run_shell_task(
             cmd.stdin(Stdio::inherit());
             cmd.stdout(Stdio::inherit());
             cmd.stderr(Stdio::inherit());
-            let status = cmd.status().await?;
-            if !status.success() {
-                if let Some(code) = status.code() {
-                    return Ok(ExecOutcome::Failed(code));
-                } else {
-                    anyhow::bail!("Task terminated by signal");
-                }
-            }
-            Ok(ExecOutcome::Success)
+            Ok(cmd.status().await?)
         }
         StdioMode::Prefixed(label) => {
             use std::process::Stdio;
@@ -579,9 +1975,9 @@ async fn run_shell_task(
                 let mut reader = BufReader::new(stream).lines();
                 while let Ok(Some(line)) = reader.next_line().await {
                     if err {
-                        println!("{}[ERR] {}", prefix, line);
+                        outln!("{}[ERR] {}", prefix, line);
                     } else {
-                        println!("{}{}", prefix, line);
+                        outln!("{}{}", prefix, line);
                     }
                 }
             }
@@ -596,14 +1992,7 @@ async fn run_shell_task(
 
             let status = child.wait().await?;
             futures::future::join_all(handles).await;
-            if !status.success() {
-                if let Some(code) = status.code() {
-                    return Ok(ExecOutcome::Failed(code));
-                } else {
-                    anyhow::bail!("Task terminated by signal");
-                }
-            }
-            Ok(ExecOutcome::Success)
+            Ok(status)
         }
     }
 }
@@ -1,200 +1,2189 @@
-use crate::config::ProcessConfig;
-use crate::state::{load_state_from_root, save_state, ManagerInfo, ManagerState, ProcessInfo};
+use crate::color::HighlightRule;
+use crate::config::{LogFormat, ProcessConfig, RateLimit, RecordEnv, StdinMode};
+use crate::control::{Request, Response};
+use crate::events::{self, EventKind};
+use crate::level::{LevelFilter, LevelPatterns, LogLevel};
+use crate::output::{out, outln};
+use crate::records::RecordFilter;
+use crate::state::{
+    load_state_from_root, save_state, ManagerInfo, ManagerState, OutputStats, ProcessInfo,
+    ProcessState,
+};
 use anyhow::Result;
 use chrono::Utc;
-use futures::future::join_all;
+use futures::future::BoxFuture;
+use regex::Regex;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::fs::OpenOptions;
-use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
-use tokio::process::Command;
+use tokio::io::{AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::Child;
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 
 #[cfg(unix)]
 use nix::sys::signal::{kill, Signal};
 #[cfg(unix)]
 use nix::unistd::{getpgid, setsid, Pid};
 
+/// Shared view of a running manager: configured processes and the state persisted to
+/// state.json. Each running child is owned by its supervisor task.
+struct Manager {
+    root: PathBuf,
+    state_dir: PathBuf,
+    /// Grows when `start` finds processes added to proc.toml since the manager started
+    configs: std::sync::RwLock<Vec<ProcessConfig>>,
+    state: Mutex<ManagerState>,
+    /// Recent automatic restart times per process, for the `max_restarts` budget
+    restart_history: Mutex<HashMap<String, VecDeque<Instant>>>,
+    /// Output tallies (lines, bytes, errors, warnings) per process, kept across restarts
+    output_counts: std::sync::Mutex<HashMap<String, Arc<OutputCounts>>>,
+    /// Signalled when a process announces its `url_pattern` URL, to record it right away
+    url_found: Arc<tokio::sync::Notify>,
+    /// Open `mirror` sockets and FIFOs per process, kept across restarts
+    mirrors: std::sync::Mutex<HashMap<String, Arc<crate::mirror::Mirror>>>,
+    /// PIDs being stopped on request; their supervisors must not treat the exit as a crash
+    stopping: std::sync::Mutex<HashSet<u32>>,
+    /// PIDs of children owned by supervisor tasks; the orphan reaper leaves these alone
+    supervised: std::sync::Mutex<HashSet<u32>>,
+    /// Serializes starting `requires_project` projects
+    requires_lock: Mutex<()>,
+    /// Writable stdin of processes with `stdin = "pipe"` or `"pty"`, by name. Holding it keeps
+    /// the process from seeing end of file.
+    inputs: Mutex<HashMap<String, ProcessInput>>,
+    /// States of processes suspended by `idle_action = "suspend"`, by PID, to go back to
+    suspended: std::sync::Mutex<HashMap<u32, ProcessState>>,
+    shutting_down: AtomicBool,
+    /// Signalled once a `stop` request has shut the processes down; the manager then exits
+    stopped: tokio::sync::Notify,
+}
+
+/// The manager's end of a process's stdin: the pipe, or the pty master.
+type ProcessInput = Box<dyn tokio::io::AsyncWrite + Send + Unpin>;
+
+/// The tasks copying a process's stdout and stderr to its logs.
+type OutputTasks = Vec<JoinHandle<()>>;
+
 pub async fn run_manager_daemon(
     configs: Vec<ProcessConfig>,
-    state_dir: std::path::PathBuf,
-    root: &std::path::Path,
+    state_dir: PathBuf,
+    root: &Path,
+    selection: &[String],
+    stop_at: Option<chrono::DateTime<Utc>>,
+    idle: Option<crate::config::IdleShutdown>,
 ) -> Result<()> {
-    let mut children = Vec::new();
-    let mut handles = Vec::new();
-    let mut proc_infos: Vec<ProcessInfo> = Vec::new();
-
-    for config in configs {
-        let mut cmd = Command::new("sh");
-        cmd.arg("-c");
-        cmd.arg(&config.command);
-        if let Some(cwd) = &config.cwd {
-            let abs = if std::path::Path::new(cwd).is_absolute() {
-                std::path::PathBuf::from(cwd)
+    // An explicit selection starts exactly those processes; otherwise every autostart process.
+    // Either way, whatever they depend on is started too.
+    let wanted: Vec<String> = configs
+        .iter()
+        .filter(|c| {
+            if selection.is_empty() {
+                c.autostart
             } else {
-                root.join(cwd)
-            };
-            if !abs.exists() {
-                return Err(anyhow::anyhow!(
-                    "Process '{}' cwd does not exist: {}",
-                    config.name,
-                    abs.display()
+                selection.contains(&c.name)
+            }
+        })
+        .map(|c| c.name.clone())
+        .collect();
+    let wanted = crate::config::with_dependencies(&configs, &wanted);
+    let (to_start, stopped): (Vec<_>, Vec<_>) = configs
+        .iter()
+        .cloned()
+        .partition(|c| wanted.contains(&c.name));
+
+    // Diagnostics go to stderr, which the daemon points at manager.log
+    let _ = tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_ansi(false)
+        .try_init();
+
+    // Become the reaper for orphans left by our children (double-forking tools), so they stay
+    // visible in the process tree and exit statuses are collected here rather than by init
+    #[cfg(target_os = "linux")]
+    let _ = nix::sys::prctl::set_child_subreaper(true);
+
+    let manager = Arc::new(Manager {
+        root: root.to_path_buf(),
+        state_dir: state_dir.clone(),
+        configs: std::sync::RwLock::new(configs),
+        state: Mutex::new(ManagerState {
+            manager: ManagerInfo {
+                pid: std::process::id(),
+                started_at: Utc::now(),
+                project_root: root.to_string_lossy().to_string(),
+                project_name: crate::config::load_project_name(root).unwrap_or_default(),
+                version: 1,
+                heartbeat: Some(Utc::now()),
+                exe: std::env::current_exe()
+                    .ok()
+                    .map(|p| p.to_string_lossy().to_string()),
+                oxproc_version: Some(env!("CARGO_PKG_VERSION").into()),
+                stop_at,
+            },
+            processes: Vec::new(),
+            stopped: stopped.into_iter().map(|c| c.name).collect(),
+            pending: Vec::new(),
+        }),
+        restart_history: Mutex::new(HashMap::new()),
+        output_counts: std::sync::Mutex::new(HashMap::new()),
+        url_found: Arc::new(tokio::sync::Notify::new()),
+        mirrors: std::sync::Mutex::new(HashMap::new()),
+        stopping: std::sync::Mutex::new(HashSet::new()),
+        supervised: std::sync::Mutex::new(HashSet::new()),
+        requires_lock: Mutex::new(()),
+        inputs: Mutex::new(HashMap::new()),
+        suspended: std::sync::Mutex::new(HashMap::new()),
+        shutting_down: AtomicBool::new(false),
+        stopped: tokio::sync::Notify::new(),
+    });
+
+    for config in &to_start {
+        manager.schedule(config, config.startup_delay()).await?;
+    }
+    save_state(&state_dir, &*manager.state.lock().await)?;
+
+    tokio::spawn(manager.clone().flush_output_counts());
+    tokio::spawn(manager.clone().heartbeat());
+    tokio::spawn(manager.clone().track_descendants());
+    tokio::spawn(manager.clone().record_stats());
+    tokio::spawn(manager.clone().reap_orphans());
+    if let Some(idle) = idle {
+        tokio::spawn(manager.clone().watch_idle(idle));
+    }
+
+    let listener = crate::control::bind(&state_dir)?;
+    let control_manager = manager.clone();
+    tokio::spawn(crate::control::serve(listener, move |req| {
+        let m = control_manager.clone();
+        async move { m.handle_request(req).await }
+    }));
+
+    // Wait for a termination signal
+    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+    let mut sigint = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())?;
+
+    tokio::select! {
+        _ = sigterm.recv() => {
+            manager.shutdown(STOP_GRACE).await;
+        }
+        _ = sigint.recv() => {
+            manager.shutdown(STOP_GRACE).await;
+        }
+        _ = time_limit(stop_at) => {
+            println!("time limit reached; stopping all processes");
+            manager.shutdown(STOP_GRACE).await;
+        }
+        // A `stop` request (or `idle_timeout`) already shut everything down
+        _ = manager.stopped.notified() => {}
+    }
+    Ok(())
+}
+
+/// Resolve at `stop_at`, or never. Checks the wall clock rather than sleeping the whole time,
+/// so a laptop that slept through the deadline stops right after waking.
+async fn time_limit(stop_at: Option<chrono::DateTime<Utc>>) {
+    let Some(at) = stop_at else {
+        return std::future::pending().await;
+    };
+    while let Ok(left) = (at - Utc::now()).to_std() {
+        tokio::time::sleep(left.min(std::time::Duration::from_secs(30))).await;
+    }
+}
+
+impl Manager {
+    async fn handle_request(self: &Arc<Self>, req: Request) -> Response {
+        match req {
+            Request::Hello { version, client } => match crate::control::negotiate(version) {
+                Ok(hello) => {
+                    if let Some(client) = client {
+                        eprintln!(
+                            "control client connected: {} (protocol {})",
+                            client, hello.protocol
+                        );
+                    }
+                    Response::ok("").with_data(hello)
+                }
+                Err(e) => Response::error(e),
+            },
+            Request::Status => Response::ok("").with_data(self.process_statuses().await),
+            Request::Metrics => Response::ok("").with_data(self.process_metrics().await),
+            Request::Start { names } => self.start_on_demand(&names).await,
+            Request::Restart { names, grace, soft } => {
+                let grace = grace.map(std::time::Duration::from_secs);
+                self.restart_on_demand(&names, grace.unwrap_or(RESTART_GRACE), soft)
+                    .await
+            }
+            Request::Reload { names } => self.reload_on_demand(&names).await,
+            Request::Send { name, text } => self.send_input(&name, &text).await,
+            Request::Debug => Response::ok("").with_data(self.debug_info().await),
+            Request::Stop { grace, names } => {
+                let grace = grace.map_or(STOP_GRACE, std::time::Duration::from_secs);
+                if !names.is_empty() {
+                    return self.stop_on_demand(&names, grace).await;
+                }
+                let summary = self.shutdown(grace).await;
+                // Leave the control task a moment to deliver this reply before exiting
+                let m = self.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                    m.stopped.notify_one();
+                });
+                Response::ok(summary.lines.join("\n")).with_data(summary)
+            }
+        }
+    }
+
+    /// Stop every process: SIGTERM to each process group, SIGKILL to those still there after
+    /// `grace`, then descendants that left their group. Later calls report nothing to do.
+    async fn shutdown(&self, grace: std::time::Duration) -> crate::control::StopSummary {
+        let mut summary = crate::control::StopSummary::default();
+        if self.shutting_down.swap(true, Ordering::SeqCst) {
+            summary.lines.push("already stopping".into());
+            return summary;
+        }
+        let _ = std::fs::remove_file(crate::state::control_socket_path(&self.state_dir));
+
+        // Take the freshest view of each process's descendants before anything exits and
+        // gets reparented
+        let table = tokio::task::spawn_blocking(crate::proctree::snapshot)
+            .await
+            .unwrap_or_default();
+        let targets: Vec<(String, u32, i32, Vec<crate::proctree::ProcEntry>)> = {
+            let mut st = self.state.lock().await;
+            let mut targets = Vec::new();
+            for p in st.processes.iter_mut().filter(|p| p.state.is_live()) {
+                p.state = ProcessState::Stopping;
+                let current = crate::proctree::descendants(&table, p.pid);
+                let tree = crate::proctree::merge_recorded(&table, current, &p.descendants);
+                targets.push((p.name.clone(), p.pid, p.pgid, tree));
+            }
+            let _ = save_state(&self.state_dir, &st);
+            // Remember what was up, for `oxproc resume`
+            let up = targets
+                .iter()
+                .map(|t| t.0.clone())
+                .chain(st.pending.clone());
+            let _ = crate::session::save(&self.state_dir, up.collect());
+            targets
+        };
+        summary.processes = targets.len();
+
+        for (name, pid, pgid, tree) in &targets {
+            let sent = kill(Pid::from_raw(-pgid), Signal::SIGTERM);
+            // A suspended process only acts on the signal once continued
+            let _ = kill(Pid::from_raw(-pgid), Signal::SIGCONT);
+            match sent {
+                Ok(_) => summary.lines.push(format!(
+                    "sent SIGTERM to {} (pid {}, pgid {})",
+                    name, pid, pgid
+                )),
+                Err(e) => summary
+                    .lines
+                    .push(format!("{} already stopped or cannot signal ({})", name, e)),
+            }
+            for e in crate::proctree::escaped(tree, *pgid) {
+                let _ = kill(Pid::from_raw(e.pid as i32), Signal::SIGTERM);
+            }
+        }
+        // Move on as soon as every group is gone rather than always waiting out the grace period
+        let began = Instant::now();
+        let mut waiting: Vec<_> = targets.iter().collect();
+        loop {
+            waiting.retain(|(name, _, pgid, _)| {
+                if group_alive(*pgid) {
+                    return true;
+                }
+                summary.lines.push(format!(
+                    "{} exited after {}",
+                    name,
+                    format_elapsed(began.elapsed())
+                ));
+                false
+            });
+            if waiting.is_empty() || began.elapsed() >= grace {
+                break;
+            }
+            tokio::time::sleep(STOP_POLL).await;
+        }
+        let mut killed = Vec::new();
+        for (name, _, pgid, _) in waiting {
+            let _ = kill(Pid::from_raw(-pgid), Signal::SIGKILL);
+            summary.lines.push(format!(
+                "escalated SIGKILL to {} (pgid {}) after {}",
+                name,
+                pgid,
+                format_elapsed(began.elapsed())
+            ));
+            summary.sigkilled += 1;
+            killed.push(name.clone());
+        }
+        // Killing `docker run` leaves its container running
+        self.remove_containers(&killed).await;
+        // Group signals can't reach descendants that moved to another process group; kill any
+        // still hanging on (the usual cause of "port still in use" after a stop)
+        for (name, _, pgid, tree) in &targets {
+            for e in crate::proctree::kill_escaped(tree, *pgid) {
+                summary.lines.push(format!(
+                    "killed {} (pid {}, pgid {}) spawned by {}, outside its process group",
+                    e.name, e.pid, e.pgid, name
                 ));
+                summary.escaped += 1;
             }
-            cmd.current_dir(abs);
         }
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
 
-        // Each child gets its own session/PGID
-        unsafe {
-            cmd.pre_exec(|| {
-                // SAFETY: called in child just before exec
-                match setsid() {
-                    Ok(_) => Ok(()),
-                    Err(e) => Err(std::io::Error::other(format!("setsid failed: {}", e))),
+        let mut st = self.state.lock().await;
+        for p in st.processes.iter_mut().filter(|p| p.state.is_live()) {
+            p.state = ProcessState::Exited;
+        }
+        let _ = save_state(&self.state_dir, &st);
+        drop(st);
+        events::record(
+            &self.state_dir,
+            EventKind::Exited,
+            None,
+            format!("manager stopped {} process(es)", summary.processes),
+        );
+        summary
+    }
+
+    /// Remove the containers (and stop the compose services) of processes whose runtime
+    /// client was killed before it could stop them.
+    async fn remove_containers(&self, names: &[String]) {
+        let configs: Vec<ProcessConfig> = self
+            .configs
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|c| names.contains(&c.name))
+            .cloned()
+            .collect();
+        for config in configs {
+            if let Some(container) = &config.container {
+                container.remove(&self.root, &config.name).await;
+            }
+            if let Some(crate::builtin::Builtin::Compose(spec)) = &config.builtin {
+                spec.stop().await;
+            }
+        }
+    }
+
+    /// Memory use and buffer levels, for `debug state`.
+    async fn debug_info(&self) -> crate::control::ManagerDebug {
+        let counts = self.output_counts.lock().unwrap().clone();
+        let inputs = self.inputs.lock().await;
+        let mut processes: Vec<crate::control::ProcessBuffers> = counts
+            .iter()
+            .map(|(name, c)| crate::control::ProcessBuffers {
+                name: name.clone(),
+                buffered_bytes: c.buffered.load(Ordering::Relaxed),
+                split_lines: c.split_lines.load(Ordering::Relaxed),
+                stdin_open: inputs.contains_key(name),
+            })
+            .collect();
+        processes.sort_by(|a, b| a.name.cmp(&b.name));
+        crate::control::ManagerDebug {
+            rss_bytes: resident_memory(),
+            max_line_bytes: crate::lines::MAX_LINE_BYTES as u64,
+            processes,
+        }
+    }
+
+    /// Output statistics of every process the manager has started, for `metrics`.
+    async fn process_metrics(&self) -> Vec<crate::control::ProcessMetrics> {
+        let st = self.state.lock().await;
+        st.processes
+            .iter()
+            .map(|p| crate::control::ProcessMetrics {
+                name: p.name.clone(),
+                output: p.output.clone(),
+            })
+            .collect()
+    }
+
+    /// Every configured process in the shape of the control protocol's `status` result.
+    async fn process_statuses(&self) -> Vec<crate::control::ProcessStatus> {
+        use crate::control::ProcessStatus;
+
+        let st = self.state.lock().await;
+        self.configs()
+            .iter()
+            .map(|c| {
+                let running = st.processes.iter().find(|p| p.name == c.name);
+                let (state, pid, restarts) = if st.pending.contains(&c.name) {
+                    (ProcessState::Pending, None, 0)
+                } else if let Some(p) = running.filter(|_| !st.stopped.contains(&c.name)) {
+                    (p.state, Some(p.pid), p.restarts)
+                } else {
+                    (ProcessState::Stopped, None, 0)
+                };
+                ProcessStatus {
+                    name: c.name.clone(),
+                    state,
+                    pid,
+                    restarts,
+                }
+            })
+            .collect()
+    }
+
+    /// Spawn a configured process, record it in state.json and hand the child to a
+    /// supervisor task. `restarts` carries the automatic restart count across instances.
+    /// Returns the new PID.
+    async fn launch(self: &Arc<Self>, config: &ProcessConfig, restarts: u32) -> Result<u32> {
+        let counts = self
+            .output_counts
+            .lock()
+            .unwrap()
+            .entry(config.name.clone())
+            .or_insert_with(|| {
+                Arc::new(OutputCounts {
+                    url_found: self.url_found.clone(),
+                    ..Default::default()
+                })
+            })
+            .clone();
+        let mirror = self.mirror(config)?;
+        let (child, mut info, input, output) = {
+            // Hold the set across the spawn so the reaper can't collect a child that exits
+            // before it is registered
+            let mut supervised = self.supervised.lock().unwrap();
+            let spawned = spawn_process(config, &self.root, counts, mirror)?;
+            supervised.insert(spawned.1.pid);
+            spawned
+        };
+        {
+            let mut inputs = self.inputs.lock().await;
+            match input {
+                Some(input) => inputs.insert(config.name.clone(), input),
+                None => inputs.remove(&config.name),
+            };
+        }
+        info.restarts = restarts;
+        let pid = info.pid;
+        let info_starting = info.state == ProcessState::Starting;
+        {
+            let mut st = self.state.lock().await;
+            st.processes.retain(|p| p.name != config.name);
+            st.processes.push(info);
+            st.stopped.retain(|n| n != &config.name);
+            st.pending.retain(|n| n != &config.name);
+            save_state(&self.state_dir, &st)?;
+        }
+        tokio::spawn(self.clone().supervise(config.clone(), child, output));
+        if info_starting {
+            tokio::spawn(self.clone().watch_startup(config.clone(), pid));
+        }
+        Ok(pid)
+    }
+
+    /// The process's `mirror`, opened the first time it starts. A socket that moved in the
+    /// config since is opened anew.
+    fn mirror(&self, config: &ProcessConfig) -> Result<Option<Arc<crate::mirror::Mirror>>> {
+        let mut mirrors = self.mirrors.lock().unwrap();
+        let Some(target) = &config.mirror else {
+            mirrors.remove(&config.name);
+            return Ok(None);
+        };
+        if let Some(mirror) = mirrors.get(&config.name).filter(|m| m.target() == target) {
+            return Ok(Some(mirror.clone()));
+        }
+        let mirror = crate::mirror::Mirror::open(target).map_err(|e| {
+            anyhow::anyhow!(
+                "Process '{}' mirror {}: {}",
+                config.name,
+                target.path().display(),
+                e
+            )
+        })?;
+        let mirror = Arc::new(mirror);
+        mirrors.insert(config.name.clone(), mirror.clone());
+        Ok(Some(mirror))
+    }
+
+    /// Move a starting instance on once it has been up for `min_uptime` and its `ready`
+    /// check passes: to `ready`, or to `running` when it has no check. Past `start_timeout`
+    /// it is marked failed instead (and killed with `kill_on_start_timeout`).
+    async fn watch_startup(self: Arc<Self>, config: ProcessConfig, pid: u32) {
+        let spawned = Instant::now();
+        loop {
+            tokio::time::sleep(crate::power::scale(READY_POLL)).await;
+            let starting = {
+                let st = self.state.lock().await;
+                st.processes.iter().any(|p| {
+                    p.name == config.name && p.pid == pid && p.state == ProcessState::Starting
+                })
+            };
+            if !starting {
+                return;
+            }
+            if let Some(timeout) = config.start_timeout.filter(|t| spawned.elapsed() >= *t) {
+                self.start_timed_out(&config, pid, timeout).await;
+                return;
+            }
+            if config.min_uptime.is_some_and(|m| spawned.elapsed() < m) {
+                continue;
+            }
+            let Some(check) = config.ready.clone() else {
+                self.set_state(&config.name, pid, ProcessState::Running)
+                    .await;
+                return;
+            };
+            if check.passes(&config, &self.root).await {
+                self.set_state(&config.name, pid, ProcessState::Ready).await;
+                return;
+            }
+        }
+    }
+
+    /// Give up on an instance that did not become ready within `start_timeout`.
+    async fn start_timed_out(
+        &self,
+        config: &ProcessConfig,
+        pid: u32,
+        timeout: std::time::Duration,
+    ) {
+        let reason = match &config.ready {
+            Some(check) => format!("ready check {} did not pass within {:?}", check, timeout),
+            None => format!("not up for min_uptime within {:?}", timeout),
+        };
+        self.fail(&config.name, pid, reason.clone()).await;
+        if config.kill_on_start_timeout {
+            let pgid = {
+                let st = self.state.lock().await;
+                st.processes
+                    .iter()
+                    .find(|p| p.name == config.name && p.pid == pid)
+                    .map(|p| p.pgid)
+            };
+            if let Some(pgid) = pgid {
+                // Not a crash: keep the supervisor from restarting it
+                self.stopping.lock().unwrap().insert(pid);
+                terminate_group(pid, pgid, RESTART_GRACE).await;
+            }
+        }
+        events::record(
+            &self.state_dir,
+            EventKind::Failed,
+            Some(&config.name),
+            reason,
+        );
+    }
+
+    /// Record a state change for the instance `pid` of `name`, if it is still the current one.
+    async fn set_state(&self, name: &str, pid: u32, state: ProcessState) {
+        let mut st = self.state.lock().await;
+        if let Some(p) = st
+            .processes
+            .iter_mut()
+            .find(|p| p.name == name && p.pid == pid)
+        {
+            p.state = state;
+            let _ = save_state(&self.state_dir, &st);
+        }
+    }
+
+    /// Mark the instance `pid` of `name` failed, with the reason `status` shows.
+    async fn fail(&self, name: &str, pid: u32, reason: String) {
+        let mut st = self.state.lock().await;
+        if let Some(p) = st
+            .processes
+            .iter_mut()
+            .find(|p| p.name == name && p.pid == pid)
+        {
+            p.state = ProcessState::Failed;
+            p.failure = Some(reason);
+            let _ = save_state(&self.state_dir, &st);
+        }
+    }
+
+    /// Save a crash dump for a process that ran out of restarts. The environment is the one it
+    /// was launched with if recorded, else resolved again, with likely credentials redacted
+    /// unless `record_env = "full"`.
+    async fn save_crash(
+        &self,
+        config: &ProcessConfig,
+        pid: u32,
+        reason: &str,
+    ) -> Option<std::path::PathBuf> {
+        let (started_at, launch) = {
+            let st = self.state.lock().await;
+            let p = st.processes.iter().find(|p| p.pid == pid)?;
+            (p.started_at, p.launch.clone())
+        };
+        let command = config.command_line(&self.root);
+        let env = match launch.as_ref().and_then(|l| l.env.clone()) {
+            Some(env) => env,
+            None => {
+                let mut env = config
+                    .env
+                    .resolved(&config.shell_command(&self.root, &command));
+                if config.record_env != RecordEnv::Full {
+                    crate::env::redact(&mut env);
+                }
+                env
+            }
+        };
+        let (stdout_log, stderr_log) = config.log_paths(&self.root);
+        let crash = crate::crash::Crash {
+            name: config.name.clone(),
+            pid,
+            started_at,
+            reason: reason.to_string(),
+            command: launch.map(|l| l.command).unwrap_or(command),
+            cwd: config.working_dir(&self.root),
+            stdout_log,
+            stderr_log,
+            env,
+        };
+        match crate::crash::save(&self.state_dir, &crash) {
+            Ok(dump) => Some(dump),
+            Err(e) => {
+                eprintln!("failed to save crash dump for {}: {}", config.name, e);
+                None
+            }
+        }
+    }
+
+    /// Mark the end of a run in the process's logs: how it ended and how long it was up.
+    async fn write_exit_banner(
+        &self,
+        config: &ProcessConfig,
+        pid: u32,
+        status: &std::io::Result<std::process::ExitStatus>,
+        stopped: bool,
+    ) {
+        use std::os::unix::process::ExitStatusExt;
+        let started_at = {
+            let st = self.state.lock().await;
+            st.processes
+                .iter()
+                .find(|p| p.pid == pid)
+                .map(|p| p.started_at)
+        };
+        let mut text = format!("{} pid {}", if stopped { "stopped" } else { "exited" }, pid);
+        match status {
+            Ok(s) => match (s.code(), s.signal()) {
+                (Some(code), _) => text.push_str(&format!(" with exit code {}", code)),
+                (None, Some(signal)) => text.push_str(&format!(" on signal {}", signal)),
+                (None, None) => {}
+            },
+            Err(e) => text.push_str(&format!(" ({})", e)),
+        }
+        if let Some(started_at) = started_at {
+            let uptime = (Utc::now() - started_at).to_std().unwrap_or_default();
+            text.push_str(&format!(" after {:.1}s", uptime.as_secs_f64()));
+        }
+        if let Some(line) = banner(config, &text) {
+            write_banner(config, &self.root, &line).await;
+        }
+    }
+
+    /// Wait for a child to exit (reaping it) and apply the restart budget.
+    // Boxed because restarting launches a new supervisor (recursive future).
+    fn supervise(
+        self: Arc<Self>,
+        config: ProcessConfig,
+        child: Child,
+        output: OutputTasks,
+    ) -> BoxFuture<'static, ()> {
+        Box::pin(self.supervise_inner(config, child, output))
+    }
+
+    async fn supervise_inner(
+        self: Arc<Self>,
+        config: ProcessConfig,
+        mut child: Child,
+        output: OutputTasks,
+    ) {
+        let pid = child.id().unwrap_or_default();
+        let status = child.wait().await;
+        self.supervised.lock().unwrap().remove(&pid);
+        // Let the logs take what the process wrote last, so the exit banner comes after it. A
+        // descendant still holding the pipes open doesn't hold the banner up for long.
+        let _ = tokio::time::timeout(OUTPUT_DRAIN, futures::future::join_all(output)).await;
+        let stopped = self.shutting_down.load(Ordering::SeqCst)
+            || self.stopping.lock().unwrap().contains(&pid);
+        self.write_exit_banner(&config, pid, &status, stopped).await;
+        let sigkilled = status
+            .as_ref()
+            .ok()
+            .and_then(crate::config::shell_exit_code)
+            == Some(128 + Signal::SIGKILL as i32);
+        if sigkilled {
+            self.remove_containers(std::slice::from_ref(&config.name))
+                .await;
+        }
+        if self.shutting_down.load(Ordering::SeqCst) {
+            return;
+        }
+        if self.stopping.lock().unwrap().remove(&pid) {
+            return;
+        }
+        let (code, success) = match &status {
+            Ok(s) => (
+                s.code(),
+                crate::config::check_exit(s, &config.allowed_exit_codes).is_ok(),
+            ),
+            Err(_) => (None, false),
+        };
+        let (prior_restarts, started_at, pids) = {
+            let st = self.state.lock().await;
+            match st.processes.iter().find(|p| p.name == config.name) {
+                // Replaced by a newer instance (e.g. started again on demand)
+                Some(p) if p.pid != pid => return,
+                Some(p) => (
+                    p.restarts,
+                    p.started_at,
+                    std::iter::once(p.pid)
+                        .chain(p.descendants.iter().map(|d| d.pid))
+                        .collect::<Vec<u32>>(),
+                ),
+                None => return,
+            }
+        };
+        let mut how = code
+            .map(|c| format!("exit code {}", c))
+            .unwrap_or_else(|| "terminated by signal".to_string());
+        if config.core_dumps && status.as_ref().is_ok_and(crate::cores::may_have_dumped) {
+            let cwd = config.working_dir(&self.root);
+            if let Some(core) =
+                crate::cores::collect(&self.state_dir, &config.name, &cwd, &pids, started_at)
+            {
+                how.push_str(&format!(", core dumped to {}", core.display()));
+            }
+        }
+        // Exiting before `min_uptime` is a failed start, even with status 0
+        let uptime = (Utc::now() - started_at).to_std().unwrap_or_default();
+        let failed_start = config.min_uptime.is_some_and(|m| uptime < m);
+        self.set_state(&config.name, pid, ProcessState::Exited)
+            .await;
+        if failed_start {
+            events::record(
+                &self.state_dir,
+                EventKind::Failed,
+                Some(&config.name),
+                format!(
+                    "pid {} exited ({}) after {:.1}s, within min_uptime; start failed",
+                    pid,
+                    how,
+                    uptime.as_secs_f64()
+                ),
+            );
+        } else {
+            events::record(
+                &self.state_dir,
+                EventKind::Exited,
+                Some(&config.name),
+                format!("pid {} exited ({})", pid, how),
+            );
+        }
+
+        // Processes with `restart = "never"` have no restart budget
+        let Some(max_restarts) = config.max_restarts else {
+            if failed_start {
+                self.fail(&config.name, pid, "exited within min_uptime".into())
+                    .await;
+            }
+            return;
+        };
+        // A clean exit is only restarted with `restart = "always"`
+        if !config.restart.restarts_after(success && !failed_start) {
+            return;
+        }
+        let attempts = {
+            let mut history = self.restart_history.lock().await;
+            let recent = history.entry(config.name.clone()).or_default();
+            let now = Instant::now();
+            while recent
+                .front()
+                .is_some_and(|t| now.duration_since(*t) > config.restart_window)
+            {
+                recent.pop_front();
+            }
+            if recent.len() as u32 >= max_restarts {
+                None
+            } else {
+                recent.push_back(now);
+                Some(recent.len())
+            }
+        };
+        let Some(attempt) = attempts else {
+            let reason = format!(
+                "exceeded {} restart(s) within {:?}",
+                max_restarts, config.restart_window
+            );
+            let dump = self
+                .save_crash(&config, pid, &reason)
+                .await
+                .map(|d| format!(" (diagnostics in {})", d.display()))
+                .unwrap_or_default();
+            self.fail(&config.name, pid, reason.clone()).await;
+            events::record(
+                &self.state_dir,
+                EventKind::Failed,
+                Some(&config.name),
+                format!("{}; giving up{}", reason, dump),
+            );
+            return;
+        };
+
+        self.set_state(&config.name, pid, ProcessState::Restarting)
+            .await;
+        // Back off as restarts pile up within the window: 1s, 2s, 4s, … up to 30s
+        let backoff = (attempt.saturating_sub(1)).min(5) as u32;
+        tokio::time::sleep(
+            RESTART_DELAY
+                .saturating_mul(1 << backoff)
+                .min(MAX_RESTART_DELAY),
+        )
+        .await;
+        if self.shutting_down.load(Ordering::SeqCst) {
+            return;
+        }
+        match self.launch(&config, prior_restarts + 1).await {
+            Ok(new_pid) => {
+                events::record(
+                    &self.state_dir,
+                    EventKind::Restarted,
+                    Some(&config.name),
+                    format!(
+                        "restarted as pid {} (attempt {}/{} in window)",
+                        new_pid, attempt, max_restarts
+                    ),
+                );
+            }
+            Err(e) => {
+                let reason = format!("restart failed: {}", e);
+                self.fail(&config.name, pid, reason.clone()).await;
+                events::record(
+                    &self.state_dir,
+                    EventKind::Failed,
+                    Some(&config.name),
+                    reason,
+                )
+            }
+        }
+    }
+
+    /// Refresh the heartbeat in state.json so `status` can tell a hung manager from a live one.
+    async fn heartbeat(self: Arc<Self>) {
+        let mut ticker = tokio::time::interval(crate::state::HEARTBEAT_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let mut st = self.state.lock().await;
+            st.manager.heartbeat = Some(Utc::now());
+            let _ = save_state(&self.state_dir, &st);
+        }
+    }
+
+    /// Periodically copy the output tallies into state.json, along with line and byte rates
+    /// over the last interval.
+    async fn flush_output_counts(self: Arc<Self>) {
+        let mut last_tick = Instant::now();
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(crate::power::scale(OUTPUT_STATS_INTERVAL)) => {}
+                _ = self.url_found.notified() => {}
+            }
+            let elapsed = last_tick.elapsed().as_secs_f64().max(0.001);
+            last_tick = Instant::now();
+            let counts = self.output_counts.lock().unwrap().clone();
+            let mut st = self.state.lock().await;
+            let mut changed = false;
+            for p in st.processes.iter_mut() {
+                let Some(c) = counts.get(&p.name) else {
+                    continue;
+                };
+                let (errors, warnings) = (
+                    c.errors.load(Ordering::Relaxed),
+                    c.warnings.load(Ordering::Relaxed),
+                );
+                let (lines, bytes) = (
+                    c.lines.load(Ordering::Relaxed),
+                    c.bytes.load(Ordering::Relaxed),
+                );
+                let output = OutputStats {
+                    lines,
+                    bytes,
+                    lines_per_sec: lines.saturating_sub(p.output.lines) as f64 / elapsed,
+                    bytes_per_sec: bytes.saturating_sub(p.output.bytes) as f64 / elapsed,
+                };
+                let url = match &*c.url.lock().unwrap() {
+                    Some((pid, url)) if *pid == p.pid => Some(url.clone()),
+                    _ => None,
+                };
+                if p.errors != errors
+                    || p.warnings != warnings
+                    || p.output != output
+                    || p.url != url
+                {
+                    p.errors = errors;
+                    p.warnings = warnings;
+                    p.output = output;
+                    p.url = url;
+                    changed = true;
+                }
+            }
+            if changed {
+                let _ = save_state(&self.state_dir, &st);
+            }
+        }
+    }
+
+    /// Periodically record each process's descendant tree in state.json. Entries recorded
+    /// earlier stay listed while they live, so a grandchild that double-forked away from its
+    /// parent is still attributed to the process that spawned it.
+    async fn track_descendants(self: Arc<Self>) {
+        loop {
+            tokio::time::sleep(crate::power::scale(DESCENDANTS_INTERVAL)).await;
+            let Ok(table) = tokio::task::spawn_blocking(crate::proctree::snapshot).await else {
+                continue;
+            };
+            let containers: HashSet<String> = self
+                .configs
+                .read()
+                .unwrap()
+                .iter()
+                .filter(|c| c.container.is_some())
+                .map(|c| c.name.clone())
+                .collect();
+            let mut st = self.state.lock().await;
+            let mut changed = false;
+            for p in st.processes.iter_mut() {
+                let current = crate::proctree::descendants(&table, p.pid);
+                let merged = crate::proctree::merge_recorded(&table, current, &p.descendants);
+                if merged != p.descendants {
+                    p.descendants = merged;
+                    changed = true;
+                }
+                if p.container.is_none() && containers.contains(&p.name) && p.state.is_live() {
+                    p.container = crate::container::container_id(&self.root, &p.name);
+                    changed |= p.container.is_some();
+                }
+            }
+            if changed {
+                let _ = save_state(&self.state_dir, &st);
+            }
+        }
+    }
+
+    /// Sample each live process's CPU share and resident memory, descendants included, into
+    /// the state dir's stats history. A process's first sample after a (re)start only sets
+    /// the baseline for its CPU share.
+    async fn record_stats(self: Arc<Self>) {
+        let mut history = crate::stats::History::default();
+        let mut last: HashMap<String, (u32, Instant, std::time::Duration)> = HashMap::new();
+        loop {
+            tokio::time::sleep(crate::power::scale(crate::stats::SAMPLE_INTERVAL)).await;
+            let live: Vec<(String, u32, Vec<u32>)> = {
+                let st = self.state.lock().await;
+                st.processes
+                    .iter()
+                    .filter(|p| p.state.is_live())
+                    .map(|p| {
+                        let pids = std::iter::once(p.pid)
+                            .chain(p.descendants.iter().map(|d| d.pid))
+                            .collect();
+                        (p.name.clone(), p.pid, pids)
+                    })
+                    .collect()
+            };
+            let Ok(measured) = tokio::task::spawn_blocking(move || {
+                live.into_iter()
+                    .map(|(name, pid, pids)| {
+                        let cpu = crate::proctree::cpu_time(&pids);
+                        (name, pid, cpu, crate::proctree::resident_memory(&pids))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .await
+            else {
+                continue;
+            };
+            let now = Instant::now();
+            for (name, pid, cpu, rss_bytes) in measured {
+                let previous = last.insert(name.clone(), (pid, now, cpu));
+                let Some((_, at, before)) = previous.filter(|(p, _, _)| *p == pid) else {
+                    continue;
+                };
+                let elapsed = now.duration_since(at).as_secs_f64().max(f64::EPSILON);
+                history.push(
+                    &name,
+                    crate::stats::Sample {
+                        at: Utc::now(),
+                        cpu_percent: cpu.saturating_sub(before).as_secs_f64() / elapsed * 100.0,
+                        rss_bytes,
+                    },
+                );
+            }
+            let _ = crate::stats::save(&self.state_dir, &history);
+        }
+    }
+
+    /// Collect exit statuses of orphaned grandchildren adopted as subreaper. Without this they
+    /// would linger as zombies for the manager's lifetime.
+    ///
+    /// Supervised processes start their own sessions, so whatever descends from them is in
+    /// another process group than the manager. Children in the manager's own group are its
+    /// helpers (ready probes, `requires_project` starts, `docker rm`), whose statuses belong to
+    /// the code waiting on them.
+    async fn reap_orphans(self: Arc<Self>) {
+        use nix::sys::wait::{waitpid, WaitPidFlag};
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut sigchld = signal(SignalKind::child()).ok();
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(5));
+        let me = std::process::id();
+        let own_group = nix::unistd::getpgrp().as_raw();
+        loop {
+            match sigchld.as_mut() {
+                Some(sig) => {
+                    tokio::select! {
+                        _ = sig.recv() => {}
+                        _ = ticker.tick() => {}
+                    }
+                }
+                None => {
+                    ticker.tick().await;
+                }
+            }
+            let Ok(table) = tokio::task::spawn_blocking(crate::proctree::snapshot).await else {
+                continue;
+            };
+            // Never waitpid(-1): that would steal statuses from the supervisors
+            let supervised = self.supervised.lock().unwrap();
+            for p in table.iter().filter(|p| p.ppid == me && p.pgid != own_group) {
+                if !supervised.contains(&p.pid) {
+                    let _ = waitpid(Pid::from_raw(p.pid as i32), Some(WaitPidFlag::WNOHANG));
+                }
+            }
+        }
+    }
+
+    /// Launch a process now, or, if it has dependencies or a delay, list it as pending and
+    /// launch it in the background once its dependencies are ready and the delay has passed.
+    /// Returns the PID when launched right away.
+    async fn schedule(
+        self: &Arc<Self>,
+        config: &ProcessConfig,
+        delay: std::time::Duration,
+    ) -> Result<Option<u32>> {
+        if delay.is_zero() && config.depends_on.is_empty() && config.requires_project.is_empty() {
+            return self.launch(config, 0).await.map(Some);
+        }
+        {
+            let mut st = self.state.lock().await;
+            if !st.pending.contains(&config.name) {
+                st.pending.push(config.name.clone());
+            }
+            st.stopped.retain(|n| n != &config.name);
+            save_state(&self.state_dir, &st)?;
+        }
+        let m = self.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            if let Err(e) = m.start_required_projects(&config).await {
+                m.give_up(&config.name, format!("not started: {:#}", e))
+                    .await;
+                return;
+            }
+            if let Err(dep) = m.wait_for_dependencies(&config).await {
+                m.give_up(
+                    &config.name,
+                    format!("not started: dependency '{}' is down", dep),
+                )
+                .await;
+                return;
+            }
+            tokio::time::sleep(delay).await;
+            // Stopped on request while waiting
+            if !m.state.lock().await.pending.contains(&config.name) {
+                return;
+            }
+            if m.is_running(&config.name).await {
+                return;
+            }
+            if let Err(e) = m.launch(&config, 0).await {
+                eprintln!("failed to start {}: {}", config.name, e);
+            }
+        });
+        Ok(None)
+    }
+
+    /// Move a pending process to stopped and record why it was not started.
+    async fn give_up(&self, name: &str, reason: String) {
+        let mut st = self.state.lock().await;
+        st.pending.retain(|n| n != name);
+        if !st.stopped.iter().any(|n| n == name) {
+            st.stopped.push(name.to_string());
+        }
+        let _ = save_state(&self.state_dir, &st);
+        drop(st);
+        events::record(&self.state_dir, EventKind::Failed, Some(name), reason);
+    }
+
+    /// Start the other projects `config` requires, one at a time so two processes requiring
+    /// the same project don't both try to start it.
+    async fn start_required_projects(&self, config: &ProcessConfig) -> Result<()> {
+        let _guard = self.requires_lock.lock().await;
+        for project in &config.requires_project {
+            let path = project.clone();
+            let started =
+                tokio::task::spawn_blocking(move || crate::requires::ensure_started(&path))
+                    .await??;
+            if started {
+                events::record(
+                    &self.state_dir,
+                    EventKind::Started,
+                    Some(&config.name),
+                    format!("started required project {}", project.display()),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait until every dependency of `config` is ready. Fails with the name of a dependency
+    /// that will not come up (stopped, or out of restarts).
+    async fn wait_for_dependencies(&self, config: &ProcessConfig) -> Result<(), String> {
+        for dep in &config.depends_on {
+            loop {
+                if self.shutting_down.load(Ordering::SeqCst) {
+                    return Err(dep.clone());
+                }
+                if self.is_ready(dep).await {
+                    break;
+                }
+                {
+                    let st = self.state.lock().await;
+                    let failed = st
+                        .processes
+                        .iter()
+                        .any(|p| &p.name == dep && p.state == ProcessState::Failed);
+                    if failed || st.stopped.contains(dep) {
+                        return Err(dep.clone());
+                    }
+                }
+                tokio::time::sleep(crate::power::scale(READY_POLL)).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Running, and past its readiness check if it has one.
+    async fn is_ready(&self, name: &str) -> bool {
+        let st = self.state.lock().await;
+        st.processes.iter().any(|p| {
+            p.name == name && matches!(p.state, ProcessState::Ready | ProcessState::Running)
+        })
+    }
+
+    /// Stop or suspend the formation once no process has printed a line or used more than
+    /// [`IDLE_CPU_SHARE`] of a CPU for `idle.timeout`. Suspended processes don't count as idle
+    /// time; the clock starts again once they are resumed.
+    async fn watch_idle(self: Arc<Self>, idle: crate::config::IdleShutdown) {
+        let interval = idle.timeout.min(IDLE_CHECK_INTERVAL);
+        let mut last = self.activity().await;
+        let mut idle_since = Instant::now();
+        loop {
+            tokio::time::sleep(interval).await;
+            let now = self.activity().await;
+            let cpu = now.1.saturating_sub(last.1);
+            let busy =
+                now.0 != last.0 || cpu.as_secs_f64() > interval.as_secs_f64() * IDLE_CPU_SHARE;
+            last = now;
+            if busy || !self.suspended.lock().unwrap().is_empty() {
+                idle_since = Instant::now();
+                continue;
+            }
+            if idle_since.elapsed() < idle.timeout {
+                continue;
+            }
+            let quiet = crate::state::format_ago(idle.timeout);
+            match idle.action {
+                crate::config::IdleAction::Stop => {
+                    events::record(
+                        &self.state_dir,
+                        EventKind::Idle,
+                        None,
+                        format!("no output or CPU use for {}; stopping", quiet),
+                    );
+                    self.shutdown(STOP_GRACE).await;
+                    self.stopped.notify_one();
+                    return;
+                }
+                crate::config::IdleAction::Suspend => {
+                    let names = self.suspend().await;
+                    events::record(
+                        &self.state_dir,
+                        EventKind::Idle,
+                        None,
+                        format!(
+                            "no output or CPU use for {}; suspended {}",
+                            quiet,
+                            names.join(", ")
+                        ),
+                    );
+                    idle_since = Instant::now();
+                }
+            }
+        }
+    }
+
+    /// Lines printed so far by every process, and the CPU time used by the live ones and
+    /// their descendants.
+    async fn activity(&self) -> (u64, std::time::Duration) {
+        let lines = self
+            .output_counts
+            .lock()
+            .unwrap()
+            .values()
+            .map(|c| c.lines.load(Ordering::Relaxed))
+            .sum();
+        let pids: Vec<u32> = {
+            let st = self.state.lock().await;
+            st.processes
+                .iter()
+                .filter(|p| p.state.is_live())
+                .flat_map(|p| std::iter::once(p.pid).chain(p.descendants.iter().map(|d| d.pid)))
+                .collect()
+        };
+        let cpu = tokio::task::spawn_blocking(move || crate::proctree::cpu_time(&pids))
+            .await
+            .unwrap_or_default();
+        (lines, cpu)
+    }
+
+    /// SIGSTOP every running process group, remembering the states to go back to. Returns
+    /// the names of the processes suspended.
+    async fn suspend(&self) -> Vec<String> {
+        let mut st = self.state.lock().await;
+        let mut suspended = self.suspended.lock().unwrap();
+        let mut names = Vec::new();
+        for p in st.processes.iter_mut().filter(|p| {
+            matches!(
+                p.state,
+                ProcessState::Starting | ProcessState::Ready | ProcessState::Running
+            )
+        }) {
+            if kill(Pid::from_raw(-p.pgid), Signal::SIGSTOP).is_ok() {
+                suspended.insert(p.pid, p.state);
+                p.state = ProcessState::Suspended;
+                names.push(p.name.clone());
+            }
+        }
+        let _ = save_state(&self.state_dir, &st);
+        names
+    }
+
+    /// SIGCONT every suspended process group and put back its state. Returns the names of the
+    /// processes resumed.
+    async fn resume(&self) -> Vec<String> {
+        let mut st = self.state.lock().await;
+        let suspended = std::mem::take(&mut *self.suspended.lock().unwrap());
+        if suspended.is_empty() {
+            return Vec::new();
+        }
+        let mut names = Vec::new();
+        for p in st.processes.iter_mut() {
+            if let Some(state) = suspended.get(&p.pid) {
+                let _ = kill(Pid::from_raw(-p.pgid), Signal::SIGCONT);
+                if p.state == ProcessState::Suspended {
+                    p.state = *state;
+                }
+                names.push(p.name.clone());
+            }
+        }
+        let _ = save_state(&self.state_dir, &st);
+        drop(st);
+        events::record(
+            &self.state_dir,
+            EventKind::Resumed,
+            None,
+            format!("resumed {}", names.join(", ")),
+        );
+        names
+    }
+
+    async fn running_pid(&self, name: &str) -> Option<u32> {
+        let st = self.state.lock().await;
+        st.processes
+            .iter()
+            .find(|p| p.name == name && p.state.is_live())
+            .map(|p| p.pid)
+    }
+
+    async fn is_running(&self, name: &str) -> bool {
+        self.running_pid(name).await.is_some()
+    }
+
+    /// The configured processes, as of the last `start` that looked for new ones.
+    fn configs(&self) -> Vec<ProcessConfig> {
+        self.configs.read().unwrap().clone()
+    }
+
+    /// Take on processes added to proc.toml since the manager started, returning their names.
+    /// Processes it already knows keep the config they were started with; a config that no
+    /// longer loads adds nothing.
+    fn add_new_configs(&self) -> Vec<String> {
+        let Ok(current) = crate::config::load_config_from(&self.root) else {
+            return Vec::new();
+        };
+        let mut configs = self.configs.write().unwrap();
+        let added: Vec<ProcessConfig> = current
+            .into_iter()
+            .filter(|c| !configs.iter().any(|known| known.name == c.name))
+            .collect();
+        let names = added.iter().map(|c| c.name.clone()).collect();
+        configs.extend(added);
+        names
+    }
+
+    /// Start the named processes, and anything they depend on, if not already running.
+    async fn start_on_demand(self: &Arc<Self>, names: &[String]) -> Response {
+        let added = self.add_new_configs();
+        let configs = self.configs();
+        let mut lines = Vec::new();
+        let resumed = self.resume().await;
+        if !resumed.is_empty() {
+            lines.push(format!("resumed {}", resumed.join(", ")));
+        }
+        if !added.is_empty() {
+            lines.push(format!("new in proc.toml: {}", added.join(", ")));
+        }
+        let mut ok = true;
+        for name in &crate::config::with_dependencies(&configs, names) {
+            let Some(config) = configs.iter().find(|c| &c.name == name) else {
+                ok = false;
+                lines.push(format!("unknown process '{}'", name));
+                continue;
+            };
+            if let Some(pid) = self.running_pid(name).await {
+                lines.push(format!("{} already running (pid {})", name, pid));
+                continue;
+            }
+            if self.state.lock().await.pending.contains(name) {
+                lines.push(format!("{} is already waiting to start", name));
+                continue;
+            }
+            match self.schedule(config, std::time::Duration::ZERO).await {
+                Ok(Some(pid)) => lines.push(format!("started {} (pid {})", name, pid)),
+                Ok(None) => lines.push(format!(
+                    "{} will start once its dependencies are ready",
+                    name
+                )),
+                Err(e) => {
+                    ok = false;
+                    lines.push(format!("failed to start {}: {}", name, e));
+                }
+            }
+        }
+        let message = lines.join("\n");
+        if ok {
+            Response::ok(message)
+        } else {
+            Response::error(message)
+        }
+    }
+
+    /// Stop the named processes (TERM to the process group, KILL after a grace period) and
+    /// launch them again. Stopped processes are simply started, except with `soft`, which only
+    /// touches running ones.
+    async fn restart_on_demand(
+        self: &Arc<Self>,
+        names: &[String],
+        grace: std::time::Duration,
+        soft: bool,
+    ) -> Response {
+        let configs = self.configs();
+        let mut lines = Vec::new();
+        let mut ok = true;
+        for name in names {
+            let Some(config) = configs.iter().find(|c| &c.name == name) else {
+                ok = false;
+                lines.push(format!("unknown process '{}'", name));
+                continue;
+            };
+            let running = {
+                let st = self.state.lock().await;
+                st.processes
+                    .iter()
+                    .find(|p| &p.name == name && p.state.is_live())
+                    .map(|p| (p.pid, p.pgid, p.restarts, p.descendants.clone()))
+            };
+            if soft && running.is_none() {
+                lines.push(format!("{} is not running; left alone", name));
+                continue;
+            }
+            if soft && config.reload_signal.is_some() {
+                match self.reload(config).await {
+                    Ok(line) => lines.push(line),
+                    Err(line) => {
+                        ok = false;
+                        lines.push(line);
+                    }
+                }
+                continue;
+            }
+            let restarts = match running {
+                Some((pid, pgid, restarts, descendants)) => {
+                    self.stopping.lock().unwrap().insert(pid);
+                    self.set_state(name, pid, ProcessState::Stopping).await;
+                    terminate_group(pid, pgid, grace).await;
+                    for e in crate::proctree::kill_escaped(&descendants, pgid) {
+                        lines.push(format!(
+                            "killed {} (pid {}) left behind by {}",
+                            e.name, e.pid, name
+                        ));
+                    }
+                    restarts
+                }
+                None => 0,
+            };
+            match self.launch(config, restarts).await {
+                Ok(pid) => {
+                    events::record(
+                        &self.state_dir,
+                        EventKind::Restarted,
+                        Some(name),
+                        format!("restarted on request as pid {}", pid),
+                    );
+                    lines.push(format!("restarted {} (pid {})", name, pid));
+                }
+                Err(e) => {
+                    ok = false;
+                    lines.push(format!("failed to restart {}: {}", name, e));
+                }
+            }
+        }
+        let message = lines.join("\n");
+        if ok {
+            Response::ok(message)
+        } else {
+            Response::error(message)
+        }
+    }
+
+    /// Stop the named processes and leave them stopped (TERM to the process group, KILL after
+    /// a grace period). Pending ones are not started. The manager and the rest keep running.
+    async fn stop_on_demand(&self, names: &[String], grace: std::time::Duration) -> Response {
+        let configs = self.configs();
+        let mut lines = Vec::new();
+        let mut ok = true;
+        for name in names {
+            if !configs.iter().any(|c| &c.name == name) {
+                ok = false;
+                lines.push(format!("unknown process '{}'", name));
+                continue;
+            }
+            let (running, pending) = {
+                let mut st = self.state.lock().await;
+                let running = st
+                    .processes
+                    .iter()
+                    .find(|p| &p.name == name && p.state.is_live())
+                    .map(|p| (p.pid, p.pgid, p.descendants.clone()));
+                let pending = st.pending.contains(name);
+                st.pending.retain(|n| n != name);
+                (running, pending)
+            };
+            match running {
+                Some((pid, pgid, descendants)) => {
+                    self.stopping.lock().unwrap().insert(pid);
+                    self.set_state(name, pid, ProcessState::Stopping).await;
+                    terminate_group(pid, pgid, grace).await;
+                    for e in crate::proctree::kill_escaped(&descendants, pgid) {
+                        lines.push(format!(
+                            "killed {} (pid {}) left behind by {}",
+                            e.name, e.pid, name
+                        ));
+                    }
+                    events::record(
+                        &self.state_dir,
+                        EventKind::Exited,
+                        Some(name),
+                        format!("pid {} stopped on request", pid),
+                    );
+                    lines.push(format!("stopped {} (pid {})", name, pid));
+                }
+                None if pending => lines.push(format!("{} will not be started", name)),
+                None => lines.push(format!("{} is not running", name)),
+            }
+            self.inputs.lock().await.remove(name);
+            let mut st = self.state.lock().await;
+            st.processes.retain(|p| &p.name != name);
+            if !st.stopped.contains(name) {
+                st.stopped.push(name.clone());
+            }
+            let _ = save_state(&self.state_dir, &st);
+        }
+        let message = lines.join("\n");
+        if ok {
+            Response::ok(message)
+        } else {
+            Response::error(message)
+        }
+    }
+
+    /// Send `reload_signal` to the named processes, or to every running process that has one.
+    async fn reload_on_demand(self: &Arc<Self>, names: &[String]) -> Response {
+        let configs = self.configs();
+        let targets: Vec<&ProcessConfig> = if names.is_empty() {
+            let st = self.state.lock().await;
+            configs
+                .iter()
+                .filter(|c| c.reload_signal.is_some())
+                .filter(|c| {
+                    st.processes
+                        .iter()
+                        .any(|p| p.name == c.name && p.state.is_live())
+                })
+                .collect()
+        } else {
+            let mut targets = Vec::new();
+            for name in names {
+                match configs.iter().find(|c| &c.name == name) {
+                    Some(c) => targets.push(c),
+                    None => return Response::error(format!("unknown process '{}'", name)),
+                }
+            }
+            targets
+        };
+        if targets.is_empty() {
+            return Response::error("no running process has a reload_signal");
+        }
+        let mut lines = Vec::new();
+        let mut ok = true;
+        for config in targets {
+            match self.reload(config).await {
+                Ok(line) => lines.push(line),
+                Err(line) => {
+                    ok = false;
+                    lines.push(line);
+                }
+            }
+        }
+        let message = lines.join("\n");
+        if ok {
+            Response::ok(message)
+        } else {
+            Response::error(message)
+        }
+    }
+
+    /// Write a line to a process's stdin.
+    async fn send_input(&self, name: &str, text: &str) -> Response {
+        let Some(config) = self.configs().into_iter().find(|c| c.name == name) else {
+            return Response::error(format!("unknown process '{}'", name));
+        };
+        let mut inputs = self.inputs.lock().await;
+        let Some(input) = inputs.get_mut(name) else {
+            return Response::error(match config.stdin {
+                StdinMode::Null => format!(
+                    "{} does not accept input; set stdin = \"pipe\" or \"pty\"",
+                    name
+                ),
+                _ => format!("{} is not running", name),
+            });
+        };
+        let line = format!("{}\n", text);
+        let written = async {
+            input.write_all(line.as_bytes()).await?;
+            input.flush().await
+        };
+        // A process that never reads its stdin fills the pipe; don't wait on it forever
+        let Ok(written) = tokio::time::timeout(SEND_TIMEOUT, written).await else {
+            return Response::error(format!(
+                "{} is not reading its stdin (timed out after {:?})",
+                name, SEND_TIMEOUT
+            ));
+        };
+        match written {
+            Ok(()) => Response::ok(""),
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {
+                Response::error(format!("{} has closed its stdin or exited", name))
+            }
+            Err(e) => Response::error(format!("failed to write to {}: {}", name, e)),
+        }
+    }
+
+    /// Signal one running process's group with its `reload_signal` and note the time.
+    /// Returns the line to report either way.
+    async fn reload(&self, config: &ProcessConfig) -> std::result::Result<String, String> {
+        let Some(signal) = config.reload_signal else {
+            return Err(format!("{} has no reload_signal", config.name));
+        };
+        let mut st = self.state.lock().await;
+        let Some(p) = st
+            .processes
+            .iter_mut()
+            .find(|p| p.name == config.name && p.state.is_live())
+        else {
+            return Err(format!("{} is not running", config.name));
+        };
+        // The whole group, so it reaches the real program behind `sh -c` or a wrapper
+        kill(Pid::from_raw(-p.pgid), signal)
+            .map_err(|e| format!("failed to signal {}: {}", config.name, e))?;
+        p.last_reload = Some(Utc::now());
+        let line = format!("sent {} to {} (pid {})", signal, config.name, p.pid);
+        let _ = save_state(&self.state_dir, &st);
+        events::record(
+            &self.state_dir,
+            EventKind::Reloaded,
+            Some(&config.name),
+            format!("sent {}", signal),
+        );
+        Ok(line)
+    }
+}
+
+/// SIGTERM a process group and wait for its leader to exit, escalating to SIGKILL after `grace`.
+async fn terminate_group(pid: u32, pgid: i32, grace: std::time::Duration) {
+    let _ = kill(Pid::from_raw(-pgid), Signal::SIGTERM);
+    let _ = kill(Pid::from_raw(-pgid), Signal::SIGCONT);
+    let deadline = Instant::now() + grace;
+    while crate::state::pid_alive(pid as i32) && Instant::now() < deadline {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    if crate::state::pid_alive(pid as i32) {
+        let _ = kill(Pid::from_raw(-pgid), Signal::SIGKILL);
+        // Give the supervisor a moment to reap it
+        for _ in 0..40 {
+            if !crate::state::pid_alive(pid as i32) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    }
+}
+
+/// How often the idle watcher samples output and CPU use (or every `idle_timeout`, if shorter).
+const IDLE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// CPU use over a sampling interval, as a share of one CPU, above which the formation counts
+/// as busy. Idle dev servers still wake up for timers and file watching now and then.
+const IDLE_CPU_SHARE: f64 = 0.01;
+
+/// How long a process gets to exit on its own when restarted on request (unless overridden).
+const RESTART_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// The manager's resident set size, from `/proc/self/status` on Linux.
+fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+/// Whether any process is left in the group (zombies included, until their parent reaps them).
+fn group_alive(pgid: i32) -> bool {
+    kill(Pid::from_raw(-pgid), None).is_ok()
+}
+
+/// Shutdown time as reported by `stop`: milliseconds under a second, else tenths of seconds.
+fn format_elapsed(d: std::time::Duration) -> String {
+    if d < std::time::Duration::from_secs(1) {
+        format!("{}ms", d.as_millis())
+    } else {
+        format!("{:.1}s", d.as_secs_f64())
+    }
+}
+
+/// How often `stop` checks whether the signalled process groups are gone.
+const STOP_POLL: std::time::Duration = std::time::Duration::from_millis(50);
+
+/// How long processes get to exit after SIGTERM when the manager stops them (unless a `stop`
+/// request says otherwise).
+const STOP_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How often a starting process's `ready` check is tried, and its dependencies looked at.
+const READY_POLL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// How often followers check state.json for restarted processes.
+const RESTART_WATCH_POLL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Followed lines waiting to be printed; past this, reading the log files waits for output.
+const FOLLOW_QUEUE: usize = 1024;
+
+/// How often process trees are walked for descendants.
+const DESCENDANTS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// How long `send` waits for a process to take its input.
+const SEND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Pause before the first automatic restart within `restart_window`; later ones back off.
+const RESTART_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// The longest wait before a restart, however many came just before it.
+const MAX_RESTART_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// How long an exited process's last output may take to reach its logs before the exit banner.
+const OUTPUT_DRAIN: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// How often output tallies and rates are written to state.json.
+const OUTPUT_STATS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Output tallies for one process, shared with its output readers.
+#[derive(Debug, Default)]
+struct OutputCounts {
+    lines: AtomicU64,
+    /// Bytes printed, newlines included; lines dropped by `log_rate_limit` count too
+    bytes: AtomicU64,
+    errors: AtomicU64,
+    warnings: AtomicU64,
+    /// Bytes of unfinished lines held in memory right now (both streams)
+    buffered: Arc<AtomicU64>,
+    /// Lines longer than `MAX_LINE_BYTES`, logged in pieces
+    split_lines: AtomicU64,
+    /// First `url_pattern` match and the PID of the instance that printed it
+    url: std::sync::Mutex<Option<(u32, String)>>,
+    url_found: Arc<tokio::sync::Notify>,
+}
+
+/// Classifies each line read from one stream of a process into its tallies.
+struct LineTally {
+    patterns: LevelPatterns,
+    stderr: bool,
+    counts: Arc<OutputCounts>,
+    /// `url_pattern`, and the PID whose announced URL it captures
+    url_pattern: Option<(regex::Regex, u32)>,
+}
+
+impl LineTally {
+    /// Count a line; returns its sequence number within the process.
+    fn count(&self, line: &str) -> u64 {
+        let seq = self.counts.lines.fetch_add(1, Ordering::Relaxed) + 1;
+        self.counts
+            .bytes
+            .fetch_add(line.len() as u64 + 1, Ordering::Relaxed);
+        match self.patterns.classify(line, self.stderr) {
+            LogLevel::Error => self.counts.errors.fetch_add(1, Ordering::Relaxed),
+            LogLevel::Warn => self.counts.warnings.fetch_add(1, Ordering::Relaxed),
+            LogLevel::Info => 0,
+        };
+        if let Some((pattern, pid)) = &self.url_pattern {
+            let mut url = self.counts.url.lock().unwrap();
+            if url.as_ref().is_none_or(|(p, _)| p != pid) {
+                if let Some(found) = crate::config::url_in(pattern, line) {
+                    *url = Some((*pid, found));
+                    self.counts.url_found.notify_one();
                 }
-            });
+            }
         }
+        seq
+    }
+}
+
+/// Everything done with one output stream's lines besides writing them to its log.
+struct StreamSink {
+    tally: LineTally,
+    limiter: Option<Arc<LogLimiter>>,
+    /// Write JSONL records stamped with this PID instead of bare lines (`log_format = "json"`)
+    json_pid: Option<u32>,
+    /// Where logged lines are copied as they are written (`mirror`)
+    mirror: Option<Arc<crate::mirror::Mirror>>,
+}
 
-        let mut child = cmd.spawn()?;
-        let pid = child.id().unwrap();
-        let pgid = getpgid(Some(Pid::from_raw(pid as i32)))
-            .unwrap_or(Pid::from_raw(pid as i32))
-            .as_raw();
+impl StreamSink {
+    /// The log file line for `text`.
+    fn format(&self, process: &str, text: &str, seq: u64) -> String {
+        let Some(pid) = self.json_pid else {
+            return format!("{}\n", text);
+        };
+        let record = crate::jsonlog::LogRecord {
+            ts: Utc::now(),
+            seq,
+            process: process.to_string(),
+            stream: if self.tally.stderr {
+                "stderr"
+            } else {
+                "stdout"
+            }
+            .to_string(),
+            pid,
+            instance: 0,
+            line: text.to_string(),
+        };
+        format!("{}\n", serde_json::to_string(&record).unwrap_or_default())
+    }
+}
 
-        let stdout = child.stdout.take().unwrap();
-        let stderr = child.stderr.take().unwrap();
+/// Fixed-window limiter for `log_rate_limit`, shared by a process's stdout and stderr readers.
+/// Lines over the limit are dropped and later accounted for with a marker line.
+struct LogLimiter {
+    limit: RateLimit,
+    window: std::sync::Mutex<LimitWindow>,
+}
 
-        let stdout_log = config
-            .stdout_log
-            .clone()
-            .unwrap_or_else(|| format!("{}.out.log", config.name));
-        let stderr_log = config
-            .stderr_log
-            .clone()
-            .unwrap_or_else(|| format!("{}.err.log", config.name));
+struct LimitWindow {
+    start: Instant,
+    lines: u32,
+    /// Dropped lines per stream (stdout, stderr) not yet reported
+    suppressed: [u64; 2],
+}
 
-        let out_handle = tokio::spawn(handle_output(
-            config.name.clone(),
-            stdout,
-            Some(if std::path::Path::new(&stdout_log).is_absolute() {
-                stdout_log.clone()
-            } else {
-                root.join(&stdout_log).to_string_lossy().to_string()
-            }),
-            false,
-            "",
-        ));
-        let err_handle = tokio::spawn(handle_output(
-            config.name.clone(),
-            stderr,
-            Some(if std::path::Path::new(&stderr_log).is_absolute() {
-                stderr_log.clone()
-            } else {
-                root.join(&stderr_log).to_string_lossy().to_string()
+impl LogLimiter {
+    fn new(limit: RateLimit) -> Self {
+        LogLimiter {
+            limit,
+            window: std::sync::Mutex::new(LimitWindow {
+                start: Instant::now(),
+                lines: 0,
+                suppressed: [0; 2],
             }),
-            false,
-            "[ERR] ",
-        ));
+        }
+    }
 
-        handles.push(out_handle);
-        handles.push(err_handle);
+    fn current(&self) -> std::sync::MutexGuard<'_, LimitWindow> {
+        let mut w = self.window.lock().unwrap();
+        if w.start.elapsed() >= self.limit.per {
+            w.start = Instant::now();
+            w.lines = 0;
+        }
+        w
+    }
 
-        proc_infos.push(ProcessInfo {
-            name: config.name.clone(),
-            pid,
-            pgid,
-            cmd: config.command.clone(),
-            cwd: config.cwd.clone(),
-            stdout_log,
-            stderr_log,
-            started_at: Utc::now(),
-        });
+    /// Whether a line may be written; counts it as dropped otherwise.
+    fn admit(&self, stderr: bool) -> bool {
+        let mut w = self.current();
+        if w.lines < self.limit.lines {
+            w.lines += 1;
+            true
+        } else {
+            w.suppressed[stderr as usize] += 1;
+            false
+        }
+    }
 
-        children.push(Arc::new(Mutex::new(child)));
+    /// When lines dropped from this stream can be reported: the end of the current window.
+    fn report_at(&self, stderr: bool) -> Option<Instant> {
+        let w = self.window.lock().unwrap();
+        (w.suppressed[stderr as usize] > 0).then(|| w.start + self.limit.per)
     }
 
-    let state = ManagerState {
-        manager: ManagerInfo {
-            pid: std::process::id(),
-            started_at: Utc::now(),
-            project_root: root.to_string_lossy().to_string(),
-            version: 1,
-        },
-        processes: proc_infos,
-    };
-    save_state(&state_dir, &state)?;
+    /// Dropped lines to report for this stream, once their window is over (or right away with
+    /// `now`). Resets the count.
+    fn take_suppressed(&self, stderr: bool, now: bool) -> u64 {
+        let mut w = self.window.lock().unwrap();
+        if !now && w.start.elapsed() < self.limit.per {
+            return 0;
+        }
+        std::mem::take(&mut w.suppressed[stderr as usize])
+    }
+}
 
-    // Wait on either child completion or termination signal
-    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
-    let mut sigint = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())?;
+/// A `=== web started pid 1234 at … ===` separator for the process's log files, so a log read
+/// later shows where each run began and ended. JSON logs go without: each record there carries
+/// its pid and time.
+fn banner(config: &ProcessConfig, text: &str) -> Option<String> {
+    (config.log_format != LogFormat::Json).then(|| {
+        format!(
+            "=== {} {} at {} ===\n",
+            config.name,
+            text,
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S %z")
+        )
+    })
+}
 
-    tokio::select! {
-        _ = join_all(handles) => {
-            // One of the streams finished; keep running until terminated, but we'll just park here
-            sigterm.recv().await;
+/// Append a banner to the process's stdout log, noting a failure in the manager log. The
+/// stderr log goes without, so `logs` shows each banner once.
+async fn write_banner(config: &ProcessConfig, root: &Path, line: &str) {
+    let (path, _) = config.log_paths(root);
+    let written = async {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
         }
-        _ = sigterm.recv() => {}
-        _ = sigint.recv() => {}
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        file.write_all(line.as_bytes()).await
+    };
+    if let Err(e) = written.await {
+        tracing::warn!(
+            "failed to write to {}'s log {}: {}",
+            config.name,
+            path.display(),
+            e
+        );
     }
+}
 
-    // Graceful shutdown: SIGTERM to each process group, then SIGKILL after 5s
-    for child in &children {
-        let c = child.lock().await;
-        if let Some(pid) = c.id() {
-            let pgid =
-                getpgid(Some(Pid::from_raw(pid as i32))).unwrap_or(Pid::from_raw(pid as i32));
-            let _ = kill(Pid::from_raw(-pgid.as_raw()), Signal::SIGTERM);
+/// Spawn one configured process in its own session, wiring its output to the log files.
+/// Also returns the manager's end of its stdin unless that is `/dev/null`, and the tasks
+/// copying its output.
+fn spawn_process(
+    config: &ProcessConfig,
+    root: &Path,
+    counts: Arc<OutputCounts>,
+    mirror: Option<Arc<crate::mirror::Mirror>>,
+) -> Result<(Child, ProcessInfo, Option<ProcessInput>, OutputTasks)> {
+    let command_line = config.command_line(root);
+    let mut cmd = config.shell_command(root, &command_line);
+    let dir = config.working_dir(root);
+    if !dir.exists() {
+        return Err(anyhow::anyhow!(
+            "Process '{}' cwd does not exist: {}",
+            config.name,
+            dir.display()
+        ));
+    }
+    let launch = crate::state::LaunchInfo {
+        command: command_line,
+        cwd: dir.to_string_lossy().to_string(),
+        env: match config.record_env {
+            RecordEnv::Off => None,
+            RecordEnv::Redacted => {
+                let mut env = config.env.resolved(&cmd);
+                crate::env::redact(&mut env);
+                Some(env)
+            }
+            RecordEnv::Full => Some(config.env.resolved(&cmd)),
+        },
+    };
+    let mut pty_master = None;
+    match config.stdin {
+        StdinMode::Null => {
+            cmd.stdin(Stdio::null());
+        }
+        StdinMode::Pipe => {
+            cmd.stdin(Stdio::piped());
         }
+        StdinMode::Pty => {
+            let pty = open_input_pty()?;
+            cmd.stdin(Stdio::from(pty.slave));
+            pty_master = Some(pty.master);
+        }
+    }
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+
+    if let Some(adj) = config.oom_score_adj {
+        crate::oom::apply(&mut cmd, adj);
+    }
+    if config.core_dumps {
+        crate::cores::apply(&mut cmd);
+    }
+
+    // Each child gets its own session/PGID
+    unsafe {
+        cmd.pre_exec(|| {
+            // SAFETY: called in child just before exec
+            match setsid() {
+                Ok(_) => Ok(()),
+                Err(e) => Err(std::io::Error::other(format!("setsid failed: {}", e))),
+            }
+        });
     }
-    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-    for child in &children {
-        let c = child.lock().await;
-        if let Some(pid) = c.id() {
-            let pgid =
-                getpgid(Some(Pid::from_raw(pid as i32))).unwrap_or(Pid::from_raw(pid as i32));
-            let _ = kill(Pid::from_raw(-pgid.as_raw()), Signal::SIGKILL);
+
+    let mut child = cmd.spawn()?;
+    let pid = child.id().unwrap();
+    if let Some(adj) = config.oom_score_adj {
+        if let Some(actual) = crate::oom::current(pid).filter(|a| *a != adj) {
+            eprintln!(
+                "warning: could not set oom_score_adj {} for {} (lowering it needs CAP_SYS_RESOURCE); it is {}",
+                adj, config.name, actual
+            );
         }
     }
+    let pgid = getpgid(Some(Pid::from_raw(pid as i32)))
+        .unwrap_or(Pid::from_raw(pid as i32))
+        .as_raw();
 
-    Ok(())
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+    let input: Option<ProcessInput> = match (child.stdin.take(), pty_master) {
+        (Some(pipe), _) => Some(Box::new(pipe)),
+        (None, Some(master)) => Some(Box::new(tokio::fs::File::from_std(std::fs::File::from(
+            master,
+        )))),
+        (None, None) => None,
+    };
+
+    let (stdout_log, stderr_log) = config.log_files(root);
+
+    let limiter = config.log_rate_limit.map(|l| Arc::new(LogLimiter::new(l)));
+    let json_pid = (config.log_format == LogFormat::Json).then_some(pid);
+    let sink = |stderr: bool| StreamSink {
+        tally: LineTally {
+            patterns: config.levels.clone(),
+            stderr,
+            counts: counts.clone(),
+            url_pattern: config.url_pattern.clone().map(|re| (re, pid)),
+        },
+        limiter: limiter.clone(),
+        json_pid,
+        mirror: mirror.clone(),
+    };
+    // The start banner goes ahead of the output in the stdout log only, like every banner
+    let started = banner(config, &format!("started pid {}", pid));
+    let output = vec![
+        tokio::spawn(handle_output(
+            config.name.clone(),
+            stdout,
+            Some(resolve_path(root, &stdout_log)),
+            started.clone(),
+            false,
+            false,
+            sink(false),
+        )),
+        tokio::spawn(handle_output(
+            config.name.clone(),
+            stderr,
+            Some(resolve_path(root, &stderr_log)),
+            None,
+            false,
+            true,
+            sink(true),
+        )),
+    ];
+
+    let info = ProcessInfo {
+        name: config.name.clone(),
+        pid,
+        pgid,
+        cmd: config.command.clone(),
+        cwd: config.cwd.clone(),
+        stdout_log,
+        stderr_log,
+        started_at: Utc::now(),
+        restarts: 0,
+        failure: None,
+        state: if config.ready.is_some() || config.min_uptime.is_some() {
+            ProcessState::Starting
+        } else {
+            ProcessState::Running
+        },
+        errors: counts.errors.load(Ordering::Relaxed),
+        warnings: counts.warnings.load(Ordering::Relaxed),
+        descendants: Vec::new(),
+        output: OutputStats::default(),
+        last_reload: None,
+        url: None,
+        oom_score_adj: config.oom_score_adj,
+        launch: Some(launch),
+        container: None,
+    };
+    Ok((child, info, input, output))
+}
+
+/// A pty for `stdin = "pty"`. Echo is off: nothing reads the master's output, and input sent
+/// to the process should not pile up there.
+fn open_input_pty() -> Result<nix::pty::OpenptyResult> {
+    use nix::sys::termios::{tcgetattr, tcsetattr, LocalFlags, SetArg};
+    let pty = nix::pty::openpty(None, None)?;
+    let mut termios = tcgetattr(&pty.slave)?;
+    termios.local_flags.remove(LocalFlags::ECHO);
+    tcsetattr(&pty.slave, SetArg::TCSANOW, &termios)?;
+    Ok(pty)
 }
 
 async fn handle_output<T: AsyncRead + Unpin>(
     child_name: String,
     stream: T,
     log_path: Option<String>,
+    banner: Option<String>,
     follow: bool,
-    prefix: &'static str,
+    stderr: bool,
+    sink: StreamSink,
 ) {
-    let mut reader = BufReader::new(stream).lines();
+    let mut reader = crate::lines::CappedLines::new(
+        BufReader::new(stream),
+        crate::lines::MAX_LINE_BYTES,
+        sink.tally.counts.buffered.clone(),
+    );
     let mut file = if let Some(path) = log_path {
         if let Some(parent) = std::path::Path::new(&path).parent() {
             let _ = tokio::fs::create_dir_all(parent).await;
         }
-        Some(
-            OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(path)
-                .await
-                .unwrap(),
-        )
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .unwrap();
+        if let Some(banner) = banner {
+            if let Err(e) = file.write_all(banner.as_bytes()).await {
+                tracing::warn!("failed to write to {}'s log {}: {}", child_name, path, e);
+            }
+        }
+        Some(file)
     } else {
         None
     };
 
-    while let Some(line) = reader.next_line().await.unwrap() {
+    let limiter = sink.limiter.clone();
+    // Markers take the sequence number of the last line seen, dropped or not
+    let suppressed_marker = |n: u64| {
+        let seq = sink.tally.counts.lines.load(Ordering::Relaxed);
+        sink.format(
+            &child_name,
+            &format!("… suppressed {} lines (log_rate_limit)", n),
+            seq,
+        )
+    };
+    loop {
+        // While lines are being dropped, wake up when the window ends to report them even if
+        // the process has gone quiet
+        let report_at = limiter.as_ref().and_then(|l| l.report_at(stderr));
+        let next = match report_at {
+            Some(at) => tokio::select! {
+                line = reader.next_line() => line,
+                _ = tokio::time::sleep_until(at.into()) => {
+                    let n = limiter.as_ref().map_or(0, |l| l.take_suppressed(stderr, false));
+                    if let (Some(file), true) = (file.as_mut(), n > 0) {
+                        let _ = file.write_all(suppressed_marker(n).as_bytes()).await;
+                    }
+                    continue;
+                }
+            },
+            None => reader.next_line().await,
+        };
+        // A read error (the pipe went away) ends the stream like end of file
+        let Ok(Some(line)) = next else {
+            break;
+        };
+        if line.split {
+            sink.tally
+                .counts
+                .split_lines
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        let line = line.text;
+        let seq = sink.tally.count(&line);
+        if let Some(limiter) = &limiter {
+            let n = limiter.take_suppressed(stderr, false);
+            if let (Some(file), true) = (file.as_mut(), n > 0) {
+                let _ = file.write_all(suppressed_marker(n).as_bytes()).await;
+            }
+            if !limiter.admit(stderr) {
+                continue;
+            }
+        }
         if follow {
-            let p = crate::color::prefix(&child_name);
-            println!("{}{}{}", p, prefix, line);
+            outln!(
+                "{}{}",
+                crate::color::stream_prefix(&child_name, stderr),
+                line
+            );
         } else if let Some(ref mut file) = file {
-            file.write_all(format!("{}\n", line).as_bytes())
-                .await
-                .unwrap();
+            let formatted = sink.format(&child_name, &line, seq);
+            file.write_all(formatted.as_bytes()).await.unwrap();
+            if let Some(mirror) = &sink.mirror {
+                mirror.send(&formatted);
+            }
         }
     }
+    let n = limiter.map_or(0, |l| l.take_suppressed(stderr, true));
+    if let (Some(file), true) = (file.as_mut(), n > 0) {
+        let _ = file.write_all(suppressed_marker(n).as_bytes()).await;
+    }
 }
 
 #[cfg(unix)]
@@ -202,13 +2191,117 @@ pub fn stop_all(root: &std::path::Path, grace: Option<std::time::Duration>) -> R
     let st = match load_state_from_root(root) {
         Ok(s) => s,
         Err(_) => {
-            println!("No daemon state found for this project.");
+            outln!("No daemon state found for this project.");
             return Ok(());
         }
     };
-    let grace = grace.unwrap_or(std::time::Duration::from_secs(5));
+    let grace = grace.unwrap_or(STOP_GRACE);
+    let dir = crate::state::state_dir_from_root(root);
+
+    // The manager knows which processes are still starting or already exiting and marks them
+    // as it goes; only signal blindly when it can't be reached
+    let via_manager = if crate::control::manager_answers(&dir) {
+        outln!(
+            "Stopping {} process(es) via the manager (pid {})...",
+            st.processes.len(),
+            st.manager.pid
+        );
+        let req = crate::control::Request::Stop {
+            grace: Some(grace.as_secs()),
+            names: Vec::new(),
+        };
+        match crate::control::send_request_timeout(
+            &dir,
+            &req,
+            grace + std::time::Duration::from_secs(30),
+        ) {
+            Ok(resp) if resp.ok => {
+                for line in resp.message.lines() {
+                    outln!("- {}", line);
+                }
+                let summary: crate::control::StopSummary = resp
+                    .data
+                    .and_then(|d| serde_json::from_value(d).ok())
+                    .unwrap_or_default();
+                Some((summary.sigkilled, summary.escaped))
+            }
+            Ok(resp) => {
+                outln!("Manager refused to stop ({}).", resp.message);
+                None
+            }
+            Err(e) => {
+                outln!("Lost the manager while stopping ({}).", e);
+                None
+            }
+        }
+    } else {
+        outln!("Manager is not answering on its control socket.");
+        None
+    };
+    let (killed, reaped) = match via_manager {
+        Some(counts) => counts,
+        None => {
+            outln!("Signalling processes directly.");
+            signal_processes(&st, grace)
+        }
+    };
+
+    // The manager exits on its own after a stop request, and a SIGTERM then finds nothing left
+    // to stop. It removes its pid file and releases the lock on the way out.
+    outln!("Stopping manager (pid {})...", st.manager.pid);
+    let manager_pid = nix::unistd::Pid::from_raw(st.manager.pid as i32);
+    let _ = kill(manager_pid, Signal::SIGTERM);
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while crate::state::pid_alive(st.manager.pid as i32) && std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    if crate::state::pid_alive(st.manager.pid as i32) {
+        let _ = kill(manager_pid, Signal::SIGKILL);
+    }
+
+    // A killed manager cannot clean up after itself; drop its pid file. The lock file is left
+    // in place: the kernel releases the lock with its holder and the next start reuses it.
+    use std::fs;
+    let pid_path = crate::state::manager_pid_path(&dir);
+    let mut removed = Vec::new();
+    if pid_path.exists() && fs::remove_file(&pid_path).is_ok() {
+        removed.push("manager.pid");
+    }
+
+    outln!("Stop complete. {} process(es) required SIGKILL.", killed);
+    if reaped > 0 {
+        outln!(
+            "Killed {} process(es) that escaped their process group.",
+            reaped
+        );
+    }
+    if !removed.is_empty() {
+        outln!(
+            "State cleaned up at {} (removed: {}).",
+            dir.display(),
+            removed.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Signal each recorded process group directly, for when the manager is unreachable. Returns
+/// how many needed SIGKILL and how many escaped descendants were killed.
+#[cfg(unix)]
+fn signal_processes(st: &ManagerState, grace: std::time::Duration) -> (usize, usize) {
+    // Take the freshest view of each process's descendants before anything exits and gets
+    // reparented
+    let table = crate::proctree::snapshot();
+    let trees: Vec<Vec<crate::proctree::ProcEntry>> = st
+        .processes
+        .iter()
+        .map(|p| {
+            let current = crate::proctree::descendants(&table, p.pid);
+            crate::proctree::merge_recorded(&table, current, &p.descendants)
+        })
+        .collect();
 
-    println!(
+    outln!(
         "Stopping {} process(es) (manager PID {})...",
         st.processes.len(),
         st.manager.pid
@@ -216,63 +2309,100 @@ pub fn stop_all(root: &std::path::Path, grace: Option<std::time::Duration>) -> R
 
     // Send SIGTERM to each process group
     for p in &st.processes {
-        match kill(nix::unistd::Pid::from_raw(-p.pgid), Signal::SIGTERM) {
-            Ok(_) => println!(
+        let sent = kill(nix::unistd::Pid::from_raw(-p.pgid), Signal::SIGTERM);
+        let _ = kill(nix::unistd::Pid::from_raw(-p.pgid), Signal::SIGCONT);
+        match sent {
+            Ok(_) => outln!(
                 "- sent SIGTERM to {} (pid {}, pgid {})",
-                p.name, p.pid, p.pgid
+                p.name,
+                p.pid,
+                p.pgid
             ),
-            Err(e) => println!("- {} already stopped or cannot signal ({}).", p.name, e),
+            Err(e) => outln!("- {} already stopped or cannot signal ({}).", p.name, e),
         }
     }
-    println!("Waiting {}s for graceful shutdown...", grace.as_secs());
-    std::thread::sleep(grace);
-
-    // Escalate with SIGKILL where needed
-    let mut killed = 0usize;
-    for p in &st.processes {
-        let alive = kill(nix::unistd::Pid::from_raw(p.pid as i32), None).is_ok();
-        if alive {
-            let _ = kill(nix::unistd::Pid::from_raw(-p.pgid), Signal::SIGKILL);
-            println!("- escalated SIGKILL to {} (pgid {})", p.name, p.pgid);
-            killed += 1;
+    for (p, tree) in st.processes.iter().zip(&trees) {
+        for e in crate::proctree::escaped(tree, p.pgid) {
+            let _ = kill(nix::unistd::Pid::from_raw(e.pid as i32), Signal::SIGTERM);
         }
     }
-
-    // Terminate manager last
-    println!("Stopping manager (pid {})...", st.manager.pid);
-    let _ = kill(
-        nix::unistd::Pid::from_raw(st.manager.pid as i32),
-        Signal::SIGTERM,
+    outln!(
+        "Waiting up to {}s for graceful shutdown...",
+        grace.as_secs()
     );
-    std::thread::sleep(std::time::Duration::from_millis(300));
-    if kill(nix::unistd::Pid::from_raw(st.manager.pid as i32), None).is_ok() {
-        let _ = kill(
-            nix::unistd::Pid::from_raw(st.manager.pid as i32),
-            Signal::SIGKILL,
-        );
+    let began = std::time::Instant::now();
+    let mut waiting: Vec<_> = st.processes.iter().collect();
+    loop {
+        waiting.retain(|p| {
+            if group_alive(p.pgid) {
+                return true;
+            }
+            outln!(
+                "- {} exited after {}",
+                p.name,
+                format_elapsed(began.elapsed())
+            );
+            false
+        });
+        if waiting.is_empty() || began.elapsed() >= grace {
+            break;
+        }
+        std::thread::sleep(STOP_POLL);
     }
 
-    // Attempt to clean up pid/lock files for this project
-    use std::fs;
-    let dir = crate::state::state_dir_from_root(root);
-    let pid_path = crate::state::manager_pid_path(&dir);
-    let lock_path = crate::state::manager_lock_path(&dir);
-    let mut removed = Vec::new();
-    if pid_path.exists() && fs::remove_file(&pid_path).is_ok() {
-        removed.push("manager.pid");
+    // Escalate with SIGKILL where needed
+    let mut killed = 0usize;
+    for p in waiting {
+        let _ = kill(nix::unistd::Pid::from_raw(-p.pgid), Signal::SIGKILL);
+        outln!(
+            "- escalated SIGKILL to {} (pgid {}) after {}",
+            p.name,
+            p.pgid,
+            format_elapsed(began.elapsed())
+        );
+        killed += 1;
     }
-    if lock_path.exists() && fs::remove_file(&lock_path).is_ok() {
-        removed.push("manager.lock");
+
+    // Group signals can't reach descendants that moved to another process group; kill any
+    // still hanging on (the usual cause of "port still in use" after a stop)
+    let mut reaped = 0usize;
+    for (p, tree) in st.processes.iter().zip(&trees) {
+        for e in crate::proctree::kill_escaped(tree, p.pgid) {
+            outln!(
+                "- killed {} (pid {}, pgid {}) spawned by {}, outside its process group",
+                e.name,
+                e.pid,
+                e.pgid,
+                p.name
+            );
+            reaped += 1;
+        }
     }
 
-    println!("Stop complete. {} process(es) required SIGKILL.", killed);
-    if !removed.is_empty() {
-        println!(
-            "State cleaned up at {} (removed: {}).",
-            dir.display(),
-            removed.join(", ")
-        );
+    (killed, reaped)
+}
+
+/// Remove the project's state dir and its processes' log files (`down --clean`).
+/// Only call once the manager has stopped.
+pub fn remove_state_and_logs(root: &Path) -> Result<()> {
+    let mut removed = 0;
+    for config in crate::config::load_config_from(root)? {
+        let (out, err) = config.log_paths(root);
+        for path in [out, err] {
+            if std::fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+        }
+    }
+    let dir = crate::state::state_dir_from_root(root);
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir)?;
     }
+    outln!(
+        "Removed {} log file(s) and state at {}.",
+        removed,
+        dir.display()
+    );
     Ok(())
 }
 
@@ -283,37 +2413,185 @@ pub fn stop_all(_grace: Option<std::time::Duration>) -> Result<()> {
 
 pub fn print_logs(
     root: &std::path::Path,
-    name: Option<String>,
+    names: &[String],
     follow: bool,
     _lines: usize,
+    level: Option<LogLevel>,
+    grep: Option<Regex>,
+    format: LogOutput,
 ) -> Result<()> {
     let st = match load_state_from_root(root) {
         Ok(s) => s,
         Err(_) => {
-            println!("No daemon state found for this project.");
+            outln!("No daemon state found for this project.");
             return Ok(());
         }
     };
     let selected: Vec<_> = st
         .processes
         .iter()
-        .filter(|p| name.as_ref().map(|n| n == &p.name).unwrap_or(true))
+        .filter(|p| names.is_empty() || names.contains(&p.name))
         .cloned()
         .collect();
 
     if selected.is_empty() {
-        println!("No matching processes.");
+        outln!("No matching processes.");
         return Ok(());
     }
 
+    crate::color::align_to(selected.iter().map(|p| p.name.as_str()));
+    crate::color::assign_colors(selected.iter().map(|p| p.name.as_str()));
+    let filters = LogFilters::load(root, level, grep, format);
+    // Display settings are best effort: logs stay readable even if the config went missing
+    let settings = crate::config::load_log_settings(root).unwrap_or_default();
+    if let Some(format) = settings.prefix_format {
+        crate::color::set_prefix_format(format);
+    }
     if follow {
-        follow_combined(selected, _lines, root)?;
+        follow_combined(
+            selected,
+            _lines,
+            root,
+            &filters,
+            Arc::new(settings.highlight),
+            false,
+        )?;
     } else {
-        print_tail(selected, _lines, root)?;
+        print_tail(selected, _lines, root, &filters)?;
     }
     Ok(())
 }
 
+/// Merged live view of every process of a running manager (`oxproc attach`). Ctrl+D detaches
+/// and leaves the processes running; Ctrl+C asks whether to stop them.
+#[cfg(unix)]
+pub fn attach(root: &Path, lines: usize) -> Result<()> {
+    if !crate::requires::is_running(root) {
+        anyhow::bail!("No running manager for this project. Start one with `oxproc start`.");
+    }
+    let st = load_state_from_root(root)?;
+    crate::color::align_to(st.processes.iter().map(|p| p.name.as_str()));
+    crate::color::assign_colors(st.processes.iter().map(|p| p.name.as_str()));
+    let settings = crate::config::load_log_settings(root).unwrap_or_default();
+    if let Some(format) = settings.prefix_format {
+        crate::color::set_prefix_format(format);
+    }
+    outln!(
+        "Attached to {} (Ctrl+D to detach, Ctrl+C to stop)…",
+        root.display()
+    );
+    let end = follow_combined(
+        st.processes,
+        lines,
+        root,
+        &LogFilters::load(root, None, None, LogOutput::Text),
+        Arc::new(settings.highlight),
+        true,
+    )?;
+    match end {
+        FollowEnd::Stop => stop_all(root, None),
+        FollowEnd::Detach => {
+            outln!("Detached; processes are still running (`oxproc stop` stops them).");
+            Ok(())
+        }
+    }
+}
+
+/// How a follow session ended.
+#[derive(Debug, PartialEq, Eq)]
+enum FollowEnd {
+    Detach,
+    /// The user confirmed stopping every process (attach only)
+    Stop,
+}
+
+/// How `logs` prints each line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogOutput {
+    /// `[name]` prefix, colors and highlighting
+    Text,
+    /// One JSON object per line
+    Json,
+    /// The line exactly as the process wrote it, for piping
+    Raw,
+}
+
+/// Per-process filters for `logs --level` and `--grep`, built from the current config, and
+/// the output format.
+struct LogFilters {
+    min: Option<LogLevel>,
+    grep: Option<Regex>,
+    format: LogOutput,
+    patterns: HashMap<String, LevelPatterns>,
+    /// `multiline` continuation patterns
+    multiline: HashMap<String, Regex>,
+}
+
+impl LogFilters {
+    fn load(root: &Path, min: Option<LogLevel>, grep: Option<Regex>, format: LogOutput) -> Self {
+        let mut filters = LogFilters {
+            min,
+            grep,
+            format,
+            patterns: HashMap::new(),
+            multiline: HashMap::new(),
+        };
+        if filters.min.is_none() && filters.grep.is_none() {
+            return filters;
+        }
+        for c in crate::config::load_config_from(root).unwrap_or_default() {
+            if let Some(re) = c.multiline {
+                filters.multiline.insert(c.name.clone(), re);
+            }
+            filters.patterns.insert(c.name, c.levels);
+        }
+        filters
+    }
+
+    fn for_stream(&self, name: &str, stderr: bool) -> RecordFilter {
+        let level = self.min.map(|min| LevelFilter {
+            patterns: self.patterns.get(name).cloned().unwrap_or_default(),
+            min,
+            stderr,
+        });
+        RecordFilter::new(self.multiline.get(name).cloned(), level, self.grep.clone())
+    }
+}
+
+/// A log line as printed: its text behind the process prefix, or a JSON object for
+/// `--format json`.
+fn render_line(
+    name: &str,
+    stderr: bool,
+    raw: &str,
+    format: LogOutput,
+    highlight: &[HighlightRule],
+) -> String {
+    match format {
+        LogOutput::Json => return crate::jsonlog::to_json(raw, name, stderr),
+        LogOutput::Raw => return crate::jsonlog::text_of(raw).into_owned(),
+        LogOutput::Text => {}
+    }
+    format!(
+        "{}{}",
+        crate::color::stream_prefix(name, stderr),
+        crate::color::highlight(&crate::jsonlog::text_of(raw), highlight)
+    )
+}
+
+/// Last `n` lines of a log, counting only lines the filter keeps.
+fn tail_filtered(path: &str, n: usize, mut filter: RecordFilter) -> Result<Vec<String>> {
+    // Every line is kept, so only the end of the file needs reading
+    if filter.is_passthrough() {
+        return tail_last_lines(path, n);
+    }
+    let data = std::fs::read(path)?;
+    let text = String::from_utf8_lossy(&data);
+    let kept: Vec<String> = text.lines().flat_map(|l| filter.push(l)).collect();
+    let skip = kept.len().saturating_sub(n);
+    Ok(kept[skip..].to_vec())
+}
+
 fn resolve_path(root: &std::path::Path, p: &str) -> String {
     if std::path::Path::new(p).is_absolute() {
         p.to_string()
@@ -322,33 +2600,41 @@ fn resolve_path(root: &std::path::Path, p: &str) -> String {
     }
 }
 
-fn print_tail(processes: Vec<ProcessInfo>, lines: usize, root: &std::path::Path) -> Result<()> {
+fn print_tail(
+    processes: Vec<ProcessInfo>,
+    lines: usize,
+    root: &std::path::Path,
+    filters: &LogFilters,
+) -> Result<()> {
     for p in processes {
-        println!("== {} ==", p.name);
-        let outp = resolve_path(root, &p.stdout_log);
-        if let Ok(v) = tail_last_lines(&outp, lines) {
-            for line in v {
-                println!("{}{}", crate::color::prefix(&p.name), line);
-            }
-        } else {
-            let pref = crate::color::prefix(&p.name);
-            println!("{}(no stdout log yet at {})", pref, outp);
+        if filters.format == LogOutput::Text {
+            outln!("== {} ==", p.name);
         }
-        let errp = resolve_path(root, &p.stderr_log);
-        if let Ok(v) = tail_last_lines(&errp, lines) {
-            for line in v {
-                let pref = crate::color::prefix(&p.name);
-                println!("{}[ERR] {}", pref, line);
+        for (log, stderr) in [(&p.stdout_log, false), (&p.stderr_log, true)] {
+            let path = resolve_path(root, log);
+            let filter = filters.for_stream(&p.name, stderr);
+            match tail_filtered(&path, lines, filter) {
+                Ok(v) => {
+                    for line in v {
+                        outln!(
+                            "{}",
+                            render_line(&p.name, stderr, &line, filters.format, &[])
+                        );
+                    }
+                }
+                Err(_) if filters.format == LogOutput::Text => {
+                    let pref = crate::color::stream_prefix(&p.name, stderr);
+                    let stream = if stderr { "stderr" } else { "stdout" };
+                    outln!("{}(no {} log yet at {})", pref, stream, path);
+                }
+                Err(_) => {}
             }
-        } else {
-            let pref = crate::color::prefix(&p.name);
-            println!("{}[ERR] (no stderr log yet at {})", pref, errp);
         }
     }
     Ok(())
 }
 
-fn tail_last_lines(path: &str, n: usize) -> Result<Vec<String>> {
+pub fn tail_last_lines(path: &str, n: usize) -> Result<Vec<String>> {
     use std::fs::File;
     use std::io::{Read, Seek, SeekFrom};
     let mut f = File::open(path)?;
@@ -392,45 +2678,77 @@ fn follow_combined(
     processes: Vec<ProcessInfo>,
     lines: usize,
     root: &std::path::Path,
-) -> Result<()> {
+    filters: &LogFilters,
+    highlight: Arc<Vec<HighlightRule>>,
+    attach: bool,
+) -> Result<FollowEnd> {
     use tokio::runtime::Runtime;
     use tokio::sync::mpsc;
 
     let rt = Runtime::new()?;
     rt.block_on(async move {
-        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        let (tx, mut rx) = mpsc::channel::<String>(FOLLOW_QUEUE);
 
         // Print initial tails
         for p in &processes {
-            let outp = resolve_path(root, &p.stdout_log);
-            if let Ok(v) = tail_last_lines(&outp, lines) {
-                for line in v {
-                    let _ = tx.send(format!("{}{}", crate::color::prefix(&p.name), line));
+            for (log, stderr) in [(&p.stdout_log, false), (&p.stderr_log, true)] {
+                let path = resolve_path(root, log);
+                let filter = filters.for_stream(&p.name, stderr);
+                if let Ok(v) = tail_filtered(&path, lines, filter) {
+                    for line in v {
+                        outln!(
+                            "{}",
+                            render_line(&p.name, stderr, &line, filters.format, &highlight)
+                        );
+                    }
                 }
             }
-            let errp = resolve_path(root, &p.stderr_log);
-            if let Ok(v) = tail_last_lines(&errp, lines) {
-                for line in v {
-                    let _ = tx.send(format!("{}[ERR] {}", crate::color::prefix(&p.name), line));
+        }
+
+        // One task follows every file
+        let mut followers = crate::follow::Followers::new();
+        for p in &processes {
+            for (log, stderr) in [(&p.stdout_log, false), (&p.stderr_log, true)] {
+                let tx = tx.clone();
+                let name = p.name.clone();
+                let mut filter = filters.for_stream(&p.name, stderr);
+                let highlight = highlight.clone();
+                let format = filters.format;
+                let notices = (tx.clone(), name.clone(), log.clone());
+                let on_line = move |line: &str| {
+                    filter.push(line).iter().all(|line| {
+                        queue_line(&tx, render_line(&name, stderr, line, format, &highlight))
+                    })
+                };
+                // JSON and raw consumers only get the lines themselves
+                if format != LogOutput::Text {
+                    followers.add(resolve_path(root, log), on_line);
+                    continue;
                 }
+                followers.add_with_notices(resolve_path(root, log), on_line, move |notice| {
+                    let (tx, name, log) = &notices;
+                    queue_line(
+                        tx,
+                        format!(
+                            "{}--- {}: {} ---",
+                            crate::color::stream_prefix(name, stderr),
+                            log,
+                            notice
+                        ),
+                    );
+                });
             }
         }
+        tokio::spawn(followers.run());
 
-        // Spawn followers for each file
-        for p in &processes {
-            let txo = tx.clone();
-            let name = p.name.clone();
-            let out = resolve_path(root, &p.stdout_log);
-            tokio::spawn(async move {
-                let _ = follow_file(out, crate::color::prefix(&name), txo).await;
-            });
-            let txe = tx.clone();
-            let namee = p.name.clone();
-            let err = resolve_path(root, &p.stderr_log);
-            tokio::spawn(async move {
-                let _ =
-                    follow_file(err, format!("{}[ERR] ", crate::color::prefix(&namee)), txe).await;
-            });
+        let names: Vec<String> = processes.iter().map(|p| p.name.clone()).collect();
+        // JSON consumers see restarts as a new `pid` in the records
+        if filters.format == LogOutput::Text {
+            tokio::spawn(watch_restarts(
+                root.to_path_buf(),
+                names.clone(),
+                tx.clone(),
+            ));
         }
 
         // Print lines as they arrive; stop on Ctrl+C / signals
@@ -440,10 +2758,44 @@ fn follow_combined(
                 tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())?;
             let mut sigterm =
                 tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+            // Ctrl+R restarts the followed processes when attached to a terminal
+            let key_mode = crate::term::KeyMode::enable();
+            let mut keys = match key_mode {
+                Some(_) => crate::term::spawn_key_reader(),
+                None => mpsc::channel(1).1,
+            };
             loop {
                 tokio::select! {
-                    Some(line) = rx.recv() => { println!("{}", line); },
-                    _ = sigint.recv() => { break; },
+                    Some(line) = rx.recv() => { outln!("{}", line); },
+                    Some(key) = keys.recv() => {
+                        if key == crate::term::CTRL_R {
+                            request_restart(root, &names).await;
+                        } else if key == crate::term::CTRL_D && attach {
+                            break;
+                        }
+                    },
+                    _ = sigint.recv() => {
+                        if !attach || key_mode.is_none() {
+                            break;
+                        }
+                        // Log lines queue up in `rx` while the prompt waits, then in the files
+                        out!("Stop all processes? [y/N] ");
+                        let _ = std::io::Write::flush(&mut std::io::stdout());
+                        tokio::select! {
+                            key = keys.recv() => {
+                                if matches!(key, Some(b'y' | b'Y')) {
+                                    outln!("y");
+                                    return Ok(FollowEnd::Stop);
+                                }
+                                outln!();
+                            },
+                            // A second Ctrl+C at the prompt detaches
+                            _ = sigint.recv() => {
+                                outln!();
+                                break;
+                            },
+                        }
+                    },
                     _ = sigterm.recv() => { break; }
                 }
             }
@@ -452,62 +2804,93 @@ fn follow_combined(
         {
             loop {
                 tokio::select! {
-                    Some(line) = rx.recv() => { println!("{}", line); },
+                    Some(line) = rx.recv() => { outln!("{}", line); },
                     _ = tokio::signal::ctrl_c() => { break; },
                 }
             }
         }
-        Ok::<(), anyhow::Error>(())
-    })?;
-    Ok(())
+        Ok::<FollowEnd, anyhow::Error>(FollowEnd::Detach)
+    })
 }
 
-async fn follow_file(
-    path: String,
-    prefix: String,
-    tx: tokio::sync::mpsc::UnboundedSender<String>,
-) -> Result<()> {
-    use tokio::fs::OpenOptions as AOpenOptions;
-    use tokio::io::{AsyncReadExt, AsyncSeekExt};
-    use tokio::time::{sleep, Duration};
-
-    // Wait for file to exist
-    let mut retries = 0;
-    loop {
-        if std::path::Path::new(&path).exists() {
-            break;
-        }
-        if retries > 40 {
-            return Ok(());
-        }
-        sleep(Duration::from_millis(250)).await;
-        retries += 1;
+/// Queue a followed line for printing, waiting while the queue is full. False once nothing
+/// prints them any more.
+fn queue_line(tx: &tokio::sync::mpsc::Sender<String>, line: String) -> bool {
+    use tokio::sync::mpsc::error::TrySendError;
+    match tx.try_send(line) {
+        Ok(()) => true,
+        // Followers share a task with other work; let the runtime move that elsewhere
+        Err(TrySendError::Full(line)) => tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current()
+                .block_on(tx.send(line))
+                .is_ok()
+        }),
+        Err(TrySendError::Closed(_)) => false,
     }
+}
 
-    let mut f = AOpenOptions::new().read(true).open(&path).await?;
-    // Seek to end
-    let mut pos = f.seek(std::io::SeekFrom::End(0)).await?;
-    let mut buf = vec![0u8; 8192];
-    let mut partial = String::new();
+/// Ask the manager to restart `names`, reporting the outcome inline with the followed logs.
+#[cfg(unix)]
+async fn request_restart(root: &Path, names: &[String]) {
+    outln!("--- restarting {} ---", names.join(", "));
+    let state_dir = crate::state::state_dir_from_root(root);
+    let req = Request::Restart {
+        names: names.to_vec(),
+        grace: None,
+        soft: false,
+    };
+    let resp =
+        tokio::task::spawn_blocking(move || crate::control::send_request(&state_dir, &req)).await;
+    match resp {
+        Ok(Ok(resp)) if resp.ok => {}
+        Ok(Ok(resp)) => eprintln!("restart failed: {}", resp.message),
+        Ok(Err(e)) => eprintln!("restart failed: {}", e),
+        Err(e) => eprintln!("restart failed: {}", e),
+    }
+}
 
+/// Poll state.json and emit a marker line whenever a followed process comes back with a new PID.
+async fn watch_restarts(root: PathBuf, names: Vec<String>, tx: tokio::sync::mpsc::Sender<String>) {
+    let pids = |st: &ManagerState| -> HashMap<String, u32> {
+        st.processes
+            .iter()
+            .filter(|p| names.contains(&p.name))
+            .map(|p| (p.name.clone(), p.pid))
+            .collect()
+    };
+    let mut known = load_state_from_root(&root)
+        .map(|st| pids(&st))
+        .unwrap_or_default();
     loop {
-        let n = f.read(&mut buf).await?;
-        if n == 0 {
-            sleep(Duration::from_millis(300)).await;
-            // If file truncated, reset position
-            let len = f.metadata().await?.len();
-            if len < pos {
-                pos = f.seek(std::io::SeekFrom::End(0)).await?;
-            }
+        tokio::time::sleep(crate::power::scale(RESTART_WATCH_POLL)).await;
+        let Ok(st) = load_state_from_root(&root) else {
             continue;
+        };
+        let current = pids(&st);
+        for (name, pid) in &current {
+            match known.get(name) {
+                Some(old) if old != pid => {
+                    let _ = tx
+                        .send(format!(
+                            "{}--- process restarted (pid {} -> {}) ---",
+                            crate::color::stream_prefix(name, false),
+                            old,
+                            pid
+                        ))
+                        .await;
+                }
+                None => {
+                    let _ = tx
+                        .send(format!(
+                            "{}--- process started (pid {}) ---",
+                            crate::color::stream_prefix(name, false),
+                            pid
+                        ))
+                        .await;
+                }
+                _ => {}
+            }
         }
-        pos += n as u64;
-        let chunk = String::from_utf8_lossy(&buf[..n]);
-        partial.push_str(&chunk);
-        while let Some(idx) = partial.find('\n') {
-            let line = partial[..idx].to_string();
-            let _ = tx.send(format!("{}{}", prefix, line));
-            partial = partial[idx + 1..].to_string();
-        }
+        known.extend(current);
     }
 }
@@ -0,0 +1,190 @@
+// Live copies of a process's output (`mirror = "unix:/tmp/web.sock"`), for log viewers and
+// test harnesses that want the stream as it happens instead of tailing files. The mirror
+// never slows the process down: lines that a reader can't take right away are dropped for it.
+
+use std::path::{Path, PathBuf};
+
+use tokio::sync::broadcast;
+
+/// Where a process's output is mirrored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MirrorTarget {
+    /// A Unix socket the manager listens on; every connected client gets every line
+    Unix(PathBuf),
+    /// A named pipe, created if missing; lines are written while a reader has it open
+    Fifo(PathBuf),
+}
+
+impl MirrorTarget {
+    /// Parse `unix:PATH` or `fifo:PATH`. Relative paths are taken from the project root.
+    pub fn parse(s: &str, root: &Path) -> Result<MirrorTarget, String> {
+        let (kind, path) = s.split_once(':').unwrap_or(("", s));
+        if path.is_empty() {
+            return Err(format!(
+                "'mirror' must be \"unix:PATH\" or \"fifo:PATH\", got '{}'",
+                s
+            ));
+        }
+        let path = root.join(path);
+        match kind {
+            "unix" => Ok(MirrorTarget::Unix(path)),
+            "fifo" => Ok(MirrorTarget::Fifo(path)),
+            _ => Err(format!(
+                "'mirror' must be \"unix:PATH\" or \"fifo:PATH\", got '{}'",
+                s
+            )),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            MirrorTarget::Unix(path) | MirrorTarget::Fifo(path) => path,
+        }
+    }
+}
+
+/// How many lines a slow socket client may fall behind before it misses some.
+const CLIENT_BACKLOG: usize = 1024;
+
+/// The sending end of a process's mirror, shared by its stdout and stderr readers and kept
+/// across restarts.
+pub struct Mirror {
+    target: MirrorTarget,
+    lines: broadcast::Sender<String>,
+}
+
+impl Mirror {
+    /// Start serving `target`: bind the socket (replacing a stale one) or create the FIFO.
+    pub fn open(target: &MirrorTarget) -> std::io::Result<Mirror> {
+        let (lines, _) = broadcast::channel(CLIENT_BACKLOG);
+        if let Some(parent) = target.path().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        match target {
+            MirrorTarget::Unix(path) => {
+                if path.exists() {
+                    std::fs::remove_file(path)?;
+                }
+                let listener = tokio::net::UnixListener::bind(path)?;
+                tokio::spawn(serve_socket(listener, lines.clone()));
+            }
+            MirrorTarget::Fifo(path) => {
+                if !path.exists() {
+                    nix::unistd::mkfifo(
+                        path.as_path(),
+                        nix::sys::stat::Mode::from_bits_truncate(0o600),
+                    )
+                    .map_err(std::io::Error::from)?;
+                }
+                tokio::spawn(feed_fifo(path.clone(), lines.subscribe()));
+            }
+        }
+        Ok(Mirror {
+            target: target.clone(),
+            lines,
+        })
+    }
+
+    pub fn target(&self) -> &MirrorTarget {
+        &self.target
+    }
+
+    /// Pass on one line, newline included. Without readers it goes nowhere.
+    pub fn send(&self, line: &str) {
+        let _ = self.lines.send(line.to_string());
+    }
+}
+
+async fn serve_socket(listener: tokio::net::UnixListener, lines: broadcast::Sender<String>) {
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let mut rx = lines.subscribe();
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            let mut stream = stream;
+            loop {
+                match rx.recv().await {
+                    Ok(line) => {
+                        if stream.write_all(line.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+}
+
+/// Write lines to the FIFO while someone reads it. Opening without a reader fails right
+/// away, so lines sent then are dropped and the next one tries again.
+async fn feed_fifo(path: PathBuf, mut rx: broadcast::Receiver<String>) {
+    use std::io::Write;
+    let mut pipe: Option<std::fs::File> = None;
+    loop {
+        let line = match rx.recv().await {
+            Ok(line) => line,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if pipe.is_none() {
+            pipe = open_fifo(&path);
+        }
+        // The pipe is non-blocking: a full pipe drops the line, a gone reader closes it
+        if let Some(file) = pipe.as_mut() {
+            match file.write_all(line.as_bytes()) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(_) => pipe = None,
+            }
+        }
+    }
+}
+
+fn open_fifo(path: &Path) -> Option<std::fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .custom_flags(nix::fcntl::OFlag::O_NONBLOCK.bits())
+        .open(path)
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncBufReadExt;
+
+    #[test]
+    fn parses_targets() {
+        let root = Path::new("/proj");
+        assert_eq!(
+            MirrorTarget::parse("unix:/tmp/web.sock", root),
+            Ok(MirrorTarget::Unix("/tmp/web.sock".into()))
+        );
+        assert_eq!(
+            MirrorTarget::parse("fifo:tmp/web.fifo", root),
+            Ok(MirrorTarget::Fifo("/proj/tmp/web.fifo".into()))
+        );
+        assert!(MirrorTarget::parse("tcp:3000", root).is_err());
+        assert!(MirrorTarget::parse("unix:", root).is_err());
+    }
+
+    #[tokio::test]
+    async fn socket_clients_get_live_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("web.sock");
+        let mirror = Mirror::open(&MirrorTarget::Unix(path.clone())).unwrap();
+        // Nobody is listening yet; this one is gone
+        mirror.send("before\n");
+        let client = tokio::net::UnixStream::connect(&path).await.unwrap();
+        let mut lines = tokio::io::BufReader::new(client).lines();
+        // Give the accept loop a moment to subscribe the client
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        mirror.send("after\n");
+        assert_eq!(lines.next_line().await.unwrap().as_deref(), Some("after"));
+    }
+}
@@ -0,0 +1,65 @@
+// Which processes the Linux OOM killer picks first (`oom_score_adj`). Raising it for
+// expendable watchers and dev servers lets the kernel reclaim memory from them before it
+// touches a database. Elsewhere the setting is accepted and has no effect.
+
+use tokio::process::Command;
+
+/// Values the kernel accepts, from never kill (-1000) to kill first (1000).
+pub const RANGE: std::ops::RangeInclusive<i32> = -1000..=1000;
+
+/// Set the child's `oom_score_adj` just before it execs, so everything it forks inherits it.
+/// Lowering the value needs CAP_SYS_RESOURCE; when the kernel refuses, the process starts
+/// anyway and [`current`] tells what it got.
+#[cfg(target_os = "linux")]
+pub fn apply(cmd: &mut Command, value: i32) {
+    // Formatted here: the child must not allocate between fork and exec
+    let text = format!("{}\n", value);
+    // SAFETY: the closure only makes the open and write system calls
+    unsafe {
+        cmd.pre_exec(move || {
+            use nix::fcntl::{open, OFlag};
+            use nix::sys::stat::Mode;
+            use std::os::fd::FromRawFd;
+            let flags = OFlag::O_WRONLY | OFlag::O_CLOEXEC;
+            if let Ok(fd) = open(c"/proc/self/oom_score_adj", flags, Mode::empty()) {
+                let fd = std::os::fd::OwnedFd::from_raw_fd(fd);
+                let _ = nix::unistd::write(&fd, text.as_bytes());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn apply(_cmd: &mut Command, _value: i32) {}
+
+/// The `oom_score_adj` a running process has.
+#[cfg(target_os = "linux")]
+pub fn current(pid: u32) -> Option<i32> {
+    std::fs::read_to_string(format!("/proc/{}/oom_score_adj", pid))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn current(_pid: u32) -> Option<i32> {
+    None
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn children_start_with_the_configured_score() {
+        let mut cmd = Command::new("sleep");
+        cmd.arg("5");
+        // Raising the score never needs privileges
+        apply(&mut cmd, 700);
+        let mut child = cmd.spawn().unwrap();
+        assert_eq!(current(child.id().unwrap()), Some(700));
+        child.kill().await.unwrap();
+    }
+}
@@ -0,0 +1,36 @@
+// Stdout printing that tolerates a closed pipe: `oxproc logs -f | head` should end quietly
+// instead of panicking in println!.
+
+use std::io::{ErrorKind, Write};
+
+/// Write to stdout. A broken pipe exits the process successfully, like other Unix tools.
+pub fn write_stdout(args: std::fmt::Arguments) {
+    if let Err(e) = std::io::stdout().lock().write_fmt(args) {
+        #[cfg(unix)]
+        crate::term::restore();
+        if e.kind() == ErrorKind::BrokenPipe {
+            std::process::exit(0);
+        }
+        eprintln!("failed writing to stdout: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// `println!` replacement routed through [`write_stdout`].
+macro_rules! outln {
+    () => {
+        $crate::output::write_stdout(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::write_stdout(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+/// `print!` replacement routed through [`write_stdout`].
+macro_rules! out {
+    ($($arg:tt)*) => {
+        $crate::output::write_stdout(format_args!($($arg)*))
+    };
+}
+
+pub(crate) use {out, outln};
@@ -0,0 +1,183 @@
+// Machine-wide view (`oxproc ps --all`): every project's state.json under the oxproc state
+// home, checked against the live process table.
+
+use crate::output::outln;
+use crate::state::{self, ManagerHealth, ManagerState};
+use anyhow::Result;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::process::Command;
+
+/// One row of the overview table.
+#[derive(Debug)]
+struct Row {
+    project: String,
+    process: String,
+    pid: u32,
+    uptime: String,
+    cpu: String,
+    mem: String,
+}
+
+/// Every readable state.json, sorted by project name (or root).
+fn all_states() -> Vec<ManagerState> {
+    let Ok(entries) = std::fs::read_dir(crate::dirs::state_home()) else {
+        return Vec::new();
+    };
+    let mut states: Vec<ManagerState> = entries
+        .flatten()
+        .filter_map(|e| std::fs::read_to_string(state::state_file_path(&e.path())).ok())
+        .filter_map(|data| serde_json::from_str(&data).ok())
+        .collect();
+    states.sort_by(|a, b| a.manager.project_label().cmp(b.manager.project_label()));
+    states
+}
+
+/// Running managers of projects that contain `root` (a normalized project root), innermost
+/// first.
+pub fn enclosing_managers(root: &std::path::Path) -> Vec<ManagerState> {
+    let mut states: Vec<ManagerState> = all_states()
+        .into_iter()
+        .filter(|st| {
+            let parent = std::path::Path::new(&st.manager.project_root);
+            parent != root && root.starts_with(parent)
+        })
+        .filter(|st| st.manager.health() == ManagerHealth::Running)
+        .collect();
+    states.sort_by_key(|st| std::cmp::Reverse(st.manager.project_root.len()));
+    states
+}
+
+/// CPU percentage and RSS in KiB per PID, from one `ps` call.
+fn usage(pids: &[u32]) -> HashMap<u32, (String, u64)> {
+    if pids.is_empty() {
+        return HashMap::new();
+    }
+    let list = pids
+        .iter()
+        .map(|p| p.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let Ok(output) = Command::new("ps")
+        .args(["-o", "pid=,%cpu=,rss=", "-p", &list])
+        .output()
+    else {
+        return HashMap::new();
+    };
+    parse_usage(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_usage(text: &str) -> HashMap<u32, (String, u64)> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let cpu = fields.next()?.to_string();
+            let rss = fields.next()?.parse().ok()?;
+            Some((pid, (cpu, rss)))
+        })
+        .collect()
+}
+
+pub fn print_all() -> Result<()> {
+    let states = all_states();
+    let live: Vec<u32> = states
+        .iter()
+        .flat_map(|st| &st.processes)
+        .map(|p| p.pid)
+        .filter(|pid| state::pid_alive(*pid as i32))
+        .collect();
+    let usage = usage(&live);
+
+    let mut rows = Vec::new();
+    for st in &states {
+        let stale = st.manager.health() == ManagerHealth::Gone;
+        let project = if stale {
+            format!("{} (stale)", st.manager.project_label())
+        } else {
+            st.manager.project_label().to_string()
+        };
+        for p in &st.processes {
+            let (uptime, cpu, mem) = match usage.get(&p.pid) {
+                Some((cpu, rss)) if live.contains(&p.pid) => (
+                    (Utc::now() - p.started_at)
+                        .to_std()
+                        .map(state::format_ago)
+                        .unwrap_or_default(),
+                    format!("{}%", cpu),
+                    state::format_bytes(*rss * 1024),
+                ),
+                _ => ("exited".to_string(), "-".to_string(), "-".to_string()),
+            };
+            rows.push(Row {
+                project: project.clone(),
+                process: p.name.clone(),
+                pid: p.pid,
+                uptime,
+                cpu,
+                mem,
+            });
+        }
+    }
+    if rows.is_empty() {
+        outln!("No oxproc projects have state on this machine.");
+        return Ok(());
+    }
+
+    let project_width = rows
+        .iter()
+        .map(|r| r.project.len())
+        .max()
+        .unwrap_or(0)
+        .max(7);
+    let process_width = rows
+        .iter()
+        .map(|r| r.process.len())
+        .max()
+        .unwrap_or(0)
+        .max(7);
+    outln!(
+        "{:<pw$}  {:<nw$}  {:>7}  {:>7}  {:>6}  {:>7}",
+        "PROJECT",
+        "PROCESS",
+        "PID",
+        "UPTIME",
+        "CPU",
+        "MEM",
+        pw = project_width,
+        nw = process_width
+    );
+    for r in &rows {
+        outln!(
+            "{:<pw$}  {:<nw$}  {:>7}  {:>7}  {:>6}  {:>7}",
+            r.project,
+            r.process,
+            r.pid,
+            r.uptime,
+            r.cpu,
+            r.mem,
+            pw = project_width,
+            nw = process_width
+        );
+    }
+    let running = rows.iter().filter(|r| r.uptime != "exited").count();
+    outln!(
+        "{} process(es) running across {} project(s).",
+        running,
+        states.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ps_usage() {
+        let usage = parse_usage("  123  1.5  20480\n 456 0.0 512\ngarbage\n");
+        assert_eq!(usage[&123], ("1.5".to_string(), 20480));
+        assert_eq!(state::format_bytes(usage[&123].1 * 1024), "20.0M");
+        assert_eq!(state::format_bytes(usage[&456].1 * 1024), "512.0K");
+    }
+}
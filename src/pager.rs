@@ -0,0 +1,86 @@
+// Page long non-follow output through `$PAGER` when stdout is a terminal, like git does.
+
+use nix::unistd::{close, dup, dup2};
+use std::io::{IsTerminal, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::process::{Child, Command, Stdio};
+
+/// A running pager that our stdout is redirected into. Dropping it restores stdout and waits
+/// for the user to quit the pager.
+pub struct Pager {
+    child: Child,
+    saved_stdout: RawFd,
+}
+
+/// The pager command: `OXPROC_PAGER`, then `PAGER`, then `less`. Empty or `cat` disables paging.
+fn pager_command() -> Option<String> {
+    let cmd = std::env::var("OXPROC_PAGER")
+        .or_else(|_| std::env::var("PAGER"))
+        .unwrap_or_else(|_| "less".to_string());
+    let cmd = cmd.trim();
+    if cmd.is_empty() || cmd == "cat" {
+        None
+    } else {
+        Some(cmd.to_string())
+    }
+}
+
+impl Pager {
+    /// Start the pager when stdout is a terminal; `None` means print directly.
+    pub fn start() -> Option<Pager> {
+        if !std::io::stdout().is_terminal() {
+            return None;
+        }
+        let cmd = pager_command()?;
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&cmd).stdin(Stdio::piped());
+        if std::env::var_os("LESS").is_none() {
+            // Quit if it fits on one screen, pass colors through, don't clear the screen
+            command.env("LESS", "FRX");
+        }
+        let mut child = command.spawn().ok()?;
+        let stdin = child.stdin.take()?;
+        let _ = std::io::stdout().flush();
+        let saved_stdout = dup(1).ok()?;
+        if dup2(stdin.as_raw_fd(), 1).is_err() {
+            let _ = close(saved_stdout);
+            return None;
+        }
+        // Output still ends up on a terminal, so keep treating it as one for colors
+        crate::color::assume_tty();
+        Some(Pager {
+            child,
+            saved_stdout,
+        })
+    }
+}
+
+impl Drop for Pager {
+    fn drop(&mut self) {
+        let _ = std::io::stdout().flush();
+        // Putting the terminal back on fd 1 closes the pipe, so the pager sees EOF
+        let _ = dup2(self.saved_stdout, 1);
+        let _ = close(self.saved_stdout);
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pager_command_prefers_oxproc_pager_and_can_be_disabled() {
+        std::env::set_var("PAGER", "more");
+        std::env::set_var("OXPROC_PAGER", "less -S");
+        assert_eq!(pager_command().as_deref(), Some("less -S"));
+        std::env::remove_var("OXPROC_PAGER");
+        assert_eq!(pager_command().as_deref(), Some("more"));
+        for disabled in ["", "  ", "cat"] {
+            std::env::set_var("PAGER", disabled);
+            assert_eq!(pager_command(), None);
+        }
+        std::env::remove_var("PAGER");
+        assert_eq!(pager_command().as_deref(), Some("less"));
+    }
+}
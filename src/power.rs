@@ -0,0 +1,119 @@
+// Low-power mode (`low_power = true` in proc.toml): while the machine runs on battery, the
+// loops that poll on a timer (following logs, ready checks and dependency waits, process tree
+// and stats sampling) wait several times longer between rounds, so a long attached session
+// drains the laptop less. `--poll-interval` sets how often followers check log files.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// How many times longer polling loops wait on battery in low-power mode.
+const SLOWDOWN: u32 = 4;
+
+/// How long a battery reading is trusted before the power source is checked again.
+const RECHECK: Duration = Duration::from_secs(30);
+
+static LOW_POWER: AtomicBool = AtomicBool::new(false);
+static FOLLOW_POLL: OnceLock<Duration> = OnceLock::new();
+static BATTERY: Mutex<Option<(Instant, bool)>> = Mutex::new(None);
+
+pub fn set_low_power(on: bool) {
+    LOW_POWER.store(on, Ordering::Relaxed);
+}
+
+/// `--poll-interval`: how often followers check log files.
+pub fn set_follow_poll(interval: Duration) {
+    let _ = FOLLOW_POLL.set(interval);
+}
+
+/// How often to check followed files: `--poll-interval`, or else `default`.
+pub fn follow_poll(default: Duration) -> Duration {
+    scale(FOLLOW_POLL.get().copied().unwrap_or(default))
+}
+
+/// `interval`, stretched while low-power mode is on and the machine runs on battery.
+pub fn scale(interval: Duration) -> Duration {
+    if LOW_POWER.load(Ordering::Relaxed) && on_battery() {
+        interval * SLOWDOWN
+    } else {
+        interval
+    }
+}
+
+/// Whether the machine runs on battery, checked at most every [`RECHECK`].
+pub fn on_battery() -> bool {
+    let mut cached = BATTERY.lock().unwrap();
+    match *cached {
+        Some((at, on)) if at.elapsed() < RECHECK => on,
+        _ => {
+            let on = read_battery();
+            *cached = Some((Instant::now(), on));
+            on
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_battery() -> bool {
+    supplies_on_battery(std::path::Path::new("/sys/class/power_supply"))
+}
+
+/// The power source IOKit reports, through `pmset`.
+#[cfg(target_os = "macos")]
+fn read_battery() -> bool {
+    std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .is_ok_and(|o| String::from_utf8_lossy(&o.stdout).contains("'Battery Power'"))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn read_battery() -> bool {
+    false
+}
+
+/// On battery: no mains adapter in the sysfs `power_supply` dir is online and a battery is
+/// discharging.
+#[cfg(any(target_os = "linux", test))]
+fn supplies_on_battery(dir: &std::path::Path) -> bool {
+    let read = |supply: &std::path::Path, file: &str| {
+        std::fs::read_to_string(supply.join(file))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default()
+    };
+    let supplies: Vec<_> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .collect();
+    let mains = supplies
+        .iter()
+        .any(|s| read(s, "type") == "Mains" && read(s, "online") == "1");
+    !mains
+        && supplies
+            .iter()
+            .any(|s| read(s, "type") == "Battery" && read(s, "status") == "Discharging")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_power_source_from_sysfs() {
+        let dir = tempfile::tempdir().unwrap();
+        let supply = |name: &str, files: &[(&str, &str)]| {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(&path).unwrap();
+            for (file, value) in files {
+                std::fs::write(path.join(file), format!("{}\n", value)).unwrap();
+            }
+        };
+        assert!(!supplies_on_battery(dir.path()));
+        supply("BAT0", &[("type", "Battery"), ("status", "Discharging")]);
+        assert!(supplies_on_battery(dir.path()));
+        supply("AC", &[("type", "Mains"), ("online", "1")]);
+        assert!(!supplies_on_battery(dir.path()));
+    }
+}
@@ -0,0 +1,398 @@
+// Process tree inspection: which processes a managed process has spawned, and whether any of
+// them left its process group (where group signals can't reach them).
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// One process in a snapshot of the system process table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcEntry {
+    pub pid: u32,
+    pub ppid: u32,
+    pub pgid: i32,
+    pub name: String,
+}
+
+/// Snapshot the process table from /proc, falling back to `ps` where /proc is unavailable.
+pub fn snapshot() -> Vec<ProcEntry> {
+    let from_proc = snapshot_proc();
+    if !from_proc.is_empty() {
+        return from_proc;
+    }
+    snapshot_ps()
+}
+
+fn snapshot_proc() -> Vec<ProcEntry> {
+    let Ok(dir) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    dir.flatten()
+        .filter_map(|e| e.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+            parse_stat(&stat)
+        })
+        .collect()
+}
+
+/// Parse `/proc/<pid>/stat`: `pid (comm) state ppid pgrp ...`. The command name may itself
+/// contain spaces and parentheses, so split at the last `)`.
+fn parse_stat(stat: &str) -> Option<ProcEntry> {
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let pid = stat[..open].trim().parse().ok()?;
+    let name = stat[open + 1..close].to_string();
+    let mut rest = stat[close + 1..].split_whitespace();
+    let _state = rest.next()?;
+    let ppid = rest.next()?.parse().ok()?;
+    let pgid = rest.next()?.parse().ok()?;
+    Some(ProcEntry {
+        pid,
+        ppid,
+        pgid,
+        name,
+    })
+}
+
+fn snapshot_ps() -> Vec<ProcEntry> {
+    let Ok(out) = std::process::Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid=,pgid=,comm="])
+        .output()
+    else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let pid = parts.next()?.parse().ok()?;
+            let ppid = parts.next()?.parse().ok()?;
+            let pgid = parts.next()?.parse().ok()?;
+            let name = parts.collect::<Vec<_>>().join(" ");
+            Some(ProcEntry {
+                pid,
+                ppid,
+                pgid,
+                name,
+            })
+        })
+        .collect()
+}
+
+/// Clock ticks per second in `/proc/<pid>/stat` (`USER_HZ`, 100 on every Linux platform).
+const USER_HZ: f64 = 100.0;
+
+/// CPU time (user and system) used so far by `pids`, counting children they have reaped.
+/// Processes that are gone count as zero.
+pub fn cpu_time(pids: &[u32]) -> Duration {
+    if std::path::Path::new("/proc/self/stat").exists() {
+        let ticks: u64 = pids
+            .iter()
+            .filter_map(|pid| std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok())
+            .filter_map(|stat| stat_cpu_ticks(&stat))
+            .sum();
+        return Duration::from_secs_f64(ticks as f64 / USER_HZ);
+    }
+    if pids.is_empty() {
+        return Duration::ZERO;
+    }
+    let list: Vec<String> = pids.iter().map(|p| p.to_string()).collect();
+    let Ok(out) = std::process::Command::new("ps")
+        .args(["-o", "time=", "-p", &list.join(",")])
+        .output()
+    else {
+        return Duration::ZERO;
+    };
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(parse_ps_time)
+        .sum()
+}
+
+/// `utime + stime + cutime + cstime` from `/proc/<pid>/stat`, in clock ticks.
+fn stat_cpu_ticks(stat: &str) -> Option<u64> {
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().skip(11).take(4).collect();
+    if fields.len() < 4 {
+        return None;
+    }
+    fields.iter().map(|f| f.parse::<u64>().ok()).sum()
+}
+
+/// `ps -o time=`: `[[dd-]hh:]mm:ss`, with fractions on some systems.
+fn parse_ps_time(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let (days, clock) = match s.split_once('-') {
+        Some((d, rest)) => (d.parse::<f64>().ok()?, rest),
+        None => (0.0, s),
+    };
+    let mut secs = 0.0;
+    for part in clock.split(':') {
+        secs = secs * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(Duration::from_secs_f64(days * 86400.0 + secs))
+}
+
+/// Resident memory of `pids` together, in bytes. Processes that are gone count as zero.
+pub fn resident_memory(pids: &[u32]) -> u64 {
+    if std::path::Path::new("/proc/self/status").exists() {
+        return pids
+            .iter()
+            .filter_map(|pid| std::fs::read_to_string(format!("/proc/{}/status", pid)).ok())
+            .filter_map(|status| {
+                let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+                line.split_whitespace().nth(1)?.parse::<u64>().ok()
+            })
+            .sum::<u64>()
+            * 1024;
+    }
+    if pids.is_empty() {
+        return 0;
+    }
+    let list: Vec<String> = pids.iter().map(|p| p.to_string()).collect();
+    let Ok(out) = std::process::Command::new("ps")
+        .args(["-o", "rss=", "-p", &list.join(",")])
+        .output()
+    else {
+        return 0;
+    };
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|l| l.trim().parse::<u64>().ok())
+        .sum::<u64>()
+        * 1024
+}
+
+/// All descendants of `root` in `table`, parents before children.
+pub fn descendants(table: &[ProcEntry], root: u32) -> Vec<ProcEntry> {
+    let mut children: HashMap<u32, Vec<&ProcEntry>> = HashMap::new();
+    for p in table {
+        children.entry(p.ppid).or_default().push(p);
+    }
+    let mut out = Vec::new();
+    let mut queue = vec![root];
+    while let Some(pid) = queue.pop() {
+        if let Some(kids) = children.get(&pid) {
+            for kid in kids {
+                // Guard against pid reuse producing a loop
+                if kid.pid != root && !out.iter().any(|p: &ProcEntry| p.pid == kid.pid) {
+                    out.push((*kid).clone());
+                    queue.push(kid.pid);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Merge a fresh descendant list with previously recorded ones: recorded processes that are
+/// still running under the same name are kept even if they were reparented (double fork).
+pub fn merge_recorded(
+    table: &[ProcEntry],
+    current: Vec<ProcEntry>,
+    recorded: &[ProcEntry],
+) -> Vec<ProcEntry> {
+    let mut merged = current;
+    for old in recorded {
+        if merged.iter().any(|p| p.pid == old.pid) {
+            continue;
+        }
+        if let Some(live) = table
+            .iter()
+            .find(|p| p.pid == old.pid && p.name == old.name)
+        {
+            merged.push(live.clone());
+        }
+    }
+    merged
+}
+
+/// Print `entries` as an indented tree under `root`. Processes outside `pgid` are flagged.
+pub fn format_tree(root: u32, pgid: i32, entries: &[ProcEntry]) -> Vec<String> {
+    fn walk(
+        parent: u32,
+        depth: usize,
+        pgid: i32,
+        entries: &[ProcEntry],
+        seen: &mut Vec<u32>,
+        out: &mut Vec<String>,
+    ) {
+        for e in entries.iter().filter(|e| e.ppid == parent) {
+            if seen.contains(&e.pid) {
+                continue;
+            }
+            seen.push(e.pid);
+            out.push(line(e, depth, pgid));
+            walk(e.pid, depth + 1, pgid, entries, seen, out);
+        }
+    }
+    fn line(e: &ProcEntry, depth: usize, pgid: i32) -> String {
+        let escaped = if e.pgid != pgid {
+            format!(" [outside pgid {}: pgid {}]", pgid, e.pgid)
+        } else {
+            String::new()
+        };
+        format!("{}└─ {} {}{}", "   ".repeat(depth), e.pid, e.name, escaped)
+    }
+    let mut out = Vec::new();
+    let mut seen = Vec::new();
+    walk(root, 1, pgid, entries, &mut seen, &mut out);
+    // Reparented processes no longer hang off the tree; list them at the top level
+    for e in entries {
+        if !seen.contains(&e.pid) {
+            seen.push(e.pid);
+            out.push(format!("{} (reparented)", line(e, 1, pgid)));
+        }
+    }
+    out
+}
+
+/// Descendants that left the process group: group signals won't reach them.
+pub fn escaped(entries: &[ProcEntry], pgid: i32) -> Vec<&ProcEntry> {
+    entries.iter().filter(|e| e.pgid != pgid).collect()
+}
+
+/// SIGKILL descendants that escaped `pgid` and are still running (same pid and name as
+/// recorded, so a recycled pid is left alone). Returns the processes that were killed.
+#[cfg(unix)]
+pub fn kill_escaped(entries: &[ProcEntry], pgid: i32) -> Vec<ProcEntry> {
+    use nix::sys::signal::{kill, Signal};
+    use nix::unistd::Pid;
+
+    live_escapees(&snapshot(), entries, pgid)
+        .into_iter()
+        .filter(|e| kill(Pid::from_raw(e.pid as i32), Signal::SIGKILL).is_ok())
+        .cloned()
+        .collect()
+}
+
+/// Escaped `entries` that `table` still lists under the same pid and name.
+fn live_escapees<'a>(
+    table: &[ProcEntry],
+    entries: &'a [ProcEntry],
+    pgid: i32,
+) -> Vec<&'a ProcEntry> {
+    escaped(entries, pgid)
+        .into_iter()
+        .filter(|e| table.iter().any(|t| t.pid == e.pid && t.name == e.name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pid: u32, ppid: u32, pgid: i32, name: &str) -> ProcEntry {
+        ProcEntry {
+            pid,
+            ppid,
+            pgid,
+            name: name.into(),
+        }
+    }
+
+    #[test]
+    fn parses_stat_with_odd_names() {
+        let e = parse_stat("42 (my (weird) proc) S 7 42 42 0 -1").unwrap();
+        assert_eq!(e, entry(42, 7, 42, "my (weird) proc"));
+    }
+
+    #[test]
+    fn reads_cpu_time() {
+        let stat = "42 (node) S 7 42 42 0 -1 4194304 100 0 0 0 250 50 7 3 20 0 1 0";
+        assert_eq!(stat_cpu_ticks(stat), Some(310));
+        assert_eq!(parse_ps_time("01:02"), Some(Duration::from_secs(62)));
+        assert_eq!(
+            parse_ps_time("1-02:00:05"),
+            Some(Duration::from_secs(86400 + 7205))
+        );
+        let busy = std::time::Instant::now();
+        while busy.elapsed() < Duration::from_millis(50) {}
+        assert!(cpu_time(&[std::process::id()]) > Duration::ZERO);
+    }
+
+    #[test]
+    fn walks_descendants_and_flags_escapees() {
+        let table = vec![
+            entry(10, 1, 10, "sh"),
+            entry(11, 10, 10, "node"),
+            entry(12, 11, 10, "esbuild"),
+            entry(13, 11, 13, "daemon"),
+            entry(20, 1, 20, "other"),
+        ];
+        let tree = descendants(&table, 10);
+        let pids: Vec<u32> = tree.iter().map(|e| e.pid).collect();
+        assert_eq!(pids.len(), 3);
+        assert!(!pids.contains(&20));
+        assert_eq!(escaped(&tree, 10).len(), 1);
+
+        let lines = format_tree(10, 10, &tree);
+        assert_eq!(lines[0], "   └─ 11 node");
+        assert!(lines
+            .iter()
+            .any(|l| l.contains("13 daemon [outside pgid 10")));
+    }
+
+    #[test]
+    fn keeps_reparented_recorded_processes() {
+        let recorded = vec![entry(13, 11, 13, "daemon")];
+        // 13 double-forked away and is now a child of init
+        let table = vec![entry(10, 1, 10, "sh"), entry(13, 1, 13, "daemon")];
+        let merged = merge_recorded(&table, descendants(&table, 10), &recorded);
+        assert_eq!(merged, vec![entry(13, 1, 13, "daemon")]);
+    }
+
+    #[test]
+    fn targets_only_escapees_that_still_run_under_their_name() {
+        let recorded = vec![
+            entry(12, 11, 10, "esbuild"),
+            entry(13, 11, 13, "daemon"),
+            entry(14, 11, 14, "worker"),
+            entry(15, 11, 15, "watcher"),
+        ];
+        let table = vec![
+            entry(12, 11, 10, "esbuild"),
+            entry(13, 1, 13, "daemon"),
+            // 14 exited and its pid went to something else; 15 is gone
+            entry(14, 1, 14, "bash"),
+        ];
+        let targets = live_escapees(&table, &recorded, 10);
+        assert_eq!(targets, vec![&entry(13, 11, 13, "daemon")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kills_live_escapees_and_nothing_else() {
+        use std::os::unix::process::{CommandExt, ExitStatusExt};
+        use std::process::Command;
+
+        let own_group = nix::unistd::getpgrp().as_raw();
+        let spawn = |own: bool| {
+            let mut cmd = Command::new("sleep");
+            cmd.arg("30");
+            if own {
+                cmd.process_group(0);
+            }
+            cmd.spawn().unwrap()
+        };
+        let mut escapee = spawn(true);
+        let mut renamed = spawn(true);
+        let mut member = spawn(false);
+        let me = std::process::id();
+        let recorded = vec![
+            entry(escapee.id(), me, escapee.id() as i32, "sleep"),
+            // Same pid, different name: a recycled pid that must be left alone
+            entry(renamed.id(), me, renamed.id() as i32, "node"),
+            entry(member.id(), me, own_group, "sleep"),
+        ];
+        let killed = kill_escaped(&recorded, own_group);
+        assert_eq!(killed, vec![recorded[0].clone()]);
+        assert_eq!(escapee.wait().unwrap().signal(), Some(9));
+        for child in [&mut renamed, &mut member] {
+            assert!(child.try_wait().unwrap().is_none());
+            child.kill().unwrap();
+            child.wait().unwrap();
+        }
+    }
+}
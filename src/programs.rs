@@ -0,0 +1,195 @@
+// Missing-program diagnostics for `check` and `start --dry-run`: find the programs a process
+// command runs and report those that are not on its PATH, with a hint at the likely fix,
+// instead of leaving an opaque `sh: 1: next: not found` in a log file. Best effort: it reads
+// plain command words and skips anything it cannot resolve without running the shell.
+
+use crate::config::{Diagnostic, ProcessConfig};
+use std::path::{Path, PathBuf};
+
+/// Words that start a command but are not programs; the next word is checked instead.
+const PREFIXES: &[&str] = &[
+    "if", "then", "else", "elif", "while", "until", "do", "!", "time", "exec", "nohup", "command",
+    "env",
+];
+
+/// Shell keywords and builtins: the rest of that command is not checked.
+const BUILTINS: &[&str] = &[
+    "fi", "done", "esac", "for", "case", "select", "function", "{", "}", "[", "[[", "cd", "echo",
+    "printf", "exit", "export", "trap", "read", "set", "unset", "source", ".", "eval", "wait",
+    "true", "false", "test", "shift", "local", "return", "break", "continue", "ulimit", "umask",
+    "alias", "type", "kill", ":",
+];
+
+/// Warnings for programs that processes run but that cannot be found.
+pub fn check(root: &Path, configs: &[ProcessConfig]) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    for c in configs {
+        let dir = match &c.cwd {
+            Some(cwd) => root.join(cwd),
+            None => root.to_path_buf(),
+        };
+        // A wrapper (direnv, mise) sets up its own PATH; we can't see it from here
+        if c.env.is_wrapped(&dir) {
+            continue;
+        }
+        let path = search_path(c);
+        for program in programs(&c.render_command(root)) {
+            if !found(&program, &dir, &path) {
+                let mut message = format!("'{}' not found", program);
+                if !program.contains('/') {
+                    message.push_str(" on PATH");
+                }
+                if let Some(hint) = hint(&program, &dir) {
+                    message.push_str(&format!(" ({})", hint));
+                }
+                out.push(Diagnostic {
+                    scope: c.name.clone(),
+                    message,
+                });
+            }
+        }
+    }
+    out
+}
+
+/// The directories searched for the process's programs: `path_prepend`, then its `PATH`.
+fn search_path(c: &ProcessConfig) -> Vec<PathBuf> {
+    let path = c
+        .env
+        .vars
+        .get("PATH")
+        .map(std::ffi::OsString::from)
+        .or_else(|| std::env::var_os("PATH"))
+        .unwrap_or_default();
+    c.env
+        .path_prepend
+        .iter()
+        .cloned()
+        .chain(std::env::split_paths(&path))
+        .collect()
+}
+
+fn found(program: &str, dir: &Path, path: &[PathBuf]) -> bool {
+    if program.contains('/') {
+        return is_executable(&dir.join(program));
+    }
+    path.iter().any(|d| is_executable(&d.join(program)))
+}
+
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+/// The program of each simple command in a shell command line, where it is a plain word.
+fn programs(command: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    // `2>&1` duplicates a descriptor; its `&` doesn't start a command
+    let command = command.replace(">&", ">").replace("<&", "<");
+    for segment in command.split(['\n', ';', '|', '&']) {
+        let mut words = segment.split_whitespace().peekable();
+        // Leading assignments (`RUST_LOG=debug cargo run`) and prefixes
+        while let Some(word) = words.peek() {
+            let assignment = word.split_once('=').is_some_and(|(name, _)| is_name(name));
+            if assignment || PREFIXES.contains(word) {
+                words.next();
+            } else {
+                break;
+            }
+        }
+        let Some(word) = words.next() else {
+            continue;
+        };
+        let plain = !word.contains(['$', '`', '\'', '"', '(', ')', '<', '>', '{', '~', '*']);
+        if plain && !BUILTINS.contains(&word) && !out.iter().any(|p| p == word) {
+            out.push(word.to_string());
+        }
+    }
+    out
+}
+
+fn is_name(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A likely fix for a missing program, from its name and the files in the process's dir.
+fn hint(program: &str, dir: &Path) -> Option<String> {
+    let has = |file: &str| dir.join(file).exists();
+    if program.contains('/') {
+        return Some(format!(
+            "no executable file at {}",
+            dir.join(program.trim_start_matches("./")).display()
+        ));
+    }
+    let toolchain = match program {
+        "node" | "npm" | "npx" => Some("install Node.js"),
+        "yarn" | "pnpm" => Some("install it, e.g. with `corepack enable`"),
+        "cargo" | "rustc" => Some("install Rust with rustup"),
+        "cargo-watch" => Some("install it with `cargo install cargo-watch`"),
+        "python" | "python3" | "pip" | "pip3" => Some("install Python, or activate a virtualenv"),
+        "ruby" | "gem" | "bundle" => Some("install Ruby"),
+        "go" => Some("install Go"),
+        "docker" => Some("install Docker"),
+        _ => None,
+    };
+    if let Some(toolchain) = toolchain {
+        return Some(toolchain.to_string());
+    }
+    if has("package.json") {
+        return Some(if has("node_modules") {
+            format!(
+                "not in node_modules/.bin either; run it through npm (`npx {}`) or add it as a dependency",
+                program
+            )
+        } else {
+            "node_modules is missing; run `npm install`".to_string()
+        });
+    }
+    if has("Gemfile") {
+        return Some(format!(
+            "run `bundle install`, or `bundle exec {}` / `bin/{}`",
+            program, program
+        ));
+    }
+    if has("pyproject.toml") || has("requirements.txt") {
+        return Some("activate the project's virtualenv or install its requirements".to_string());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_programs_in_shell_commands() {
+        assert_eq!(
+            programs("RUST_LOG=debug cargo run -p api && ./bin/worker | tee out.log"),
+            vec!["cargo", "./bin/worker", "tee"]
+        );
+        assert_eq!(
+            programs("while true; do echo 'tick'; sleep 2; done"),
+            vec!["sleep"]
+        );
+        assert_eq!(
+            programs("exec $HOME/bin/app; \"$CMD\" x"),
+            Vec::<String>::new()
+        );
+        assert_eq!(programs("cd web && npm run dev"), vec!["npm"]);
+        assert_eq!(
+            programs("rm -f x >/dev/null 2>&1; exec run"),
+            vec!["rm", "run"]
+        );
+    }
+
+    #[test]
+    fn hints_at_project_fixes() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("package.json"), "{}").unwrap();
+        assert!(hint("next", dir.path()).unwrap().contains("npm install"));
+        assert_eq!(hint("npm", dir.path()).unwrap(), "install Node.js");
+        assert!(hint("./run.sh", dir.path()).unwrap().contains("run.sh"));
+    }
+}
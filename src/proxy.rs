@@ -0,0 +1,514 @@
+// `type = "proxy"`: a small HTTP reverse proxy in front of the project's processes, so a dev
+// setup needs no separate Caddy or nginx config. Requests go to the route with the longest
+// matching path prefix. While a backend process is starting (or down) the browser gets a page
+// that says so and reloads itself, instead of a connection error.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::config::ProcessConfig;
+use crate::state::ProcessState;
+
+/// A proxy's `listen` port and `routes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxySpec {
+    pub listen: u16,
+    /// Path prefix and where it goes, longest prefix first
+    pub routes: Vec<(String, Target)>,
+}
+
+/// Where a route sends its requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// A managed process, reached on its `port` (`"/api" = "api"`)
+    Process(String),
+    /// A fixed address (`"/" = "3000"` or `"localhost:3000"`)
+    Address(String),
+    /// Static files from a directory (`"/assets" = "files:public"`)
+    Files(PathBuf),
+}
+
+impl Target {
+    fn parse(s: &str, root: &Path) -> Result<Target, String> {
+        if let Some(dir) = s.strip_prefix("files:") {
+            return Ok(Target::Files(root.join(dir)));
+        }
+        if s.parse::<u16>().is_ok() {
+            return Ok(Target::Address(format!("127.0.0.1:{}", s)));
+        }
+        if s.contains(':') {
+            return Ok(Target::Address(s.to_string()));
+        }
+        if s.is_empty() {
+            return Err("route targets must not be empty".into());
+        }
+        Ok(Target::Process(s.to_string()))
+    }
+}
+
+impl ProxySpec {
+    /// Read `listen` and `routes = { "/api" = "api", "/" = "web" }` from a process table.
+    pub fn from_table(tbl: &toml::value::Table, root: &Path) -> Result<ProxySpec, String> {
+        let listen = crate::builtin::listen_port(tbl)?;
+        let Some(table) = tbl.get("routes").and_then(|v| v.as_table()) else {
+            return Err(
+                "a proxy needs 'routes', e.g. { \"/api\" = \"api\", \"/\" = \"web\" }".into(),
+            );
+        };
+        let mut routes = Vec::new();
+        for (prefix, target) in table {
+            if !prefix.starts_with('/') {
+                return Err(format!("route '{}' must start with '/'", prefix));
+            }
+            let target = target
+                .as_str()
+                .ok_or_else(|| format!("route '{}' must be a string", prefix))?;
+            routes.push((prefix.clone(), Target::parse(target, root)?));
+        }
+        routes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        Ok(ProxySpec { listen, routes })
+    }
+
+    /// The route serving `path`, if any.
+    fn route(&self, path: &str) -> Option<(&str, &Target)> {
+        self.routes
+            .iter()
+            .find(|(prefix, _)| {
+                let prefix = prefix.trim_end_matches('/');
+                path.strip_prefix(prefix)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+            })
+            .map(|(prefix, target)| (prefix.as_str(), target))
+    }
+}
+
+/// Processes a proxy routes to must exist and have a port.
+pub fn check_routes(spec: &ProxySpec, configs: &[ProcessConfig]) -> Result<(), String> {
+    for (prefix, target) in &spec.routes {
+        let Target::Process(name) = target else {
+            continue;
+        };
+        match configs.iter().find(|c| &c.name == name) {
+            None => {
+                return Err(format!(
+                    "route '{}' goes to unknown process '{}'",
+                    prefix, name
+                ))
+            }
+            Some(c) if c.effective_port().is_none() => {
+                return Err(format!(
+                    "route '{}' goes to '{}', which has no 'port'",
+                    prefix, name
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Longest request head accepted.
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+
+/// How long to wait for a backend to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Serve until killed.
+pub async fn serve(
+    root: &Path,
+    spec: ProxySpec,
+    configs: Vec<ProcessConfig>,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", spec.listen)).await?;
+    println!("proxy listening on http://127.0.0.1:{}", spec.listen);
+    for (prefix, target) in &spec.routes {
+        println!("  {} -> {}", prefix, describe(target));
+    }
+    run(listener, root.to_path_buf(), spec, configs).await
+}
+
+async fn run(
+    listener: TcpListener,
+    root: PathBuf,
+    spec: ProxySpec,
+    configs: Vec<ProcessConfig>,
+) -> anyhow::Result<()> {
+    let shared = std::sync::Arc::new((root, spec, configs));
+    loop {
+        let (client, peer) = listener.accept().await?;
+        let shared = shared.clone();
+        tokio::spawn(async move {
+            let (root, spec, configs) = &*shared;
+            if let Err(e) = handle(client, peer, root, spec, configs).await {
+                eprintln!("{}: {}", peer, e);
+            }
+        });
+    }
+}
+
+fn describe(target: &Target) -> String {
+    match target {
+        Target::Process(name) => name.clone(),
+        Target::Address(addr) => addr.clone(),
+        Target::Files(dir) => dir.display().to_string(),
+    }
+}
+
+async fn handle(
+    mut client: TcpStream,
+    peer: std::net::SocketAddr,
+    root: &Path,
+    spec: &ProxySpec,
+    configs: &[ProcessConfig],
+) -> std::io::Result<()> {
+    let Some((head, body_start)) = read_head(&mut client).await? else {
+        return Ok(());
+    };
+    let Some(request) = Request::parse(&head) else {
+        return respond(
+            &mut client,
+            page(400, "Bad Request", "That is not an HTTP request.", None),
+        )
+        .await;
+    };
+    let Some((prefix, target)) = spec.route(&request.path) else {
+        println!("{} {} -> no route (404)", request.method, request.path);
+        let message = format!("No route matches {}.", request.path);
+        return respond(&mut client, page(404, "Not Found", &message, None)).await;
+    };
+    let (name, addr) = match target {
+        Target::Files(dir) => {
+            let path = request.path.split('?').next().unwrap_or("/");
+            let rest = &path[prefix.trim_end_matches('/').len()..];
+            let response = static_file(dir, rest, &request.method).await;
+            println!("{} {} -> {}", request.method, request.path, dir.display());
+            return respond(&mut client, response).await;
+        }
+        Target::Address(addr) => (addr.clone(), addr.clone()),
+        Target::Process(name) => {
+            let port = configs
+                .iter()
+                .find(|c| &c.name == name)
+                .and_then(|c| c.effective_port())
+                .unwrap_or_default();
+            if let Some(response) = not_ready(root, name) {
+                println!(
+                    "{} {} -> {} (not ready, 503)",
+                    request.method, request.path, name
+                );
+                return respond(&mut client, response).await;
+            }
+            (name.clone(), format!("127.0.0.1:{}", port))
+        }
+    };
+    let backend = match tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&addr)).await {
+        Ok(Ok(backend)) => backend,
+        _ => {
+            println!(
+                "{} {} -> {} (unreachable, 502)",
+                request.method, request.path, name
+            );
+            let message = format!("{} is not accepting connections on {} yet.", name, addr);
+            return respond(&mut client, page(502, "Bad Gateway", &message, Some(2))).await;
+        }
+    };
+    println!("{} {} -> {}", request.method, request.path, name);
+    let mut backend = backend;
+    backend
+        .write_all(rewrite_head(&head, peer).as_bytes())
+        .await?;
+    backend.write_all(&body_start).await?;
+    tokio::io::copy_bidirectional(&mut client, &mut backend).await?;
+    Ok(())
+}
+
+/// Read up to the blank line ending the request head. Returns the head and whatever body
+/// bytes came with it, or `None` if the client went away first.
+async fn read_head(client: &mut TcpStream) -> std::io::Result<Option<(String, Vec<u8>)>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let n = client.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let body = buf.split_off(end + 4);
+            return Ok(Some((String::from_utf8_lossy(&buf).into_owned(), body)));
+        }
+        if buf.len() > MAX_HEAD_BYTES {
+            return Err(std::io::Error::other("request head too large"));
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+}
+
+impl Request {
+    fn parse(head: &str) -> Option<Request> {
+        let mut parts = head.lines().next()?.split_whitespace();
+        let method = parts.next()?.to_string();
+        let path = parts.next()?.to_string();
+        parts
+            .next()?
+            .starts_with("HTTP/")
+            .then_some(Request { method, path })
+    }
+}
+
+/// The head as sent to the backend: one request per connection (unless it is upgrading to
+/// a WebSocket), with the usual `X-Forwarded-*` headers.
+fn rewrite_head(head: &str, peer: std::net::SocketAddr) -> String {
+    let mut lines = head.trim_end().split("\r\n");
+    let mut out = format!("{}\r\n", lines.next().unwrap_or_default());
+    let headers: Vec<(&str, &str)> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim(), v.trim()))
+        .collect();
+    let upgrade = headers
+        .iter()
+        .any(|(k, _)| k.eq_ignore_ascii_case("upgrade"));
+    let mut host = None;
+    for (key, value) in &headers {
+        let hop = ["connection", "keep-alive", "proxy-connection"]
+            .iter()
+            .any(|h| key.eq_ignore_ascii_case(h));
+        if hop && !upgrade {
+            continue;
+        }
+        if key.eq_ignore_ascii_case("host") {
+            host = Some(*value);
+        }
+        out.push_str(&format!("{}: {}\r\n", key, value));
+    }
+    if !upgrade {
+        out.push_str("Connection: close\r\n");
+    }
+    out.push_str(&format!("X-Forwarded-For: {}\r\n", peer.ip()));
+    out.push_str("X-Forwarded-Proto: http\r\n");
+    if let Some(host) = host {
+        out.push_str(&format!("X-Forwarded-Host: {}\r\n", host));
+    }
+    out.push_str("\r\n");
+    out
+}
+
+/// A page for a backend process that can't take requests yet, from what state.json says.
+fn not_ready(root: &Path, name: &str) -> Option<Vec<u8>> {
+    let st = crate::state::load_state_from_root(root).ok()?;
+    let state = st
+        .processes
+        .iter()
+        .find(|p| p.name == name)
+        .map(|p| p.state)
+        .unwrap_or(if st.pending.iter().any(|n| n == name) {
+            ProcessState::Pending
+        } else {
+            ProcessState::Stopped
+        });
+    let message = match state {
+        ProcessState::Ready | ProcessState::Running => return None,
+        ProcessState::Starting | ProcessState::Pending | ProcessState::Restarting => {
+            format!("{} is starting; this page reloads when it is up.", name)
+        }
+        other => format!(
+            "{} is {}. Start it with `oxproc start {}`.",
+            name, other, name
+        ),
+    };
+    Some(page(503, "Service Unavailable", &message, Some(1)))
+}
+
+async fn static_file(dir: &Path, rest: &str, method: &str) -> Vec<u8> {
+    if method != "GET" && method != "HEAD" {
+        return page(
+            405,
+            "Method Not Allowed",
+            "Static files are read-only.",
+            None,
+        );
+    }
+    let relative = rest.trim_start_matches('/');
+    if relative.split('/').any(|segment| segment == "..") {
+        return page(404, "Not Found", "No such file.", None);
+    }
+    let mut path = dir.join(relative);
+    if path.is_dir() {
+        path = path.join("index.html");
+    }
+    let Ok(body) = tokio::fs::read(&path).await else {
+        return page(
+            404,
+            "Not Found",
+            &format!("No file at /{}.", relative),
+            None,
+        );
+    };
+    let mut response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        content_type(&path),
+        body.len()
+    )
+    .into_bytes();
+    if method == "GET" {
+        response.extend_from_slice(&body);
+    }
+    response
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+    {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css",
+        "js" | "mjs" => "text/javascript",
+        "json" | "map" => "application/json",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "ico" => "image/x-icon",
+        "wasm" => "application/wasm",
+        "woff2" => "font/woff2",
+        "txt" => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// A small HTML page, reloading itself every `refresh` seconds if given.
+fn page(status: u16, reason: &str, message: &str, refresh: Option<u32>) -> Vec<u8> {
+    let refresh = refresh
+        .map(|s| format!("<meta http-equiv=\"refresh\" content=\"{}\">", s))
+        .unwrap_or_default();
+    let body = format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\">{}<title>{} {}</title>\
+         <style>body{{font:16px system-ui,sans-serif;margin:4em auto;max-width:40em;color:#333}}\
+         h1{{font-size:1.4em}}small{{color:#888}}</style></head><body><h1>{} {}</h1><p>{}</p>\
+         <small>oxproc proxy</small></body></html>",
+        refresh,
+        status,
+        reason,
+        status,
+        reason,
+        html_escape(message)
+    );
+    format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+async fn respond(client: &mut TcpStream, response: Vec<u8>) -> std::io::Result<()> {
+    client.write_all(&response).await?;
+    client.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(routes: &str) -> ProxySpec {
+        let tbl: toml::value::Table =
+            toml::from_str(&format!("listen = 8080\nroutes = {}", routes)).unwrap();
+        ProxySpec::from_table(&tbl, Path::new("/proj")).unwrap()
+    }
+
+    #[test]
+    fn routes_by_longest_prefix() {
+        let spec = spec(r#"{ "/" = "web", "/api" = "4000", "/assets/" = "files:public" }"#);
+        assert_eq!(
+            spec.route("/api/users?page=2"),
+            Some(("/api", &Target::Address("127.0.0.1:4000".into())))
+        );
+        assert_eq!(spec.route("/api"), spec.route("/api/"));
+        assert_eq!(
+            spec.route("/apiary"),
+            Some(("/", &Target::Process("web".into())))
+        );
+        assert_eq!(
+            spec.route("/assets/app.css"),
+            Some(("/assets/", &Target::Files("/proj/public".into())))
+        );
+    }
+
+    #[test]
+    fn forwards_one_request_per_connection() {
+        let head = "GET /api HTTP/1.1\r\nHost: localhost:8080\r\nConnection: keep-alive\r\n\r\n";
+        let peer = "127.0.0.1:5555".parse().unwrap();
+        let out = rewrite_head(head, peer);
+        assert!(out.starts_with("GET /api HTTP/1.1\r\nHost: localhost:8080\r\n"));
+        assert!(!out.contains("keep-alive"));
+        assert!(out.contains("Connection: close\r\n"));
+        assert!(out.contains("X-Forwarded-Host: localhost:8080\r\n"));
+        assert!(out.ends_with("\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn proxies_to_backends_and_explains_unreachable_ones() {
+        // A backend answering every request with its path
+        let backend = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let backend_addr = backend.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut conn, _) = backend.accept().await.unwrap();
+                let (head, _) = read_head(&mut conn).await.unwrap().unwrap();
+                let path = Request::parse(&head).unwrap().path;
+                let reply = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                    path.len(),
+                    path
+                );
+                conn.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+        // Nothing listens on a port we just freed
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed_addr = closed.local_addr().unwrap();
+        drop(closed);
+
+        let spec = spec(&format!(
+            r#"{{ "/" = "{}", "/down" = "{}" }}"#,
+            backend_addr, closed_addr
+        ));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(run(listener, PathBuf::from("/proj"), spec, Vec::new()));
+
+        let get = |path: &str| {
+            let request = format!("GET {} HTTP/1.1\r\nHost: test\r\n\r\n", path);
+            async move {
+                let mut conn = TcpStream::connect(addr).await.unwrap();
+                conn.write_all(request.as_bytes()).await.unwrap();
+                let mut response = String::new();
+                conn.read_to_string(&mut response).await.unwrap();
+                response
+            }
+        };
+        let ok = get("/hello").await;
+        assert!(ok.starts_with("HTTP/1.1 200"), "{}", ok);
+        assert!(ok.ends_with("/hello"));
+        let down = get("/down/x").await;
+        assert!(down.starts_with("HTTP/1.1 502"), "{}", down);
+        assert!(down.contains("http-equiv=\"refresh\""));
+    }
+}
@@ -0,0 +1,268 @@
+// Readiness probes (`ready = "tcp:3000"` or `ready = { cmd = "..." }`) and the checklist
+// shown by `start --follow`.
+
+use crate::config::ProcessConfig;
+use crate::state::{load_state_from_root, pid_alive, ManagerState, ProcessState};
+use std::fmt;
+use std::io::{IsTerminal, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How to tell that a running process is ready to serve.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadyCheck {
+    /// A TCP connection to `host:port` succeeds
+    Tcp { host: String, port: u16 },
+    /// A command exits 0; it runs in the process's directory and environment, with its
+    /// template variables (`ready = { cmd = "curl -f localhost:{port}/health" }`)
+    Command(String),
+}
+
+/// How long a `ready` command may run before it counts as failed.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl ReadyCheck {
+    /// Parse `tcp:3000` or `tcp:host:3000`.
+    pub fn parse(s: &str) -> Result<ReadyCheck, String> {
+        let Some(target) = s.strip_prefix("tcp:") else {
+            return Err(format!(
+                "unsupported ready check '{}' (expected tcp:PORT)",
+                s
+            ));
+        };
+        let (host, port) = match target.rsplit_once(':') {
+            Some((host, port)) => (host.to_string(), port),
+            None => ("127.0.0.1".to_string(), target),
+        };
+        let port = port
+            .parse::<u16>()
+            .map_err(|_| format!("invalid port in ready check '{}'", s))?;
+        Ok(ReadyCheck::Tcp { host, port })
+    }
+
+    /// Whether `config`'s process passes the check now.
+    pub async fn passes(&self, config: &ProcessConfig, root: &Path) -> bool {
+        match self {
+            ReadyCheck::Tcp { host, port } => {
+                let (host, port) = (host.clone(), *port);
+                tokio::task::spawn_blocking(move || tcp_open(&host, port))
+                    .await
+                    .unwrap_or(false)
+            }
+            ReadyCheck::Command(template) => {
+                let line = config.helper_command_line(root, template);
+                let mut cmd = config.shell_command(root, &line);
+                cmd.stdin(std::process::Stdio::null())
+                    .stdout(std::process::Stdio::null())
+                    .stderr(std::process::Stdio::null())
+                    .kill_on_drop(true);
+                matches!(
+                    tokio::time::timeout(COMMAND_TIMEOUT, cmd.status()).await,
+                    Ok(Ok(status)) if status.success()
+                )
+            }
+        }
+    }
+}
+
+/// Whether something accepts TCP connections on `host:port`.
+fn tcp_open(host: &str, port: u16) -> bool {
+    let Ok(addrs) = (host, port).to_socket_addrs() else {
+        return false;
+    };
+    addrs
+        .into_iter()
+        .any(|a| TcpStream::connect_timeout(&a, Duration::from_millis(200)).is_ok())
+}
+
+impl fmt::Display for ReadyCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadyCheck::Tcp { host, port } if host == "127.0.0.1" => write!(f, "tcp:{}", port),
+            ReadyCheck::Tcp { host, port } => write!(f, "tcp:{}:{}", host, port),
+            ReadyCheck::Command(cmd) => write!(f, "`{}`", cmd),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Progress {
+    Starting,
+    Delayed,
+    Waiting(String),
+    Ready,
+    Exited,
+    Failed(String),
+    NotStarted,
+}
+
+impl Progress {
+    fn settled(&self) -> bool {
+        matches!(
+            self,
+            Progress::Ready | Progress::Exited | Progress::Failed(_) | Progress::NotStarted
+        )
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Progress::Starting => write!(f, "starting…"),
+            Progress::Delayed => write!(f, "pending (delayed start)"),
+            Progress::Waiting(check) => write!(f, "waiting on {}…", check),
+            Progress::Ready => write!(f, "ready"),
+            Progress::Exited => write!(f, "exited"),
+            Progress::Failed(reason) if reason.is_empty() => write!(f, "failed"),
+            Progress::Failed(reason) => write!(f, "failed: {}", reason),
+            Progress::NotStarted => write!(f, "not started (see oxproc status)"),
+        }
+    }
+}
+
+fn progress_of(config: &ProcessConfig, st: &ManagerState) -> Progress {
+    if st.pending.contains(&config.name) {
+        if config.depends_on.is_empty() {
+            return Progress::Delayed;
+        }
+        return Progress::Waiting(config.depends_on.join(", "));
+    }
+    let Some(p) = st.processes.iter().find(|p| p.name == config.name) else {
+        if st.stopped.contains(&config.name) {
+            return Progress::NotStarted;
+        }
+        return Progress::Starting;
+    };
+    match p.state {
+        ProcessState::Starting => {
+            let up = (chrono::Utc::now() - p.started_at)
+                .to_std()
+                .unwrap_or_default();
+            match (config.min_uptime, &config.ready) {
+                (Some(m), _) if up < m => Progress::Waiting(format!("min_uptime {:?}", m)),
+                (_, Some(check)) => Progress::Waiting(check.to_string()),
+                _ => Progress::Starting,
+            }
+        }
+        ProcessState::Ready | ProcessState::Running => Progress::Ready,
+        ProcessState::Stopping | ProcessState::Restarting => Progress::Starting,
+        ProcessState::Failed => Progress::Failed(p.failure.clone().unwrap_or_default()),
+        _ => Progress::Exited,
+    }
+}
+
+/// Show a per-process checklist until every process is ready (or exited) or `timeout` passes.
+/// On a terminal the list is redrawn in place; otherwise only changes are printed. Once
+/// settled, the URLs processes announced (`url_pattern`) are listed. Returns whether
+/// everything became ready in time.
+pub fn wait_with_checklist(root: &Path, configs: &[ProcessConfig], timeout: Duration) -> bool {
+    let tty = std::io::stdout().is_terminal();
+    let width = configs.iter().map(|c| c.name.len()).max().unwrap_or(0);
+    let start = Instant::now();
+    let mut last: Vec<Option<Progress>> = vec![None; configs.len()];
+    let mut drawn = false;
+    loop {
+        // Ignore state left behind by a previous manager
+        let st = load_state_from_root(root)
+            .ok()
+            .filter(|st| pid_alive(st.manager.pid as i32));
+        let current: Vec<Progress> = configs
+            .iter()
+            .map(|c| match &st {
+                Some(st) => progress_of(c, st),
+                None => Progress::Starting,
+            })
+            .collect();
+        let mut out = std::io::stdout().lock();
+        if tty {
+            if drawn {
+                let _ = write!(out, "\x1b[{}A", configs.len());
+            }
+            for (c, p) in configs.iter().zip(&current) {
+                let _ = writeln!(out, "\x1b[2K  {:width$}  {}", c.name, p, width = width);
+            }
+            drawn = true;
+        } else {
+            for ((c, p), prev) in configs.iter().zip(&current).zip(last.iter_mut()) {
+                if prev.as_ref() != Some(p) {
+                    let _ = writeln!(out, "  {:width$}  {}", c.name, p, width = width);
+                    *prev = Some(p.clone());
+                }
+            }
+        }
+        if current.iter().all(Progress::settled) {
+            // Then where each process serves, for those announcing it (`url_pattern`)
+            for c in configs {
+                let url = st.as_ref().and_then(|st| {
+                    st.processes
+                        .iter()
+                        .find(|p| p.name == c.name)
+                        .and_then(|p| p.url.as_ref())
+                });
+                if let Some(url) = url {
+                    let _ = writeln!(out, "  {:width$}  → {}", c.name, url, width = width);
+                }
+            }
+            let _ = out.flush();
+            return current.iter().all(|p| *p == Progress::Ready);
+        }
+        let _ = out.flush();
+        if start.elapsed() >= timeout {
+            return false;
+        }
+        std::thread::sleep(crate::power::scale(Duration::from_millis(250)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tcp_checks() {
+        assert_eq!(
+            ReadyCheck::parse("tcp:3000").unwrap(),
+            ReadyCheck::Tcp {
+                host: "127.0.0.1".into(),
+                port: 3000
+            }
+        );
+        let remote = ReadyCheck::parse("tcp:db.local:5432").unwrap();
+        assert_eq!(remote.to_string(), "tcp:db.local:5432");
+        assert!(ReadyCheck::parse("http://localhost").is_err());
+        assert!(ReadyCheck::parse("tcp:99999").is_err());
+    }
+
+    #[test]
+    fn probes_a_listening_port() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(tcp_open("127.0.0.1", port));
+        drop(listener);
+        assert!(!tcp_open("127.0.0.1", port));
+    }
+
+    #[tokio::test]
+    async fn check_commands_run_in_the_process_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("app")).unwrap();
+        std::fs::write(
+            dir.path().join("proc.toml"),
+            r#"
+[processes.web]
+cmd = "sleep 1"
+cwd = "app"
+port = 4321
+env = { GREETING = "hi" }
+ready = { cmd = 'test "$GREETING" = hi && test {port} = 4321 && test -f up' }
+"#,
+        )
+        .unwrap();
+        let configs = crate::config::load_config_from(dir.path()).unwrap();
+        let check = configs[0].ready.clone().unwrap();
+        assert!(!check.passes(&configs[0], dir.path()).await);
+        std::fs::write(dir.path().join("app/up"), "").unwrap();
+        assert!(check.passes(&configs[0], dir.path()).await);
+    }
+}
@@ -0,0 +1,133 @@
+// Multi-line log records: a line matching a process's `multiline` continuation pattern (an
+// indented stack frame, say) belongs to the record started by the line before it. Filters
+// (`logs --level`, `logs --grep`) then keep or drop whole records, so a stack trace is never
+// split from the error that produced it.
+
+use crate::level::LevelFilter;
+use regex::Regex;
+
+/// Continuation pattern used for `multiline = true`: indented lines and `Caused by:` chains.
+pub const DEFAULT_CONTINUATION: &str = r"^(\s|Caused by:)";
+
+/// Lines of a record held back while it does not match yet; older ones are dropped.
+const MAX_PENDING: usize = 500;
+
+/// Streaming filter over the lines of one log stream. Feed lines in order with [`push`]; it
+/// returns the lines to show now. A record is shown once any of its lines passes the level
+/// filter and any of its lines matches `grep`; its remaining lines then pass straight through.
+///
+/// [`push`]: RecordFilter::push
+#[derive(Debug)]
+pub struct RecordFilter {
+    continuation: Option<Regex>,
+    level: Option<LevelFilter>,
+    grep: Option<Regex>,
+    pending: Vec<String>,
+    showing: bool,
+    level_hit: bool,
+    grep_hit: bool,
+}
+
+impl RecordFilter {
+    pub fn new(
+        continuation: Option<Regex>,
+        level: Option<LevelFilter>,
+        grep: Option<Regex>,
+    ) -> Self {
+        RecordFilter {
+            continuation,
+            level,
+            grep,
+            pending: Vec::new(),
+            showing: false,
+            level_hit: false,
+            grep_hit: false,
+        }
+    }
+
+    /// Whether every line is shown as is.
+    pub fn is_passthrough(&self) -> bool {
+        self.level.is_none() && self.grep.is_none()
+    }
+
+    /// Feed the next raw line (plain or a JSONL record; patterns see the record's text).
+    pub fn push(&mut self, raw: &str) -> Vec<String> {
+        let text = crate::jsonlog::text_of(raw);
+        let line = text.as_ref();
+        let in_record = self.showing || !self.pending.is_empty();
+        let continues = in_record
+            && self
+                .continuation
+                .as_ref()
+                .is_some_and(|re| re.is_match(line));
+        if !continues {
+            self.pending.clear();
+            self.showing = false;
+            self.level_hit = false;
+            self.grep_hit = false;
+        }
+        if self.showing {
+            return vec![raw.to_string()];
+        }
+        self.level_hit |= self.level.as_ref().is_none_or(|f| f.keeps(line));
+        self.grep_hit |= self.grep.as_ref().is_none_or(|re| re.is_match(line));
+        self.pending.push(raw.to_string());
+        if self.level_hit && self.grep_hit {
+            self.showing = true;
+            return std::mem::take(&mut self.pending);
+        }
+        if self.pending.len() > MAX_PENDING {
+            self.pending.remove(0);
+        }
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(filter: &mut RecordFilter, lines: &[&str]) -> Vec<String> {
+        lines.iter().flat_map(|l| filter.push(l)).collect()
+    }
+
+    const LOG: &[&str] = &[
+        "INFO starting",
+        "ERROR request failed",
+        "    at handler (app.js:10)",
+        "    at main (app.js:2)",
+        "Caused by: timeout",
+        "INFO done",
+    ];
+
+    #[test]
+    fn grep_keeps_stack_traces_whole() {
+        let cont = Some(Regex::new(DEFAULT_CONTINUATION).unwrap());
+        // The match is on a frame, but the whole record comes out
+        let mut f = RecordFilter::new(cont, None, Some(Regex::new("handler").unwrap()));
+        assert_eq!(run(&mut f, LOG), LOG[1..5].to_vec());
+    }
+
+    #[test]
+    fn without_multiline_lines_are_independent() {
+        let mut f = RecordFilter::new(None, None, Some(Regex::new("handler").unwrap()));
+        assert_eq!(run(&mut f, LOG), vec!["    at handler (app.js:10)"]);
+        assert!(RecordFilter::new(None, None, None).is_passthrough());
+    }
+
+    #[test]
+    fn level_filter_keeps_continuations_of_errors() {
+        use crate::level::{LevelPatterns, LogLevel};
+        let level = LevelFilter {
+            patterns: LevelPatterns {
+                error: Some(Regex::new("ERROR").unwrap()),
+                ..Default::default()
+            },
+            min: LogLevel::Error,
+            stderr: false,
+        };
+        let cont = Some(Regex::new(DEFAULT_CONTINUATION).unwrap());
+        let mut f = RecordFilter::new(cont, Some(level), None);
+        assert_eq!(run(&mut f, LOG), LOG[1..5].to_vec());
+    }
+}
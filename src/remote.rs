@@ -0,0 +1,98 @@
+// Remote mode (`--host`): run the same oxproc command on another machine over SSH. The
+// remote oxproc does the work; its output (including followed logs) streams back through ssh.
+
+use anyhow::{Context, Result};
+use std::ffi::OsString;
+use std::io::IsTerminal;
+use std::process::Command;
+
+use crate::watch::shell_quote;
+
+/// Split `--host` into the SSH destination and an optional remote project root, scp-style:
+/// `dev-box`, `me@dev-box` or `dev-box:src/app`.
+pub fn parse_host(host: &str) -> (&str, Option<&str>) {
+    match host.split_once(':') {
+        Some((dest, path)) if !path.is_empty() => (dest, Some(path)),
+        Some((dest, _)) => (dest, None),
+        None => (host, None),
+    }
+}
+
+/// The command-line arguments without `--host`, which must not be forwarded. Everything after
+/// `--` belongs to a task and is kept as-is.
+fn forwarded_args(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut out = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            out.push(arg);
+            out.extend(args.by_ref());
+            break;
+        }
+        if arg == "--host" {
+            args.next();
+            continue;
+        }
+        if arg.to_string_lossy().starts_with("--host=") {
+            continue;
+        }
+        out.push(arg);
+    }
+    out
+}
+
+/// Run this invocation (`args`, without the program name) on `host` and return the remote
+/// exit code. The remote binary is `oxproc` unless `OXPROC_REMOTE_BIN` says otherwise.
+pub fn run(host: &str, root: Option<&std::path::Path>, args: Vec<OsString>) -> Result<i32> {
+    let (dest, remote_root) = parse_host(host);
+    if remote_root.is_some() && root.is_some() {
+        anyhow::bail!(
+            "give the remote project either as --host {} or with --root, not both",
+            host
+        );
+    }
+    let bin = std::env::var("OXPROC_REMOTE_BIN").unwrap_or_else(|_| "oxproc".to_string());
+    let mut remote = vec![bin];
+    if let Some(path) = remote_root {
+        remote.push("--root".into());
+        remote.push(shell_quote(path));
+    }
+    for arg in forwarded_args(args) {
+        remote.push(shell_quote(&arg.to_string_lossy()));
+    }
+
+    let mut ssh = Command::new("ssh");
+    // A terminal on the far side keeps colors, the pager and key bindings working
+    if std::io::stdout().is_terminal() && std::io::stdin().is_terminal() {
+        ssh.arg("-t");
+    }
+    ssh.arg(dest).arg(remote.join(" "));
+    let status = ssh
+        .status()
+        .with_context(|| format!("failed to run ssh to {}", dest))?;
+    Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_host_and_strips_host_flag() {
+        assert_eq!(parse_host("dev-box"), ("dev-box", None));
+        assert_eq!(
+            parse_host("me@dev-box:src/app"),
+            ("me@dev-box", Some("src/app"))
+        );
+
+        let args = [
+            "--host", "dev-box", "logs", "--host=x", "web", "--", "--host",
+        ]
+        .map(OsString::from)
+        .to_vec();
+        assert_eq!(
+            forwarded_args(args),
+            ["logs", "web", "--", "--host"].map(OsString::from).to_vec()
+        );
+    }
+}
@@ -0,0 +1,54 @@
+// Cross-project dependencies (`requires_project`): make sure another oxproc project's manager
+// is running before starting processes here. The other project is started by invoking this
+// binary against it, so it uses its own proc.toml and state dir.
+
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::state;
+
+/// How long to wait for a required project's manager to come up.
+const START_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Whether `project` has a live manager.
+pub fn is_running(project: &Path) -> bool {
+    let dir = state::state_dir_from_root(project);
+    std::fs::read_to_string(state::manager_pid_path(&dir))
+        .ok()
+        .and_then(|s| s.trim().parse::<i32>().ok())
+        .is_some_and(state::pid_alive)
+}
+
+/// Start `project`'s formation in the background unless its manager is already running.
+/// Returns whether it had to be started.
+pub fn ensure_started(project: &Path) -> Result<bool> {
+    let project = project
+        .canonicalize()
+        .with_context(|| format!("required project {} not found", project.display()))?;
+    crate::config::detect_source(&project)
+        .with_context(|| format!("required project {}", project.display()))?;
+    if is_running(&project) {
+        return Ok(false);
+    }
+    let exe = std::env::current_exe()?;
+    let output = Command::new(exe)
+        .arg("--root")
+        .arg(&project)
+        .arg("start")
+        // Started on behalf of this project, which may well enclose it
+        .arg("--ignore-parent")
+        .output()
+        .with_context(|| format!("failed to start required project {}", project.display()))?;
+    // Lost a race with another starter: fine as long as it is running now
+    if !output.status.success() && !is_running(&project) {
+        anyhow::bail!(
+            "required project {} failed to start: {}",
+            project.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    state::wait_for_manager_ready(&project, START_TIMEOUT)?;
+    Ok(true)
+}
@@ -0,0 +1,325 @@
+// Machine interface for editor extensions (`oxproc serve --stdio`): newline-delimited JSON
+// requests on stdin, replies and pushed events on stdout. Unlike the control socket, this
+// works without a running manager and streams logs and task output as they happen.
+//
+// Requests carry an optional `id` that is echoed in the reply:
+//   {"id":1,"cmd":"list"}                      -> {"id":1,"ok":true,"result":{...}}
+//   {"id":2,"cmd":"status"}                    -> result: [ProcessStatus]
+//   {"id":3,"cmd":"watch_status"}              -> result now, then {"event":"status",...} on change
+//   {"id":4,"cmd":"follow","names":["web"]}    -> {"event":"log","process":"web","stream":"stdout","line":"..."}
+//   {"id":5,"cmd":"unfollow"}
+//   {"id":6,"cmd":"run","task":"build","args":[]} -> {"event":"task_output",...}, {"event":"task_exit",...}
+//   {"id":7,"cmd":"cancel","run":6}
+
+use crate::control::{self, ProcessState, ProcessStatus};
+use crate::output::outln;
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    #[serde(default)]
+    id: Value,
+    #[serde(flatten)]
+    request: ServeRequest,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum ServeRequest {
+    List,
+    Status,
+    WatchStatus,
+    Follow {
+        #[serde(default)]
+        names: Vec<String>,
+    },
+    Unfollow,
+    Run {
+        task: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    Cancel {
+        run: Value,
+    },
+}
+
+/// How often `watch_status` checks for changes.
+const STATUS_POLL: std::time::Duration = std::time::Duration::from_secs(1);
+
+struct Session {
+    root: PathBuf,
+    out: UnboundedSender<Value>,
+    followers: Vec<JoinHandle<()>>,
+    status_watch: Option<JoinHandle<()>>,
+    /// Running tasks by request id (as JSON text), with their process group
+    runs: HashMap<String, i32>,
+}
+
+pub fn run(root: &Path) -> Result<()> {
+    let rt = tokio::runtime::Runtime::new()?;
+    rt.block_on(serve_stdio(root.to_path_buf()))
+}
+
+async fn serve_stdio(root: PathBuf) -> Result<()> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Value>();
+    let writer = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            outln!("{}", msg);
+        }
+    });
+    let (done_tx, mut done_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let mut session = Session {
+        root,
+        out: tx,
+        followers: Vec::new(),
+        status_watch: None,
+        runs: HashMap::new(),
+    };
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else { break };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<Envelope>(&line) {
+                    Ok(env) => session.handle(env, &done_tx).await,
+                    Err(e) => session.reply_err(&Value::Null, format!("invalid request: {}", e)),
+                }
+            }
+            Some(id) = done_rx.recv() => {
+                session.runs.remove(&id);
+            }
+        }
+    }
+    // stdin closed: the client is gone
+    session.unfollow();
+    if let Some(h) = session.status_watch.take() {
+        h.abort();
+    }
+    for pgid in session.runs.values() {
+        kill_group(*pgid);
+    }
+    drop(session);
+    let _ = writer.await;
+    Ok(())
+}
+
+impl Session {
+    fn reply(&self, id: &Value, result: Value) {
+        let _ = self
+            .out
+            .send(json!({"id": id, "ok": true, "result": result}));
+    }
+
+    fn reply_err(&self, id: &Value, error: impl Into<String>) {
+        let _ = self
+            .out
+            .send(json!({"id": id, "ok": false, "error": error.into()}));
+    }
+
+    async fn handle(&mut self, env: Envelope, done: &UnboundedSender<String>) {
+        let id = env.id;
+        match env.request {
+            ServeRequest::List => match crate::list::gather_list_info(&self.root) {
+                Ok(info) => self.reply(&id, json!(info)),
+                Err(e) => self.reply_err(&id, format!("{:#}", e)),
+            },
+            ServeRequest::Status => {
+                let status = status_of(&self.root).await;
+                self.reply(&id, json!(status));
+            }
+            ServeRequest::WatchStatus => {
+                let status = status_of(&self.root).await;
+                self.reply(&id, json!(status));
+                if self.status_watch.is_none() {
+                    self.status_watch = Some(tokio::spawn(watch_status(
+                        self.root.clone(),
+                        status,
+                        self.out.clone(),
+                    )));
+                }
+            }
+            ServeRequest::Follow { names } => match crate::config::load_config_from(&self.root) {
+                Ok(configs) => {
+                    self.unfollow();
+                    for c in configs
+                        .iter()
+                        .filter(|c| names.is_empty() || names.contains(&c.name))
+                    {
+                        let (out, err) = c.log_paths(&self.root);
+                        for (path, stream) in [(out, "stdout"), (err, "stderr")] {
+                            self.followers.push(tokio::spawn(follow_log(
+                                path,
+                                c.name.clone(),
+                                stream,
+                                self.out.clone(),
+                            )));
+                        }
+                    }
+                    self.reply(&id, Value::Null);
+                }
+                Err(e) => self.reply_err(&id, e.to_string()),
+            },
+            ServeRequest::Unfollow => {
+                self.unfollow();
+                self.reply(&id, Value::Null);
+            }
+            ServeRequest::Run { task, args } => match self.start_task(&id, &task, &args, done) {
+                Ok(()) => self.reply(&id, Value::Null),
+                Err(e) => self.reply_err(&id, format!("{:#}", e)),
+            },
+            ServeRequest::Cancel { run } => match self.runs.get(&run.to_string()) {
+                Some(pgid) => {
+                    kill_group(*pgid);
+                    self.reply(&id, Value::Null);
+                }
+                None => self.reply_err(&id, format!("no running task with id {}", run)),
+            },
+        }
+    }
+
+    fn unfollow(&mut self) {
+        for h in self.followers.drain(..) {
+            h.abort();
+        }
+    }
+
+    /// Run a task through `oxproc run` in its own process group, streaming its output as
+    /// `task_output` events tagged with the request id and finishing with `task_exit`.
+    fn start_task(
+        &mut self,
+        id: &Value,
+        task: &str,
+        args: &[String],
+        done: &UnboundedSender<String>,
+    ) -> Result<()> {
+        let mut cmd = tokio::process::Command::new(std::env::current_exe()?);
+        cmd.arg("--root").arg(&self.root);
+        if let Some(variant) = crate::dirs::variant_for(&self.root) {
+            cmd.args(variant.args());
+        }
+        cmd.args(["--color", "never", "run", task, "--"])
+            .args(args)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .process_group(0);
+        let mut child = cmd.spawn()?;
+        let pgid = child.id().unwrap_or_default() as i32;
+        self.runs.insert(id.to_string(), pgid);
+
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let out = self.out.clone();
+        let done = done.clone();
+        let id = id.clone();
+        tokio::spawn(async move {
+            let readers = futures::future::join(
+                forward_output(stdout, "stdout", &id, &out),
+                forward_output(stderr, "stderr", &id, &out),
+            );
+            readers.await;
+            let code = child.wait().await.ok().and_then(|s| s.code());
+            let _ = out.send(json!({"event": "task_exit", "id": id, "code": code}));
+            let _ = done.send(id.to_string());
+        });
+        Ok(())
+    }
+}
+
+async fn forward_output<R: AsyncRead + Unpin>(
+    stream: Option<R>,
+    name: &str,
+    id: &Value,
+    out: &UnboundedSender<Value>,
+) {
+    let Some(stream) = stream else { return };
+    let mut lines = BufReader::new(stream).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let _ = out.send(json!({"event": "task_output", "id": id, "stream": name, "line": line}));
+    }
+}
+
+async fn follow_log(path: PathBuf, name: String, stream: &str, out: UnboundedSender<Value>) {
+    crate::follow::tail_file(path, |line| {
+        let line = crate::jsonlog::text_of(line);
+        out.send(json!({"event": "log", "process": name, "stream": stream, "line": line}))
+            .is_ok()
+    })
+    .await;
+}
+
+async fn watch_status(root: PathBuf, mut last: Vec<ProcessStatus>, out: UnboundedSender<Value>) {
+    loop {
+        tokio::time::sleep(STATUS_POLL).await;
+        let status = status_of(&root).await;
+        if status != last {
+            if out
+                .send(json!({"event": "status", "processes": status}))
+                .is_err()
+            {
+                return;
+            }
+            last = status;
+        }
+    }
+}
+
+/// Process states from the running manager, or every configured process as stopped.
+async fn status_of(root: &Path) -> Vec<ProcessStatus> {
+    let root = root.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let dir = crate::state::state_dir_from_root(&root);
+        let from_manager = control::send_request(&dir, &control::Request::Status)
+            .ok()
+            .and_then(|resp| resp.data)
+            .and_then(|data| serde_json::from_value(data).ok());
+        from_manager.unwrap_or_else(|| {
+            crate::config::load_config_from(&root)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|c| ProcessStatus {
+                    name: c.name,
+                    state: ProcessState::Stopped,
+                    pid: None,
+                    restarts: 0,
+                })
+                .collect()
+        })
+    })
+    .await
+    .unwrap_or_default()
+}
+
+fn kill_group(pgid: i32) {
+    use nix::sys::signal::{kill, Signal};
+    if pgid > 0 {
+        let _ = kill(nix::unistd::Pid::from_raw(-pgid), Signal::SIGTERM);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_requests_with_ids() {
+        let env: Envelope =
+            serde_json::from_str(r#"{"id":4,"cmd":"follow","names":["web"]}"#).unwrap();
+        assert_eq!(env.id, json!(4));
+        assert!(matches!(env.request, ServeRequest::Follow { ref names } if names == &["web"]));
+        let env: Envelope = serde_json::from_str(r#"{"cmd":"status"}"#).unwrap();
+        assert!(env.id.is_null());
+        assert!(serde_json::from_str::<Envelope>(r#"{"cmd":"nope"}"#).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+// What was running when the manager last went down (`session.json` in the state dir), so
+// `oxproc resume` can bring exactly that back after a reboot, including processes started by
+// hand that a plain `start` would leave out.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::state::{self, ManagerHealth};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub saved_at: DateTime<Utc>,
+    /// Processes that were running or waiting to start
+    pub processes: Vec<String>,
+}
+
+pub fn session_path(dir: &Path) -> PathBuf {
+    dir.join("session.json")
+}
+
+/// Record the processes that were up as the manager shut down.
+pub fn save(dir: &Path, processes: Vec<String>) -> anyhow::Result<()> {
+    let session = Session {
+        saved_at: Utc::now(),
+        processes,
+    };
+    std::fs::write(session_path(dir), serde_json::to_string_pretty(&session)?)?;
+    Ok(())
+}
+
+/// The last session of the project at `root`. A manager that died without shutting down
+/// (power loss, `kill -9`) saved nothing, so its state.json says what was running instead.
+pub fn load(root: &Path) -> Option<Session> {
+    let dir = state::state_dir_from_root(root);
+    if let Ok(st) = state::load_state_from_root(root) {
+        if st.manager.health() == ManagerHealth::Gone
+            && st.processes.iter().any(|p| p.state.is_live())
+        {
+            let saved_at = st.manager.heartbeat.unwrap_or(st.manager.started_at);
+            let mut processes: Vec<String> = st
+                .processes
+                .iter()
+                .filter(|p| p.state.is_live())
+                .map(|p| p.name.clone())
+                .chain(st.pending.iter().cloned())
+                .collect();
+            processes.dedup();
+            return Some(Session {
+                saved_at,
+                processes,
+            });
+        }
+    }
+    let data = std::fs::read_to_string(session_path(&dir)).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+/// A login item that runs `oxproc resume` for the project at `root`: a LaunchAgent plist on
+/// macOS, a systemd user unit elsewhere. Returns where it goes and its contents.
+pub fn login_agent(root: &Path, exe: &Path) -> (String, String) {
+    let label = format!("oxproc.resume.{}", crate::dirs::project_id(root));
+    let (exe, root) = (exe.to_string_lossy(), root.to_string_lossy());
+    if cfg!(target_os = "macos") {
+        let plist = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>Label</key>
+  <string>{label}</string>
+  <key>ProgramArguments</key>
+  <array>
+    <string>{exe}</string>
+    <string>--root</string>
+    <string>{root}</string>
+    <string>resume</string>
+  </array>
+  <key>RunAtLoad</key>
+  <true/>
+</dict>
+</plist>
+"#,
+            label = xml_escape(&label),
+            exe = xml_escape(&exe),
+            root = xml_escape(&root),
+        );
+        (format!("~/Library/LaunchAgents/{}.plist", label), plist)
+    } else {
+        let unit = format!(
+            "[Unit]\nDescription=Resume oxproc formation in {root}\n\n\
+             [Service]\nType=oneshot\nRemainAfterExit=yes\nExecStart=\"{exe}\" --root \"{root}\" resume\n\n\
+             [Install]\nWantedBy=default.target\n",
+        );
+        (format!("~/.config/systemd/user/{}.service", label), unit)
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_and_loads_the_last_session() {
+        let dir = tempfile::tempdir().unwrap();
+        save(dir.path(), vec!["db".into(), "web".into()]).unwrap();
+        let data = std::fs::read_to_string(session_path(dir.path())).unwrap();
+        let session: Session = serde_json::from_str(&data).unwrap();
+        assert_eq!(session.processes, vec!["db", "web"]);
+
+        let (path, agent) = login_agent(Path::new("/work/app"), Path::new("/usr/bin/oxproc"));
+        assert!(path.contains("oxproc.resume."));
+        assert!(agent.contains("/work/app"));
+        assert!(agent.contains("resume"));
+    }
+}
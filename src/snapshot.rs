@@ -0,0 +1,185 @@
+// `oxproc state export` and `state import`: a project's formation (its proc.toml or
+// Procfile) and what oxproc knows about it at runtime, as one JSON document to move to another
+// machine or attach to a support ticket. Importing writes the config back and records the
+// processes that were up as the last session, so `oxproc resume` brings exactly those back.
+
+use std::path::Path;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, ConfigSource};
+use crate::state::{self, ManagerHealth, ProcessState};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub oxproc_version: String,
+    pub exported_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_name: Option<String>,
+    pub config: ConfigFile,
+    /// Processes that were running or waiting to start
+    pub running: Vec<String>,
+    /// Processes stopped by hand while the manager kept running
+    #[serde(default)]
+    pub stopped: Vec<String>,
+    /// Each process instance the manager knew of
+    #[serde(default)]
+    pub processes: Vec<ProcessSnapshot>,
+}
+
+/// The config file, as written.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigFile {
+    /// `proc.toml` (or the `-f` file) or `Procfile`
+    pub name: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessSnapshot {
+    pub name: String,
+    pub state: ProcessState,
+    pub restarts: u32,
+}
+
+/// Capture the project at `root`: its config and, from the running manager or else the last
+/// session, which processes were up.
+pub fn export(root: &Path) -> anyhow::Result<Snapshot> {
+    let path = match config::detect_source(root)? {
+        ConfigSource::ProcToml => config::proc_toml_path(root),
+        ConfigSource::Procfile => root.join("Procfile"),
+    };
+    let config = ConfigFile {
+        name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        content: std::fs::read_to_string(&path)
+            .with_context(|| format!("reading {}", path.display()))?,
+    };
+    let mut snapshot = Snapshot {
+        oxproc_version: env!("CARGO_PKG_VERSION").into(),
+        exported_at: Utc::now(),
+        project_name: config::load_project_name(root).unwrap_or_default(),
+        config,
+        running: Vec::new(),
+        stopped: Vec::new(),
+        processes: Vec::new(),
+    };
+    match state::load_state_from_root(root) {
+        Ok(st) if st.manager.health() != ManagerHealth::Gone => {
+            snapshot.running = running_names(&st);
+            snapshot.stopped = st.stopped;
+            snapshot.processes = st
+                .processes
+                .iter()
+                .map(|p| ProcessSnapshot {
+                    name: p.name.clone(),
+                    state: p.state,
+                    restarts: p.restarts,
+                })
+                .collect();
+        }
+        _ => {
+            if let Some(session) = crate::session::load(root) {
+                snapshot.running = session.processes;
+            }
+        }
+    }
+    Ok(snapshot)
+}
+
+/// Restore `snapshot` into the project at `root`: write its config file, which must not differ
+/// from one already there unless `force`, and save its running processes as the last session.
+/// Refuses while a manager runs, since it would overwrite that session on exit.
+/// Processes that are up or waiting out their `start_delay`, sorted, each once.
+fn running_names(st: &state::ManagerState) -> Vec<String> {
+    let mut names: Vec<String> = st
+        .processes
+        .iter()
+        .filter(|p| p.state.is_live())
+        .map(|p| p.name.clone())
+        .chain(st.pending.iter().cloned())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+pub fn import(root: &Path, snapshot: &Snapshot, force: bool) -> anyhow::Result<()> {
+    if let Ok(st) = state::load_state_from_root(root) {
+        if st.manager.health() != ManagerHealth::Gone {
+            anyhow::bail!("A manager is running for this project; stop it before importing");
+        }
+    }
+    let name = Path::new(&snapshot.config.name);
+    if name.components().count() != 1 || name.file_name().is_none() {
+        anyhow::bail!("Invalid config file name '{}'", snapshot.config.name);
+    }
+    let path = root.join(name);
+    match std::fs::read_to_string(&path) {
+        Ok(current) if current != snapshot.config.content && !force => anyhow::bail!(
+            "{} differs from the snapshot's; pass --force to replace it",
+            path.display()
+        ),
+        _ => std::fs::write(&path, &snapshot.config.content)
+            .with_context(|| format!("writing {}", path.display()))?,
+    }
+    let dir = state::state_dir_from_root(root);
+    std::fs::create_dir_all(&dir)?;
+    crate::session::save(&dir, snapshot.running.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_each_running_process_once() {
+        let process = |name: &str, state: &str| {
+            serde_json::json!({
+                "name": name, "pid": 1, "pgid": 1, "cmd": "", "cwd": null,
+                "stdout_log": "", "stderr_log": "", "started_at": Utc::now(), "state": state,
+            })
+        };
+        let st: state::ManagerState = serde_json::from_value(serde_json::json!({
+            "manager": { "pid": 1, "started_at": Utc::now(), "project_root": "/", "version": 1 },
+            "processes": [
+                process("web", "running"),
+                process("api", "ready"),
+                process("db", "exited"),
+            ],
+            "pending": ["worker", "web"],
+        }))
+        .unwrap();
+        assert_eq!(running_names(&st), ["api", "web", "worker"]);
+    }
+
+    #[test]
+    fn imports_the_config_file() {
+        let root = tempfile::tempdir().unwrap();
+        let snapshot: Snapshot = serde_json::from_value(serde_json::json!({
+            "oxproc_version": "0.1.0",
+            "exported_at": Utc::now(),
+            "config": { "name": "proc.toml", "content": "[processes.web]\ncmd = \"serve\"\n" },
+            "running": ["web"],
+        }))
+        .unwrap();
+        import(root.path(), &snapshot, false).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(root.path().join("proc.toml")).unwrap(),
+            snapshot.config.content
+        );
+        assert_eq!(export(root.path()).unwrap().config, snapshot.config);
+
+        std::fs::write(root.path().join("proc.toml"), "# edited\n").unwrap();
+        assert!(import(root.path(), &snapshot, false).is_err());
+        import(root.path(), &snapshot, true).unwrap();
+
+        let mut escaping = snapshot.clone();
+        escaping.config.name = "../proc.toml".into();
+        assert!(import(root.path(), &escaping, true).is_err());
+    }
+}
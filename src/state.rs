@@ -1,4 +1,5 @@
 use crate::dirs::state_dir_for_project;
+use crate::output::outln;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::fs;
@@ -11,7 +12,185 @@ pub struct ManagerInfo {
     pub pid: u32,
     pub started_at: DateTime<Utc>,
     pub project_root: String,
+    /// `name` from proc.toml, if set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_name: Option<String>,
     pub version: u32,
+    /// Refreshed every [`HEARTBEAT_INTERVAL`] while the manager runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<DateTime<Utc>>,
+    /// The manager's executable, to tell it apart from an unrelated process that reused its PID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exe: Option<String>,
+    /// The oxproc release the manager runs; unset for managers older than this field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oxproc_version: Option<String>,
+    /// When the manager stops everything by itself (`start --for`/`--until`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_at: Option<DateTime<Utc>>,
+}
+
+/// How often the manager refreshes `heartbeat` in state.json.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Whether the manager recorded in state.json is still there.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ManagerHealth {
+    Running,
+    /// Its PID is dead or now belongs to a different program
+    Gone,
+    /// Alive, but the heartbeat stopped
+    Unresponsive,
+}
+
+impl ManagerInfo {
+    /// The project's `name`, or its root path when it has none.
+    pub fn project_label(&self) -> &str {
+        self.project_name.as_deref().unwrap_or(&self.project_root)
+    }
+
+    /// The manager's oxproc release when it isn't this CLI's, as after a `cargo install`
+    /// upgrade while it ran.
+    pub fn other_release(&self) -> Option<String> {
+        match self.oxproc_version.as_deref() {
+            Some(env!("CARGO_PKG_VERSION")) => None,
+            Some(version) => Some(format!("oxproc {}", version)),
+            None => Some("an older oxproc".into()),
+        }
+    }
+
+    pub fn health(&self) -> ManagerHealth {
+        if !pid_alive(self.pid as i32) {
+            return ManagerHealth::Gone;
+        }
+        if let (Some(recorded), Some(live)) = (&self.exe, process_exe(self.pid)) {
+            if Path::new(recorded) != live {
+                return ManagerHealth::Gone;
+            }
+        }
+        match self.heartbeat {
+            Some(beat)
+                if Utc::now() - beat
+                    > chrono::Duration::from_std(HEARTBEAT_INTERVAL * 3).unwrap() =>
+            {
+                ManagerHealth::Unresponsive
+            }
+            _ => ManagerHealth::Running,
+        }
+    }
+}
+
+/// The executable a live process runs, where the platform exposes it.
+#[cfg(target_os = "linux")]
+fn process_exe(pid: u32) -> Option<PathBuf> {
+    let link = fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+    // A binary replaced by a rebuild shows up as "<path> (deleted)"
+    let text = link.to_string_lossy();
+    Some(PathBuf::from(
+        text.strip_suffix(" (deleted)").unwrap_or(&text),
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_exe(_pid: u32) -> Option<PathBuf> {
+    None
+}
+
+/// Compact "time ago" for status banners: 45s, 12m, 3h, 2d.
+pub fn format_ago(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+/// Where a process is in its lifecycle. The manager moves each instance through
+/// `starting` → `ready` (or straight to `running` without a `ready` check) → `stopping` →
+/// `exited`, with `restarting` between a crash and the next attempt and `failed` once the
+/// restart budget is spent. `pending` and `stopped` describe configured processes with no
+/// instance yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessState {
+    /// Spawned, waiting for its `ready` check to pass
+    Starting,
+    /// Passed its `ready` check
+    Ready,
+    /// Alive (no `ready` check configured). Also assumed for state files written before
+    /// states were recorded.
+    #[default]
+    Running,
+    /// Asked to stop; waiting for it to exit
+    Stopping,
+    /// Paused (SIGSTOP) after the formation sat idle; the next `oxproc start` continues it
+    Suspended,
+    /// Crashed; about to be started again
+    Restarting,
+    /// Exited and not (yet) restarted
+    Exited,
+    /// Out of restart budget
+    Failed,
+    /// Waiting on a start delay or dependencies
+    Pending,
+    Stopped,
+}
+
+impl ProcessState {
+    /// Whether an instance in this state is up (it may still be coming up or going down).
+    pub fn is_live(self) -> bool {
+        matches!(
+            self,
+            ProcessState::Starting
+                | ProcessState::Ready
+                | ProcessState::Running
+                | ProcessState::Stopping
+                | ProcessState::Suspended
+        )
+    }
+
+    /// What `oxproc ready` makes of this state.
+    pub fn readiness(self) -> Readiness {
+        match self {
+            ProcessState::Ready | ProcessState::Running => Readiness::Ready,
+            ProcessState::Starting | ProcessState::Stopping | ProcessState::Suspended => {
+                Readiness::NotReady
+            }
+            _ => Readiness::Down,
+        }
+    }
+}
+
+/// Whether a process can be relied on yet, as reported by `oxproc ready`; the value is its
+/// exit status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Readiness {
+    /// Up, and its `ready` check (if any) passed
+    Ready = 0,
+    /// Up, but still starting, suspended or already stopping
+    NotReady = 1,
+    /// Not running
+    Down = 2,
+}
+
+impl std::fmt::Display for ProcessState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ProcessState::Starting => "starting",
+            ProcessState::Ready => "ready",
+            ProcessState::Running => "running",
+            ProcessState::Stopping => "stopping",
+            ProcessState::Suspended => "suspended",
+            ProcessState::Restarting => "restarting",
+            ProcessState::Exited => "exited",
+            ProcessState::Failed => "failed",
+            ProcessState::Pending => "pending",
+            ProcessState::Stopped => "stopped",
+        };
+        f.write_str(name)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -24,12 +203,100 @@ pub struct ProcessInfo {
     pub stdout_log: String,
     pub stderr_log: String,
     pub started_at: DateTime<Utc>,
+    /// Automatic restarts performed since the process was first started
+    #[serde(default)]
+    pub restarts: u32,
+    /// Lifecycle state, as last recorded by the manager
+    #[serde(default)]
+    pub state: ProcessState,
+    /// Why the process is `failed`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<String>,
+    /// Lines classified as errors/warnings (see `error_pattern`/`warn_pattern`)
+    #[serde(default)]
+    pub errors: u64,
+    #[serde(default)]
+    pub warnings: u64,
+    /// Processes spawned by this one, as last observed by the manager
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub descendants: Vec<crate::proctree::ProcEntry>,
+    /// Log volume, to find the process behind log spam or disk churn
+    #[serde(default)]
+    pub output: OutputStats,
+    /// When `reload_signal` was last sent to this instance
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_reload: Option<DateTime<Utc>>,
+    /// Where the process serves, as announced in its output (`url_pattern`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Configured `oom_score_adj`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oom_score_adj: Option<i32>,
+    /// What the manager started this instance with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub launch: Option<LaunchInfo>,
+    /// ID of the container it runs in (`runtime`), once the runtime has created it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+}
+
+/// The command line, directory and (with `record_env`) environment a process was spawned
+/// with, after substitutions, wrappers and `env` handling.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct LaunchInfo {
+    pub command: String,
+    pub cwd: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<std::collections::BTreeMap<String, String>>,
+}
+
+/// Lines and bytes a process has logged since the manager started, and the recent rate.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct OutputStats {
+    pub lines: u64,
+    pub bytes: u64,
+    pub lines_per_sec: f64,
+    pub bytes_per_sec: f64,
+}
+
+impl std::fmt::Display for OutputStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.1} lines/s, {}/s ({} lines, {} total)",
+            self.lines_per_sec,
+            format_bytes(self.bytes_per_sec as u64),
+            self.lines,
+            format_bytes(self.bytes)
+        )
+    }
+}
+
+/// A byte count as a short human-readable size: 512B, 14.2K, 3.1M, 1.0G.
+pub fn format_bytes(bytes: u64) -> String {
+    const K: f64 = 1024.0;
+    let b = bytes as f64;
+    if b < K {
+        format!("{}B", bytes)
+    } else if b < K * K {
+        format!("{:.1}K", b / K)
+    } else if b < K * K * K {
+        format!("{:.1}M", b / (K * K))
+    } else {
+        format!("{:.1}G", b / (K * K * K))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ManagerState {
     pub manager: ManagerInfo,
     pub processes: Vec<ProcessInfo>,
+    /// Configured processes the manager knows about but has not started
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stopped: Vec<String>,
+    /// Processes waiting out their `start_delay`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pending: Vec<String>,
 }
 
 pub fn state_dir_from_root(root: &Path) -> PathBuf {
@@ -52,6 +319,29 @@ pub fn manager_log_path(dir: &Path) -> PathBuf {
     dir.join("manager.log")
 }
 
+pub fn events_log_path(dir: &Path) -> PathBuf {
+    dir.join("events.jsonl")
+}
+
+pub fn control_socket_path(dir: &Path) -> PathBuf {
+    dir.join("control.sock")
+}
+
+/// PID recorded in `manager.lock` by the process holding it, if any.
+pub fn read_lock_holder(dir: &Path) -> Option<i32> {
+    fs::read_to_string(manager_lock_path(dir))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+}
+
+/// Whether a process with this PID exists. EPERM means it does, owned by someone else.
+pub fn pid_alive(pid: i32) -> bool {
+    use nix::errno::Errno;
+    use nix::sys::signal::kill;
+    use nix::unistd::Pid;
+    pid > 0 && matches!(kill(Pid::from_raw(pid), None), Ok(()) | Err(Errno::EPERM))
+}
+
 pub fn save_state(dir: &Path, state: &ManagerState) -> anyhow::Result<()> {
     fs::create_dir_all(dir)?;
     let tmp = dir.join("state.json.tmp");
@@ -69,36 +359,200 @@ pub fn load_state_from_root(root: &Path) -> anyhow::Result<ManagerState> {
     Ok(st)
 }
 
-pub fn print_status(root: &Path) -> anyhow::Result<()> {
-    use nix::sys::signal::kill;
-    use nix::unistd::Pid;
-
+pub fn print_status(root: &Path, tree: bool, verbose: bool) -> anyhow::Result<()> {
     let st = match load_state_from_root(root) {
         Ok(s) => s,
         Err(_) => {
-            println!("No daemon state found for this project.");
+            outln!("No daemon state found for this project.");
             return Ok(());
         }
     };
-    println!(
+    let stats = verbose
+        .then(|| crate::stats::load(&state_dir_from_root(root)))
+        .flatten();
+    let health = st.manager.health();
+    match health {
+        ManagerHealth::Running => {}
+        ManagerHealth::Gone => {
+            let dir = state_dir_from_root(root);
+            let last_seen = st.manager.heartbeat.map(DateTime::<Utc>::into).or_else(|| {
+                fs::metadata(state_file_path(&dir))
+                    .and_then(|m| m.modified())
+                    .ok()
+            });
+            let ago = last_seen
+                .and_then(|t| std::time::SystemTime::now().duration_since(t).ok())
+                .map(|d| format!(" (last seen {} ago)", format_ago(d)))
+                .unwrap_or_default();
+            outln!("!! Manager not running — state is stale{}.", ago);
+            outln!("   The processes below are from its last run and may no longer exist.");
+            outln!("   Run `oxproc start` to start again, or `oxproc down --clean` to clear the state.");
+            outln!();
+        }
+        ManagerHealth::Unresponsive => {
+            let ago = st
+                .manager
+                .heartbeat
+                .and_then(|t| (Utc::now() - t).to_std().ok())
+                .map(format_ago)
+                .unwrap_or_default();
+            outln!(
+                "!! Manager (pid {}) is running but its last heartbeat was {} ago; it may be hung.",
+                st.manager.pid,
+                ago
+            );
+            outln!(
+                "   Check {} or stop it with `kill {}`.",
+                manager_log_path(&state_dir_from_root(root)).display(),
+                st.manager.pid
+            );
+            outln!();
+        }
+    }
+    if let Some(name) = &st.manager.project_name {
+        outln!("Project: {} ({})", name, st.manager.project_root);
+    }
+    outln!(
         "Manager PID: {} (since {})",
-        st.manager.pid, st.manager.started_at
+        st.manager.pid,
+        st.manager.started_at
     );
-    println!("Processes:");
+    if let Some(at) = st.manager.stop_at {
+        let left = (at - Utc::now()).to_std().unwrap_or_default();
+        outln!(
+            "Stops at: {} (in {})",
+            at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+            format_ago(left)
+        );
+    }
+    outln!("Processes:");
     for p in &st.processes {
-        let alive = kill(Pid::from_raw(p.pid as i32), None).is_ok();
-        println!(
-            "- {:<12} pid={} pgid={} alive={} cmd={}",
-            p.name, p.pid, p.pgid, alive, p.cmd
+        let state = current_state(&health, p);
+        let mut extra = String::new();
+        if p.restarts > 0 {
+            extra.push_str(&format!(" restarts={}", p.restarts));
+        }
+        if p.errors > 0 || p.warnings > 0 {
+            extra.push_str(&format!(" errors={} warnings={}", p.errors, p.warnings));
+        }
+        if let Some(at) = p.last_reload {
+            if let Ok(ago) = (Utc::now() - at).to_std() {
+                extra.push_str(&format!(" reloaded={} ago", format_ago(ago)));
+            }
+        }
+        if let Some(adj) = p.oom_score_adj {
+            extra.push_str(&format!(" oom_score_adj={}", adj));
+            // Lowering it takes privileges oxproc may not have had
+            if let Some(actual) = crate::oom::current(p.pid).filter(|a| *a != adj) {
+                if state.is_live() {
+                    extra.push_str(&format!(" (not applied: {})", actual));
+                }
+            }
+        }
+        if let Some(url) = &p.url {
+            extra.push_str(&format!(" url={}", url));
+        }
+        if let (ProcessState::Failed, Some(reason)) = (state, &p.failure) {
+            extra.push_str(&format!(" ({})", reason));
+        }
+        // A container is what runs; its runtime's process group is only the client
+        let group = match &p.container {
+            Some(id) => format!("container={}", &id[..id.len().min(12)]),
+            None => format!("pgid={}", p.pgid),
+        };
+        outln!(
+            "- {:<12} pid={} {} state={}{} cmd={}",
+            p.name,
+            p.pid,
+            group,
+            state,
+            extra,
+            p.cmd
         );
+        if verbose {
+            outln!("  output: {}", p.output);
+            if let Some(stats) = stats.as_ref().filter(|_| state.is_live()) {
+                if let Some(samples) = stats.processes.get(&p.name) {
+                    for line in crate::stats::summary(samples, stats.interval_secs) {
+                        outln!("  {}", line);
+                    }
+                }
+            }
+            if let Some(launch) = &p.launch {
+                outln!("  launched: {} (in {})", launch.command, launch.cwd);
+                if let Some(env) = &launch.env {
+                    outln!(
+                        "  env: {} variables (oxproc debug process {})",
+                        env.len(),
+                        p.name
+                    );
+                }
+            }
+        }
+        if tree {
+            for line in crate::proctree::format_tree(p.pid, p.pgid, &p.descendants) {
+                outln!("{}", line);
+            }
+        }
+    }
+    // Annotate pending and on-demand processes from the current config (best effort)
+    let configs = crate::config::load_config_from(root).unwrap_or_default();
+    for name in &st.pending {
+        let config = configs.iter().find(|c| &c.name == name);
+        let deps = config.map(|c| c.depends_on.clone()).unwrap_or_default();
+        let projects = config
+            .map(|c| c.requires_project.clone())
+            .unwrap_or_default();
+        if let Some(project) = projects.iter().find(|p| !crate::requires::is_running(p)) {
+            outln!(
+                "- {:<12} pending (requires project {})",
+                name,
+                project.display()
+            );
+        } else if deps.is_empty() {
+            outln!("- {:<12} pending (delayed start)", name);
+        } else {
+            outln!("- {:<12} pending (depends on {})", name, deps.join(", "));
+        }
+    }
+    if !st.stopped.is_empty() {
+        for name in &st.stopped {
+            let manual = configs.iter().any(|c| &c.name == name && !c.autostart);
+            outln!(
+                "- {:<12} stopped{}",
+                name,
+                if manual { " (manual)" } else { "" }
+            );
+        }
     }
     Ok(())
 }
 
-pub fn cleanup_stale_state_if_any(root: &Path) -> anyhow::Result<bool> {
-    use nix::sys::signal::kill;
-    use nix::unistd::Pid;
+/// A process's recorded state, corrected for a manager that is gone: nothing updates the
+/// recorded states then, so check what is still there.
+fn current_state(health: &ManagerHealth, p: &ProcessInfo) -> ProcessState {
+    if *health == ManagerHealth::Gone && p.state.is_live() && !pid_alive(p.pid as i32) {
+        ProcessState::Exited
+    } else {
+        p.state
+    }
+}
 
+/// The state of process `name` in `st`, if the manager knows it.
+pub fn process_state(st: &ManagerState, name: &str) -> Option<ProcessState> {
+    if let Some(p) = st.processes.iter().find(|p| p.name == name) {
+        return Some(current_state(&st.manager.health(), p));
+    }
+    if st.pending.iter().any(|n| n == name) {
+        Some(ProcessState::Pending)
+    } else if st.stopped.iter().any(|n| n == name) {
+        Some(ProcessState::Stopped)
+    } else {
+        None
+    }
+}
+
+pub fn cleanup_stale_state_if_any(root: &Path) -> anyhow::Result<bool> {
     let dir = state_dir_from_root(root);
     let pid_path = manager_pid_path(&dir);
     if !pid_path.exists() {
@@ -112,11 +566,10 @@ pub fn cleanup_stale_state_if_any(root: &Path) -> anyhow::Result<bool> {
         Ok(p) => p,
         Err(_) => return Ok(false),
     };
-    let alive = kill(Pid::from_raw(pid), None).is_ok();
-    if !alive {
+    if !pid_alive(pid) {
         let _ = fs::remove_file(&pid_path);
         // state.json may still be useful, keep it
-        println!("Removed stale manager.pid (pid {}).", pid);
+        outln!("Removed stale manager.pid (pid {}).", pid);
         return Ok(true);
     }
     Ok(false)
@@ -134,8 +587,9 @@ pub fn wait_for_manager_ready(root: &Path, timeout: Duration) -> anyhow::Result<
         match fs::read_to_string(&state_path) {
             Ok(data) => {
                 if let Ok(st) = serde_json::from_str::<ManagerState>(&data) {
-                    // Consider ready if file is valid; processes list can be empty in edge cases
-                    if !st.manager.project_root.is_empty() {
+                    // Consider ready if file is valid and written by a live manager (not left
+                    // over from a previous run); processes list can be empty in edge cases
+                    if !st.manager.project_root.is_empty() && pid_alive(st.manager.pid as i32) {
                         return Ok(());
                     }
                 }
@@ -182,21 +636,118 @@ mod tests {
         let state_home = unique_temp_dir("state-ready");
         env::set_var("XDG_STATE_HOME", &state_home);
 
-        // Prepare a minimal valid state.json
+        // Prepare a minimal valid state.json owned by a live "manager" (this test process)
         let dir = state_dir_from_root(&root);
         let _ = std::fs::create_dir_all(&dir);
         let st = ManagerState {
             manager: ManagerInfo {
-                pid: 12345,
+                pid: std::process::id(),
                 started_at: Utc::now(),
                 project_root: root.to_string_lossy().to_string(),
+                project_name: None,
                 version: 1,
+                heartbeat: Some(Utc::now()),
+                exe: None,
+                oxproc_version: None,
+                stop_at: None,
             },
             processes: vec![],
+            stopped: vec![],
+            pending: vec![],
         };
         save_state(&dir, &st).expect("write state");
 
         let res = wait_for_manager_ready(&root, Duration::from_secs(1));
         assert!(res.is_ok());
     }
+
+    #[test]
+    fn formats_output_rates() {
+        assert_eq!(format_bytes(512), "512B");
+        assert_eq!(format_bytes(14_540), "14.2K");
+        assert_eq!(format_bytes(3 * 1024 * 1024 + 100 * 1024), "3.1M");
+        assert_eq!(format_bytes(1 << 30), "1.0G");
+        let stats = OutputStats {
+            lines: 1200,
+            bytes: 90_000,
+            lines_per_sec: 12.34,
+            bytes_per_sec: 2048.0,
+        };
+        assert_eq!(
+            stats.to_string(),
+            "12.3 lines/s, 2.0K/s (1200 lines, 87.9K total)"
+        );
+    }
+
+    #[test]
+    fn pid_alive_counts_processes_we_may_not_signal() {
+        assert!(pid_alive(std::process::id() as i32));
+        // init exists; without root, probing it fails with EPERM
+        assert!(pid_alive(1));
+        assert!(!pid_alive(0));
+        assert!(!pid_alive(i32::MAX));
+    }
+
+    #[test]
+    fn manager_health_detects_dead_and_hung_managers() {
+        let mut info = ManagerInfo {
+            pid: std::process::id(),
+            started_at: Utc::now(),
+            project_root: String::new(),
+            project_name: None,
+            version: 1,
+            heartbeat: Some(Utc::now()),
+            exe: std::env::current_exe()
+                .ok()
+                .map(|p| p.to_string_lossy().to_string()),
+            oxproc_version: Some(env!("CARGO_PKG_VERSION").into()),
+            stop_at: None,
+        };
+        assert_eq!(info.health(), ManagerHealth::Running);
+        assert_eq!(info.other_release(), None);
+        info.oxproc_version = Some("0.0.1".into());
+        assert_eq!(info.other_release().as_deref(), Some("oxproc 0.0.1"));
+        info.heartbeat = Some(Utc::now() - chrono::Duration::minutes(5));
+        assert_eq!(info.health(), ManagerHealth::Unresponsive);
+        #[cfg(target_os = "linux")]
+        {
+            info.exe = Some("/usr/bin/not-oxproc".into());
+            assert_eq!(info.health(), ManagerHealth::Gone);
+        }
+        info.pid = i32::MAX as u32;
+        assert_eq!(info.health(), ManagerHealth::Gone);
+        assert_eq!(format_ago(Duration::from_secs(3 * 3600 + 5)), "3h");
+    }
+
+    #[test]
+    fn readiness_follows_the_recorded_state() {
+        let process = |name: &str, pid: u32, state: &str| {
+            serde_json::json!({
+                "name": name, "pid": pid, "pgid": pid, "cmd": "", "cwd": null,
+                "stdout_log": "", "stderr_log": "", "started_at": Utc::now(), "state": state,
+            })
+        };
+        let me = std::process::id();
+        let mut st: ManagerState = serde_json::from_value(serde_json::json!({
+            "manager": {
+                "pid": me, "started_at": Utc::now(), "project_root": "", "version": 1,
+                "heartbeat": Utc::now(),
+            },
+            "processes": [
+                process("web", me, "ready"),
+                process("worker", i32::MAX as u32, "starting"),
+            ],
+            "pending": ["api"],
+        }))
+        .unwrap();
+        let readiness = |st: &ManagerState, name| process_state(st, name).map(|s| s.readiness());
+        assert_eq!(readiness(&st, "web"), Some(Readiness::Ready));
+        assert_eq!(readiness(&st, "worker"), Some(Readiness::NotReady));
+        assert_eq!(readiness(&st, "api"), Some(Readiness::Down));
+        assert_eq!(readiness(&st, "db"), None);
+        // Without its manager, a process whose PID is gone is down whatever was recorded
+        st.manager.pid = i32::MAX as u32;
+        assert_eq!(readiness(&st, "web"), Some(Readiness::Ready));
+        assert_eq!(readiness(&st, "worker"), Some(Readiness::Down));
+    }
 }
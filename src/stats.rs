@@ -0,0 +1,185 @@
+// Resource history: the manager samples each live process's CPU and memory (with its
+// descendants) every `SAMPLE_INTERVAL` into `stats.json` in the state dir, keeping the last
+// `SAMPLES`. `status --verbose` draws them as sparklines and `oxproc stats` prints them as JSON.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// How often the manager samples.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Samples kept per process: 15 minutes at `SAMPLE_INTERVAL`.
+pub const SAMPLES: usize = 90;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    pub at: DateTime<Utc>,
+    /// Share of one CPU since the previous sample, in percent
+    pub cpu_percent: f64,
+    pub rss_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    pub interval_secs: u64,
+    /// Oldest first
+    pub processes: BTreeMap<String, VecDeque<Sample>>,
+}
+
+impl Default for History {
+    fn default() -> History {
+        History {
+            interval_secs: SAMPLE_INTERVAL.as_secs(),
+            processes: BTreeMap::new(),
+        }
+    }
+}
+
+impl History {
+    /// Append a sample for `name`, dropping the oldest once there are `SAMPLES`.
+    pub fn push(&mut self, name: &str, sample: Sample) {
+        let samples = self.processes.entry(name.to_string()).or_default();
+        if samples.len() == SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+}
+
+pub fn stats_path(dir: &Path) -> PathBuf {
+    dir.join("stats.json")
+}
+
+pub fn save(dir: &Path, history: &History) -> anyhow::Result<()> {
+    let tmp = dir.join("stats.json.tmp");
+    let mut f = std::fs::File::create(&tmp)?;
+    serde_json::to_writer(&mut f, history)?;
+    f.flush()?;
+    std::fs::rename(tmp, stats_path(dir))?;
+    Ok(())
+}
+
+/// The recorded history, or none yet.
+pub fn load(dir: &Path) -> Option<History> {
+    let data = std::fs::read_to_string(stats_path(dir)).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// One bar per value, scaled between the smallest and largest.
+pub fn sparkline(values: &[f64]) -> String {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|v| {
+            if max <= min {
+                BARS[0]
+            } else {
+                let i = ((v - min) / (max - min) * (BARS.len() - 1) as f64).round();
+                BARS[i as usize]
+            }
+        })
+        .collect()
+}
+
+/// `status --verbose` lines for a process's samples: CPU and memory sparklines with their
+/// range and the span they cover.
+pub fn summary(samples: &VecDeque<Sample>, interval_secs: u64) -> Vec<String> {
+    if samples.is_empty() {
+        return Vec::new();
+    }
+    let cpu: Vec<f64> = samples.iter().map(|s| s.cpu_percent).collect();
+    let rss: Vec<f64> = samples.iter().map(|s| s.rss_bytes as f64).collect();
+    let range = |values: &[f64]| {
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        (min, max)
+    };
+    let (cpu_min, cpu_max) = range(&cpu);
+    let (rss_min, rss_max) = range(&rss);
+    let span = crate::state::format_ago(Duration::from_secs(
+        samples.len() as u64 * interval_secs.max(1),
+    ));
+    vec![
+        format!(
+            "cpu: {} {:.1}–{:.1}% (last {})",
+            sparkline(&cpu),
+            cpu_min,
+            cpu_max,
+            span
+        ),
+        format!(
+            "memory: {} {}–{}",
+            sparkline(&rss),
+            crate::state::format_bytes(rss_min as u64),
+            crate::state::format_bytes(rss_max as u64)
+        ),
+    ]
+}
+
+/// `oxproc stats [NAME]`: the recorded samples as JSON, for one process or all of them.
+pub fn print(root: &Path, name: Option<&str>) -> anyhow::Result<()> {
+    let dir = crate::state::state_dir_from_root(root);
+    let history = load(&dir).unwrap_or_default();
+    let json = match name {
+        None => serde_json::to_string_pretty(&history)?,
+        Some(name) => {
+            let Some(samples) = history.processes.get(name) else {
+                anyhow::bail!("No samples recorded for '{}'", name);
+            };
+            serde_json::to_string_pretty(&serde_json::json!({
+                "name": name,
+                "interval_secs": history.interval_secs,
+                "samples": samples,
+            }))?
+        }
+    };
+    crate::output::outln!("{}", json);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_latest_samples_and_draws_them() {
+        let mut history = History::default();
+        for i in 0..SAMPLES + 5 {
+            history.push(
+                "web",
+                Sample {
+                    at: Utc::now(),
+                    cpu_percent: i as f64,
+                    rss_bytes: 1024 * 1024,
+                },
+            );
+        }
+        let samples = &history.processes["web"];
+        assert_eq!(samples.len(), SAMPLES);
+        assert_eq!(samples[0].cpu_percent, 5.0);
+
+        assert_eq!(sparkline(&[0.0, 50.0, 100.0]), "▁▅█");
+        assert_eq!(sparkline(&[3.0, 3.0]), "▁▁");
+
+        let recent: VecDeque<Sample> = [(2.0, 10), (40.0, 20), (80.0, 30)]
+            .into_iter()
+            .map(|(cpu_percent, mb)| Sample {
+                at: Utc::now(),
+                cpu_percent,
+                rss_bytes: mb * 1024 * 1024,
+            })
+            .collect();
+        assert_eq!(
+            summary(&recent, 10),
+            vec!["cpu: ▁▄█ 2.0–80.0% (last 30s)", "memory: ▁▅█ 10.0M–30.0M"]
+        );
+    }
+}
@@ -11,9 +11,7 @@ pub fn display_task_name(s: &str) -> String {
 /// Otherwise, it is appended to the parent's name with a dot.
 pub fn resolve_child_name(parent: &str, child: &str) -> String {
     let child_norm = normalize_task_query(child);
-    if child_norm.contains('.') {
-        child_norm
-    } else if parent.is_empty() {
+    if child_norm.contains('.') || parent.is_empty() {
         child_norm
     } else {
         format!("{}.{}", parent, child_norm)
@@ -0,0 +1,69 @@
+// Terminal helpers for interactive follow mode.
+
+use nix::sys::termios::{self, LocalFlags, SetArg, SpecialCharacterIndices, Termios};
+use std::io::{IsTerminal, Read};
+use std::sync::Mutex;
+
+/// Terminal settings to put back on exit, while key mode is active.
+static ORIGINAL: Mutex<Option<Termios>> = Mutex::new(None);
+
+/// Ctrl+R as delivered by a terminal in non-canonical mode.
+pub const CTRL_R: u8 = 0x12;
+
+/// Ctrl+D; with ICANON off it arrives as a byte instead of signalling end of input.
+pub const CTRL_D: u8 = 0x04;
+
+/// Key presses read ahead of whoever handles them.
+const KEY_QUEUE: usize = 64;
+
+/// Puts stdin into non-canonical, no-echo mode so single key presses can be read, restoring
+/// the original settings on drop. Signals (Ctrl+C) keep working.
+pub struct KeyMode(());
+
+impl KeyMode {
+    /// Enable key mode when stdin is a terminal; `None` otherwise.
+    pub fn enable() -> Option<KeyMode> {
+        let stdin = std::io::stdin();
+        if !stdin.is_terminal() {
+            return None;
+        }
+        let original = termios::tcgetattr(&stdin).ok()?;
+        let mut raw = original.clone();
+        raw.local_flags
+            .remove(LocalFlags::ICANON | LocalFlags::ECHO);
+        raw.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+        raw.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+        termios::tcsetattr(&stdin, SetArg::TCSANOW, &raw).ok()?;
+        *ORIGINAL.lock().unwrap() = Some(original);
+        Some(KeyMode(()))
+    }
+}
+
+impl Drop for KeyMode {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Restore the terminal if key mode is active; for exit paths that skip destructors.
+pub fn restore() {
+    if let Some(original) = ORIGINAL.lock().unwrap().take() {
+        let _ = termios::tcsetattr(std::io::stdin(), SetArg::TCSANOW, &original);
+    }
+}
+
+/// Read stdin on a background thread, sending each byte. The thread ends with stdin, and
+/// stops reading while `KEY_QUEUE` keys wait.
+pub fn spawn_key_reader() -> tokio::sync::mpsc::Receiver<u8> {
+    let (tx, rx) = tokio::sync::mpsc::channel(KEY_QUEUE);
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let mut buf = [0u8; 1];
+        while let Ok(1) = stdin.read(&mut buf) {
+            if tx.blocking_send(buf[0]).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
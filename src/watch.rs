@@ -0,0 +1,98 @@
+// Wrapping a process command in an external file watcher (`watch_tool`).
+// The wrapper runs as the process itself, so stop/restart signals reach it (and the command it
+// supervises) through the process group like any other process.
+
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchTool {
+    CargoWatch,
+    Watchexec,
+    /// cargo-watch for Cargo projects, otherwise watchexec, whichever is installed
+    Auto,
+}
+
+impl WatchTool {
+    pub fn parse(s: &str) -> Result<WatchTool, String> {
+        match s {
+            "cargo-watch" => Ok(WatchTool::CargoWatch),
+            "watchexec" => Ok(WatchTool::Watchexec),
+            "auto" => Ok(WatchTool::Auto),
+            other => Err(format!(
+                "unknown watch_tool '{}' (expected cargo-watch, watchexec or auto)",
+                other
+            )),
+        }
+    }
+
+    /// Pick a concrete tool for `auto`; `None` if nothing suitable is installed.
+    fn resolve(self, dir: &Path) -> Option<WatchTool> {
+        match self {
+            WatchTool::Auto => {
+                if dir.join("Cargo.toml").exists() && find_in_path("cargo-watch").is_some() {
+                    Some(WatchTool::CargoWatch)
+                } else if find_in_path("watchexec").is_some() {
+                    Some(WatchTool::Watchexec)
+                } else {
+                    None
+                }
+            }
+            tool => Some(tool),
+        }
+    }
+}
+
+/// Wrap `cmd` so the watcher restarts it when files under `paths` change (the watcher's
+/// defaults when empty). `dir` is where the process runs. With `auto` and no watcher
+/// installed, the command is returned unchanged.
+pub fn wrap(cmd: &str, tool: WatchTool, paths: &[String], dir: &Path) -> String {
+    let Some(tool) = tool.resolve(dir) else {
+        return cmd.to_string();
+    };
+    let mut out = match tool {
+        WatchTool::CargoWatch => "cargo watch".to_string(),
+        // --restart: stop the running command on change instead of waiting for it to exit
+        _ => "watchexec --restart".to_string(),
+    };
+    for p in paths {
+        out.push_str(" -w ");
+        out.push_str(&shell_quote(p));
+    }
+    match tool {
+        WatchTool::CargoWatch => out.push_str(" -s "),
+        _ => out.push_str(" -- sh -c "),
+    }
+    out.push_str(&shell_quote(cmd));
+    out
+}
+
+/// Quote a string for `sh` using single quotes.
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+pub fn find_in_path(program: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_commands_for_each_tool() {
+        let dir = Path::new("/");
+        assert_eq!(
+            wrap("cargo run", WatchTool::CargoWatch, &["src".into()], dir),
+            "cargo watch -w 'src' -s 'cargo run'"
+        );
+        assert_eq!(
+            wrap("echo 'hi'", WatchTool::Watchexec, &[], dir),
+            r#"watchexec --restart -- sh -c 'echo '\''hi'\'''"#
+        );
+        assert!(WatchTool::parse("nodemon").is_err());
+    }
+}
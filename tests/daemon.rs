@@ -0,0 +1,1581 @@
+// End-to-end tests against the built binary. Each test keeps its state in a temp dir via
+// `--state-dir`, so they neither see nor disturb managers running on the machine.
+#![cfg(unix)]
+
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+struct Project {
+    root: TempDir,
+    state: TempDir,
+}
+
+impl Project {
+    fn new(proc_toml: &str) -> Self {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("proc.toml"), proc_toml).unwrap();
+        Project {
+            root,
+            state: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_oxproc"));
+        cmd.arg("--root")
+            .arg(self.root.path())
+            .arg("--state-dir")
+            .arg(self.state.path())
+            .args(args)
+            .env_remove("OXPROC_STATE_DIR")
+            .env("NO_COLOR", "1");
+        cmd
+    }
+
+    fn oxproc(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    /// Wait until `n` processes are running, and return the status output.
+    fn wait_running(&self, n: usize) -> String {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let out = self.ok(&["status"]);
+            if out.matches("state=running").count() >= n || Instant::now() > deadline {
+                return out;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Start a command that keeps running (`logs -f`, `attach`), collecting its output.
+    fn spawn(&self, args: &[&str]) -> Running {
+        let mut child = self
+            .command(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let output = Arc::new(Mutex::new(String::new()));
+        let mut stdout = child.stdout.take().unwrap();
+        let sink = output.clone();
+        // Not joined: a daemon forked by the command may hold the pipe open
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            while let Ok(n) = stdout.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                sink.lock()
+                    .unwrap()
+                    .push_str(&String::from_utf8_lossy(&buf[..n]));
+            }
+        });
+        Running { child, output }
+    }
+
+    /// Run a command that must succeed and return its stdout.
+    fn ok(&self, args: &[&str]) -> String {
+        let out = self.oxproc(args);
+        assert!(
+            out.status.success(),
+            "oxproc {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&out.stderr)
+        );
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    }
+
+    /// Poll a command until its output contains `needle`.
+    fn wait_for(&self, args: &[&str], needle: &str) -> String {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let out = self.ok(args);
+            if out.contains(needle) || Instant::now() > deadline {
+                return out;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+/// The project's state.json.
+fn state_json(project: &Project) -> serde_json::Value {
+    let state = std::fs::read_dir(project.state.path())
+        .unwrap()
+        .map(|e| e.unwrap().path().join("state.json"))
+        .find(|p| p.exists())
+        .expect("no state.json");
+    serde_json::from_str(&std::fs::read_to_string(state).unwrap()).unwrap()
+}
+
+/// Pids by process name, from state.json.
+fn pids(project: &Project) -> Vec<(String, u64)> {
+    state_json(project)["processes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| {
+            (
+                p["name"].as_str().unwrap().to_string(),
+                p["pid"].as_u64().unwrap(),
+            )
+        })
+        .collect()
+}
+
+/// Whether `pid` runs. An exited process that nobody reaped (its parent, the manager, is
+/// gone) counts as exited.
+fn alive(pid: u64) -> bool {
+    if let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        return !stat
+            .rsplit(')')
+            .next()
+            .is_some_and(|rest| rest.trim_start().starts_with('Z'));
+    }
+    nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid as i32), None).is_ok()
+}
+
+/// A command started with [`Project::spawn`].
+struct Running {
+    child: Child,
+    output: Arc<Mutex<String>>,
+}
+
+impl Running {
+    /// Wait until the output so far contains `needle`, and return it.
+    fn wait_for(&self, needle: &str) -> String {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let out = self.output.lock().unwrap().clone();
+            if out.contains(needle) || Instant::now() > deadline {
+                return out;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Send SIGINT (Ctrl+C) and wait for the command to exit.
+    fn interrupt(&mut self) {
+        let pid = nix::unistd::Pid::from_raw(self.child.id() as i32);
+        nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGINT).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(20);
+        while self.child.try_wait().unwrap().is_none() {
+            assert!(Instant::now() < deadline, "command didn't exit on SIGINT");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        // Never leave a manager behind when a test fails halfway
+        let _ = self.oxproc(&["stop", "--grace", "1"]);
+    }
+}
+
+fn project_dirs(state: &Path) -> usize {
+    std::fs::read_dir(state).unwrap().count()
+}
+
+#[test]
+fn start_logs_stop_round_trip() {
+    let project = Project::new(
+        r#"
+[processes.echo]
+cmd = "echo hello-out; echo hello-err >&2; sleep 30"
+"#,
+    );
+    assert!(project.ok(&["status"]).contains("No daemon state found"));
+
+    let started = project.ok(&["start"]);
+    assert!(started.contains(&project.state.path().display().to_string()));
+    assert_eq!(project_dirs(project.state.path()), 1);
+
+    let logs = project.wait_for(&["logs", "--no-pager"], "hello-err");
+    assert!(logs.contains("[echo] hello-out"), "{}", logs);
+    assert!(logs.contains("[echo] [ERR] hello-err"), "{}", logs);
+    assert!(project
+        .wait_for(&["status"], "state=running")
+        .contains("state=running"));
+
+    let stopped = project.ok(&["stop", "--grace", "1"]);
+    assert!(stopped.contains("Stop complete"), "{}", stopped);
+    assert!(project.ok(&["status"]).contains("state=exited"));
+}
+
+#[test]
+fn state_dirs_are_isolated() {
+    let project = Project::new(
+        r#"
+[processes.sleeper]
+cmd = "sleep 30"
+"#,
+    );
+    project.ok(&["start"]);
+    project.wait_for(&["status"], "state=running");
+
+    // Same project, different state dir: no manager to be found there
+    let other = tempfile::tempdir().unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_oxproc"))
+        .arg("--root")
+        .arg(project.root.path())
+        .arg("--state-dir")
+        .arg(other.path())
+        .arg("status")
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&out.stdout).contains("No daemon state found"));
+
+    // The environment variable selects the same state as the flag
+    let out = Command::new(env!("CARGO_BIN_EXE_oxproc"))
+        .arg("--root")
+        .arg(project.root.path())
+        .arg("status")
+        .env("OXPROC_STATE_DIR", project.state.path())
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&out.stdout).contains("state=running"));
+}
+
+#[test]
+fn status_reports_lifecycle_states() {
+    let project = Project::new(
+        r#"
+[processes.waiting]
+cmd = "sleep 30"
+ready = "tcp:127.0.0.1:1"
+
+[processes.crashing]
+cmd = "exit 3"
+max_restarts = 1
+"#,
+    );
+    project.ok(&["start"]);
+    let status = project.wait_for(&["status"], "state=failed");
+    assert!(status.contains("state=failed"), "{}", status);
+    assert!(status.contains("restarts=1"), "{}", status);
+    // Nothing listens on port 1, so the ready check never passes
+    assert!(status.contains("state=starting"), "{}", status);
+}
+
+#[test]
+fn exits_within_min_uptime_are_failed_starts() {
+    let project = Project::new(
+        r#"
+[processes.quick]
+cmd = "true"
+min_uptime = "5s"
+"#,
+    );
+    project.ok(&["start"]);
+    let status = project.wait_for(&["status"], "state=failed");
+    assert!(status.contains("state=failed"), "{}", status);
+}
+
+#[test]
+fn following_logs_marks_restarts() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "echo up; sleep 30"
+"#,
+    );
+    project.ok(&["start"]);
+    project.wait_for(&["status"], "pid=");
+    let follow = project.spawn(&["logs", "-f", "--name", "web"]);
+    follow.wait_for("[web] up");
+    let before = pids(&project);
+    assert!(alive(before[0].1));
+
+    project.ok(&["restart", "web"]);
+    let out = follow.wait_for("process restarted");
+    let after = pids(&project);
+    assert_ne!(before, after);
+    assert!(
+        out.contains(&format!(
+            "process restarted (pid {} -> {})",
+            before[0].1, after[0].1
+        )),
+        "{}",
+        out
+    );
+}
+
+#[test]
+fn attach_follows_live_output_and_detaches() {
+    let project = Project::new(
+        r#"
+[processes.ticker]
+cmd = "while true; do echo tick; sleep 0.2; done"
+
+[processes.idle]
+cmd = "echo idle-up; sleep 30"
+"#,
+    );
+    assert!(!project.oxproc(&["attach"]).status.success());
+    project.ok(&["start"]);
+    project.wait_for(&["status"], "pid=");
+
+    let mut attach = project.spawn(&["attach", "-n", "5"]);
+    let out = attach.wait_for("[idle  ] idle-up");
+    assert!(out.contains("Attached to"), "{}", out);
+    let before = pids(&project);
+    // Live lines keep coming after the initial tails
+    let seen = out.matches("tick").count();
+    std::thread::sleep(Duration::from_secs(1));
+    let out = attach.wait_for("tick");
+    assert!(out.matches("tick").count() > seen, "{}", out);
+
+    // Without a terminal to prompt on, Ctrl+C detaches
+    attach.interrupt();
+    let out = attach.wait_for("Detached");
+    assert!(out.contains("processes are still running"), "{}", out);
+    assert_eq!(pids(&project), before);
+    assert!(before.iter().all(|(_, pid)| alive(*pid)), "{:?}", before);
+}
+
+#[test]
+fn start_follow_says_what_ctrl_c_did() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "echo up; sleep 30"
+"#,
+    );
+    let mut follow = project.spawn(&["start", "-f"]);
+    let out = follow.wait_for("[web] up");
+    assert!(out.contains("Ctrl+C detaches"), "{}", out);
+    let web = pids(&project)[0].1;
+    follow.interrupt();
+    let out = follow.wait_for("Detached");
+    assert!(out.contains("processes are still running"), "{}", out);
+    assert!(alive(web));
+    project.ok(&["stop", "--grace", "1"]);
+
+    let mut follow = project.spawn(&["start", "-f", "--stop-on-detach"]);
+    let out = follow.wait_for("[web] up");
+    assert!(out.contains("Ctrl+C stops all processes"), "{}", out);
+    let web = pids(&project)[0].1;
+    follow.interrupt();
+    assert!(!alive(web));
+}
+
+#[test]
+fn soft_restart_leaves_stopped_processes_down() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "sleep 30"
+
+[processes.worker]
+cmd = "sleep 30"
+
+[processes.manual]
+cmd = "sleep 30"
+autostart = false
+"#,
+    );
+    project.ok(&["start"]);
+    project.wait_running(2);
+    project.ok(&["stop", "worker"]);
+    let before = pids(&project);
+
+    project.ok(&["restart", "--soft"]);
+    let after = pids(&project);
+    let names: Vec<&str> = after.iter().map(|(n, _)| n.as_str()).collect();
+    assert!(!names.contains(&"manual"), "{:?}", after);
+    let worker = |pids: &[(String, u64)]| pids.iter().find(|(n, _)| n == "worker").cloned();
+    assert_eq!(worker(&after), worker(&before));
+    let status = project.ok(&["status"]);
+    for line in status
+        .lines()
+        .filter(|l| l.contains("worker") || l.contains("manual"))
+    {
+        assert!(!line.contains("state=running"), "{}", status);
+    }
+    // The one that was running did restart
+    let web = |pids: &[(String, u64)]| pids.iter().find(|(n, _)| n == "web").cloned();
+    assert_ne!(web(&after), web(&before));
+}
+
+#[test]
+fn stop_goes_through_the_manager_and_falls_back_to_signals() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "sleep 30"
+"#,
+    );
+    project.ok(&["start"]);
+    project.wait_for(&["status"], "state=running");
+    let manager = state_json(&project)["manager"]["pid"].as_u64().unwrap();
+    let web = pids(&project)[0].1;
+    let stopped = project.ok(&["stop", "--grace", "1"]);
+    assert!(stopped.contains("via the manager"), "{}", stopped);
+    assert!(
+        !stopped.contains("Signalling processes directly"),
+        "{}",
+        stopped
+    );
+    assert!(!alive(web) && !alive(manager));
+    // The manager recorded the exit itself
+    assert!(project.ok(&["status"]).contains("state=exited"));
+
+    // A manager that died without cleaning up: stop signals the recorded groups itself
+    project.ok(&["start"]);
+    project.wait_for(&["status"], "state=running");
+    let manager = state_json(&project)["manager"]["pid"].as_u64().unwrap();
+    let web = pids(&project)[0].1;
+    nix::sys::signal::kill(
+        nix::unistd::Pid::from_raw(manager as i32),
+        nix::sys::signal::Signal::SIGKILL,
+    )
+    .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while alive(manager) && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    assert!(alive(web));
+    let stopped = project.ok(&["stop", "--grace", "1"]);
+    assert!(stopped.contains("not answering"), "{}", stopped);
+    assert!(
+        stopped.contains("Signalling processes directly"),
+        "{}",
+        stopped
+    );
+    assert!(!alive(web));
+}
+
+#[test]
+fn stop_returns_as_soon_as_processes_exit() {
+    let project = Project::new(
+        r#"
+[processes.quick]
+cmd = "sleep 30"
+"#,
+    );
+    project.ok(&["start"]);
+    project.wait_for(&["status"], "state=running");
+    let began = Instant::now();
+    let stopped = project.ok(&["stop", "--grace", "10"]);
+    assert!(began.elapsed() < Duration::from_secs(5), "{}", stopped);
+    assert!(stopped.contains("quick exited after"), "{}", stopped);
+    assert!(
+        stopped.contains("0 process(es) required SIGKILL"),
+        "{}",
+        stopped
+    );
+
+    // One that ignores SIGTERM is killed once the grace period is up
+    let project = Project::new(
+        r#"
+[processes.stubborn]
+cmd = "trap '' TERM; while true; do sleep 0.1; done"
+"#,
+    );
+    project.ok(&["start"]);
+    project.wait_for(&["status"], "state=running");
+    std::thread::sleep(Duration::from_millis(300));
+    let began = Instant::now();
+    let stopped = project.ok(&["stop", "--grace", "1"]);
+    assert!(began.elapsed() >= Duration::from_secs(1), "{}", stopped);
+    assert!(
+        stopped.contains("escalated SIGKILL to stubborn"),
+        "{}",
+        stopped
+    );
+    assert!(
+        stopped.contains("1 process(es) required SIGKILL"),
+        "{}",
+        stopped
+    );
+}
+
+#[test]
+fn start_with_a_running_manager_starts_only_what_is_missing() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "sleep 30"
+"#,
+    );
+    project.ok(&["start"]);
+    project.wait_for(&["status"], "state=running");
+    let manager = state_json(&project)["manager"]["pid"].as_u64().unwrap();
+    let before = pids(&project);
+
+    std::fs::write(
+        project.root.path().join("proc.toml"),
+        "[processes.web]\ncmd = \"sleep 30\"\n\n[processes.api]\ncmd = \"sleep 30\"\n",
+    )
+    .unwrap();
+    let out = project.ok(&["start"]);
+    assert!(out.contains("starting what is missing"), "{}", out);
+    assert!(
+        out.contains(&format!("web already running (pid {})", before[0].1)),
+        "{}",
+        out
+    );
+    assert!(out.contains("started api (pid"), "{}", out);
+
+    let after = pids(&project);
+    assert_eq!(
+        state_json(&project)["manager"]["pid"].as_u64(),
+        Some(manager)
+    );
+    assert!(after.contains(&before[0]), "{:?}", after);
+    assert!(after.iter().any(|(n, _)| n == "api"), "{:?}", after);
+}
+
+#[test]
+fn stop_keep_leaves_the_named_processes_running() {
+    let project = Project::new(
+        r#"
+[processes.db]
+cmd = "sleep 30"
+
+[processes.app]
+cmd = "sleep 30"
+
+[processes.worker]
+cmd = "sleep 30"
+"#,
+    );
+    project.ok(&["start"]);
+    project.wait_running(3);
+    let manager = state_json(&project)["manager"]["pid"].as_u64().unwrap();
+    let db = |pids: &[(String, u64)]| pids.iter().find(|(n, _)| n == "db").cloned();
+    let before = pids(&project);
+
+    project.ok(&["stop", "--keep", "db"]);
+    let after = pids(&project);
+    assert_eq!(db(&after), db(&before));
+    assert!(alive(db(&after).unwrap().1));
+    assert!(alive(manager));
+    let status = project.ok(&["status"]);
+    for line in status.lines() {
+        if line.contains("app") || line.contains("worker") {
+            assert!(!line.contains("state=running"), "{}", status);
+        }
+    }
+    assert_eq!(status.matches("state=running").count(), 1, "{}", status);
+
+    // Plain `start` brings the rest back
+    project.ok(&["start"]);
+    let status = project.wait_running(3);
+    assert_eq!(status.matches("state=running").count(), 3, "{}", status);
+}
+
+#[test]
+fn bouncing_one_process_leaves_the_others_alone() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "sleep 30"
+
+[processes.worker]
+cmd = "sleep 30"
+
+[processes.db]
+cmd = "sleep 30"
+"#,
+    );
+    project.ok(&["start"]);
+    project.wait_running(3);
+    let entries = |project: &Project| -> Vec<(String, serde_json::Value)> {
+        let st = state_json(project);
+        let mut entries: Vec<_> = st["processes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| {
+                let name = p["name"].as_str().unwrap().to_string();
+                (
+                    name,
+                    serde_json::json!([p["pid"], p["started_at"], p["state"]]),
+                )
+            })
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    };
+    let before = entries(&project);
+
+    project.ok(&["restart", "--name", "web"]);
+    project.wait_running(3);
+    let after = entries(&project);
+    for ((name, old), (_, new)) in before.iter().zip(&after) {
+        if name == "web" {
+            assert_ne!(old, new, "web wasn't restarted");
+        } else {
+            assert_eq!(old, new, "{} changed", name);
+        }
+    }
+
+    project.ok(&["stop", "--name", "worker"]);
+    let status = project.ok(&["status"]);
+    assert_eq!(status.matches("state=running").count(), 2, "{}", status);
+    project.ok(&["start", "--name", "worker"]);
+    let status = project.wait_running(3);
+    assert_eq!(status.matches("state=running").count(), 3, "{}", status);
+    let restarted = entries(&project);
+    assert_eq!(restarted[0], after[0], "db changed");
+    assert_eq!(restarted[1], after[1], "web changed");
+}
+
+#[test]
+fn restart_policy_decides_which_exits_restart() {
+    let project = Project::new(
+        r#"
+[processes.always]
+cmd = "exit 0"
+restart = "always"
+max_restarts = 1
+
+[processes.on_failure]
+cmd = "exit 0"
+restart = "on-failure"
+max_restarts = 1
+
+[processes.never]
+cmd = "exit 1"
+restart = "never"
+max_restarts = 3
+"#,
+    );
+    project.ok(&["start"]);
+    // A clean exit is restarted with "always" until the budget runs out
+    let status = project.wait_for(&["status"], "state=failed");
+    let line = |name: &str| {
+        status
+            .lines()
+            .find(|l| l.contains(&format!("- {} ", name)))
+            .unwrap_or_default()
+            .to_string()
+    };
+    assert!(line("always").contains("state=failed"), "{}", status);
+    assert!(line("always").contains("restarts=1"), "{}", status);
+    // …but not with "on-failure"; "never" wins over max_restarts
+    for name in ["on_failure", "never"] {
+        assert!(line(name).contains("state=exited"), "{}", status);
+        assert!(!line(name).contains("restarts="), "{}", status);
+    }
+}
+
+#[test]
+fn restarts_back_off() {
+    let project = Project::new(
+        r#"
+[processes.crashing]
+cmd = "exit 1"
+max_restarts = 2
+"#,
+    );
+    let began = Instant::now();
+    project.ok(&["start"]);
+    let status = project.wait_for(&["status"], "state=failed");
+    assert!(status.contains("restarts=2"), "{}", status);
+    // 1s before the first restart, 2s before the second
+    assert!(
+        began.elapsed() >= Duration::from_secs(3),
+        "{:?}",
+        began.elapsed()
+    );
+}
+
+#[test]
+fn restart_without_max_restarts_is_capped() {
+    let project = Project::new(
+        r#"
+[processes.flaky]
+cmd = "sleep 30"
+restart = "on-failure"
+
+[processes.once]
+cmd = "sleep 30"
+restart = "never"
+max_restarts = 3
+"#,
+    );
+    let bundle = project.root.path().join("bundle.tar");
+    project.ok(&["logs", "--output", bundle.to_str().unwrap()]);
+    let data = String::from_utf8_lossy(&std::fs::read(&bundle).unwrap()).into_owned();
+    let config = &data[data.find("[\n  {").expect("no config.json")..];
+    let config: serde_json::Value = serde_json::Deserializer::from_str(config)
+        .into_iter()
+        .next()
+        .unwrap()
+        .unwrap();
+    let max_restarts = |name: &str| {
+        config
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|p| p["name"] == name)
+            .unwrap()["max_restarts"]
+            .clone()
+    };
+    assert_eq!(max_restarts("flaky"), 10);
+    assert!(max_restarts("once").is_null());
+}
+
+#[test]
+fn logs_mark_where_each_run_starts_and_ends() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "echo hello; echo oops >&2; sleep 30"
+"#,
+    );
+    project.ok(&["start"]);
+    project.wait_for(&["logs", "--no-pager"], "oops");
+    let pid = pids(&project)[0].1;
+    project.ok(&["stop", "--grace", "1"]);
+
+    let log = std::fs::read_to_string(project.root.path().join("web.out.log")).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", log);
+    assert!(
+        lines[0].starts_with(&format!("=== web started pid {} at ", pid)),
+        "{}",
+        log
+    );
+    assert_eq!(lines[1], "hello");
+    assert!(
+        lines[2].starts_with(&format!("=== web stopped pid {} ", pid)),
+        "{}",
+        log
+    );
+    // The stderr log has only what the process wrote there
+    let err = std::fs::read_to_string(project.root.path().join("web.err.log")).unwrap();
+    assert_eq!(err, "oops\n");
+    let logs = project.ok(&["logs", "--no-pager"]);
+    assert_eq!(logs.matches("=== web started").count(), 1, "{}", logs);
+    // `-n` counts the lines shown
+    let logs = project.ok(&["logs", "--no-pager", "-n", "2"]);
+    assert_eq!(logs.matches("[web] ").count(), 3, "{}", logs);
+}
+
+#[test]
+fn start_follow_keeps_global_flags() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "echo up; sleep 30"
+"#,
+    );
+    // Following happens in the same process, so --color (over NO_COLOR) and --state-dir
+    // apply to it as well as to the daemon it starts
+    let mut follow = project.spawn(&["--color", "always", "start", "-f"]);
+    let out = follow.wait_for("up");
+    assert!(out.contains("\x1b["), "{:?}", out);
+    follow.interrupt();
+    assert_eq!(project_dirs(project.state.path()), 1);
+}
+
+#[test]
+fn following_logs_into_a_closed_pipe_exits_quietly() {
+    let project = Project::new(
+        r#"
+[processes.ticker]
+cmd = "while true; do echo tick; sleep 0.1; done"
+"#,
+    );
+    project.ok(&["start"]);
+    project.wait_for(&["logs", "--no-pager"], "tick");
+
+    // `oxproc logs -f | head -n 1`
+    let mut child = project
+        .command(&["logs", "-f"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let mut buf = [0u8; 64];
+    assert!(stdout.read(&mut buf).unwrap() > 0);
+    drop(stdout);
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while child.try_wait().unwrap().is_none() {
+        assert!(Instant::now() < deadline, "logs -f kept running");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success(), "{:?}", out.status);
+    assert!(
+        out.stderr.is_empty(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
+
+#[test]
+fn logs_are_not_paged_into_a_pipe() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "echo hello; sleep 30"
+"#,
+    );
+    project.ok(&["start"]);
+    project.wait_for(&["logs", "--no-pager"], "hello");
+    // A pager that would swallow everything; stdout isn't a terminal, so it never runs
+    let out = project
+        .command(&["logs"])
+        .env("OXPROC_PAGER", "true")
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&out.stdout).contains("[web] hello"));
+}
+
+#[test]
+fn command_ready_checks_keep_their_exit_status() {
+    // Each check passes only the first time it runs, so a probe whose exit status the
+    // manager's orphan reaper collected would leave its process starting forever
+    let mut toml = String::new();
+    for i in 0..6 {
+        toml.push_str(&format!(
+            "[processes.p{i}]\ncmd = \"sleep 30\"\nready = {{ cmd = \"mkdir p{i}.ready\" }}\n\n"
+        ));
+    }
+    let project = Project::new(&toml);
+    project.ok(&["start"]);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut status = project.ok(&["status"]);
+    while status.matches("state=ready").count() < 6 && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+        status = project.ok(&["status"]);
+    }
+    assert_eq!(status.matches("state=ready").count(), 6, "{}", status);
+}
+
+#[test]
+fn requires_project_starts_the_other_project_first() {
+    let project = Project::new(
+        r#"
+[processes.app]
+cmd = "sleep 30"
+requires_project = "dep"
+"#,
+    );
+    /// The required project, sharing the state dir. Stopping `project` leaves it running, so
+    /// it is stopped on its own.
+    struct Dep<'a> {
+        project: &'a Project,
+        root: std::path::PathBuf,
+    }
+    impl Dep<'_> {
+        fn oxproc(&self, args: &[&str]) -> String {
+            let out = Command::new(env!("CARGO_BIN_EXE_oxproc"))
+                .arg("--root")
+                .arg(&self.root)
+                .arg("--state-dir")
+                .arg(self.project.state.path())
+                .args(args)
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&out.stdout).into_owned()
+        }
+    }
+    impl Drop for Dep<'_> {
+        fn drop(&mut self) {
+            self.oxproc(&["stop", "--grace", "1"]);
+        }
+    }
+    let dep = Dep {
+        project: &project,
+        root: project.root.path().join("dep"),
+    };
+    std::fs::create_dir(&dep.root).unwrap();
+    std::fs::write(
+        dep.root.join("proc.toml"),
+        "[processes.db]\ncmd = \"sleep 30\"\n",
+    )
+    .unwrap();
+
+    project.ok(&["start"]);
+    project.wait_running(1);
+    let status = dep.oxproc(&["status"]);
+    assert!(status.contains("- db "), "{}", status);
+    assert!(status.contains("state=running"), "{}", status);
+    assert_eq!(project_dirs(project.state.path()), 2);
+
+    project.ok(&["stop", "--grace", "1"]);
+    assert!(dep.oxproc(&["status"]).contains("state=running"));
+}
+
+#[test]
+fn foreground_ctrl_c_lets_children_clean_up() {
+    let scratch = tempfile::tempdir().unwrap();
+    let marker = scratch.path().join("cleaned-up");
+    let project = Project::new(&format!(
+        r#"
+[processes.web]
+cmd = "trap 'sleep 0.5; touch {}; exit 0' INT; echo up; while true; do sleep 0.1; done"
+"#,
+        marker.display()
+    ));
+    let mut foreground = project.spawn(&[]);
+    foreground.wait_for("[web] up");
+    foreground.interrupt();
+    assert!(marker.exists());
+}
+
+#[test]
+fn foreground_second_ctrl_c_kills_stubborn_children() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "trap '' INT; echo up; while true; do sleep 0.1; done"
+"#,
+    );
+    let mut foreground = project.spawn(&[]);
+    let out = foreground.wait_for("[web] up");
+    let web: u64 = out
+        .split("Started web with PID: ")
+        .nth(1)
+        .and_then(|rest| rest.lines().next())
+        .and_then(|pid| pid.trim().parse().ok())
+        .unwrap_or_else(|| panic!("no pid in {}", out));
+    let sigint = |child: &Child| {
+        nix::sys::signal::kill(
+            nix::unistd::Pid::from_raw(child.id() as i32),
+            nix::sys::signal::Signal::SIGINT,
+        )
+        .unwrap()
+    };
+
+    // The first Ctrl+C waits for the children
+    sigint(&foreground.child);
+    std::thread::sleep(Duration::from_secs(1));
+    assert!(foreground.child.try_wait().unwrap().is_none());
+    assert!(alive(web));
+
+    sigint(&foreground.child);
+    let deadline = Instant::now() + Duration::from_secs(3);
+    while foreground.child.try_wait().unwrap().is_none() {
+        assert!(Instant::now() < deadline, "second Ctrl+C didn't kill");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    assert!(!alive(web));
+}
+
+#[test]
+fn verbose_status_counts_what_each_process_logs() {
+    let project = Project::new(
+        r#"
+[processes.chatty]
+cmd = "echo one; echo two; echo three >&2; sleep 30"
+
+[processes.quiet]
+cmd = "sleep 30"
+"#,
+    );
+    project.ok(&["start"]);
+    let status = project.wait_for(&["status", "-v"], "(3 lines");
+    let output = |name: &str| {
+        let mut lines = status.lines();
+        lines.find(|l| l.starts_with(&format!("- {} ", name)));
+        lines.next().unwrap_or_default().to_string()
+    };
+    assert!(
+        output("chatty").contains("(3 lines, 14B total)"),
+        "{}",
+        status
+    );
+    assert!(
+        output("quiet").contains("(0 lines, 0B total)"),
+        "{}",
+        status
+    );
+    assert!(!project.ok(&["status"]).contains("lines/s"));
+}
+
+#[test]
+fn dry_run_shows_the_plan_without_starting_anything() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "serve --port {port}"
+port = 4100
+depends_on = ["db"]
+env = { MODE = "dev" }
+
+[processes.db]
+cmd = "sleep 30"
+
+[processes.manual]
+cmd = "sleep 30"
+autostart = false
+"#,
+    );
+    let out = project.ok(&["start", "--dry-run"]);
+    assert!(out.contains("Start order: db -> web"), "{}", out);
+    assert!(out.contains("command: serve --port 4100"), "{}", out);
+    assert!(out.contains("port:    4100"), "{}", out);
+    assert!(out.contains("after:   db"), "{}", out);
+    assert!(out.contains("MODE=dev"), "{}", out);
+    assert!(out.contains("Not started: manual"), "{}", out);
+    assert_eq!(project_dirs(project.state.path()), 0);
+    assert!(project.ok(&["status"]).contains("No daemon state found"));
+}
+
+#[test]
+fn stdin_modes_decide_what_daemon_processes_read() {
+    let project = Project::new(
+        r#"
+[processes.null]
+cmd = "cat; echo null-eof; sleep 30"
+
+[processes.pipe]
+cmd = "cat; echo pipe-eof; sleep 30"
+stdin = "pipe"
+
+[processes.pty]
+cmd = "test -t 0 && echo on-a-tty; sleep 30"
+stdin = "pty"
+"#,
+    );
+    project.ok(&["start"]);
+    let logs = project.wait_for(&["logs", "--no-pager"], "on-a-tty");
+    assert!(logs.contains("on-a-tty"), "{}", logs);
+    let logs = project.wait_for(&["logs", "--no-pager"], "null-eof");
+    assert!(logs.contains("null-eof"), "{}", logs);
+    // The manager holds the pipe open, so cat keeps waiting for input
+    std::thread::sleep(Duration::from_millis(500));
+    assert!(!project.ok(&["logs", "--no-pager"]).contains("pipe-eof"));
+}
+
+#[test]
+fn send_writes_a_line_to_a_process_stdin() {
+    let project = Project::new(
+        r#"
+[processes.repl]
+cmd = "while read line; do echo \"got: $line\"; done"
+stdin = "pipe"
+
+[processes.deaf]
+cmd = "sleep 30"
+"#,
+    );
+    project.ok(&["start"]);
+    project.wait_running(2);
+    project.ok(&["send", "repl", "say hello"]);
+    let logs = project.wait_for(&["logs", "--no-pager"], "got: say hello");
+    assert!(logs.contains("[repl] got: say hello"), "{}", logs);
+
+    let out = project.oxproc(&["send", "deaf", "hello"]);
+    assert!(!out.status.success());
+    assert!(
+        String::from_utf8_lossy(&out.stderr).contains("does not accept input"),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
+
+#[test]
+fn services_run_from_the_global_config_in_any_directory() {
+    // The project's own proc.toml plays no part in `oxproc service`
+    let project = Project::new("[processes.web]\ncmd = \"sleep 30\"\n");
+    let home = tempfile::tempdir().unwrap();
+    let config = home.path().join("config");
+    let data = home.path().join("data");
+    std::fs::create_dir_all(config.join("oxproc")).unwrap();
+    std::fs::write(
+        config.join("oxproc/services.toml"),
+        "[processes.cache]\ncmd = \"echo cache-up; sleep 30\"\n",
+    )
+    .unwrap();
+    struct Services<'a>(&'a Project, std::path::PathBuf, std::path::PathBuf);
+    impl Services<'_> {
+        fn oxproc(&self, args: &[&str]) -> String {
+            let out = Command::new(env!("CARGO_BIN_EXE_oxproc"))
+                .arg("--state-dir")
+                .arg(self.0.state.path())
+                .arg("service")
+                .args(args)
+                .current_dir(self.0.root.path())
+                .env("XDG_CONFIG_HOME", &self.1)
+                .env("XDG_DATA_HOME", &self.2)
+                .env("NO_COLOR", "1")
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&out.stdout).into_owned()
+        }
+    }
+    impl Drop for Services<'_> {
+        fn drop(&mut self) {
+            self.oxproc(&["stop", "--grace", "1"]);
+        }
+    }
+    let services = Services(&project, config, data.clone());
+
+    services.oxproc(&["start"]);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !services.oxproc(&["status"]).contains("state=running") {
+        assert!(Instant::now() < deadline, "cache didn't start");
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    let status = services.oxproc(&["status"]);
+    assert!(status.contains("- cache "), "{}", status);
+    assert!(!status.contains("- web "), "{}", status);
+    assert!(project.ok(&["status"]).contains("No daemon state found"));
+    // Default log files go under the services root
+    let log = data.join("oxproc/services/cache.out.log");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !std::fs::read_to_string(&log).is_ok_and(|l| l.contains("cache-up")) {
+        assert!(Instant::now() < deadline, "no {}", log.display());
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn completion_offers_managed_and_configured_names() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "sleep 30"
+
+[processes.manual]
+cmd = "sleep 30"
+autostart = false
+"#,
+    );
+    let complete = |line: &[&str]| -> Vec<String> {
+        let out = Command::new(env!("CARGO_BIN_EXE_oxproc"))
+            .arg("--")
+            .args(line)
+            .current_dir(project.root.path())
+            .env("COMPLETE", "fish")
+            .env("OXPROC_STATE_DIR", project.state.path())
+            .output()
+            .unwrap();
+        let mut names: Vec<String> = String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .map(|l| l.split('\t').next().unwrap_or_default().to_string())
+            .filter(|c| !c.starts_with('-'))
+            .collect();
+        names.sort();
+        names
+    };
+    // Nothing is running yet
+    assert!(complete(&["oxproc", "restart", ""]).is_empty());
+    assert_eq!(complete(&["oxproc", "start", ""]), ["manual", "web"]);
+
+    project.ok(&["start"]);
+    project.wait_running(1);
+    assert_eq!(complete(&["oxproc", "restart", ""]), ["web"]);
+    assert_eq!(complete(&["oxproc", "logs", "--name", ""]), ["web"]);
+}
+
+#[test]
+fn start_timeout_fails_processes_that_never_become_ready() {
+    // Nothing listens on port 1, so neither check ever passes
+    let project = Project::new(
+        r#"
+[processes.kept]
+cmd = "sleep 30"
+ready = "tcp:127.0.0.1:1"
+start_timeout = "1s"
+
+[processes.killed]
+cmd = "sleep 30"
+ready = "tcp:127.0.0.1:1"
+start_timeout = "1s"
+kill_on_start_timeout = true
+"#,
+    );
+    let mut follow = project.spawn(&["start", "-f"]);
+    let out = follow.wait_for("Attaching to logs");
+    assert!(
+        out.contains("failed: ready check tcp:1 did not pass within 1s"),
+        "{}",
+        out
+    );
+    assert!(out.contains("Not all processes became ready"), "{}", out);
+    follow.interrupt();
+
+    let status = project.ok(&["status"]);
+    assert_eq!(status.matches("state=failed").count(), 2, "{}", status);
+    assert!(status.contains("did not pass within 1s"), "{}", status);
+    let pid = |name: &str| pids(&project).into_iter().find(|p| p.0 == name).unwrap().1;
+    assert!(alive(pid("kept")));
+    let killed = pid("killed");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while alive(killed) && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    assert!(!alive(killed));
+    let events = std::fs::read_dir(project.state.path())
+        .unwrap()
+        .map(|e| e.unwrap().path().join("events.jsonl"))
+        .find(|p| p.exists())
+        .map(|p| std::fs::read_to_string(p).unwrap())
+        .unwrap_or_default();
+    assert_eq!(
+        events.matches("did not pass within 1s").count(),
+        2,
+        "{}",
+        events
+    );
+}
+
+#[test]
+fn debug_state_shows_the_managers_bookkeeping() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "sleep 30"
+
+[processes.once]
+cmd = "exit 3"
+restart = "never"
+"#,
+    );
+    let out = project.ok(&["debug", "state"]);
+    assert!(out.contains("Lock holder:    none"), "{}", out);
+    assert!(out.contains("Events (0 of 0"), "{}", out);
+
+    project.ok(&["start"]);
+    project.wait_for(&["status"], "state=exited");
+    let manager = state_json(&project)["manager"]["pid"].as_u64().unwrap();
+    let web = pids(&project).into_iter().find(|p| p.0 == "web").unwrap().1;
+    let out = project.ok(&["debug", "state", "-n", "1"]);
+    assert!(
+        out.contains(&format!("Lock holder:    pid {} (alive)", manager)),
+        "{}",
+        out
+    );
+    assert!(out.contains("answers hello"), "{}", out);
+    assert!(out.contains("Heartbeat:"), "{}", out);
+    assert!(
+        out.contains(&format!("web          pid={} ", web)) && out.contains("alive=true"),
+        "{}",
+        out
+    );
+    let events = out.split("Events (").nth(1).expect(&out);
+    assert!(events.starts_with("1 of "), "{}", out);
+    assert_eq!(events.lines().count(), 2, "{}", out);
+}
+
+#[test]
+fn following_a_slow_reader_loses_no_lines() {
+    let go = tempfile::tempdir().unwrap();
+    let project = Project::new(&format!(
+        "[processes.web]\ncmd = \"while [ ! -e {0}/go ]; do sleep 0.1; done; seq 1 50000; sleep 30\"\n",
+        go.path().display()
+    ));
+    project.ok(&["start"]);
+    project.wait_running(1);
+    let mut child = project
+        .command(&["logs", "-f", "-n", "0"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(Duration::from_secs(1));
+    std::fs::write(go.path().join("go"), "").unwrap();
+    // Nothing reads for a while: the pipe and the follow queue fill up, and `logs -f` has to
+    // wait for its reader instead of buffering without end
+    std::thread::sleep(Duration::from_secs(2));
+
+    let stdout = child.stdout.take().unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut numbers = Vec::new();
+        for line in std::io::BufRead::lines(std::io::BufReader::new(stdout)) {
+            let Ok(line) = line else { break };
+            if let Some(n) = line.strip_prefix("[web] ") {
+                numbers.push(n.parse::<u32>().unwrap());
+                if n == "50000" {
+                    break;
+                }
+            }
+        }
+        let _ = tx.send(numbers);
+    });
+    let numbers = rx.recv_timeout(Duration::from_secs(20)).unwrap();
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        numbers.iter().copied().eq(1..=50000),
+        "{} lines",
+        numbers.len()
+    );
+}
+
+#[test]
+fn starting_inside_a_running_project_needs_ignore_parent() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "sleep 30"
+port = 47311
+"#,
+    );
+    let inner = project.root.path().join("inner");
+    std::fs::create_dir(&inner).unwrap();
+    std::fs::write(
+        inner.join("proc.toml"),
+        "[processes.api]\ncmd = \"sleep 30\"\nport = 47311\n",
+    )
+    .unwrap();
+    let oxproc = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_oxproc"))
+            .arg("--root")
+            .arg(&inner)
+            .arg("--state-dir")
+            .arg(project.state.path())
+            .args(args)
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap()
+    };
+
+    // Nothing runs around it yet
+    let out = oxproc(&["start", "--dry-run"]);
+    assert!(out.status.success());
+
+    project.ok(&["start"]);
+    project.wait_running(1);
+    let out = oxproc(&["start"]);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!out.status.success(), "{}", stderr);
+    assert!(stderr.contains("whose oxproc manager"), "{}", stderr);
+    assert!(
+        stderr.contains("both use port 47311 (api here, web there)"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("--ignore-parent"), "{}", stderr);
+    let status = String::from_utf8_lossy(&oxproc(&["status"]).stdout).into_owned();
+    assert!(!status.contains("- api "), "{}", status);
+
+    let out = oxproc(&["start", "--ignore-parent"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut status = String::new();
+    while !status.contains("state=running") && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+        status = String::from_utf8_lossy(&oxproc(&["status"]).stdout).into_owned();
+    }
+    oxproc(&["stop", "--grace", "1"]);
+    assert!(status.contains("- api "), "{}", status);
+    assert!(status.contains("state=running"), "{}", status);
+}
+
+#[test]
+fn raw_logs_print_lines_as_the_process_wrote_them() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "echo '{\"a\":1}'; echo oops >&2; sleep 30"
+
+[processes.api]
+cmd = "echo hi; sleep 30"
+log_format = "json"
+"#,
+    );
+    project.ok(&["start"]);
+    project.wait_for(&["logs", "--no-pager"], "[api] hi");
+    project.wait_for(&["logs", "--no-pager"], "[web] [ERR] oops");
+
+    let raw = project.ok(&["logs", "--raw", "--no-pager"]);
+    let lines: Vec<&str> = raw.lines().collect();
+    assert!(lines.contains(&r#"{"a":1}"#), "{}", raw);
+    assert!(lines.contains(&"oops"), "{}", raw);
+    // JSONL records come back as the line the process printed
+    assert!(lines.contains(&"hi"), "{}", raw);
+    assert!(!raw.contains("[web]") && !raw.contains("[ERR]"), "{}", raw);
+    assert!(!raw.contains("== web =="), "{}", raw);
+    assert_eq!(raw, project.ok(&["logs", "--format", "raw", "--no-pager"]));
+
+    let out = project.oxproc(&["logs", "--raw", "--format", "json"]);
+    assert!(!out.status.success());
+}
+
+#[test]
+fn namespaces_keep_state_logs_and_ports_apart() {
+    let project = Project::new(
+        r#"
+[processes.web]
+cmd = "echo port={port}; sleep 30"
+port = 47500
+
+[processes.api]
+cmd = "echo api; sleep 30"
+stdout = "logs/api.log"
+"#,
+    );
+    std::fs::create_dir(project.root.path().join("logs")).unwrap();
+    let ns = ["--namespace", "b", "--port-offset", "100"];
+    let in_ns = |args: &[&'static str]| [&ns[..], args].concat();
+
+    project.ok(&["start"]);
+    project.ok(&in_ns(&["start"]));
+    let default_status = project.wait_running(2);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let ns_status = loop {
+        let out = project.ok(&in_ns(&["status"]));
+        if out.matches("state=running").count() == 2 || Instant::now() > deadline {
+            break out;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    };
+    let read = |file: &str| {
+        let path = project.root.path().join(file);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let text = std::fs::read_to_string(&path).unwrap_or_default();
+            if text.lines().count() > 1 || Instant::now() > deadline {
+                return text;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    };
+    let logs = [
+        read("web.out.log"),
+        read("web.b.out.log"),
+        read("logs/api.log"),
+        read("logs/api.b.log"),
+    ];
+    project.ok(&in_ns(&["stop", "--grace", "1"]));
+    let after = project.ok(&["status"]);
+
+    assert_eq!(
+        ns_status.matches("state=running").count(),
+        2,
+        "{}",
+        ns_status
+    );
+    assert_eq!(project_dirs(project.state.path()), 2);
+    for pid in default_status
+        .split_whitespace()
+        .filter(|w| w.starts_with("pid="))
+    {
+        assert!(
+            !ns_status.contains(&format!("{} ", pid)),
+            "{}\n{}",
+            default_status,
+            ns_status
+        );
+    }
+    assert!(logs[0].contains("port=47500"), "{}", logs[0]);
+    assert!(logs[1].contains("port=47600"), "{}", logs[1]);
+    assert!(
+        logs[2].contains("api") && logs[3].contains("api"),
+        "{:?}",
+        logs
+    );
+    // Stopping the namespace leaves the default formation running
+    assert_eq!(after.matches("state=running").count(), 2, "{}", after);
+}
+
+#[test]
+fn port_offset_moves_published_container_ports() {
+    let project = Project::new(
+        r#"
+[processes.db]
+runtime = "docker"
+image = "postgres:16"
+ports = ["5433:5432", "127.0.0.1:6380:6379"]
+ready = "tcp:5433"
+"#,
+    );
+    let plan = project.ok(&["start", "--dry-run"]);
+    assert!(
+        plan.contains(" -p 5433:5432 -p 127.0.0.1:6380:6379 "),
+        "{}",
+        plan
+    );
+    let plan = project.ok(&[
+        "--namespace",
+        "b",
+        "--port-offset",
+        "100",
+        "start",
+        "--dry-run",
+    ]);
+    assert!(
+        plan.contains(" -p 5533:5432 -p 127.0.0.1:6480:6379 "),
+        "{}",
+        plan
+    );
+    assert!(plan.contains("port:    5533"), "{}", plan);
+}
+
+#[test]
+fn exec_and_in_tasks_run_in_the_process_container() {
+    let project = Project::new(
+        r#"
+[processes.db]
+runtime = "docker"
+image = "postgres:16"
+
+[processes.web]
+cmd = "sleep 30"
+
+[tasks.migrate]
+cmd = "migrate --all"
+in = "db"
+cwd = "/app"
+env = { FOO = "1" }
+"#,
+    );
+    // A stand-in for docker that shows how it was called
+    let bin = tempfile::tempdir().unwrap();
+    let docker = bin.path().join("docker");
+    std::fs::write(
+        &docker,
+        "#!/bin/sh\nIFS='|'; echo \"docker|$*|FOO=$FOO\"; exit 7\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&docker, std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    let path = format!(
+        "{}:{}",
+        bin.path().display(),
+        std::env::var("PATH").unwrap_or_default()
+    );
+    let run = |args: &[&str]| project.command(args).env("PATH", &path).output().unwrap();
+
+    let out = run(&["exec", "db", "psql", "-U", "postgres"]);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert_eq!(out.status.code(), Some(7), "{}", stdout);
+    assert!(stdout.starts_with("docker|exec|-i|oxproc-"), "{}", stdout);
+    assert!(stdout.contains("-db|psql|-U|postgres|"), "{}", stdout);
+
+    let out = run(&["run", "migrate"]);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(!out.status.success(), "{}", stdout);
+    assert!(
+        stdout.contains("docker|exec|-i|-w|/app|-e|FOO|oxproc-"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("-db|sh|-c|migrate --all|FOO=1"),
+        "{}",
+        stdout
+    );
+
+    let out = run(&["exec", "web", "true"]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("doesn't run in a container"));
+}